@@ -77,9 +77,6 @@ impl HttpClient {
             received.extend_from_slice(&buf[..bytes_read]);
         }
 
-        match core::str::from_utf8(&received) {
-            Ok(response) => HttpResponse::new(response.to_string()),
-            Err(e) => return Err(Error::Network(format!("invalid received response: {}", e))),
-        }
+        HttpResponse::new(received)
     }
 }
@@ -0,0 +1,402 @@
+use crate::encoding::code_point;
+use crate::encoding::index::JIS0208;
+use crate::encoding::index::JIS0212;
+use crate::encoding::ByteDecoder;
+use crate::encoding::DecodeResult;
+use alloc::vec::Vec;
+
+/// https://encoding.spec.whatwg.org/#index-code-point
+fn index_code_point(index: &[u16], pointer: usize) -> Option<u32> {
+    match index.get(pointer) {
+        Some(0) | None => None,
+        Some(c) => Some(*c as u32),
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#shift_jis-decoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ShiftJisDecoder {
+    lead: u8,
+}
+
+impl ShiftJisDecoder {
+    pub(crate) fn new() -> Self {
+        Self { lead: 0 }
+    }
+}
+
+impl ByteDecoder for ShiftJisDecoder {
+    fn handle(&mut self, byte: Option<u8>, prepend: &mut Vec<u8>) -> DecodeResult {
+        let byte = match byte {
+            Some(b) => b,
+            None => {
+                if self.lead != 0 {
+                    self.lead = 0;
+                    return DecodeResult::Error;
+                }
+                return DecodeResult::Continue;
+            }
+        };
+
+        if self.lead != 0 {
+            let lead = self.lead as usize;
+            self.lead = 0;
+            let offset = if byte < 0x7F { 0x40 } else { 0x41 };
+            let lead_offset = if lead < 0xA0 { 0x81 } else { 0xC1 };
+            let pointer = match byte {
+                0x40..=0x7E | 0x80..=0xFC => {
+                    Some((lead - lead_offset) * 188 + byte as usize - offset)
+                }
+                _ => None,
+            };
+
+            // 外字領域はPrivate Use Areaに対応させる
+            if let Some(p @ 8836..=10715) = pointer {
+                return code_point(0xE000 - 8836 + p as u32);
+            }
+
+            if let Some(c) = pointer.and_then(|p| index_code_point(&JIS0208, p)) {
+                return code_point(c);
+            }
+
+            if byte.is_ascii() {
+                prepend.push(byte);
+            }
+            return DecodeResult::Error;
+        }
+
+        match byte {
+            0x00..=0x80 => DecodeResult::CodePoint(byte as char),
+            // 半角カタカナ
+            0xA1..=0xDF => code_point(0xFF61 - 0xA1 + byte as u32),
+            0x81..=0x9F | 0xE0..=0xFC => {
+                self.lead = byte;
+                DecodeResult::Continue
+            }
+            _ => DecodeResult::Error,
+        }
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#euc-jp-decoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EucJpDecoder {
+    jis0212: bool,
+    lead: u8,
+}
+
+impl EucJpDecoder {
+    pub(crate) fn new() -> Self {
+        Self {
+            jis0212: false,
+            lead: 0,
+        }
+    }
+}
+
+impl ByteDecoder for EucJpDecoder {
+    fn handle(&mut self, byte: Option<u8>, prepend: &mut Vec<u8>) -> DecodeResult {
+        let byte = match byte {
+            Some(b) => b,
+            None => {
+                if self.lead != 0 {
+                    self.lead = 0;
+                    return DecodeResult::Error;
+                }
+                return DecodeResult::Continue;
+            }
+        };
+
+        // 半角カタカナ
+        if self.lead == 0x8E && (0xA1..=0xDF).contains(&byte) {
+            self.lead = 0;
+            return code_point(0xFF61 - 0xA1 + byte as u32);
+        }
+
+        // 補助漢字（JIS X 0212）
+        if self.lead == 0x8F && (0xA1..=0xFE).contains(&byte) {
+            self.jis0212 = true;
+            self.lead = byte;
+            return DecodeResult::Continue;
+        }
+
+        if self.lead != 0 {
+            let lead = self.lead;
+            self.lead = 0;
+            let mut c = None;
+            if (0xA1..=0xFE).contains(&lead) && (0xA1..=0xFE).contains(&byte) {
+                let pointer = (lead as usize - 0xA1) * 94 + byte as usize - 0xA1;
+                c = if self.jis0212 {
+                    index_code_point(&JIS0212, pointer)
+                } else {
+                    index_code_point(&JIS0208, pointer)
+                };
+            }
+            self.jis0212 = false;
+
+            if let Some(c) = c {
+                return code_point(c);
+            }
+
+            if byte.is_ascii() {
+                prepend.push(byte);
+            }
+            return DecodeResult::Error;
+        }
+
+        match byte {
+            0x00..=0x7F => DecodeResult::CodePoint(byte as char),
+            0x8E | 0x8F | 0xA1..=0xFE => {
+                self.lead = byte;
+                DecodeResult::Continue
+            }
+            _ => DecodeResult::Error,
+        }
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#iso-2022-jp-decoder
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Iso2022JpState {
+    Ascii,
+    Roman,
+    Katakana,
+    LeadByte,
+    TrailByte,
+    EscapeStart,
+    Escape,
+}
+
+/// https://encoding.spec.whatwg.org/#iso-2022-jp-decoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Iso2022JpDecoder {
+    state: Iso2022JpState,
+    output_state: Iso2022JpState,
+    lead: u8,
+    output: bool,
+}
+
+impl Iso2022JpDecoder {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Iso2022JpState::Ascii,
+            output_state: Iso2022JpState::Ascii,
+            lead: 0,
+            output: false,
+        }
+    }
+}
+
+impl ByteDecoder for Iso2022JpDecoder {
+    fn handle(&mut self, byte: Option<u8>, prepend: &mut Vec<u8>) -> DecodeResult {
+        match self.state {
+            Iso2022JpState::Ascii => match byte {
+                Some(0x1B) => {
+                    self.state = Iso2022JpState::EscapeStart;
+                    DecodeResult::Continue
+                }
+                Some(b @ 0x00..=0x7F) if b != 0x0E && b != 0x0F => {
+                    self.output = false;
+                    DecodeResult::CodePoint(b as char)
+                }
+                None => DecodeResult::Continue,
+                _ => {
+                    self.output = false;
+                    DecodeResult::Error
+                }
+            },
+            Iso2022JpState::Roman => match byte {
+                Some(0x1B) => {
+                    self.state = Iso2022JpState::EscapeStart;
+                    DecodeResult::Continue
+                }
+                Some(0x5C) => {
+                    self.output = false;
+                    DecodeResult::CodePoint('\u{A5}')
+                }
+                Some(0x7E) => {
+                    self.output = false;
+                    DecodeResult::CodePoint('\u{203E}')
+                }
+                Some(b @ 0x00..=0x7F) if b != 0x0E && b != 0x0F => {
+                    self.output = false;
+                    DecodeResult::CodePoint(b as char)
+                }
+                None => DecodeResult::Continue,
+                _ => {
+                    self.output = false;
+                    DecodeResult::Error
+                }
+            },
+            Iso2022JpState::Katakana => match byte {
+                Some(0x1B) => {
+                    self.state = Iso2022JpState::EscapeStart;
+                    DecodeResult::Continue
+                }
+                Some(b @ 0x21..=0x5F) => {
+                    self.output = false;
+                    code_point(0xFF61 - 0x21 + b as u32)
+                }
+                None => DecodeResult::Continue,
+                _ => {
+                    self.output = false;
+                    DecodeResult::Error
+                }
+            },
+            Iso2022JpState::LeadByte => match byte {
+                Some(0x1B) => {
+                    self.state = Iso2022JpState::EscapeStart;
+                    DecodeResult::Continue
+                }
+                Some(b @ 0x21..=0x7E) => {
+                    self.output = false;
+                    self.lead = b;
+                    self.state = Iso2022JpState::TrailByte;
+                    DecodeResult::Continue
+                }
+                None => DecodeResult::Continue,
+                _ => {
+                    self.output = false;
+                    DecodeResult::Error
+                }
+            },
+            Iso2022JpState::TrailByte => match byte {
+                Some(0x1B) => {
+                    self.state = Iso2022JpState::EscapeStart;
+                    DecodeResult::Error
+                }
+                Some(b @ 0x21..=0x7E) => {
+                    self.state = Iso2022JpState::LeadByte;
+                    let pointer = (self.lead as usize - 0x21) * 94 + b as usize - 0x21;
+                    match index_code_point(&JIS0208, pointer) {
+                        Some(c) => code_point(c),
+                        None => DecodeResult::Error,
+                    }
+                }
+                // 終端の場合も、LeadByte状態では終端で何も出力しないのでエラーを返すだけでよい
+                _ => {
+                    self.state = Iso2022JpState::LeadByte;
+                    DecodeResult::Error
+                }
+            },
+            Iso2022JpState::EscapeStart => match byte {
+                Some(b @ (0x24 | 0x28)) => {
+                    self.lead = b;
+                    self.state = Iso2022JpState::Escape;
+                    DecodeResult::Continue
+                }
+                _ => {
+                    if let Some(b) = byte {
+                        prepend.push(b);
+                    }
+                    self.output = false;
+                    self.state = self.output_state;
+                    DecodeResult::Error
+                }
+            },
+            Iso2022JpState::Escape => {
+                let lead = self.lead;
+                self.lead = 0;
+
+                let state = match (lead, byte) {
+                    (0x28, Some(0x42)) => Some(Iso2022JpState::Ascii),
+                    (0x28, Some(0x4A)) => Some(Iso2022JpState::Roman),
+                    (0x28, Some(0x49)) => Some(Iso2022JpState::Katakana),
+                    (0x24, Some(0x40 | 0x42)) => Some(Iso2022JpState::LeadByte),
+                    _ => None,
+                };
+
+                if let Some(state) = state {
+                    self.state = state;
+                    self.output_state = state;
+                    // エスケープシーケンスが連続して何も出力されなかった場合はエラーにする
+                    let output = self.output;
+                    self.output = true;
+                    return if output {
+                        DecodeResult::Error
+                    } else {
+                        DecodeResult::Continue
+                    };
+                }
+
+                prepend.push(lead);
+                if let Some(b) = byte {
+                    prepend.push(b);
+                }
+                self.output = false;
+                self.state = self.output_state;
+                DecodeResult::Error
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alloc::string::ToString;
+    use crate::encoding::decode;
+    use crate::encoding::Decoder;
+    use crate::encoding::Encoding;
+    use alloc::string::String;
+
+    #[test]
+    fn test_shift_jis() {
+        // "日本語ｶﾅ~"
+        let bytes = [0x93, 0xFA, 0x96, 0x7B, 0x8C, 0xEA, 0xB6, 0xC5, 0x7E];
+        assert_eq!("日本語ｶﾅ~".to_string(), decode(&bytes, Encoding::ShiftJis));
+        // 機種依存文字と外字
+        assert_eq!(
+            "①\u{E000}".to_string(),
+            decode(&[0x87, 0x40, 0xF0, 0x40], Encoding::ShiftJis)
+        );
+        // 不正な後続バイトがASCIIの場合はそのバイトを処理し直す
+        assert_eq!(
+            "\u{FFFD} \u{FFFD}".to_string(),
+            decode(&[0x93, 0x20, 0x93], Encoding::ShiftJis)
+        );
+    }
+
+    #[test]
+    fn test_euc_jp() {
+        // "日本語ｶ" と補助漢字の"丂"
+        let bytes = [
+            0xC6, 0xFC, 0xCB, 0xDC, 0xB8, 0xEC, 0x8E, 0xB6, 0x8F, 0xB0, 0xA1,
+        ];
+        assert_eq!("日本語ｶ丂".to_string(), decode(&bytes, Encoding::EucJp));
+        assert_eq!(
+            "\u{FFFD}a".to_string(),
+            decode(&[0xC6, 0x61], Encoding::EucJp)
+        );
+    }
+
+    #[test]
+    fn test_iso_2022_jp() {
+        // "a日本ｶ¥"
+        let bytes = b"a\x1b$BF|K\\\x1b(I6\x1b(J\\\x1b(B";
+        assert_eq!("a日本ｶ¥".to_string(), decode(bytes, Encoding::Iso2022Jp));
+        // 何も出力しないエスケープシーケンスが続いた場合はエラー
+        assert_eq!(
+            "\u{FFFD}a".to_string(),
+            decode(b"\x1b$B\x1b(Ba", Encoding::Iso2022Jp)
+        );
+        // 不明なエスケープシーケンスはエラーにして、ESCに続くバイトを処理し直す
+        assert_eq!(
+            "\u{FFFD}(Z".to_string(),
+            decode(b"\x1b(Z", Encoding::Iso2022Jp)
+        );
+    }
+
+    #[test]
+    fn test_decode_in_chunks() {
+        let bytes = b"\x1b$BF|K\\8l\x1b(B!";
+        for size in 1..bytes.len() {
+            let mut decoder = Decoder::new(Encoding::Iso2022Jp);
+            let mut output = String::new();
+            for chunk in bytes.chunks(size) {
+                output.push_str(&decoder.decode(chunk, false));
+            }
+            output.push_str(&decoder.decode(&[], true));
+            assert_eq!("日本語!".to_string(), output);
+        }
+    }
+}
@@ -0,0 +1,479 @@
+mod index;
+mod japanese;
+
+use crate::encoding::japanese::EucJpDecoder;
+use crate::encoding::japanese::Iso2022JpDecoder;
+use crate::encoding::japanese::ShiftJisDecoder;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// https://encoding.spec.whatwg.org/#names-and-labels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Encoding {
+    /// https://encoding.spec.whatwg.org/#utf-8
+    Utf8,
+    /// https://encoding.spec.whatwg.org/#utf-16be
+    Utf16Be,
+    /// https://encoding.spec.whatwg.org/#utf-16le
+    Utf16Le,
+    /// https://encoding.spec.whatwg.org/#shift_jis
+    ShiftJis,
+    /// https://encoding.spec.whatwg.org/#euc-jp
+    EucJp,
+    /// https://encoding.spec.whatwg.org/#iso-2022-jp
+    Iso2022Jp,
+    /// https://encoding.spec.whatwg.org/#windows-1252
+    Windows1252,
+}
+
+impl Encoding {
+    /// https://encoding.spec.whatwg.org/#concept-encoding-get
+    pub fn for_label(label: &str) -> Option<Self> {
+        let label = label
+            .trim_matches(|c: char| matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' '))
+            .to_ascii_lowercase();
+
+        match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Encoding::Utf8),
+            "unicodefffe" | "utf-16be" => Some(Encoding::Utf16Be),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Encoding::Utf16Le),
+            "csshiftjis" | "ms932" | "ms_kanji" | "shift-jis" | "shift_jis" | "sjis"
+            | "windows-31j" | "x-sjis" => Some(Encoding::ShiftJis),
+            "cseucpkdfmtjapanese" | "euc-jp" | "x-euc-jp" => Some(Encoding::EucJp),
+            "csiso2022jp" | "iso-2022-jp" => Some(Encoding::Iso2022Jp),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Encoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    /// https://encoding.spec.whatwg.org/#name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::EucJp => "EUC-JP",
+            Encoding::Iso2022Jp => "ISO-2022-JP",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// https://encoding.spec.whatwg.org/#bom-sniff
+    /// BOMが見つかった場合、そのエンコーディングとBOMのバイト数を返す
+    pub fn sniff_bom(bytes: &[u8]) -> Option<(Self, usize)> {
+        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return Some((Encoding::Utf8, 3));
+        }
+        if bytes.starts_with(&[0xFE, 0xFF]) {
+            return Some((Encoding::Utf16Be, 2));
+        }
+        if bytes.starts_with(&[0xFF, 0xFE]) {
+            return Some((Encoding::Utf16Le, 2));
+        }
+        None
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#decode
+/// BOMがある場合は`encoding`よりもBOMを優先し、BOM自体は出力に含めない
+pub fn decode(bytes: &[u8], encoding: Encoding) -> String {
+    let (encoding, bytes) = match Encoding::sniff_bom(bytes) {
+        Some((e, len)) => (e, &bytes[len..]),
+        None => (encoding, bytes),
+    };
+
+    Decoder::new(encoding).decode(bytes, true)
+}
+
+/// https://encoding.spec.whatwg.org/#concept-encoding-process
+/// デコーダが1バイト（またはストリームの終端）を処理した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DecodeResult {
+    /// 出力がなく、次のバイトを待つ
+    Continue,
+    /// コードポイントを1つ出力する
+    CodePoint(char),
+    /// デコードエラー。置換モードなのでU+FFFDを出力する
+    Error,
+}
+
+/// https://encoding.spec.whatwg.org/#decoder
+/// 1バイトずつ処理するデコーダ。`prepend`に積まれたバイトは入力の先頭に戻され、もう一度処理される
+pub(crate) trait ByteDecoder {
+    fn handle(&mut self, byte: Option<u8>, prepend: &mut Vec<u8>) -> DecodeResult;
+}
+
+/// チャンクに分かれたバイト列を順番にデコードする。マルチバイト文字がチャンクの境界で分かれていてもよい
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoder {
+    encoding: Encoding,
+    inner: DecoderKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DecoderKind {
+    Utf8(Utf8Decoder),
+    Utf16(Utf16Decoder),
+    ShiftJis(ShiftJisDecoder),
+    EucJp(EucJpDecoder),
+    Iso2022Jp(Iso2022JpDecoder),
+    Windows1252(Windows1252Decoder),
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding) -> Self {
+        let inner = match encoding {
+            Encoding::Utf8 => DecoderKind::Utf8(Utf8Decoder::new()),
+            Encoding::Utf16Be => DecoderKind::Utf16(Utf16Decoder::new(true)),
+            Encoding::Utf16Le => DecoderKind::Utf16(Utf16Decoder::new(false)),
+            Encoding::ShiftJis => DecoderKind::ShiftJis(ShiftJisDecoder::new()),
+            Encoding::EucJp => DecoderKind::EucJp(EucJpDecoder::new()),
+            Encoding::Iso2022Jp => DecoderKind::Iso2022Jp(Iso2022JpDecoder::new()),
+            Encoding::Windows1252 => DecoderKind::Windows1252(Windows1252Decoder {}),
+        };
+
+        Self { encoding, inner }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// `bytes`をデコードする。`last`がtrueの場合はストリームの終端として扱い、
+    /// 途中のマルチバイト文字をエラーとしてU+FFFDに置き換える
+    pub fn decode(&mut self, bytes: &[u8], last: bool) -> String {
+        let mut output = String::new();
+        let mut queue: VecDeque<u8> = bytes.iter().copied().collect();
+        self.process_queue(&mut queue, &mut output);
+
+        if last {
+            // https://encoding.spec.whatwg.org/#concept-encoding-run
+            // 終端の処理でバイトが戻された場合は、それらを処理してからもう一度終端を処理する
+            loop {
+                let mut prepend = Vec::new();
+                let result = self.handler().handle(None, &mut prepend);
+                Self::push_result(result, &mut output);
+                if prepend.is_empty() {
+                    break;
+                }
+                queue.extend(prepend);
+                self.process_queue(&mut queue, &mut output);
+            }
+        }
+
+        output
+    }
+
+    fn process_queue(&mut self, queue: &mut VecDeque<u8>, output: &mut String) {
+        while let Some(byte) = queue.pop_front() {
+            let mut prepend = Vec::new();
+            let result = self.handler().handle(Some(byte), &mut prepend);
+            Self::push_result(result, output);
+            for b in prepend.into_iter().rev() {
+                queue.push_front(b);
+            }
+        }
+    }
+
+    fn handler(&mut self) -> &mut dyn ByteDecoder {
+        match &mut self.inner {
+            DecoderKind::Utf8(d) => d,
+            DecoderKind::Utf16(d) => d,
+            DecoderKind::ShiftJis(d) => d,
+            DecoderKind::EucJp(d) => d,
+            DecoderKind::Iso2022Jp(d) => d,
+            DecoderKind::Windows1252(d) => d,
+        }
+    }
+
+    fn push_result(result: DecodeResult, output: &mut String) {
+        match result {
+            DecodeResult::Continue => {}
+            DecodeResult::CodePoint(c) => output.push(c),
+            DecodeResult::Error => output.push(char::REPLACEMENT_CHARACTER),
+        }
+    }
+}
+
+/// コードポイントをcharに変換する。サロゲートなどcharで表せない値はエラーにする
+pub(crate) fn code_point(c: u32) -> DecodeResult {
+    match char::from_u32(c) {
+        Some(c) => DecodeResult::CodePoint(c),
+        None => DecodeResult::Error,
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#utf-8-decoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Utf8Decoder {
+    code_point: u32,
+    bytes_seen: u8,
+    bytes_needed: u8,
+    lower_boundary: u8,
+    upper_boundary: u8,
+}
+
+impl Utf8Decoder {
+    fn new() -> Self {
+        Self {
+            code_point: 0,
+            bytes_seen: 0,
+            bytes_needed: 0,
+            lower_boundary: 0x80,
+            upper_boundary: 0xBF,
+        }
+    }
+}
+
+impl ByteDecoder for Utf8Decoder {
+    fn handle(&mut self, byte: Option<u8>, prepend: &mut Vec<u8>) -> DecodeResult {
+        let byte = match byte {
+            Some(b) => b,
+            None => {
+                if self.bytes_needed != 0 {
+                    self.bytes_needed = 0;
+                    return DecodeResult::Error;
+                }
+                return DecodeResult::Continue;
+            }
+        };
+
+        if self.bytes_needed == 0 {
+            match byte {
+                0x00..=0x7F => return DecodeResult::CodePoint(byte as char),
+                0xC2..=0xDF => {
+                    self.bytes_needed = 1;
+                    self.code_point = (byte & 0x1F) as u32;
+                }
+                0xE0..=0xEF => {
+                    if byte == 0xE0 {
+                        self.lower_boundary = 0xA0;
+                    }
+                    if byte == 0xED {
+                        self.upper_boundary = 0x9F;
+                    }
+                    self.bytes_needed = 2;
+                    self.code_point = (byte & 0xF) as u32;
+                }
+                0xF0..=0xF4 => {
+                    if byte == 0xF0 {
+                        self.lower_boundary = 0x90;
+                    }
+                    if byte == 0xF4 {
+                        self.upper_boundary = 0x8F;
+                    }
+                    self.bytes_needed = 3;
+                    self.code_point = (byte & 0x7) as u32;
+                }
+                _ => return DecodeResult::Error,
+            }
+            return DecodeResult::Continue;
+        }
+
+        if byte < self.lower_boundary || byte > self.upper_boundary {
+            *self = Utf8Decoder::new();
+            prepend.push(byte);
+            return DecodeResult::Error;
+        }
+
+        self.lower_boundary = 0x80;
+        self.upper_boundary = 0xBF;
+        self.code_point = (self.code_point << 6) | (byte & 0x3F) as u32;
+        self.bytes_seen += 1;
+        if self.bytes_seen != self.bytes_needed {
+            return DecodeResult::Continue;
+        }
+
+        let c = self.code_point;
+        *self = Utf8Decoder::new();
+        code_point(c)
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#shared-utf-16-decoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Utf16Decoder {
+    is_big_endian: bool,
+    lead_byte: Option<u8>,
+    lead_surrogate: Option<u16>,
+}
+
+impl Utf16Decoder {
+    fn new(is_big_endian: bool) -> Self {
+        Self {
+            is_big_endian,
+            lead_byte: None,
+            lead_surrogate: None,
+        }
+    }
+}
+
+impl ByteDecoder for Utf16Decoder {
+    fn handle(&mut self, byte: Option<u8>, prepend: &mut Vec<u8>) -> DecodeResult {
+        let byte = match byte {
+            Some(b) => b,
+            None => {
+                if self.lead_byte.is_some() || self.lead_surrogate.is_some() {
+                    self.lead_byte = None;
+                    self.lead_surrogate = None;
+                    return DecodeResult::Error;
+                }
+                return DecodeResult::Continue;
+            }
+        };
+
+        let lead_byte = match self.lead_byte.take() {
+            Some(b) => b,
+            None => {
+                self.lead_byte = Some(byte);
+                return DecodeResult::Continue;
+            }
+        };
+
+        let code_unit = if self.is_big_endian {
+            ((lead_byte as u16) << 8) + byte as u16
+        } else {
+            ((byte as u16) << 8) + lead_byte as u16
+        };
+
+        if let Some(lead_surrogate) = self.lead_surrogate.take() {
+            if (0xDC00..=0xDFFF).contains(&code_unit) {
+                return code_point(
+                    0x10000
+                        + ((lead_surrogate as u32 - 0xD800) << 10)
+                        + (code_unit as u32 - 0xDC00),
+                );
+            }
+            // 後続のサロゲートではないので、このコードユニットを戻して処理し直す
+            let byte1 = (code_unit >> 8) as u8;
+            let byte2 = (code_unit & 0xFF) as u8;
+            if self.is_big_endian {
+                prepend.extend([byte1, byte2]);
+            } else {
+                prepend.extend([byte2, byte1]);
+            }
+            return DecodeResult::Error;
+        }
+
+        match code_unit {
+            0xD800..=0xDBFF => {
+                self.lead_surrogate = Some(code_unit);
+                DecodeResult::Continue
+            }
+            0xDC00..=0xDFFF => DecodeResult::Error,
+            _ => code_point(code_unit as u32),
+        }
+    }
+}
+
+/// https://encoding.spec.whatwg.org/index-windows-1252.txt
+/// 0x80から0x9Fまでのバイトに対応するコードポイント。それ以外のバイトはISO-8859-1と同じ
+static WINDOWS_1252_C1: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+/// https://encoding.spec.whatwg.org/#single-byte-decoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Windows1252Decoder {}
+
+impl ByteDecoder for Windows1252Decoder {
+    fn handle(&mut self, byte: Option<u8>, _prepend: &mut Vec<u8>) -> DecodeResult {
+        match byte {
+            None => DecodeResult::Continue,
+            Some(b @ 0x80..=0x9F) => code_point(WINDOWS_1252_C1[(b - 0x80) as usize] as u32),
+            Some(b) => DecodeResult::CodePoint(b as char),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+
+    #[test]
+    fn test_for_label() {
+        assert_eq!(
+            Some(Encoding::ShiftJis),
+            Encoding::for_label(" Shift_JIS\n")
+        );
+        assert_eq!(Some(Encoding::ShiftJis), Encoding::for_label("windows-31j"));
+        assert_eq!(Some(Encoding::EucJp), Encoding::for_label("EUC-JP"));
+        assert_eq!(Some(Encoding::Utf16Le), Encoding::for_label("utf-16"));
+        assert_eq!(
+            Some(Encoding::Windows1252),
+            Encoding::for_label("iso-8859-1")
+        );
+        assert_eq!(None, Encoding::for_label("utf-7"));
+    }
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(
+            "aあ😀".to_string(),
+            decode("aあ😀".as_bytes(), Encoding::Utf8)
+        );
+        // BOMは出力に含めない
+        assert_eq!(
+            "a".to_string(),
+            decode(&[0xEF, 0xBB, 0xBF, b'a'], Encoding::Windows1252)
+        );
+        // 不正なバイトはU+FFFDに置き換え、続くバイトは処理し直す
+        assert_eq!(
+            "\u{FFFD}a\u{FFFD}\u{FFFD}".to_string(),
+            decode(&[0xE3, b'a', 0xFF, 0xE3, 0x81], Encoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn test_utf16() {
+        assert_eq!(
+            "aあ😀".to_string(),
+            decode(
+                &[0xFF, 0xFE, 0x61, 0x00, 0x42, 0x30, 0x3D, 0xD8, 0x00, 0xDE],
+                Encoding::Utf8
+            )
+        );
+        assert_eq!(
+            "aあ".to_string(),
+            decode(&[0x00, 0x61, 0x30, 0x42], Encoding::Utf16Be)
+        );
+        // 対になっていないサロゲートと、終端で途切れたコードユニット
+        assert_eq!(
+            "\u{FFFD}a\u{FFFD}".to_string(),
+            decode(&[0xD8, 0x3D, 0x00, 0x61, 0x30], Encoding::Utf16Be)
+        );
+    }
+
+    #[test]
+    fn test_windows1252() {
+        assert_eq!(
+            "a€\u{81}é".to_string(),
+            decode(&[0x61, 0x80, 0x81, 0xE9], Encoding::Windows1252)
+        );
+    }
+
+    #[test]
+    fn test_decode_in_chunks() {
+        let bytes = "あいうえお😀".as_bytes();
+        for size in 1..bytes.len() {
+            let mut decoder = Decoder::new(Encoding::Utf8);
+            let mut output = String::new();
+            for chunk in bytes.chunks(size) {
+                output.push_str(&decoder.decode(chunk, false));
+            }
+            output.push_str(&decoder.decode(&[], true));
+            assert_eq!("あいうえお😀".to_string(), output);
+        }
+    }
+}
@@ -5,5 +5,24 @@ pub enum Error {
     Network(String),
     UnexpectedInput(String),
     InvalidUI(String),
+    /// https://webidl.spec.whatwg.org/#idl-DOMException
+    Dom(DomException),
     Other(String),
 }
+
+/// https://webidl.spec.whatwg.org/#idl-DOMException-error-names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomException {
+    /// https://webidl.spec.whatwg.org/#hierarchyrequesterror
+    /// ツリーの構造として正しくない操作
+    HierarchyRequest,
+    /// https://webidl.spec.whatwg.org/#invalidcharactererror
+    /// 名前やトークンに使えない文字が含まれている
+    InvalidCharacter,
+    /// https://webidl.spec.whatwg.org/#notfounderror
+    /// 操作の対象となるノードが見つからない
+    NotFound,
+    /// https://webidl.spec.whatwg.org/#syntaxerror
+    /// 文字列が期待される構文に合っていない
+    Syntax,
+}
@@ -20,49 +20,75 @@ pub struct HttpResponse {
     status_code: u32,
     reason: String,
     headers: Vec<Header>,
-    body: String,
+    body: Vec<u8>,
 }
 
 impl HttpResponse {
-    pub fn new(raw_response: String) -> Result<Self, Error> {
-        // レスポンスの前処理 改行コードの統一
-        let preprocessed_response = raw_response.trim_start().replace("\n\r", "\n");
+    /// ボディのエンコーディングはHTMLのパース時に判定するので、ボディはバイト列のまま保持する
+    pub fn new(raw_response: Vec<u8>) -> Result<Self, Error> {
+        // レスポンスの前処理 先頭の空白を取り除く
+        let start = raw_response
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(raw_response.len());
+        let raw_response = &raw_response[start..];
 
         // リクエストラインの分割
-        let (status_line, remaining) = match preprocessed_response.split_once("\n") {
-            Some((s, r)) => (s, r),
+        let (status_line, remaining) = match raw_response.iter().position(|b| *b == b'\n') {
+            Some(i) => (&raw_response[..i], &raw_response[i..]),
             None => {
                 return Err(Error::Network(format!(
                     "invalid http response: {}",
-                    preprocessed_response
+                    String::from_utf8_lossy(raw_response)
                 )))
             }
         };
 
         // ヘッダとボディの分割
-        let (headers, body) = match remaining.split_once("\n\n") {
-            Some((h, b)) => {
+        let (headers, body) = match find_blank_line(remaining) {
+            Some((header_end, body_start)) => {
                 let mut headers = Vec::new();
-                for header in h.split("\n") {
-                    let splitted_header: Vec<&str> = header.splitn(2, ":").collect();
+                // ヘッダはASCIIの文字列として扱う
+                for header in String::from_utf8_lossy(&remaining[..header_end]).split('\n') {
+                    let header = header.trim();
+                    if header.is_empty() {
+                        continue;
+                    }
+                    let splitted_header: Vec<&str> = header.splitn(2, ':').collect();
                     headers.push(Header::new(
                         String::from(splitted_header[0].trim()),
-                        String::from(splitted_header[1].trim()),
+                        String::from(splitted_header.get(1).unwrap_or(&"").trim()),
                     ));
                 }
-                (headers, b)
+                (headers, &remaining[body_start..])
             }
-            None => (Vec::new(), remaining),
+            None => (Vec::new(), &remaining[1..]),
         };
 
-        let statuses: Vec<&str> = status_line.split(' ').collect();
+        // ステータスラインは、バージョン、ステータスコード、理由句の順に並ぶ。
+        // 理由句は空白を含むことがあるので、最初の2つの空白だけで分割する。
+        // 理由句は空でもよいので、必須なのはバージョンとステータスコードだけ
+        let status_line = String::from_utf8_lossy(status_line);
+        let statuses: Vec<&str> = status_line.trim_end().splitn(3, ' ').collect();
+        let (version, status_code, reason) =
+            match (statuses.first(), statuses.get(1), statuses.get(2)) {
+                (Some(version), Some(status_code), reason) => {
+                    (version, status_code, reason.unwrap_or(&""))
+                }
+                _ => {
+                    return Err(Error::Network(format!(
+                        "invalid status line: {}",
+                        status_line
+                    )))
+                }
+            };
 
         Ok(Self {
-            version: statuses[0].to_string(),
-            status_code: statuses[1].parse().unwrap_or(404),
-            reason: statuses[2].to_string(),
+            version: version.to_string(),
+            status_code: status_code.parse().unwrap_or(404),
+            reason: reason.to_string(),
             headers,
-            body: body.to_string(),
+            body: body.to_vec(),
         })
     }
 
@@ -82,13 +108,13 @@ impl HttpResponse {
         self.headers.clone()
     }
 
-    pub fn body(&self) -> String {
+    pub fn body(&self) -> Vec<u8> {
         self.body.clone()
     }
 
     pub fn header_value(&self, name: &str) -> Result<String, String> {
         for h in &self.headers {
-            if h.name == name {
+            if h.name.eq_ignore_ascii_case(name) {
                 return Ok(h.value.clone());
             }
         }
@@ -97,12 +123,29 @@ impl HttpResponse {
     }
 }
 
+/// 空行（"\n\n"または"\n\r\n"）を探し、空行の開始位置と空行の直後の位置を返す
+fn find_blank_line(bytes: &[u8]) -> Option<(usize, usize)> {
+    for i in 0..bytes.len() {
+        if bytes[i] != b'\n' {
+            continue;
+        }
+        if bytes[i + 1..].starts_with(b"\n") {
+            return Some((i, i + 2));
+        }
+        if bytes[i + 1..].starts_with(b"\r\n") {
+            return Some((i, i + 3));
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
     #[test]
     fn test_status_line_only() {
-        let raw = "HTTP/1.1 200 OK\n\n".to_string();
+        let raw = "HTTP/1.1 200 OK\n\n".as_bytes().to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
@@ -111,7 +154,7 @@ mod tests {
 
     #[test]
     fn test_one_header() {
-        let raw = "HTTP/1.1 200 OK\nDate:xx xx xx\n\n".to_string();
+        let raw = "HTTP/1.1 200 OK\nDate:xx xx xx\n\n".as_bytes().to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse http response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
@@ -122,7 +165,9 @@ mod tests {
 
     #[test]
     fn test_two_headers_with_white_space() {
-        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\nContent-Length: 42\n\n".to_string();
+        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\nContent-Length: 42\n\n"
+            .as_bytes()
+            .to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse http response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
@@ -134,7 +179,9 @@ mod tests {
 
     #[test]
     fn test_body() {
-        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\n\nbody message".to_string();
+        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\n\nbody message"
+            .as_bytes()
+            .to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse http response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
@@ -142,12 +189,54 @@ mod tests {
 
         assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
 
-        assert_eq!(res.body(), "body message".to_string());
+        assert_eq!(res.body(), b"body message".to_vec());
+    }
+
+    #[test]
+    fn test_crlf_and_binary_body() {
+        let mut raw =
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=Shift_JIS\r\n\r\n".to_vec();
+        // Shift_JISの"日本"
+        raw.extend([0x93, 0xFA, 0x96, 0x7B]);
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "OK");
+
+        assert_eq!(
+            res.header_value("content-type"),
+            Ok("text/html; charset=Shift_JIS".to_string())
+        );
+        assert_eq!(res.body(), vec![0x93, 0xFA, 0x96, 0x7B]);
+    }
+
+    #[test]
+    fn test_multi_word_reason() {
+        let raw = "HTTP/1.1 404 Not Found\n\n".as_bytes().to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 404);
+        assert_eq!(res.reason(), "Not Found");
+    }
+
+    #[test]
+    fn test_empty_reason() {
+        let raw = "HTTP/1.1 200 \r\n\r\n".as_bytes().to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.version(), "HTTP/1.1");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "");
+
+        let raw = "HTTP/1.1 200\n\n".as_bytes().to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.reason(), "");
     }
 
     #[test]
     fn test_invalid() {
-        let raw = "HTTP/1.1 200 OK".to_string();
+        let raw = "HTTP/1.1 200 OK".as_bytes().to_vec();
         assert!(HttpResponse::new(raw).is_err());
+        // ステータスコードのない、途中で切れたステータスラインはパニックせずにエラーになる
+        let raw = "HTTP/1.1\r\n\r\n".as_bytes().to_vec();
+        assert!(matches!(HttpResponse::new(raw), Err(Error::Network(_))));
     }
 }
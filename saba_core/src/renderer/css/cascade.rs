@@ -0,0 +1,213 @@
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::Declaration;
+use crate::renderer::css::cssom::Origin;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::selector::Specificity;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::css::value::parse_values;
+use crate::renderer::css::value::CssValue;
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::collections::btree_map::Entry;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// https://www.w3.org/TR/css-cascade-4/#cascaded
+/// 1つの要素について、プロパティごとにカスケードで勝った宣言の値を保持する
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeclaredValues {
+    values: BTreeMap<String, Vec<CssValue>>,
+}
+
+impl DeclaredValues {
+    pub fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// propertyの値を返す。どの宣言もない場合はNoneを返す
+    pub fn get(&self, property: &str) -> Option<&[CssValue]> {
+        self.values.get(property).map(|v| v.as_slice())
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// https://www.w3.org/TR/css-cascade-4/#cascade-sort
+/// 宣言を比べるときのキー。フィールドの順に比べ、大きい方が優先される
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct CascadeKey {
+    /// https://www.w3.org/TR/css-cascade-4/#cascade-origin
+    precedence: u8,
+    /// https://www.w3.org/TR/css-cascade-4/#style-attr
+    /// style属性の宣言は、どのセレクタよりも優先される
+    inline: bool,
+    /// https://www.w3.org/TR/css-cascade-4/#cascade-specificity
+    specificity: Specificity,
+    /// https://www.w3.org/TR/css-cascade-4/#cascade-order
+    /// 後に出てきた宣言ほど大きい
+    order: usize,
+}
+
+/// https://www.w3.org/TR/css-cascade-4/#cascade-origin
+/// 重要な宣言では、オリジンの優先順位が逆になる
+fn precedence(origin: Origin, important: bool) -> u8 {
+    match (origin, important) {
+        (Origin::UserAgent, false) => 0,
+        (Origin::Author, false) => 1,
+        (Origin::Author, true) => 2,
+        (Origin::UserAgent, true) => 3,
+    }
+}
+
+/// https://www.w3.org/TR/css-cascade-4/#cascading
+/// スタイルシートとstyle属性から、nodeの要素に適用される宣言を集め、
+/// オリジンと重要性、style属性かどうか、詳細度、出現順でプロパティごとに勝者を決める
+pub fn cascade(dom: &Dom, node: NodeId, stylesheets: &[StyleSheet]) -> DeclaredValues {
+    let element = match dom.node(node).kind {
+        NodeKind::Element(ref e) => e,
+        _ => return DeclaredValues::new(),
+    };
+
+    let mut winners: BTreeMap<String, (CascadeKey, Vec<CssValue>)> = BTreeMap::new();
+    let mut order = 0;
+    let mut apply = |declarations: &[Declaration], origin, inline, specificity| {
+        for declaration in declarations {
+            order += 1;
+            // 値が空の宣言や、値を解釈できない宣言は、カスケードに参加しない
+            let values = match parse_values(&declaration.value) {
+                Ok(values) if !values.is_empty() => values,
+                _ => continue,
+            };
+            let key = CascadeKey {
+                precedence: precedence(origin, declaration.important),
+                inline,
+                specificity,
+                order,
+            };
+            let property = declaration.property.to_ascii_lowercase();
+            // 優先順位が同じ場合は、後から現れた宣言が勝つ
+            match winners.entry(property) {
+                Entry::Occupied(mut entry) if entry.get().0 <= key => {
+                    entry.insert((key, values));
+                }
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert((key, values));
+                }
+            }
+        }
+    };
+
+    for sheet in stylesheets {
+        for rule in &sheet.rules {
+            if let Some(specificity) = rule.selector.matched_specificity(dom, node) {
+                apply(&rule.declarations, sheet.origin, false, specificity);
+            }
+        }
+    }
+
+    // https://drafts.csswg.org/css-style-attr/#interpret
+    if let Some(style) = element.get_attribute("style") {
+        let declarations = CssParser::new(CssTokenizer::new(style)).parse_list_of_declarations();
+        apply(&declarations, Origin::Author, true, Specificity::default());
+    }
+
+    DeclaredValues {
+        values: winners
+            .into_iter()
+            .map(|(property, (_, values))| (property, values))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::value::Length;
+    use crate::renderer::css::value::LengthUnit;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use crate::renderer::layout::computed_style::Color;
+    use crate::renderer::resource_loader::ResourceLoader;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn keyword(s: &str) -> Vec<CssValue> {
+        vec![CssValue::Keyword(s.to_string())]
+    }
+
+    fn color(name: &str) -> Vec<CssValue> {
+        vec![CssValue::Color(Color::from_name(name).unwrap())]
+    }
+
+    /// htmlを解釈し、id属性がidの要素の宣言値を返す。user_agentはユーザーエージェントのスタイルシート
+    fn declared_values(html: &str, user_agent: &str, id: &str) -> DeclaredValues {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+
+        let mut ua = CssParser::new(CssTokenizer::new(user_agent.to_string())).parse_stylesheet();
+        ua.set_origin(Origin::UserAgent);
+        let mut stylesheets = vec![ua];
+        stylesheets.extend(ResourceLoader::new().stylesheets(&dom, document));
+
+        let node = get_element_by_id(&dom, document, &id.to_string()).expect("should exist");
+        cascade(&dom, node, &stylesheets)
+    }
+
+    #[test]
+    fn test_specificity_and_order() {
+        let html = "<style>#x { color: red } p.a { color: blue; display: block } p { color: green; display: inline } p.a { display: none }</style><p id=\"x\" class=\"a\"></p>";
+        let values = declared_values(html, "", "x");
+
+        // 後に出てきても、詳細度の低い宣言は負ける
+        assert_eq!(Some(color("red").as_slice()), values.get("color"));
+        // 詳細度が同じ場合は、後に出てきた宣言が勝つ
+        assert_eq!(Some(keyword("none").as_slice()), values.get("display"));
+        assert_eq!(2, values.len());
+    }
+
+    #[test]
+    fn test_origin_and_importance() {
+        let user_agent = "p { color: red; display: block !important; background-color: white }";
+        let html = "<style>p { color: blue; display: none } #x { color: green !important }</style><p id=\"x\" style=\"color: black; background-color: gray !important\"></p>";
+        let values = declared_values(html, user_agent, "x");
+
+        // 重要な宣言は、style属性や詳細度の高い宣言にも勝つ
+        assert_eq!(Some(color("green").as_slice()), values.get("color"));
+        // ユーザーエージェントの重要な宣言は、作成者のどの宣言よりも優先される
+        assert_eq!(Some(keyword("block").as_slice()), values.get("display"));
+        assert_eq!(
+            Some(color("gray").as_slice()),
+            values.get("background-color")
+        );
+
+        let values = declared_values("<p id=\"x\"></p>", user_agent, "x");
+        assert_eq!(Some(color("red").as_slice()), values.get("color"));
+    }
+
+    #[test]
+    fn test_style_attribute() {
+        let html = "<style>#x.a { color: red; display: block }</style><p id=\"x\" class=\"a\" style=\"COLOR : blue; display: ; width: 1px 2px\"></p>";
+        let values = declared_values(html, "", "x");
+
+        // style属性の宣言は、詳細度に関わらず勝つ
+        assert_eq!(Some(color("blue").as_slice()), values.get("color"));
+        // 値のない宣言は無視される
+        assert_eq!(Some(keyword("block").as_slice()), values.get("display"));
+        let px = |v: f64| CssValue::Length(Length::new(v, LengthUnit::Px));
+        assert_eq!(Some(vec![px(1.0), px(2.0)].as_slice()), values.get("width"));
+    }
+}
@@ -1,35 +1,56 @@
 use crate::alloc::string::ToString;
+use crate::error::Error;
+use crate::renderer::css::selector::parse_selector_list;
+use crate::renderer::css::selector::Selector;
 use crate::renderer::css::token::CssToken;
 use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::node::NodeId;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::iter::Peekable;
 
-pub type ComponentValue = CssToken;
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Selector {
-    /// https://www.w3.org/TR/selectors-4/#type-selectors
-    TypeSelector(String),
-    /// https://www.w3.org/TR/selectors-4/#class-html
-    ClassSelector(String),
-    /// https://www.w3.org/TR/selectors-4/#id-selectors
-    IdSelector(String),
-    /// パース中にエラーが起こったときに使用されるセレクタ
-    UnknownSelector,
+/// https://www.w3.org/TR/css-syntax-3/#component-value
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentValue {
+    /// https://www.w3.org/TR/css-syntax-3/#preserved-tokens
+    /// 関数とブロックを作らないトークン
+    PreservedToken(CssToken),
+    /// https://www.w3.org/TR/css-syntax-3/#function
+    /// 例: rgb(0, 0, 0)、calc(100% - 10px)
+    Function {
+        name: String,
+        values: Vec<ComponentValue>,
+    },
+    /// https://www.w3.org/TR/css-syntax-3/#simple-block
+    /// associatedは、ブロックを始めたトークン。{、[、(のいずれか
+    SimpleBlock {
+        associated: CssToken,
+        values: Vec<ComponentValue>,
+    },
+}
+
+impl ComponentValue {
+    pub fn is_whitespace(&self) -> bool {
+        *self == ComponentValue::PreservedToken(CssToken::Whitespace)
+    }
 }
 
+/// https://www.w3.org/TR/css-syntax-3/#declaration
 #[derive(Debug, Clone, PartialEq)]
 pub struct Declaration {
     pub property: String,
-    pub value: ComponentValue,
+    /// 前後の空白と!importantを取り除いた、コンポーネント値のリスト
+    pub value: Vec<ComponentValue>,
+    /// https://www.w3.org/TR/css-cascade-4/#importance
+    pub important: bool,
 }
 
 impl Declaration {
     pub fn new() -> Self {
         Self {
             property: String::new(),
-            value: ComponentValue::Ident(String::new()),
+            value: Vec::new(),
+            important: false,
         }
     }
 
@@ -37,9 +58,13 @@ impl Declaration {
         self.property = property;
     }
 
-    pub fn set_value(&mut self, value: ComponentValue) {
+    pub fn set_value(&mut self, value: Vec<ComponentValue>) {
         self.value = value;
     }
+
+    pub fn set_important(&mut self, important: bool) {
+        self.important = important;
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -67,20 +92,48 @@ impl QualifiedRule {
     }
 }
 
+/// https://www.w3.org/TR/css-cascade-4/#cascading-origins
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// https://www.w3.org/TR/css-cascade-4/#cascade-origin-ua
+    /// ブラウザに組み込まれたスタイルシート
+    UserAgent,
+    /// https://www.w3.org/TR/css-cascade-4/#cascade-origin-author
+    /// ページの<style>要素や<link>要素、style属性のスタイル
+    Author,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct StyleSheet {
     /// https::/drafts.csswg.org/cssom/#dom-cssstylesheet-cssrules
     pub rules: Vec<QualifiedRule>,
+    /// https://drafts.csswg.org/cssom/#concept-css-style-sheet-owner-node
+    /// スタイルシートの元になった<style>要素
+    pub owner_node: Option<NodeId>,
+    /// https://www.w3.org/TR/css-cascade-4/#origin
+    pub origin: Origin,
 }
 
 impl StyleSheet {
     pub fn new() -> Self {
-        Self { rules: Vec::new() }
+        Self {
+            rules: Vec::new(),
+            owner_node: None,
+            origin: Origin::Author,
+        }
     }
 
     pub fn set_rules(&mut self, rules: Vec<QualifiedRule>) {
         self.rules = rules;
     }
+
+    pub fn set_owner_node(&mut self, owner_node: Option<NodeId>) {
+        self.owner_node = owner_node;
+    }
+
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.origin = origin;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -100,6 +153,22 @@ impl CssParser {
         sheet
     }
 
+    /// https://www.w3.org/TR/css-syntax-3/#parse-a-list-of-declarations
+    /// style属性の値のような、{}で囲まれていない宣言のリストを解釈する
+    pub fn parse_list_of_declarations(&mut self) -> Vec<Declaration> {
+        self.consume_list_of_declarations()
+    }
+
+    /// https://www.w3.org/TR/selectors-4/#parse-a-selector
+    /// querySelectorなどに渡される、カンマで区切られたセレクタのリストを解釈する
+    pub fn parse_selector_list(&mut self) -> Result<Vec<Selector>, Error> {
+        let mut values = Vec::new();
+        while let Some(value) = self.consume_component_value() {
+            values.push(value);
+        }
+        parse_selector_list(&values)
+    }
+
     fn consume_list_of_rules(&mut self) -> Vec<QualifiedRule> {
         let mut rules = Vec::new();
 
@@ -109,26 +178,56 @@ impl CssParser {
                 None => return rules,
             };
             match token {
+                // トップレベルの空白と、<!--と-->は無視する
+                CssToken::Whitespace | CssToken::Cdo | CssToken::Cdc => {
+                    self.t.next();
+                }
                 // Atkeywordトークンが出てきた場合、他のCSSをインポートする
                 // @import、メディアクエリを表す@mediaなどのルールが始まることを表す
                 CssToken::AtKeyword(_keyword) => {
-                    let _rule = self.consume_quarified_rule();
                     // しかし、今回の実装では@から始まるルールはサポートしないので無視する。
+                    self.consume_at_rule();
                 }
                 _ => {
-                    // 1つのルールを解釈し、ベクタに追加する
-                    let rule = self.consume_quarified_rule();
-                    match rule {
-                        Some(r) => rules.push(r),
-                        None => return rules,
+                    // 1つのルールを解釈し、セレクタが有効な場合はベクタに追加する
+                    if let Some(r) = self.consume_quarified_rule() {
+                        rules.push(r);
                     }
                 }
             }
         }
     }
 
+    /// https://www.w3.org/TR/css-syntax-3/#consume-an-at-rule
+    /// @から始まるルールを、;か{}のブロックの終わりまで読み飛ばす
+    fn consume_at_rule(&mut self) {
+        self.t.next();
+
+        loop {
+            match self.t.peek() {
+                None => return,
+                Some(CssToken::SemiColon) => {
+                    self.t.next();
+                    return;
+                }
+                Some(CssToken::OpenCurly) => {
+                    self.consume_component_value();
+                    return;
+                }
+                Some(_) => {
+                    self.consume_component_value();
+                }
+            }
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-a-qualified-rule
+    /// https://www.w3.org/TR/css-syntax-3/#style-rules
+    /// {の前のコンポーネント値をセレクタのリストとして解釈する。
+    /// セレクタのリストが無効な場合は、ルール全体を無視してNoneを返す
     fn consume_quarified_rule(&mut self) -> Option<QualifiedRule> {
         let mut rule = QualifiedRule::new();
+        let mut prelude = Vec::new();
 
         loop {
             let token = match self.t.peek() {
@@ -140,53 +239,20 @@ impl CssParser {
                 CssToken::OpenCurly => {
                     assert_eq!(self.t.next(), Some(CssToken::OpenCurly));
                     rule.set_declarations(self.consume_list_of_declarations());
+                    let selectors = parse_selector_list(&prelude).ok()?;
+                    rule.set_selector(Selector::from_list(selectors));
                     return Some(rule);
                 }
                 _ => {
-                    rule.set_selector(self.consume_selector());
-                }
-            }
-        }
-    }
-
-    fn consume_selector(&mut self) -> Selector {
-        let token = match self.t.next() {
-            Some(t) => t,
-            None => panic!("should have a token but got None"),
-        };
-
-        match token {
-            CssToken::HashToken(value) => Selector::IdSelector(value[1..].to_string()),
-            CssToken::Delim(delim) => {
-                if delim == '.' {
-                    return Selector::ClassSelector(self.consume_ident());
-                }
-                panic!("Parse error: {:?} is an unexpected token.", token);
-            }
-            CssToken::Ident(ident) => {
-                // a:hoverのようなセレクタはタイプセレクタとして扱うため、
-                // もし、コロン(:)が出てきた場合は宣言ブロックの開始直前までトークンを進める
-                if self.t.peek() == Some(&CssToken::Colon) {
-                    while self.t.peek() != Some(&CssToken::OpenCurly) {
-                        self.t.next();
+                    if let Some(value) = self.consume_component_value() {
+                        prelude.push(value);
                     }
                 }
-                Selector::TypeSelector(ident.to_string())
-            }
-            CssToken::AtKeyword(_keyworkd) => {
-                // @から始まるルールを無視するために、宣言ブロックの開始直前までトークンを進める
-                while self.t.peek() != Some(&CssToken::OpenCurly) {
-                    self.t.next();
-                }
-                Selector::UnknownSelector
-            }
-            _ => {
-                self.t.next();
-                Selector::UnknownSelector
             }
         }
     }
 
+    /// https://www.w3.org/TR/css-syntax-3/#consume-a-list-of-declarations
     fn consume_list_of_declarations(&mut self) -> Vec<Declaration> {
         let mut declarations = Vec::new();
 
@@ -201,66 +267,139 @@ impl CssParser {
                     assert_eq!(self.t.next(), Some(CssToken::CloseCurly));
                     return declarations;
                 }
-                CssToken::SemiColon => {
-                    assert_eq!(self.t.next(), Some(CssToken::SemiColon));
+                CssToken::Whitespace | CssToken::SemiColon => {
                     // 1つの宣言が終了。何もしない。
+                    self.t.next();
                 }
                 CssToken::Ident(ref _ident) => {
-                    if let Some(declaration) = self.consume_declaration() {
+                    let values = self.consume_declaration_values();
+                    if let Some(declaration) = self.consume_declaration(values) {
                         declarations.push(declaration);
                     }
                 }
                 _ => {
-                    self.t.next();
+                    // パースエラー。次の宣言の直前まで読み飛ばす
+                    self.consume_declaration_values();
                 }
             }
         }
     }
 
-    fn consume_declaration(&mut self) -> Option<Declaration> {
-        if self.t.peek().is_none() {
-            return None;
+    /// 1つの宣言を構成するコンポーネント値を、;か}の直前まで読む
+    fn consume_declaration_values(&mut self) -> Vec<ComponentValue> {
+        let mut values = Vec::new();
+        while !matches!(
+            self.t.peek(),
+            None | Some(CssToken::SemiColon) | Some(CssToken::CloseCurly)
+        ) {
+            if let Some(value) = self.consume_component_value() {
+                values.push(value);
+            }
         }
+        values
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-declaration
+    /// valuesは、プロパティ名の識別子から始まるコンポーネント値のリスト
+    fn consume_declaration(&mut self, values: Vec<ComponentValue>) -> Option<Declaration> {
+        let mut values = values.into_iter().peekable();
 
         // Declaration構造体を初期化する
         let mut declaration = Declaration::new();
         // Declaration構造体のプロパティに識別子を設定する
-        declaration.set_property(self.consume_ident());
+        match values.next() {
+            Some(ComponentValue::PreservedToken(CssToken::Ident(property))) => {
+                declaration.set_property(property)
+            }
+            _ => return None,
+        }
 
         // もし次のトークンがコロンでない場合は、パースエラーなので、Noneを返す
-        match self.t.next() {
-            Some(token) => match token {
-                CssToken::Colon => {}
-                _ => return None,
-            },
-            _ => return None,
+        while values.peek().is_some_and(|v| v.is_whitespace()) {
+            values.next();
+        }
+        if values.next() != Some(ComponentValue::PreservedToken(CssToken::Colon)) {
+            return None;
         }
 
-        // Declaration構造体の値にコンポーネント値を設定する
-        declaration.set_value(self.consume_component_value());
+        // Declaration構造体の値に、前後の空白を除いたコンポーネント値のリストを設定する
+        let mut value: Vec<ComponentValue> = values.skip_while(|v| v.is_whitespace()).collect();
+        trim_trailing_whitespace(&mut value);
+
+        // 最後の2つのトークンが!とimportantの場合、それらを取り除き、重要な宣言にする
+        if let Some(ComponentValue::PreservedToken(CssToken::Ident(ident))) = value.last() {
+            if ident.eq_ignore_ascii_case("important") {
+                let mut rest = value[..value.len() - 1].to_vec();
+                trim_trailing_whitespace(&mut rest);
+                if rest.last() == Some(&ComponentValue::PreservedToken(CssToken::Delim('!'))) {
+                    rest.pop();
+                    trim_trailing_whitespace(&mut rest);
+                    value = rest;
+                    declaration.set_important(true);
+                }
+            }
+        }
+        declaration.set_value(value);
 
         Some(declaration)
     }
 
-    fn consume_ident(&mut self) -> String {
-        let token = match self.t.next() {
-            Some(t) => t,
-            None => panic!("should have a token but got None"),
+    /// https://www.w3.org/TR/css-syntax-3/#consume-a-component-value
+    fn consume_component_value(&mut self) -> Option<ComponentValue> {
+        let token = self.t.next()?;
+
+        let value = match token {
+            CssToken::OpenCurly | CssToken::OpenSquareBracket | CssToken::OpenParenthesis => {
+                self.consume_simple_block(token)
+            }
+            CssToken::Function(name) => ComponentValue::Function {
+                name,
+                values: self.consume_block_contents(CssToken::ColseParenthesis),
+            },
+            _ => ComponentValue::PreservedToken(token),
         };
+        Some(value)
+    }
 
-        match token {
-            CssToken::Ident(ref ident) => ident.to_string(),
-            _ => {
-                panic!("parse error: {:?} is an unexpected token.", token);
+    /// https://www.w3.org/TR/css-syntax-3/#consume-simple-block
+    fn consume_simple_block(&mut self, associated: CssToken) -> ComponentValue {
+        let ending = match associated {
+            CssToken::OpenCurly => CssToken::CloseCurly,
+            CssToken::OpenSquareBracket => CssToken::CloseSquareBracket,
+            _ => CssToken::ColseParenthesis,
+        };
+        ComponentValue::SimpleBlock {
+            associated,
+            values: self.consume_block_contents(ending),
+        }
+    }
+
+    /// endingのトークンまでのコンポーネント値を読む。endingは消費するが、結果には含めない。
+    /// 入力の最後に到達した場合は、そこでブロックを閉じる
+    fn consume_block_contents(&mut self, ending: CssToken) -> Vec<ComponentValue> {
+        let mut values = Vec::new();
+
+        loop {
+            match self.t.peek() {
+                None => return values,
+                Some(token) if *token == ending => {
+                    self.t.next();
+                    return values;
+                }
+                Some(_) => {
+                    if let Some(value) = self.consume_component_value() {
+                        values.push(value);
+                    }
+                }
             }
         }
     }
+}
 
-    /// https://www.w3.org/TR/css-syntax-3/#consume-a-component-value
-    fn consume_component_value(&mut self) -> ComponentValue {
-        self.t
-            .next()
-            .expect("should have a token in consume_component_value")
+/// コンポーネント値のリストの末尾の空白を取り除く
+fn trim_trailing_whitespace(values: &mut Vec<ComponentValue>) {
+    while values.last().is_some_and(|v| v.is_whitespace()) {
+        values.pop();
     }
 }
 
@@ -288,7 +427,9 @@ mod tests {
         rule.set_selector(Selector::TypeSelector("p".to_string()));
         let mut declaration = Declaration::new();
         declaration.set_property("color".to_string());
-        declaration.set_value(ComponentValue::Ident("red".to_string()));
+        declaration.set_value(vec![ComponentValue::PreservedToken(CssToken::Ident(
+            "red".to_string(),
+        ))]);
         rule.set_declarations(vec![declaration]);
 
         let expected = [rule];
@@ -311,7 +452,9 @@ mod tests {
         rule.set_selector(Selector::IdSelector("id".to_string()));
         let mut declaration = Declaration::new();
         declaration.set_property("color".to_string());
-        declaration.set_value(ComponentValue::Ident("red".to_string()));
+        declaration.set_value(vec![ComponentValue::PreservedToken(CssToken::Ident(
+            "red".to_string(),
+        ))]);
         rule.set_declarations(vec![declaration]);
 
         let expected = [rule];
@@ -334,7 +477,9 @@ mod tests {
         rule.set_selector(Selector::ClassSelector("class".to_string()));
         let mut declaration = Declaration::new();
         declaration.set_property("color".to_string());
-        declaration.set_value(ComponentValue::Ident("red".to_string()));
+        declaration.set_value(vec![ComponentValue::PreservedToken(CssToken::Ident(
+            "red".to_string(),
+        ))]);
         rule.set_declarations(vec![declaration]);
 
         let expected = [rule];
@@ -357,17 +502,21 @@ mod tests {
         rule1.set_selector(Selector::TypeSelector("p".to_string()));
         let mut declaration1 = Declaration::new();
         declaration1.set_property("content".to_string());
-        declaration1.set_value(ComponentValue::StringToken("Hey".to_string()));
+        declaration1.set_value(vec![ComponentValue::PreservedToken(CssToken::StringToken(
+            "Hey".to_string(),
+        ))]);
         rule1.set_declarations(vec![declaration1]);
 
         let mut rule2 = QualifiedRule::new();
         rule2.set_selector(Selector::TypeSelector("h1".to_string()));
         let mut declaration2 = Declaration::new();
         declaration2.set_property("font-size".to_string());
-        declaration2.set_value(ComponentValue::Number(40.0));
+        declaration2.set_value(vec![ComponentValue::PreservedToken(CssToken::Number(40.0))]);
         let mut declaration3 = Declaration::new();
         declaration3.set_property("color".to_string());
-        declaration3.set_value(ComponentValue::Ident("blue".to_string()));
+        declaration3.set_value(vec![ComponentValue::PreservedToken(CssToken::Ident(
+            "blue".to_string(),
+        ))]);
         rule2.set_declarations(vec![declaration2, declaration3]);
 
         let expected = [rule1, rule2];
@@ -379,4 +528,139 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_parse_selector_list() {
+        let parse =
+            |s: &str| CssParser::new(CssTokenizer::new(s.to_string())).parse_selector_list();
+
+        assert_eq!(
+            Ok(vec![
+                Selector::TypeSelector("p".to_string()),
+                Selector::ClassSelector("a".to_string()),
+                Selector::IdSelector("b".to_string()),
+                Selector::UniversalSelector,
+            ]),
+            parse("p, .a, #b, *")
+        );
+        assert!(parse("").is_err());
+        assert!(parse("p,").is_err());
+        assert_eq!(
+            Ok(vec![Selector::CompoundSelector(vec![
+                Selector::TypeSelector("p".to_string()),
+                Selector::ClassSelector("a".to_string()),
+            ])]),
+            parse("p.a")
+        );
+        assert!(parse("p.").is_err());
+    }
+
+    #[test]
+    fn test_whitespace_and_comments() {
+        let style = "<!-- /* a */ p/**/{color : red}\n\th1 { color:/* b */blue ; } -->".to_string();
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+
+        assert_eq!(2, cssom.rules.len());
+        assert_eq!(
+            Selector::TypeSelector("p".to_string()),
+            cssom.rules[0].selector
+        );
+        assert_eq!(
+            vec![ComponentValue::PreservedToken(CssToken::Ident(
+                "red".to_string()
+            ))],
+            cssom.rules[0].declarations[0].value
+        );
+        assert_eq!(
+            Selector::TypeSelector("h1".to_string()),
+            cssom.rules[1].selector
+        );
+        assert_eq!(
+            vec![ComponentValue::PreservedToken(CssToken::Ident(
+                "blue".to_string()
+            ))],
+            cssom.rules[1].declarations[0].value
+        );
+    }
+
+    #[test]
+    fn test_multi_value_declarations() {
+        let style = "p { margin: 0 auto; border : 1px solid red ! IMPORTANT ; width: calc(100% - (2 * 10px)); x: [a] {b}; 1: 2; color; font: 12px sans-serif }".to_string();
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+        let token = |t: CssToken| ComponentValue::PreservedToken(t);
+        let ident = |s: &str| token(CssToken::Ident(s.to_string()));
+        let px = |n: f64| token(CssToken::Dimension(n, "px".to_string()));
+        let declarations = &cssom.rules[0].declarations;
+
+        // コロンのない宣言と、識別子から始まらない宣言は無視される
+        let properties: Vec<&str> = declarations.iter().map(|d| d.property.as_str()).collect();
+        assert_eq!(vec!["margin", "border", "width", "x", "font"], properties);
+
+        assert_eq!(
+            vec![
+                token(CssToken::Number(0.0)),
+                token(CssToken::Whitespace),
+                ident("auto"),
+            ],
+            declarations[0].value
+        );
+        assert!(!declarations[0].important);
+
+        assert_eq!(
+            vec![
+                px(1.0),
+                token(CssToken::Whitespace),
+                ident("solid"),
+                token(CssToken::Whitespace),
+                ident("red"),
+            ],
+            declarations[1].value
+        );
+        assert!(declarations[1].important);
+
+        assert_eq!(
+            vec![ComponentValue::Function {
+                name: "calc".to_string(),
+                values: vec![
+                    token(CssToken::Percentage(100.0)),
+                    token(CssToken::Whitespace),
+                    token(CssToken::Delim('-')),
+                    token(CssToken::Whitespace),
+                    ComponentValue::SimpleBlock {
+                        associated: CssToken::OpenParenthesis,
+                        values: vec![
+                            token(CssToken::Number(2.0)),
+                            token(CssToken::Whitespace),
+                            token(CssToken::Delim('*')),
+                            token(CssToken::Whitespace),
+                            px(10.0),
+                        ],
+                    },
+                ],
+            }],
+            declarations[2].value
+        );
+
+        // {}ブロックの中の}では、宣言のリストは終わらない
+        assert_eq!(
+            vec![
+                ComponentValue::SimpleBlock {
+                    associated: CssToken::OpenSquareBracket,
+                    values: vec![ident("a")],
+                },
+                token(CssToken::Whitespace),
+                ComponentValue::SimpleBlock {
+                    associated: CssToken::OpenCurly,
+                    values: vec![ident("b")],
+                },
+            ],
+            declarations[3].value
+        );
+
+        assert_eq!(
+            vec![px(12.0), token(CssToken::Whitespace), ident("sans-serif")],
+            declarations[4].value
+        );
+        assert_eq!(1, cssom.rules.len());
+    }
 }
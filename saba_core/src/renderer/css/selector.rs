@@ -0,0 +1,1037 @@
+use crate::error::Error;
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::Element;
+use crate::renderer::dom::node::Namespace;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// https://www.w3.org/TR/selectors-4/#type-selectors
+    TypeSelector(String),
+    /// https://www.w3.org/TR/selectors-4/#class-html
+    ClassSelector(String),
+    /// https://www.w3.org/TR/selectors-4/#id-selectors
+    IdSelector(String),
+    /// https://www.w3.org/TR/selectors-4/#the-universal-selector
+    UniversalSelector,
+    /// https://www.w3.org/TR/selectors-4/#attribute-selectors
+    /// case_insensitiveは、[type="a" i]のように値の大文字と小文字を区別しないかどうか
+    AttributeSelector {
+        name: String,
+        operator: AttributeOperator,
+        value: String,
+        case_insensitive: bool,
+    },
+    /// https://www.w3.org/TR/selectors-4/#pseudo-classes
+    PseudoClass(PseudoClass),
+    /// https://www.w3.org/TR/selectors-4/#compound
+    /// 結合子を挟まずに並んだ単純セレクタ。例: a.button
+    CompoundSelector(Vec<Selector>),
+    /// https://www.w3.org/TR/selectors-4/#complex
+    /// 結合子で区切られたセレクタ。例: ul > li。
+    /// 左結合なので、div p > aは(div p) > aになる
+    ComplexSelector {
+        left: Box<Selector>,
+        combinator: Combinator,
+        right: Box<Selector>,
+    },
+    /// https://www.w3.org/TR/selectors-4/#selector-list
+    /// カンマで区切られたセレクタ。どれかに一致すればよい
+    SelectorList(Vec<Selector>),
+    /// パース中にエラーが起こったときに使用されるセレクタ
+    UnknownSelector,
+}
+
+/// https://www.w3.org/TR/selectors-4/#selector-combinator
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Combinator {
+    /// https://www.w3.org/TR/selectors-4/#descendant-combinators
+    /// 空白。例: div p
+    Descendant,
+    /// https://www.w3.org/TR/selectors-4/#child-combinators
+    /// >。例: ul > li
+    Child,
+    /// https://www.w3.org/TR/selectors-4/#adjacent-sibling-combinators
+    /// +。例: h1 + p
+    NextSibling,
+    /// https://www.w3.org/TR/selectors-4/#general-sibling-combinators
+    /// ~。例: h1 ~ p
+    SubsequentSibling,
+}
+
+/// https://www.w3.org/TR/selectors-4/#attribute-representation
+/// https://www.w3.org/TR/selectors-4/#attribute-substrings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttributeOperator {
+    /// [att]
+    Exists,
+    /// [att=val]
+    Equal,
+    /// [att~=val]
+    Includes,
+    /// [att|=val]
+    DashMatch,
+    /// [att^=val]
+    Prefix,
+    /// [att$=val]
+    Suffix,
+    /// [att*=val]
+    Substring,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PseudoClass {
+    /// https://www.w3.org/TR/selectors-4/#the-root-pseudo
+    Root,
+    /// https://www.w3.org/TR/selectors-4/#the-empty-pseudo
+    Empty,
+    /// https://www.w3.org/TR/selectors-4/#the-first-child-pseudo
+    FirstChild,
+    /// https://www.w3.org/TR/selectors-4/#the-last-child-pseudo
+    LastChild,
+    /// https://www.w3.org/TR/selectors-4/#the-only-child-pseudo
+    OnlyChild,
+    /// https://www.w3.org/TR/selectors-4/#the-nth-child-pseudo
+    /// an+bのaとb
+    NthChild(i64, i64),
+    /// https://www.w3.org/TR/selectors-4/#the-nth-last-child-pseudo
+    NthLastChild(i64, i64),
+    /// https://www.w3.org/TR/selectors-4/#negation
+    Not(Vec<Selector>),
+    /// https://www.w3.org/TR/selectors-4/#matches
+    Is(Vec<Selector>),
+    /// https://www.w3.org/TR/selectors-4/#zero-matches
+    /// 一致する要素は:is()と同じで、詳細度だけが異なる
+    Where(Vec<Selector>),
+    /// https://www.w3.org/TR/selectors-4/#the-hover-pseudo
+    Hover,
+    /// https://www.w3.org/TR/selectors-4/#the-link-pseudo
+    Link,
+    /// https://www.w3.org/TR/selectors-4/#visited-pseudo
+    /// この実装では訪問履歴を持たないので、どの要素にも一致しない
+    Visited,
+    /// https://www.w3.org/TR/selectors-4/#the-any-link-pseudo
+    AnyLink,
+}
+
+/// https://www.w3.org/TR/selectors-4/#specificity-rules
+/// 辞書順に比較するので、IDセレクタが1つでもあれば、クラスセレクタがいくつあっても勝つ
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Specificity {
+    /// IDセレクタの数
+    ids: u32,
+    /// クラスセレクタ、属性セレクタ、疑似クラスの数
+    classes: u32,
+    /// 型セレクタの数
+    types: u32,
+}
+
+impl Specificity {
+    pub fn new(ids: u32, classes: u32, types: u32) -> Self {
+        Self {
+            ids,
+            classes,
+            types,
+        }
+    }
+
+    pub fn ids(&self) -> u32 {
+        self.ids
+    }
+
+    pub fn classes(&self) -> u32 {
+        self.classes
+    }
+
+    pub fn types(&self) -> u32 {
+        self.types
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self::new(
+            self.ids + other.ids,
+            self.classes + other.classes,
+            self.types + other.types,
+        )
+    }
+}
+
+impl Selector {
+    /// セレクタのリストを1つのセレクタにする。要素が1つの場合は、その要素をそのまま使う
+    pub fn from_list(mut selectors: Vec<Selector>) -> Self {
+        if selectors.len() == 1 {
+            return selectors.remove(0);
+        }
+        Selector::SelectorList(selectors)
+    }
+
+    /// https://www.w3.org/TR/selectors-4/#specificity-rules
+    /// セレクタのリストの場合は、最も詳細度の高いセレクタの詳細度になる
+    pub fn specificity(&self) -> Specificity {
+        match self {
+            Selector::TypeSelector(_) => Specificity::new(0, 0, 1),
+            Selector::ClassSelector(_) | Selector::AttributeSelector { .. } => {
+                Specificity::new(0, 1, 0)
+            }
+            Selector::IdSelector(_) => Specificity::new(1, 0, 0),
+            Selector::PseudoClass(pseudo_class) => pseudo_class.specificity(),
+            Selector::CompoundSelector(selectors) => selectors
+                .iter()
+                .fold(Specificity::default(), |acc, s| acc.add(s.specificity())),
+            Selector::ComplexSelector { left, right, .. } => {
+                left.specificity().add(right.specificity())
+            }
+            Selector::SelectorList(selectors) => max_specificity(selectors),
+            Selector::UniversalSelector | Selector::UnknownSelector => Specificity::default(),
+        }
+    }
+
+    /// https://www.w3.org/TR/css-cascade-4/#cascade-specificity
+    /// nodeの要素がセレクタに一致する場合、その詳細度を返す。
+    /// セレクタのリストの場合は、一致したセレクタのうち最も詳細度の高いものを使う
+    pub fn matched_specificity(&self, dom: &Dom, node: NodeId) -> Option<Specificity> {
+        match self {
+            Selector::SelectorList(selectors) => selectors
+                .iter()
+                .filter_map(|s| s.matched_specificity(dom, node))
+                .max(),
+            _ if self.matches(dom, node) => Some(self.specificity()),
+            _ => None,
+        }
+    }
+
+    /// https://www.w3.org/TR/selectors-4/#match-against-element
+    /// nodeの要素がセレクタに一致するかどうか。要素でないノードは、どのセレクタにも一致しない
+    pub fn matches(&self, dom: &Dom, node: NodeId) -> bool {
+        let element = match dom.node(node).kind {
+            NodeKind::Element(ref e) => e,
+            _ => return false,
+        };
+
+        match self {
+            Selector::TypeSelector(type_name) => {
+                // HTMLの要素のタグ名は、大文字と小文字を区別しない
+                if element.namespace() == Namespace::Html {
+                    element.tag_name().eq_ignore_ascii_case(type_name)
+                } else {
+                    element.tag_name() == *type_name
+                }
+            }
+            Selector::ClassSelector(class_name) => {
+                // class属性は、空白で区切られたクラス名のリストとして扱う
+                element.get_attribute("class").is_some_and(|classes| {
+                    classes
+                        .split_ascii_whitespace()
+                        .any(|c| c == class_name.as_str())
+                })
+            }
+            Selector::IdSelector(id_name) => element.get_attribute("id").as_ref() == Some(id_name),
+            Selector::UniversalSelector => true,
+            Selector::AttributeSelector {
+                name,
+                operator,
+                value,
+                case_insensitive,
+            } => match element.get_attribute(name) {
+                Some(actual) => {
+                    matches_attribute_value(*operator, &actual, value, *case_insensitive)
+                }
+                None => false,
+            },
+            Selector::PseudoClass(pseudo_class) => pseudo_class.matches(dom, node, element),
+            Selector::CompoundSelector(selectors) => selectors.iter().all(|s| s.matches(dom, node)),
+            // 右から左に、つまり対象の要素に近い方から調べる
+            Selector::ComplexSelector {
+                left,
+                combinator,
+                right,
+            } => {
+                if !right.matches(dom, node) {
+                    return false;
+                }
+                match combinator {
+                    Combinator::Descendant => dom.ancestors(node).any(|a| left.matches(dom, a)),
+                    Combinator::Child => dom
+                        .node(node)
+                        .parent()
+                        .is_some_and(|p| left.matches(dom, p)),
+                    Combinator::NextSibling => previous_element_siblings(dom, node)
+                        .next()
+                        .is_some_and(|s| left.matches(dom, s)),
+                    Combinator::SubsequentSibling => {
+                        previous_element_siblings(dom, node).any(|s| left.matches(dom, s))
+                    }
+                }
+            }
+            Selector::SelectorList(selectors) => selectors.iter().any(|s| s.matches(dom, node)),
+            Selector::UnknownSelector => false,
+        }
+    }
+}
+
+impl PseudoClass {
+    /// :not()と:is()は引数の中で最も詳細度の高いセレクタの詳細度になり、:where()は常に0になる
+    fn specificity(&self) -> Specificity {
+        match self {
+            PseudoClass::Not(selectors) | PseudoClass::Is(selectors) => max_specificity(selectors),
+            PseudoClass::Where(_) => Specificity::default(),
+            _ => Specificity::new(0, 1, 0),
+        }
+    }
+
+    fn matches(&self, dom: &Dom, node: NodeId, element: &Element) -> bool {
+        match self {
+            PseudoClass::Root => dom
+                .node(node)
+                .parent()
+                .is_some_and(|p| dom.node(p).get_document().is_some()),
+            // コメントは子に含めない
+            PseudoClass::Empty => dom.children(node).all(|c| match dom.node(c).kind {
+                NodeKind::Element(_) => false,
+                NodeKind::Text(ref t) => t.is_empty(),
+                _ => true,
+            }),
+            PseudoClass::FirstChild => previous_element_siblings(dom, node).next().is_none(),
+            PseudoClass::LastChild => next_element_siblings(dom, node).next().is_none(),
+            PseudoClass::OnlyChild => {
+                previous_element_siblings(dom, node).next().is_none()
+                    && next_element_siblings(dom, node).next().is_none()
+            }
+            PseudoClass::NthChild(a, b) => {
+                matches_an_plus_b(*a, *b, previous_element_siblings(dom, node).count() + 1)
+            }
+            PseudoClass::NthLastChild(a, b) => {
+                matches_an_plus_b(*a, *b, next_element_siblings(dom, node).count() + 1)
+            }
+            PseudoClass::Not(selectors) => !selectors.iter().any(|s| s.matches(dom, node)),
+            PseudoClass::Is(selectors) | PseudoClass::Where(selectors) => {
+                selectors.iter().any(|s| s.matches(dom, node))
+            }
+            // ポインタが子孫の上にある場合も、その要素はホバーされている
+            PseudoClass::Hover => dom
+                .hovered()
+                .is_some_and(|h| h == node || dom.ancestors(h).any(|a| a == node)),
+            PseudoClass::Link | PseudoClass::AnyLink => {
+                element.namespace() == Namespace::Html
+                    && matches!(element.tag_name().as_str(), "a" | "area")
+                    && element.has_attribute("href")
+            }
+            PseudoClass::Visited => false,
+        }
+    }
+}
+
+fn max_specificity(selectors: &[Selector]) -> Specificity {
+    selectors
+        .iter()
+        .map(|s| s.specificity())
+        .max()
+        .unwrap_or_default()
+}
+
+/// nodeの前にある兄弟の要素を、近い順に返す
+fn previous_element_siblings(dom: &Dom, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+    core::iter::successors(dom.node(node).previuos_sibling(), |n| {
+        dom.node(*n).previuos_sibling()
+    })
+    .filter(|n| matches!(dom.node(*n).kind, NodeKind::Element(_)))
+}
+
+/// nodeの後にある兄弟の要素を、近い順に返す
+fn next_element_siblings(dom: &Dom, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+    core::iter::successors(dom.node(node).next_sibling(), |n| {
+        dom.node(*n).next_sibling()
+    })
+    .filter(|n| matches!(dom.node(*n).kind, NodeKind::Element(_)))
+}
+
+/// https://www.w3.org/TR/selectors-4/#attribute-representation
+fn matches_attribute_value(
+    operator: AttributeOperator,
+    actual: &str,
+    value: &str,
+    case_insensitive: bool,
+) -> bool {
+    let (actual, value) = if case_insensitive {
+        (actual.to_ascii_lowercase(), value.to_ascii_lowercase())
+    } else {
+        (actual.to_string(), value.to_string())
+    };
+
+    match operator {
+        AttributeOperator::Exists => true,
+        AttributeOperator::Equal => actual == value,
+        // 空白を含む値や空の値は、空白で区切られたどの単語にも一致しない
+        AttributeOperator::Includes => {
+            !value.is_empty()
+                && !value.contains(|c: char| c.is_ascii_whitespace())
+                && actual.split_ascii_whitespace().any(|w| w == value)
+        }
+        AttributeOperator::DashMatch => {
+            actual == value || actual.starts_with(&format!("{}-", value))
+        }
+        AttributeOperator::Prefix => !value.is_empty() && actual.starts_with(&value),
+        AttributeOperator::Suffix => !value.is_empty() && actual.ends_with(&value),
+        AttributeOperator::Substring => !value.is_empty() && actual.contains(&value),
+    }
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#anb-microsyntax
+/// 0以上の整数nについて、an+bがindexになるものがあるかどうか
+fn matches_an_plus_b(a: i64, b: i64, index: usize) -> bool {
+    let diff = index as i64 - b;
+    if a == 0 {
+        return diff == 0;
+    }
+    diff % a == 0 && diff / a >= 0
+}
+
+/// https://www.w3.org/TR/selectors-4/#parse-selector
+/// コンポーネント値のリストを、カンマで区切られたセレクタのリストとして解釈する。
+/// サポートしていないセレクタを含む場合は、リスト全体が無効になる
+pub fn parse_selector_list(values: &[ComponentValue]) -> Result<Vec<Selector>, Error> {
+    let mut parser = SelectorParser { values, pos: 0 };
+    let mut selectors = Vec::new();
+
+    loop {
+        parser.skip_whitespace();
+        selectors.push(parser.parse_complex_selector()?);
+        parser.skip_whitespace();
+        match parser.next() {
+            None => return Ok(selectors),
+            Some(ComponentValue::PreservedToken(CssToken::Comma)) => {}
+            Some(value) => return Err(unexpected(value)),
+        }
+    }
+}
+
+fn unexpected(value: &ComponentValue) -> Error {
+    Error::UnexpectedInput(format!("{:?} is an unexpected value in a selector", value))
+}
+
+/// https://www.w3.org/TR/selectors-4/#grammar
+struct SelectorParser<'a> {
+    values: &'a [ComponentValue],
+    pos: usize,
+}
+
+impl<'a> SelectorParser<'a> {
+    fn peek(&self) -> Option<&'a ComponentValue> {
+        self.values.get(self.pos)
+    }
+
+    fn peek_token(&self) -> Option<&'a CssToken> {
+        match self.peek() {
+            Some(ComponentValue::PreservedToken(token)) => Some(token),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a ComponentValue> {
+        let value = self.peek();
+        if value.is_some() {
+            self.pos += 1;
+        }
+        value
+    }
+
+    /// 空白を読み飛ばし、1つでも読み飛ばしたかどうかを返す
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(|v| v.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.pos != start
+    }
+
+    /// <complex-selector> = <compound-selector> [ <combinator>? <compound-selector> ]*
+    fn parse_complex_selector(&mut self) -> Result<Selector, Error> {
+        let mut selector = self.parse_compound_selector()?;
+
+        loop {
+            let has_whitespace = self.skip_whitespace();
+            let combinator = match self.peek_token() {
+                None | Some(CssToken::Comma) => {
+                    // 末尾の空白は、次のカンマか入力の最後の前の空白として扱う
+                    if has_whitespace {
+                        self.pos -= 1;
+                    }
+                    return Ok(selector);
+                }
+                Some(CssToken::Delim('>')) => Combinator::Child,
+                Some(CssToken::Delim('+')) => Combinator::NextSibling,
+                Some(CssToken::Delim('~')) => Combinator::SubsequentSibling,
+                _ if has_whitespace => Combinator::Descendant,
+                _ => return Err(unexpected(self.peek().expect("should have a value"))),
+            };
+            if combinator != Combinator::Descendant {
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+
+            selector = Selector::ComplexSelector {
+                left: Box::new(selector),
+                combinator,
+                right: Box::new(self.parse_compound_selector()?),
+            };
+        }
+    }
+
+    /// <compound-selector> = [ <type-selector>? <subclass-selector>* <pseudo-class-selector>* ]!
+    fn parse_compound_selector(&mut self) -> Result<Selector, Error> {
+        let mut selectors = Vec::new();
+
+        match self.peek_token() {
+            Some(CssToken::Ident(name)) => {
+                self.pos += 1;
+                selectors.push(Selector::TypeSelector(name.clone()));
+            }
+            Some(CssToken::Delim('*')) => {
+                self.pos += 1;
+                selectors.push(Selector::UniversalSelector);
+            }
+            _ => {}
+        }
+
+        loop {
+            let selector = match self.peek() {
+                Some(ComponentValue::PreservedToken(CssToken::HashToken(value))) => {
+                    self.pos += 1;
+                    // #の後は識別子でなければならないので、#1aのようなものはIDセレクタにならない
+                    let id = &value[1..];
+                    if id.starts_with(|c: char| c.is_ascii_digit())
+                        || (id.starts_with('-')
+                            && id[1..].starts_with(|c: char| c.is_ascii_digit()))
+                    {
+                        return Err(Error::UnexpectedInput(format!(
+                            "{:?} is not a valid id selector",
+                            value
+                        )));
+                    }
+                    Selector::IdSelector(id.to_string())
+                }
+                Some(ComponentValue::PreservedToken(CssToken::Delim('.'))) => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(ComponentValue::PreservedToken(CssToken::Ident(name))) => {
+                            Selector::ClassSelector(name.clone())
+                        }
+                        value => {
+                            return Err(Error::UnexpectedInput(format!(
+                                "expected a class name but got {:?}",
+                                value
+                            )))
+                        }
+                    }
+                }
+                Some(ComponentValue::SimpleBlock {
+                    associated: CssToken::OpenSquareBracket,
+                    values,
+                }) => {
+                    self.pos += 1;
+                    parse_attribute_selector(values)?
+                }
+                Some(ComponentValue::PreservedToken(CssToken::Colon)) => {
+                    self.pos += 1;
+                    Selector::PseudoClass(self.parse_pseudo_class()?)
+                }
+                _ => break,
+            };
+            selectors.push(selector);
+        }
+
+        match selectors.len() {
+            0 => Err(match self.peek() {
+                Some(value) => unexpected(value),
+                None => Error::UnexpectedInput("expected a selector but got nothing".to_string()),
+            }),
+            1 => Ok(selectors.remove(0)),
+            _ => Ok(Selector::CompoundSelector(selectors)),
+        }
+    }
+
+    /// https://www.w3.org/TR/selectors-4/#pseudo-classes
+    /// :の直後から読む。::beforeのような疑似要素はサポートしない
+    fn parse_pseudo_class(&mut self) -> Result<PseudoClass, Error> {
+        match self.next() {
+            Some(ComponentValue::PreservedToken(CssToken::Ident(name))) => {
+                match name.to_ascii_lowercase().as_str() {
+                    "root" => Ok(PseudoClass::Root),
+                    "empty" => Ok(PseudoClass::Empty),
+                    "first-child" => Ok(PseudoClass::FirstChild),
+                    "last-child" => Ok(PseudoClass::LastChild),
+                    "only-child" => Ok(PseudoClass::OnlyChild),
+                    "hover" => Ok(PseudoClass::Hover),
+                    "link" => Ok(PseudoClass::Link),
+                    "visited" => Ok(PseudoClass::Visited),
+                    "any-link" => Ok(PseudoClass::AnyLink),
+                    _ => Err(Error::UnexpectedInput(format!(
+                        "pseudo-class :{} is not supported yet",
+                        name
+                    ))),
+                }
+            }
+            Some(ComponentValue::Function { name, values }) => {
+                match name.to_ascii_lowercase().as_str() {
+                    "nth-child" => {
+                        let (a, b) = parse_an_plus_b(values)?;
+                        Ok(PseudoClass::NthChild(a, b))
+                    }
+                    "nth-last-child" => {
+                        let (a, b) = parse_an_plus_b(values)?;
+                        Ok(PseudoClass::NthLastChild(a, b))
+                    }
+                    "not" => Ok(PseudoClass::Not(parse_selector_list(values)?)),
+                    "is" => Ok(PseudoClass::Is(parse_selector_list(values)?)),
+                    "where" => Ok(PseudoClass::Where(parse_selector_list(values)?)),
+                    _ => Err(Error::UnexpectedInput(format!(
+                        "pseudo-class :{}() is not supported yet",
+                        name
+                    ))),
+                }
+            }
+            value => Err(Error::UnexpectedInput(format!(
+                "expected a pseudo-class but got {:?}",
+                value
+            ))),
+        }
+    }
+}
+
+/// https://www.w3.org/TR/selectors-4/#attribute-selectors
+/// [と]の間のコンポーネント値を解釈する
+fn parse_attribute_selector(values: &[ComponentValue]) -> Result<Selector, Error> {
+    let mut parser = SelectorParser { values, pos: 0 };
+
+    parser.skip_whitespace();
+    let name = match parser.next() {
+        Some(ComponentValue::PreservedToken(CssToken::Ident(name))) => name.clone(),
+        value => {
+            return Err(Error::UnexpectedInput(format!(
+                "expected an attribute name but got {:?}",
+                value
+            )))
+        }
+    };
+    parser.skip_whitespace();
+
+    let operator = match parser.next() {
+        None => {
+            return Ok(Selector::AttributeSelector {
+                name,
+                operator: AttributeOperator::Exists,
+                value: String::new(),
+                case_insensitive: false,
+            })
+        }
+        Some(ComponentValue::PreservedToken(CssToken::Delim('='))) => AttributeOperator::Equal,
+        Some(ComponentValue::PreservedToken(CssToken::Delim(c))) => {
+            let operator = match c {
+                '~' => AttributeOperator::Includes,
+                '|' => AttributeOperator::DashMatch,
+                '^' => AttributeOperator::Prefix,
+                '$' => AttributeOperator::Suffix,
+                '*' => AttributeOperator::Substring,
+                _ => return Err(unexpected(&values[parser.pos - 1])),
+            };
+            // ~=などの2文字は、間に空白を挟まずに続く
+            match parser.next() {
+                Some(ComponentValue::PreservedToken(CssToken::Delim('='))) => operator,
+                value => {
+                    return Err(Error::UnexpectedInput(format!(
+                        "expected = but got {:?}",
+                        value
+                    )))
+                }
+            }
+        }
+        Some(value) => return Err(unexpected(value)),
+    };
+    parser.skip_whitespace();
+
+    let value = match parser.next() {
+        Some(ComponentValue::PreservedToken(CssToken::Ident(v)))
+        | Some(ComponentValue::PreservedToken(CssToken::StringToken(v))) => v.clone(),
+        value => {
+            return Err(Error::UnexpectedInput(format!(
+                "expected an attribute value but got {:?}",
+                value
+            )))
+        }
+    };
+    parser.skip_whitespace();
+
+    // https://www.w3.org/TR/selectors-4/#attribute-case
+    let case_insensitive = match parser.next() {
+        None => false,
+        Some(ComponentValue::PreservedToken(CssToken::Ident(modifier))) => {
+            let case_insensitive = match modifier.to_ascii_lowercase().as_str() {
+                "i" => true,
+                "s" => false,
+                _ => return Err(unexpected(&values[parser.pos - 1])),
+            };
+            parser.skip_whitespace();
+            if let Some(value) = parser.next() {
+                return Err(unexpected(value));
+            }
+            case_insensitive
+        }
+        Some(value) => return Err(unexpected(value)),
+    };
+
+    Ok(Selector::AttributeSelector {
+        name,
+        operator,
+        value,
+        case_insensitive,
+    })
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#anb-microsyntax
+/// odd、even、3、2n+1、-n+3のような値を、aとbの組にする
+fn parse_an_plus_b(values: &[ComponentValue]) -> Result<(i64, i64), Error> {
+    let invalid = || Error::UnexpectedInput(format!("{:?} is not a valid an+b", values));
+    let integer = |n: f64| {
+        if n == (n as i64) as f64 {
+            Ok(n as i64)
+        } else {
+            Err(invalid())
+        }
+    };
+
+    // 2n+1は<dimension-token>と<number-token>に、2n-1は1つの<dimension-token>になるなど、
+    // トークンの分かれ方が複雑なので、空白を除いた文字列に戻してから解釈する
+    let mut s = String::new();
+    for value in values {
+        match value {
+            ComponentValue::PreservedToken(CssToken::Whitespace) => {}
+            ComponentValue::PreservedToken(CssToken::Ident(name)) => s.push_str(name),
+            // 2n+1の+1は符号付きの<number-token>になるので、符号を戻す。
+            // -n + 3のように符号が別のトークンになっている場合は、戻さない
+            ComponentValue::PreservedToken(CssToken::Number(n)) => {
+                if s.ends_with(['+', '-']) {
+                    s.push_str(&format!("{}", integer(*n)?))
+                } else {
+                    s.push_str(&format!("{:+}", integer(*n)?))
+                }
+            }
+            ComponentValue::PreservedToken(CssToken::Dimension(n, unit)) => {
+                s.push_str(&format!("{}{}", integer(*n)?, unit))
+            }
+            ComponentValue::PreservedToken(CssToken::Delim(c @ ('+' | '-'))) => s.push(*c),
+            _ => return Err(invalid()),
+        }
+    }
+    let s = s.to_ascii_lowercase();
+
+    match s.as_str() {
+        "odd" => return Ok((2, 1)),
+        "even" => return Ok((2, 0)),
+        _ => {}
+    }
+
+    let (a, b) = match s.split_once('n') {
+        Some((a, b)) => {
+            let a = match a {
+                "" | "+" => 1,
+                "-" => -1,
+                _ => a.parse::<i64>().map_err(|_| invalid())?,
+            };
+            // bには符号が必要
+            let b = match b {
+                "" => 0,
+                _ if b.starts_with(['+', '-']) => b.parse::<i64>().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            };
+            (a, b)
+        }
+        None => (0, s.parse::<i64>().map_err(|_| invalid())?),
+    };
+    Ok((a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::dom::api::query_selector_all;
+    use crate::renderer::html::attribute::Attribute;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use core::cell::RefCell;
+
+    fn parse(selectors: &str) -> Result<Vec<Selector>, Error> {
+        CssParser::new(CssTokenizer::new(selectors.to_string())).parse_selector_list()
+    }
+
+    fn type_selector(name: &str) -> Box<Selector> {
+        Box::new(Selector::TypeSelector(name.to_string()))
+    }
+
+    fn create_dom(html: &str) -> (Rc<RefCell<Dom>>, NodeId) {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().dom();
+        let document = window.borrow().document();
+        (dom, document)
+    }
+
+    /// セレクタに一致する要素のidを、ツリー順に並べる
+    fn matched_ids(dom: &Dom, document: NodeId, selectors: &str) -> Vec<String> {
+        query_selector_all(dom, document, selectors)
+            .expect("failed to parse selectors")
+            .iter()
+            .filter_map(|n| dom.node(*n).get_element()?.get_attribute("id"))
+            .collect()
+    }
+
+    #[test]
+    fn test_selector_matches() {
+        let mut attr = Attribute::new();
+        for c in "class".chars() {
+            attr.add_char(c, true);
+        }
+        for c in " a\tb ".chars() {
+            attr.add_char(c, false);
+        }
+        let mut dom = Dom::new();
+        let div = dom.create_node(NodeKind::Element(Element::new("div", vec![attr])));
+
+        assert!(Selector::TypeSelector("div".to_string()).matches(&dom, div));
+        assert!(Selector::TypeSelector("DIV".to_string()).matches(&dom, div));
+        assert!(!Selector::TypeSelector("p".to_string()).matches(&dom, div));
+        // class属性の中のクラス名のどれかに一致すればよい
+        assert!(Selector::ClassSelector("a".to_string()).matches(&dom, div));
+        assert!(Selector::ClassSelector("b".to_string()).matches(&dom, div));
+        assert!(!Selector::ClassSelector("a b".to_string()).matches(&dom, div));
+        assert!(!Selector::IdSelector("a".to_string()).matches(&dom, div));
+        assert!(Selector::UniversalSelector.matches(&dom, div));
+
+        // SVGの要素のタグ名は大文字と小文字を区別する
+        let svg = dom.create_node(NodeKind::Element(Element::new_with_namespace(
+            "clipPath",
+            Vec::new(),
+            Namespace::Svg,
+        )));
+        assert!(Selector::TypeSelector("clipPath".to_string()).matches(&dom, svg));
+        assert!(!Selector::TypeSelector("clippath".to_string()).matches(&dom, svg));
+    }
+
+    #[test]
+    fn test_parse_complex_selectors() {
+        assert_eq!(
+            Ok(vec![Selector::CompoundSelector(vec![
+                Selector::TypeSelector("a".to_string()),
+                Selector::ClassSelector("button".to_string()),
+                Selector::IdSelector("x".to_string()),
+            ])]),
+            parse("a.button#x")
+        );
+        assert_eq!(
+            Ok(vec![Selector::ComplexSelector {
+                left: Box::new(Selector::ComplexSelector {
+                    left: type_selector("div"),
+                    combinator: Combinator::Descendant,
+                    right: type_selector("ul"),
+                }),
+                combinator: Combinator::Child,
+                right: type_selector("li"),
+            }]),
+            parse(" div  ul>li ")
+        );
+        assert_eq!(
+            Ok(vec![
+                Selector::ComplexSelector {
+                    left: type_selector("h1"),
+                    combinator: Combinator::NextSibling,
+                    right: type_selector("p"),
+                },
+                Selector::ComplexSelector {
+                    left: type_selector("h1"),
+                    combinator: Combinator::SubsequentSibling,
+                    right: Box::new(Selector::UniversalSelector),
+                },
+            ]),
+            parse("h1 + p, h1~*")
+        );
+        assert!(parse("div >").is_err());
+        assert!(parse("> p").is_err());
+        assert!(parse("#1a").is_err());
+        assert!(parse("p::before").is_err());
+        assert!(parse("p:unknown").is_err());
+    }
+
+    #[test]
+    fn test_parse_attribute_and_pseudo_class() {
+        let attribute =
+            |name: &str, operator, value: &str, case_insensitive| Selector::AttributeSelector {
+                name: name.to_string(),
+                operator,
+                value: value.to_string(),
+                case_insensitive,
+            };
+        assert_eq!(
+            Ok(vec![
+                attribute("href", AttributeOperator::Exists, "", false),
+                attribute("type", AttributeOperator::Equal, "text", false),
+                attribute("class", AttributeOperator::Includes, "a", false),
+                attribute("lang", AttributeOperator::DashMatch, "en", false),
+                attribute("href", AttributeOperator::Prefix, "http", true),
+                attribute("href", AttributeOperator::Suffix, ".png", false),
+                attribute("title", AttributeOperator::Substring, "x", false),
+            ]),
+            parse("[href], [type=text], [ class ~= \"a\" ], [lang|=en], [href^=http i], [href$='.png' s], [title*=x]")
+        );
+        assert!(parse("[href=]").is_err());
+        assert!(parse("[href ~ = a]").is_err());
+        assert!(parse("[href=a x]").is_err());
+
+        assert_eq!(
+            Ok(vec![
+                Selector::CompoundSelector(vec![
+                    Selector::TypeSelector("li".to_string()),
+                    Selector::PseudoClass(PseudoClass::FirstChild),
+                ]),
+                Selector::PseudoClass(PseudoClass::NthChild(2, 1)),
+                Selector::PseudoClass(PseudoClass::NthChild(2, -1)),
+                Selector::PseudoClass(PseudoClass::NthChild(-1, 3)),
+                Selector::PseudoClass(PseudoClass::NthChild(0, 5)),
+                Selector::PseudoClass(PseudoClass::NthChild(1, 0)),
+                Selector::PseudoClass(PseudoClass::NthLastChild(2, 0)),
+                Selector::PseudoClass(PseudoClass::Not(vec![
+                    Selector::ClassSelector("a".to_string()),
+                    Selector::TypeSelector("p".to_string()),
+                ])),
+            ]),
+            parse("li:first-child, :nth-child(odd), :nth-child(2n-1), :nth-child(-n + 3), :nth-child(5), :NTH-CHILD(n), :nth-last-child(even), :not(.a, p)")
+        );
+        // 型セレクタは複合セレクタの先頭にしか置けない
+        assert!(parse(":first-child li").is_ok());
+        assert!(parse(":first-child(li)").is_err());
+        assert!(parse(":nth-child(2n+)").is_err());
+        assert!(parse(":nth-child(n2)").is_err());
+        assert!(parse(":nth-child(1.5)").is_err());
+    }
+
+    #[test]
+    fn test_match_combinators() {
+        let (dom, document) = create_dom(
+            "<div id=\"d\"><ul id=\"u\"><li id=\"l1\"><p id=\"p1\"></p></li><li id=\"l2\"></li><!-- c --><li id=\"l3\"></li></ul><h1 id=\"h\"></h1>text<p id=\"p2\"></p><p id=\"p3\"></p></div>",
+        );
+        let dom = dom.borrow();
+
+        assert_eq!(vec!["p1"], matched_ids(&dom, document, "div p:first-child"));
+        assert_eq!(
+            vec!["l1", "l2", "l3"],
+            matched_ids(&dom, document, "div > ul > li")
+        );
+        assert!(matched_ids(&dom, document, "div > li").is_empty());
+        // テキストとコメントは兄弟の要素として数えない
+        assert_eq!(vec!["l3"], matched_ids(&dom, document, "#l2 + li"));
+        assert_eq!(vec!["p2"], matched_ids(&dom, document, "h1 + p"));
+        assert_eq!(vec!["p2", "p3"], matched_ids(&dom, document, "ul ~ p"));
+        assert_eq!(vec!["p3"], matched_ids(&dom, document, "div ul ~ p + p"));
+        // 一致しうる祖先が複数ある場合も、すべて調べる
+        assert_eq!(vec!["p1"], matched_ids(&dom, document, "div > ul li p"));
+    }
+
+    #[test]
+    fn test_match_attributes_and_pseudo_classes() {
+        let (dom, document) = create_dom(
+            "<ul id=\"u\"><li id=\"a\" lang=\"en-US\" class=\"x y\"></li><li id=\"b\" lang=\"EN\"></li><li id=\"c\" title=\"hello\"></li><li id=\"d\"><a id=\"e\" href=\"/\">link</a><a id=\"f\">anchor</a></li></ul>",
+        );
+        let mut dom = dom.borrow_mut();
+
+        assert_eq!(vec!["a", "b"], matched_ids(&dom, document, "[lang]"));
+        assert_eq!(vec!["a"], matched_ids(&dom, document, "[lang|=en]"));
+        assert_eq!(vec!["a", "b"], matched_ids(&dom, document, "[lang|=en i]"));
+        assert_eq!(vec!["b"], matched_ids(&dom, document, "[lang=EN]"));
+        assert_eq!(vec!["a"], matched_ids(&dom, document, "[class~=y]"));
+        assert!(matched_ids(&dom, document, "[class~=\"x y\"]").is_empty());
+        assert_eq!(
+            vec!["c"],
+            matched_ids(&dom, document, "[title^=he][title$=lo][title*=ll]")
+        );
+        assert!(matched_ids(&dom, document, "[title^=\"\"]").is_empty());
+
+        assert_eq!(vec!["a"], matched_ids(&dom, document, "li:first-child"));
+        assert_eq!(vec!["d"], matched_ids(&dom, document, "li:last-child"));
+        assert_eq!(
+            vec!["a", "c"],
+            matched_ids(&dom, document, "li:nth-child(odd)")
+        );
+        assert_eq!(
+            vec!["a", "b"],
+            matched_ids(&dom, document, "li:nth-child(-n+2)")
+        );
+        assert_eq!(
+            vec!["c"],
+            matched_ids(&dom, document, "li:nth-last-child(2)")
+        );
+        assert_eq!(
+            vec!["b", "c", "d"],
+            matched_ids(&dom, document, "li:not(.x)")
+        );
+        assert_eq!(vec!["c", "d"], matched_ids(&dom, document, "li:is(#c, #d)"));
+        assert_eq!(
+            vec!["a"],
+            matched_ids(&dom, document, ":where(ul) > :not(:not(.x))")
+        );
+        assert_eq!(vec!["a", "b", "c"], matched_ids(&dom, document, "li:empty"));
+        assert_eq!(vec!["u"], matched_ids(&dom, document, "body > :only-child"));
+        assert_eq!(vec!["e"], matched_ids(&dom, document, "a:link"));
+        assert!(matched_ids(&dom, document, ":visited").is_empty());
+        assert!(matched_ids(&dom, document, "html:not(:root)").is_empty());
+
+        // ホバーされている要素とその祖先が:hoverに一致する
+        assert!(matched_ids(&dom, document, "li:hover").is_empty());
+        let e = get_element_by_id(&dom, document, &"e".to_string()).unwrap();
+        dom.set_hovered(Some(e));
+        assert_eq!(
+            vec!["u", "d", "e"],
+            matched_ids(&dom, document, "[id]:hover")
+        );
+    }
+
+    #[test]
+    fn test_specificity() {
+        let specificity = |s: &str| Selector::from_list(parse(s).unwrap()).specificity();
+
+        assert_eq!(Specificity::new(0, 0, 0), specificity("*"));
+        assert_eq!(Specificity::new(0, 0, 2), specificity("ul li"));
+        assert_eq!(Specificity::new(0, 1, 1), specificity("a:hover"));
+        assert_eq!(Specificity::new(0, 2, 1), specificity("a.button[href]"));
+        assert_eq!(Specificity::new(1, 0, 1), specificity("#nav > li"));
+        assert_eq!(Specificity::new(1, 0, 1), specificity("li:not(#a, .b)"));
+        assert_eq!(Specificity::new(0, 1, 1), specificity("li:nth-child(2n+1)"));
+        assert_eq!(Specificity::new(0, 0, 1), specificity("li:where(#a, .b)"));
+        // セレクタのリストは最も詳細度の高いものになる
+        assert_eq!(Specificity::new(1, 0, 0), specificity("p, .a, #b"));
+        assert!(Specificity::new(1, 0, 0) > Specificity::new(0, 10, 10));
+
+        // 要素に一致したセレクタの中で最も詳細度の高いものを使う
+        let (dom, document) = create_dom("<p id=\"a\" class=\"b\"></p>");
+        let dom = dom.borrow();
+        let p = get_element_by_id(&dom, document, &"a".to_string()).unwrap();
+        let selector = Selector::from_list(parse("#x, p.b, p").unwrap());
+        assert_eq!(
+            Some(Specificity::new(0, 1, 1)),
+            selector.matched_specificity(&dom, p)
+        );
+        assert_eq!(
+            None,
+            Selector::from_list(parse("#x, div").unwrap()).matched_specificity(&dom, p)
+        );
+    }
+}
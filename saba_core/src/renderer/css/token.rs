@@ -4,15 +4,33 @@ use alloc::vec::Vec;
 #[derive(Debug, Clone, PartialEq)]
 pub enum CssToken {
     /// https://www.w3.org/TR/css-syntax-3/#typedef-hash-token
+    /// この実装では、値は先頭の#を含む
     HashToken(String),
     /// https://www.w3.org/TR/css-syntax-3/#typedef-delim-token
     Delim(char),
     /// https://www.w3.org/TR/css-syntax-3/#typedef-number-token
     Number(f64),
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-percentage-token
+    Percentage(f64),
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-dimension-token
+    /// 数値と単位。例: 12px
+    Dimension(f64, String),
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-whitespace-token
+    Whitespace,
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-cdo-token
+    Cdo,
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-cdc-token
+    Cdc,
     /// https://www.w3.org/TR/css-syntax-3/#typedef-colon-token
     Colon,
     /// https://www.w3.org/TR/css-syntax-3/#typedef-semicolon-token
     SemiColon,
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-comma-token
+    Comma,
+    /// https://www.w3.org/TR/css-syntax-3/#tokendef-open-square
+    OpenSquareBracket,
+    /// https://www.w3.org/TR/css-syntax-3/#tokendef-close-square
+    CloseSquareBracket,
     /// https://www.w3.org/TR/css-syntax-3/#typedef-open-token
     OpenParenthesis,
     /// https://www.w3.org/TR/css-syntax-3/#typedef-close-token
@@ -23,176 +41,528 @@ pub enum CssToken {
     CloseCurly,
     /// https://www.w3.org/TR/css-syntax-3/#typedef-ident-curly
     Ident(String),
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-function-token
+    /// 名前の直後に(が続くもの。値は名前だけで、(は含まない。例: rgb(
+    Function(String),
     /// https://www.w3.org/TR/css-syntax-3/#typedef-string-curly
     StringToken(String),
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-bad-string-token
+    /// 閉じる引用符の前に改行が現れた文字列
+    BadString,
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-url-token
+    /// 引用符で囲まれていないurl()
+    Url(String),
+    /// https://www.w3.org/TR/css-syntax-3/#typedef-bad-url-token
+    BadUrl,
     /// https://www.w3.org/TR/css-syntax-3/#typedef-at-keyword-token
     AtKeyword(String),
 }
 
+/// トークンが始まる位置。行と列はどちらも1から数える
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    line: usize,
+    column: usize,
+}
+
+impl SourceLocation {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CssTokenizer {
     pos: usize,
     input: Vec<char>,
+    /// 最後に行と列を計算した位置
+    location_pos: usize,
+    /// location_posの行と列
+    location: SourceLocation,
 }
 
 impl CssTokenizer {
     pub fn new(css: String) -> Self {
         Self {
             pos: 0,
-            input: css.chars().collect(),
+            input: preprocess(&css),
+            location_pos: 0,
+            location: SourceLocation::new(1, 1),
         }
     }
 
-    /// https://www.w3.org/TR/css-syntax-3/#consume-a-string-token
-    fn consume_string_token(&mut self) -> String {
-        let mut s = String::new();
+    /// 次のトークンと、そのトークンが始まる位置を返す
+    pub fn next_with_location(&mut self) -> Option<(CssToken, SourceLocation)> {
+        // コメントはトークンにならないので、コメントの後からトークンが始まる
+        self.consume_comments();
+        let location = self.location();
+        let token = self.consume_token()?;
+        Some((token, location))
+    }
 
-        loop {
-            if self.pos >= self.input.len() {
-                return s;
+    /// 現在の位置の行と列を返す
+    fn location(&mut self) -> SourceLocation {
+        // 位置は前にしか進まないので、前回計算した位置から数え直す
+        while self.location_pos < self.pos {
+            if self.input[self.location_pos] == '\n' {
+                self.location.line += 1;
+                self.location.column = 1;
+            } else {
+                self.location.column += 1;
             }
+            self.location_pos += 1;
+        }
+        self.location
+    }
+
+    /// 現在の位置からn文字先の文字を、消費せずに返す
+    fn peek(&self, n: usize) -> Option<char> {
+        self.input.get(self.pos + n).copied()
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        let c = self.peek(0);
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    /// 最後に消費した文字を、もう一度消費できるようにする
+    fn reconsume(&mut self) {
+        self.pos -= 1;
+    }
 
+    fn consume_whitespace(&mut self) {
+        while self.peek(0).is_some_and(is_whitespace) {
             self.pos += 1;
-            let c = self.input[self.pos];
-            match c {
-                '"' | '\'' => break,
-                _ => s.push(c),
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-comments
+    fn consume_comments(&mut self) {
+        while self.peek(0) == Some('/') && self.peek(1) == Some('*') {
+            self.pos += 2;
+            loop {
+                match self.consume() {
+                    Some('*') if self.peek(0) == Some('/') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(_) => {}
+                    // 閉じられていないコメントは、入力の最後で終わる
+                    None => return,
+                }
             }
         }
+    }
 
-        s
+    /// https://www.w3.org/TR/css-syntax-3/#consume-token
+    fn consume_token(&mut self) -> Option<CssToken> {
+        self.consume_comments();
+
+        let c = self.consume()?;
+        let token = match c {
+            '\n' | '\t' | ' ' => {
+                self.consume_whitespace();
+                CssToken::Whitespace
+            }
+            '"' | '\'' => self.consume_string_token(c),
+            '#' => {
+                if self.peek(0).is_some_and(is_ident) || is_valid_escape(self.peek(0), self.peek(1))
+                {
+                    let mut value = String::from("#");
+                    value.push_str(&self.consume_name());
+                    CssToken::HashToken(value)
+                } else {
+                    CssToken::Delim('#')
+                }
+            }
+            '(' => CssToken::OpenParenthesis,
+            ')' => CssToken::ColseParenthesis,
+            '+' => {
+                if starts_number(Some(c), self.peek(0), self.peek(1)) {
+                    self.reconsume();
+                    self.consume_numeric_token()
+                } else {
+                    CssToken::Delim('+')
+                }
+            }
+            ',' => CssToken::Comma,
+            '-' => {
+                if starts_number(Some(c), self.peek(0), self.peek(1)) {
+                    self.reconsume();
+                    self.consume_numeric_token()
+                } else if self.peek(0) == Some('-') && self.peek(1) == Some('>') {
+                    self.pos += 2;
+                    CssToken::Cdc
+                } else if would_start_ident(Some(c), self.peek(0), self.peek(1)) {
+                    self.reconsume();
+                    self.consume_ident_like_token()
+                } else {
+                    CssToken::Delim('-')
+                }
+            }
+            '.' => {
+                if starts_number(Some(c), self.peek(0), self.peek(1)) {
+                    self.reconsume();
+                    self.consume_numeric_token()
+                } else {
+                    CssToken::Delim('.')
+                }
+            }
+            ':' => CssToken::Colon,
+            ';' => CssToken::SemiColon,
+            '<' => {
+                if self.peek(0) == Some('!')
+                    && self.peek(1) == Some('-')
+                    && self.peek(2) == Some('-')
+                {
+                    self.pos += 3;
+                    CssToken::Cdo
+                } else {
+                    CssToken::Delim('<')
+                }
+            }
+            '@' => {
+                // 次の3文字が識別子を始める場合、<at-keyword-token>を作成して返す。
+                // それ以外の場合、<delim-token>を返す。
+                if would_start_ident(self.peek(0), self.peek(1), self.peek(2)) {
+                    CssToken::AtKeyword(self.consume_name())
+                } else {
+                    CssToken::Delim('@')
+                }
+            }
+            '[' => CssToken::OpenSquareBracket,
+            ']' => CssToken::CloseSquareBracket,
+            '{' => CssToken::OpenCurly,
+            '}' => CssToken::CloseCurly,
+            '\\' => {
+                if is_valid_escape(Some(c), self.peek(0)) {
+                    self.reconsume();
+                    self.consume_ident_like_token()
+                } else {
+                    // 改行のエスケープは、識別子の外ではパースエラーになる
+                    CssToken::Delim('\\')
+                }
+            }
+            '0'..='9' => {
+                self.reconsume();
+                self.consume_numeric_token()
+            }
+            _ if is_ident_start(c) => {
+                self.reconsume();
+                self.consume_ident_like_token()
+            }
+            _ => CssToken::Delim(c),
+        };
+
+        Some(token)
     }
 
-    /// https://www.w3.org/TR/css-syntax-3/#consume-number
-    fn consure_numeric_token(&mut self) -> f64 {
-        let mut num = 0f64;
-        let mut floating = false;
-        let mut floating_digit = 1f64;
+    /// https://www.w3.org/TR/css-syntax-3/#consume-a-string-token
+    /// endingは、文字列を始めた引用符
+    fn consume_string_token(&mut self, ending: char) -> CssToken {
+        let mut s = String::new();
 
         loop {
-            if self.pos >= self.input.len() {
-                return num;
+            match self.consume() {
+                // 入力の最後に到達した場合は、そこで文字列を終える
+                None => return CssToken::StringToken(s),
+                Some(c) if c == ending => return CssToken::StringToken(s),
+                Some('\n') => {
+                    // 改行は文字列に含められないので、次のトークンとして扱う
+                    self.reconsume();
+                    return CssToken::BadString;
+                }
+                Some('\\') => match self.peek(0) {
+                    None => {}
+                    // エスケープされた改行は、文字列を次の行に続ける
+                    Some('\n') => self.pos += 1,
+                    Some(_) => s.push(self.consume_escaped_code_point()),
+                },
+                Some(c) => s.push(c),
             }
+        }
+    }
 
-            let c = self.input[self.pos];
+    /// https://www.w3.org/TR/css-syntax-3/#consume-an-escaped-code-point
+    /// \の直後から、エスケープされた1文字を読む
+    fn consume_escaped_code_point(&mut self) -> char {
+        let c = match self.consume() {
+            Some(c) => c,
+            None => return '\u{FFFD}',
+        };
+        let mut value = match c.to_digit(16) {
+            Some(v) => v,
+            None => return c,
+        };
 
-            match c {
-                '0'..='9' => {
-                    if floating {
-                        floating_digit *= 1f64 / 10f64;
-                        num += (c.to_digit(10).unwrap() as f64) * floating_digit;
-                    } else {
-                        num = num * 10.0 + (c.to_digit(10).unwrap() as f64);
-                    }
+        // 16進数は最大6桁で、その後の1つの空白は16進数の終わりを表す
+        for _ in 1..6 {
+            match self.peek(0).and_then(|h| h.to_digit(16)) {
+                Some(v) => {
+                    value = value * 16 + v;
                     self.pos += 1;
                 }
-                '.' => {
-                    floating = true;
+                None => break,
+            }
+        }
+        if self.peek(0).is_some_and(is_whitespace) {
+            self.pos += 1;
+        }
+
+        // 0、サロゲート、Unicodeの最大値を超える値は、置換文字になる
+        char::from_u32(value)
+            .filter(|c| *c != '\0')
+            .unwrap_or('\u{FFFD}')
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-number
+    fn consume_number(&mut self) -> f64 {
+        let mut repr = String::new();
+
+        if let Some(sign @ ('+' | '-')) = self.peek(0) {
+            repr.push(sign);
+            self.pos += 1;
+        }
+        self.consume_digits(&mut repr);
+
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            repr.push('.');
+            self.pos += 1;
+            self.consume_digits(&mut repr);
+        }
+
+        // 指数部。例: 1e3、2.5E-2
+        if let Some('e' | 'E') = self.peek(0) {
+            let has_sign = matches!(self.peek(1), Some('+' | '-'));
+            let digit = if has_sign { self.peek(2) } else { self.peek(1) };
+            if digit.is_some_and(|c| c.is_ascii_digit()) {
+                repr.push('e');
+                self.pos += 1;
+                if has_sign {
+                    repr.push(self.input[self.pos]);
                     self.pos += 1;
                 }
-                _ => break,
+                self.consume_digits(&mut repr);
             }
         }
 
-        num
+        // https://www.w3.org/TR/css-syntax-3/#convert-string-to-number
+        repr.parse::<f64>().unwrap_or(0.0)
+    }
+
+    fn consume_digits(&mut self, repr: &mut String) {
+        while let Some(c) = self.peek(0).filter(|c| c.is_ascii_digit()) {
+            repr.push(c);
+            self.pos += 1;
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-numeric-token
+    fn consume_numeric_token(&mut self) -> CssToken {
+        let number = self.consume_number();
+
+        if would_start_ident(self.peek(0), self.peek(1), self.peek(2)) {
+            return CssToken::Dimension(number, self.consume_name());
+        }
+        if self.peek(0) == Some('%') {
+            self.pos += 1;
+            return CssToken::Percentage(number);
+        }
+        CssToken::Number(number)
     }
 
     /// https://www.w3.org/TR/css-syntax-3/#consume-ident-like-token
+    fn consume_ident_like_token(&mut self) -> CssToken {
+        let name = self.consume_name();
+
+        if self.peek(0) != Some('(') {
+            return CssToken::Ident(name);
+        }
+        self.pos += 1;
+
+        if name.eq_ignore_ascii_case("url") {
+            while self.peek(0).is_some_and(is_whitespace) && self.peek(1).is_some_and(is_whitespace)
+            {
+                self.pos += 1;
+            }
+            // url("a.png")のように引用符で囲まれている場合は、通常の関数として扱う
+            let quoted = match (self.peek(0), self.peek(1)) {
+                (Some('"' | '\''), _) => true,
+                (Some(c), Some('"' | '\'')) => is_whitespace(c),
+                _ => false,
+            };
+            if !quoted {
+                return self.consume_url_token();
+            }
+        }
+
+        CssToken::Function(name)
+    }
+
     /// https://www.w3.org/TR/css-syntax-3/#consume-name
-    fn consume_ident_token(&mut self) -> String {
+    fn consume_name(&mut self) -> String {
         let mut s = String::new();
-        s.push(self.input[self.pos]);
 
         loop {
-            self.pos += 1;
-            let c = self.input[self.pos];
-            match c {
-                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => {
+            match self.peek(0) {
+                Some(c) if is_ident(c) => {
                     s.push(c);
+                    self.pos += 1;
+                }
+                Some('\\') if is_valid_escape(Some('\\'), self.peek(1)) => {
+                    self.pos += 1;
+                    s.push(self.consume_escaped_code_point());
                 }
-                _ => break,
+                _ => return s,
             }
         }
-
-        s
     }
-}
 
-impl Iterator for CssTokenizer {
-    type Item = CssToken;
+    /// https://www.w3.org/TR/css-syntax-3/#consume-url-token
+    /// url(の直後から、)までを読む
+    fn consume_url_token(&mut self) -> CssToken {
+        let mut url = String::new();
+        self.consume_whitespace();
 
-    /// https://www.w3.org/TR/css-syntax-3/#consume-token
-    fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if self.pos >= self.input.len() {
-                return None;
-            }
-
-            let c = self.input[self.pos];
-
-            let token = match c {
-                '(' => CssToken::OpenParenthesis,
-                ')' => CssToken::ColseParenthesis,
-                ',' => CssToken::Delim(','),
-                '.' => CssToken::Delim('.'),
-                ':' => CssToken::Colon,
-                ';' => CssToken::SemiColon,
-                '{' => CssToken::OpenCurly,
-                '}' => CssToken::CloseCurly,
-                ' ' | '\n' => {
-                    self.pos += 1;
-                    continue;
+            match self.consume() {
+                // 入力の最後に到達した場合は、そこでURLを終える
+                Some(')') | None => return CssToken::Url(url),
+                Some(c) if is_whitespace(c) => {
+                    self.consume_whitespace();
+                    match self.peek(0) {
+                        Some(')') => {
+                            self.pos += 1;
+                            return CssToken::Url(url);
+                        }
+                        None => return CssToken::Url(url),
+                        // URLの途中に空白を含めることはできない
+                        Some(_) => {
+                            self.consume_remnants_of_bad_url();
+                            return CssToken::BadUrl;
+                        }
+                    }
                 }
-                '"' | '\'' => {
-                    let value = self.consume_string_token();
-                    CssToken::StringToken(value)
+                Some('"' | '\'' | '(') => {
+                    self.consume_remnants_of_bad_url();
+                    return CssToken::BadUrl;
                 }
-                '#' => {
-                    // この実装では、常に #IDの形式のIDセレクタとして扱う。
-                    let value = self.consume_ident_token();
-                    self.pos -= 1;
-                    CssToken::HashToken(value)
+                Some(c) if is_non_printable(c) => {
+                    self.consume_remnants_of_bad_url();
+                    return CssToken::BadUrl;
                 }
-                '-' => {
-                    // この実装では、負の数を取り扱わないため、ハイフンは識別子の一つとして扱う。
-                    let t = CssToken::Ident(self.consume_ident_token());
-                    self.pos -= 1;
-                    t
-                }
-                '@' => {
-                    // 次の3文字が識別子として有効な文字の場合、<at-keyword-token>を作成して返す。
-                    // それ以外の場合、<delim-token>を返す。
-                    if self.input[self.pos + 1].is_ascii_alphabetic()
-                        && self.input[self.pos + 2].is_ascii_alphabetic()
-                        && self.input[self.pos + 3].is_ascii_alphabetic()
-                    {
-                        // skip @
-                        self.pos += 1;
-                        let t = CssToken::AtKeyword(self.consume_ident_token());
-                        self.pos -= 1;
-                        t
+                Some('\\') => {
+                    if is_valid_escape(Some('\\'), self.peek(0)) {
+                        url.push(self.consume_escaped_code_point());
                     } else {
-                        CssToken::Delim('@')
+                        self.consume_remnants_of_bad_url();
+                        return CssToken::BadUrl;
                     }
                 }
-                'a'..='z' | 'A'..='Z' | '_' => {
-                    let t = CssToken::Ident(self.consume_ident_token());
-                    self.pos -= 1;
-                    t
-                }
-                '0'..='9' => {
-                    let t = CssToken::Number(self.consure_numeric_token());
-                    self.pos -= 1;
-                    t
+                Some(c) => url.push(c),
+            }
+        }
+    }
+
+    /// https://www.w3.org/TR/css-syntax-3/#consume-remnants-of-bad-url
+    fn consume_remnants_of_bad_url(&mut self) {
+        loop {
+            match self.consume() {
+                Some(')') | None => return,
+                Some('\\') if is_valid_escape(Some('\\'), self.peek(0)) => {
+                    // エスケープされた)で、URLが終わらないようにする
+                    self.consume_escaped_code_point();
                 }
-                _ => {
-                    unimplemented!("char {} is not supported yet", c);
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+impl Iterator for CssTokenizer {
+    type Item = CssToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_location().map(|(token, _)| token)
+    }
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#input-preprocessing
+/// 改行をすべて\nにそろえ、NULL文字を置換文字にする
+fn preprocess(css: &str) -> Vec<char> {
+    let mut input = Vec::new();
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
                 }
-            };
+                input.push('\n');
+            }
+            '\u{000C}' => input.push('\n'),
+            '\0' => input.push('\u{FFFD}'),
+            _ => input.push(c),
+        }
+    }
+    input
+}
 
-            self.pos += 1;
-            return Some(token);
+/// https://www.w3.org/TR/css-syntax-3/#whitespace
+fn is_whitespace(c: char) -> bool {
+    matches!(c, '\n' | '\t' | ' ')
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#ident-start-code-point
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || !c.is_ascii() || c == '_'
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#ident-code-point
+fn is_ident(c: char) -> bool {
+    is_ident_start(c) || c.is_ascii_digit() || c == '-'
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#non-printable-code-point
+fn is_non_printable(c: char) -> bool {
+    matches!(c, '\u{0}'..='\u{8}' | '\u{B}' | '\u{E}'..='\u{1F}' | '\u{7F}')
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#starts-with-a-valid-escape
+fn is_valid_escape(first: Option<char>, second: Option<char>) -> bool {
+    first == Some('\\') && second != Some('\n')
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#would-start-an-identifier
+fn would_start_ident(first: Option<char>, second: Option<char>, third: Option<char>) -> bool {
+    match first {
+        Some('-') => {
+            second.is_some_and(|c| is_ident_start(c) || c == '-') || is_valid_escape(second, third)
         }
+        Some('\\') => is_valid_escape(first, second),
+        Some(c) => is_ident_start(c),
+        None => false,
+    }
+}
+
+/// https://www.w3.org/TR/css-syntax-3/#starts-with-a-number
+fn starts_number(first: Option<char>, second: Option<char>, third: Option<char>) -> bool {
+    let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+    match first {
+        Some('+' | '-') => is_digit(second) || (second == Some('.') && is_digit(third)),
+        Some('.') => is_digit(second),
+        c => is_digit(c),
     }
 }
 
@@ -200,6 +570,7 @@ impl Iterator for CssTokenizer {
 mod tests {
     use super::*;
     use alloc::string::ToString;
+    use alloc::vec;
 
     #[test]
     fn test_empty() {
@@ -214,11 +585,15 @@ mod tests {
         let mut t = CssTokenizer::new(style);
         let expected = [
             CssToken::Ident("p".to_string()),
+            CssToken::Whitespace,
             CssToken::OpenCurly,
+            CssToken::Whitespace,
             CssToken::Ident("color".to_string()),
             CssToken::Colon,
+            CssToken::Whitespace,
             CssToken::Ident("red".to_string()),
             CssToken::SemiColon,
+            CssToken::Whitespace,
             CssToken::CloseCurly,
         ];
         for e in expected {
@@ -233,11 +608,15 @@ mod tests {
         let mut t = CssTokenizer::new(style);
         let expected = [
             CssToken::HashToken("#id".to_string()),
+            CssToken::Whitespace,
             CssToken::OpenCurly,
+            CssToken::Whitespace,
             CssToken::Ident("color".to_string()),
             CssToken::Colon,
+            CssToken::Whitespace,
             CssToken::Ident("red".to_string()),
             CssToken::SemiColon,
+            CssToken::Whitespace,
             CssToken::CloseCurly,
         ];
         for e in expected {
@@ -253,11 +632,15 @@ mod tests {
         let expected = [
             CssToken::Delim('.'),
             CssToken::Ident("class".to_string()),
+            CssToken::Whitespace,
             CssToken::OpenCurly,
+            CssToken::Whitespace,
             CssToken::Ident("color".to_string()),
             CssToken::Colon,
+            CssToken::Whitespace,
             CssToken::Ident("red".to_string()),
             CssToken::SemiColon,
+            CssToken::Whitespace,
             CssToken::CloseCurly,
         ];
         for e in expected {
@@ -272,22 +655,33 @@ mod tests {
         let mut t = CssTokenizer::new(style);
         let expected = [
             CssToken::Ident("p".to_string()),
+            CssToken::Whitespace,
             CssToken::OpenCurly,
+            CssToken::Whitespace,
             CssToken::Ident("content".to_string()),
             CssToken::Colon,
+            CssToken::Whitespace,
             CssToken::StringToken("Hey".to_string()),
             CssToken::SemiColon,
+            CssToken::Whitespace,
             CssToken::CloseCurly,
+            CssToken::Whitespace,
             CssToken::Ident("h1".to_string()),
+            CssToken::Whitespace,
             CssToken::OpenCurly,
+            CssToken::Whitespace,
             CssToken::Ident("font-size".to_string()),
             CssToken::Colon,
+            CssToken::Whitespace,
             CssToken::Number(40.0),
             CssToken::SemiColon,
+            CssToken::Whitespace,
             CssToken::Ident("color".to_string()),
             CssToken::Colon,
+            CssToken::Whitespace,
             CssToken::Ident("blue".to_string()),
             CssToken::SemiColon,
+            CssToken::Whitespace,
             CssToken::CloseCurly,
         ];
         for e in expected {
@@ -295,4 +689,202 @@ mod tests {
         }
         assert!(t.next().is_none());
     }
+
+    fn tokenize(css: &str) -> Vec<CssToken> {
+        CssTokenizer::new(css.to_string()).collect()
+    }
+
+    #[test]
+    fn test_comments_and_whitespace() {
+        assert_eq!(
+            vec![
+                CssToken::Ident("a".to_string()),
+                CssToken::Whitespace,
+                CssToken::Ident("b".to_string()),
+                CssToken::Ident("c".to_string()),
+            ],
+            tokenize("/* x */a \t\r\n b/* y *//**/c/* 閉じられていない")
+        );
+        assert_eq!(
+            vec![
+                CssToken::Cdo,
+                CssToken::Whitespace,
+                CssToken::Cdc,
+                CssToken::Delim('<'),
+                CssToken::Delim('!'),
+            ],
+            tokenize("<!-- --><!")
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(
+            vec![
+                CssToken::Number(1.0),
+                CssToken::Whitespace,
+                CssToken::Number(-2.5),
+                CssToken::Whitespace,
+                CssToken::Number(0.5),
+                CssToken::Whitespace,
+                CssToken::Number(3.0),
+                CssToken::Whitespace,
+                CssToken::Number(1000.0),
+                CssToken::Whitespace,
+                CssToken::Number(0.02),
+                CssToken::Whitespace,
+                CssToken::Number(-0.5),
+            ],
+            tokenize("1 -2.5 .5 +3 1e3 2E-2 -.5")
+        );
+        assert_eq!(
+            vec![
+                CssToken::Dimension(12.0, "px".to_string()),
+                CssToken::Whitespace,
+                CssToken::Percentage(50.0),
+                CssToken::Whitespace,
+                CssToken::Dimension(-1.5, "em".to_string()),
+                CssToken::Whitespace,
+                // 指数部の後に数字がない場合は、単位として扱う
+                CssToken::Dimension(1.0, "e".to_string()),
+                CssToken::Whitespace,
+                CssToken::Number(1.0),
+                CssToken::Delim('.'),
+            ],
+            tokenize("12px 50% -1.5em 1e 1.")
+        );
+    }
+
+    #[test]
+    fn test_hyphen_and_plus() {
+        assert_eq!(
+            vec![
+                CssToken::Ident("-webkit-box".to_string()),
+                CssToken::Whitespace,
+                CssToken::Ident("--custom".to_string()),
+                CssToken::Whitespace,
+                CssToken::Delim('-'),
+                CssToken::Whitespace,
+                CssToken::Delim('+'),
+                CssToken::Ident("a".to_string()),
+                CssToken::Whitespace,
+                CssToken::Number(-1.0),
+            ],
+            tokenize("-webkit-box --custom - +a -1")
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(
+            vec![
+                CssToken::Ident("abc".to_string()),
+                CssToken::Whitespace,
+                CssToken::HashToken("#123".to_string()),
+                CssToken::Whitespace,
+                CssToken::Ident("a b".to_string()),
+                CssToken::Whitespace,
+                CssToken::Ident("\u{FFFD}x".to_string()),
+                CssToken::Whitespace,
+                CssToken::Delim('\\'),
+                CssToken::Whitespace,
+            ],
+            tokenize("\\61 bc #\\31 23 a\\ b \\0x \\\n")
+        );
+        assert_eq!(
+            vec![
+                CssToken::Delim('#'),
+                CssToken::Whitespace,
+                CssToken::AtKeyword("media".to_string()),
+                CssToken::Whitespace,
+                CssToken::AtKeyword("-x".to_string()),
+                CssToken::Delim('@'),
+                CssToken::Number(1.0),
+            ],
+            tokenize("# @media @-x@1")
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(
+            vec![
+                CssToken::StringToken("a'b".to_string()),
+                CssToken::StringToken("c\"d".to_string()),
+                CssToken::StringToken("ef".to_string()),
+                CssToken::Whitespace,
+                CssToken::StringToken("\u{2603}".to_string()),
+            ],
+            tokenize("\"a'b\"'c\"d'\"e\\\nf\" '\\2603")
+        );
+        // 改行で終わった文字列は、改行の前までが<bad-string-token>になる
+        assert_eq!(
+            vec![
+                CssToken::BadString,
+                CssToken::Whitespace,
+                CssToken::Ident("b".to_string()),
+            ],
+            tokenize("\"a\nb")
+        );
+    }
+
+    #[test]
+    fn test_functions_and_urls() {
+        assert_eq!(
+            vec![
+                CssToken::Function("rgb".to_string()),
+                CssToken::Number(1.0),
+                CssToken::Comma,
+                CssToken::Whitespace,
+                CssToken::Number(2.0),
+                CssToken::ColseParenthesis,
+                CssToken::Whitespace,
+                CssToken::Url("a.png".to_string()),
+                CssToken::Whitespace,
+                CssToken::Function("URL".to_string()),
+                CssToken::Whitespace,
+                CssToken::StringToken("b.png".to_string()),
+                CssToken::ColseParenthesis,
+                CssToken::Whitespace,
+                CssToken::BadUrl,
+                CssToken::Whitespace,
+                CssToken::Url("c)".to_string()),
+            ],
+            tokenize("rgb(1, 2) url(  a.png  ) URL(  \"b.png\") url(a b) url(c\\))")
+        );
+        assert_eq!(
+            vec![
+                CssToken::OpenSquareBracket,
+                CssToken::Ident("href".to_string()),
+                CssToken::Delim('^'),
+                CssToken::Delim('='),
+                CssToken::StringToken("http".to_string()),
+                CssToken::CloseSquareBracket,
+            ],
+            tokenize("[href^=\"http\"]")
+        );
+    }
+
+    #[test]
+    fn test_source_location() {
+        let mut t = CssTokenizer::new("p {\r\n  color: /* c */ red;\n}".to_string());
+        let mut locations = Vec::new();
+        while let Some((token, location)) = t.next_with_location() {
+            if token != CssToken::Whitespace {
+                locations.push((token, location.line(), location.column()));
+            }
+        }
+        assert_eq!(
+            vec![
+                (CssToken::Ident("p".to_string()), 1, 1),
+                (CssToken::OpenCurly, 1, 3),
+                (CssToken::Ident("color".to_string()), 2, 3),
+                (CssToken::Colon, 2, 8),
+                (CssToken::Ident("red".to_string()), 2, 18),
+                (CssToken::SemiColon, 2, 21),
+                (CssToken::CloseCurly, 3, 1),
+            ],
+            locations
+        );
+    }
 }
@@ -0,0 +1,296 @@
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::Origin;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::token::CssTokenizer;
+use alloc::string::ToString;
+
+/// https://html.spec.whatwg.org/multipage/rendering.html
+/// HTMLの仕様の「レンダリング」の章にあるスタイルシートを元にしたもの。
+/// この実装のレイアウトはブロックとインラインしかサポートしないので、
+/// list-itemやtableなどの値はblockで、table-cellはインラインで代用する
+const USER_AGENT_STYLESHEET: &str = r#"
+/* https://html.spec.whatwg.org/multipage/rendering.html#hidden-elements */
+area, base, basefont, datalist, head, link, meta, noembed,
+noframes, param, rp, script, style, template, title {
+  display: none;
+}
+
+[hidden]:not([hidden=until-found i]) {
+  display: none;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#the-page */
+html, body {
+  display: block;
+}
+
+body {
+  margin: 8px;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#flow-content-3 */
+address, blockquote, center, dialog, div, figure, figcaption, footer, form,
+header, hr, legend, listing, main, p, plaintext, pre, search, xmp {
+  display: block;
+}
+
+blockquote, figure, listing, p, plaintext, pre, xmp {
+  margin-top: 1em;
+  margin-bottom: 1em;
+}
+
+blockquote, figure {
+  margin-left: 40px;
+  margin-right: 40px;
+}
+
+address {
+  font-style: italic;
+}
+
+listing, plaintext, pre, xmp {
+  font-family: monospace;
+  white-space: pre;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#phrasing-content-3 */
+cite, dfn, em, i, var {
+  font-style: italic;
+}
+
+b, strong {
+  font-weight: bolder;
+}
+
+code, kbd, samp, tt {
+  font-family: monospace;
+}
+
+big {
+  font-size: larger;
+}
+
+small {
+  font-size: smaller;
+}
+
+sub {
+  vertical-align: sub;
+}
+
+sup {
+  vertical-align: super;
+}
+
+sub, sup {
+  font-size: smaller;
+}
+
+mark {
+  background-color: yellow;
+  color: black;
+}
+
+u, ins {
+  text-decoration: underline;
+}
+
+s, del, strike {
+  text-decoration: line-through;
+}
+
+:link {
+  color: #0000EE;
+}
+
+:visited {
+  color: #551A8B;
+}
+
+:link, :visited {
+  text-decoration: underline;
+  cursor: pointer;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#sections-and-headings */
+article, aside, h1, h2, h3, h4, h5, h6, hgroup, nav, section {
+  display: block;
+}
+
+h1 {
+  margin-top: 0.67em;
+  margin-bottom: 0.67em;
+  font-size: 2.00em;
+  font-weight: bold;
+}
+
+h2 {
+  margin-top: 0.83em;
+  margin-bottom: 0.83em;
+  font-size: 1.50em;
+  font-weight: bold;
+}
+
+h3 {
+  margin-top: 1.00em;
+  margin-bottom: 1.00em;
+  font-size: 1.17em;
+  font-weight: bold;
+}
+
+h4 {
+  margin-top: 1.33em;
+  margin-bottom: 1.33em;
+  font-size: 1.00em;
+  font-weight: bold;
+}
+
+h5 {
+  margin-top: 1.67em;
+  margin-bottom: 1.67em;
+  font-size: 0.83em;
+  font-weight: bold;
+}
+
+h6 {
+  margin-top: 2.33em;
+  margin-bottom: 2.33em;
+  font-size: 0.67em;
+  font-weight: bold;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#lists */
+dir, dd, dl, dt, menu, ol, ul, li {
+  display: block;
+}
+
+dir, dl, menu, ol, ul {
+  margin-top: 1em;
+  margin-bottom: 1em;
+}
+
+:is(dir, dl, menu, ol, ul) :is(dir, dl, menu, ol, ul) {
+  margin-top: 0;
+  margin-bottom: 0;
+}
+
+dd {
+  margin-left: 40px;
+}
+
+dir, menu, ol, ul {
+  padding-left: 40px;
+}
+
+ol {
+  list-style-type: decimal;
+}
+
+dir, menu, ul {
+  list-style-type: disc;
+}
+
+:is(dir, menu, ol, ul) :is(dir, menu, ul) {
+  list-style-type: circle;
+}
+
+:is(dir, menu, ol, ul) :is(dir, menu, ol, ul) :is(dir, menu, ul) {
+  list-style-type: square;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#tables-2 */
+table, caption, colgroup, col, thead, tbody, tfoot, tr {
+  display: block;
+}
+
+table {
+  border-spacing: 2px;
+  border-collapse: separate;
+}
+
+td, th {
+  padding: 1px;
+}
+
+th {
+  font-weight: bold;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#the-hr-element-2 */
+hr {
+  color: gray;
+  margin-top: 0.5em;
+  margin-bottom: 0.5em;
+}
+
+/* https://html.spec.whatwg.org/multipage/rendering.html#the-fieldset-and-legend-elements */
+fieldset {
+  display: block;
+  margin-left: 2px;
+  margin-right: 2px;
+}
+"#;
+
+/// https://www.w3.org/TR/css-cascade-4/#cascade-origin-ua
+/// ユーザーエージェントのスタイルシートを解釈する。カスケードで最も優先順位の低いオリジンになる
+pub fn user_agent_stylesheet() -> StyleSheet {
+    let mut sheet =
+        CssParser::new(CssTokenizer::new(USER_AGENT_STYLESHEET.to_string())).parse_stylesheet();
+    sheet.set_origin(Origin::UserAgent);
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cascade::cascade;
+    use crate::renderer::css::value::CssValue;
+    use crate::renderer::dom::api::query_selector;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use crate::renderer::layout::computed_style::Color;
+    use alloc::vec;
+
+    #[test]
+    fn test_user_agent_stylesheet() {
+        let sheet = user_agent_stylesheet();
+        assert_eq!(Origin::UserAgent, sheet.origin);
+        // すべてのルールのセレクタを解釈できる
+        assert_eq!(
+            USER_AGENT_STYLESHEET.matches('{').count(),
+            sheet.rules.len()
+        );
+
+        let html = "<ul><li><ul><li><a href=\"/\">a</a></li></ul></li></ul><h1 hidden>b</h1>";
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+        let stylesheets = vec![sheet];
+        let values = |selectors: &str| {
+            let node = query_selector(&dom, document, selectors)
+                .unwrap()
+                .expect("should exist");
+            cascade(&dom, node, &stylesheets)
+        };
+        let keyword = |s: &str| vec![CssValue::Keyword(s.to_string())];
+
+        let link = values("a");
+        assert_eq!(
+            Some(vec![CssValue::Color(Color::from_hex("#0000ee").unwrap())].as_slice()),
+            link.get("color")
+        );
+        assert_eq!(
+            Some(keyword("underline").as_slice()),
+            link.get("text-decoration")
+        );
+        assert_eq!(
+            Some(keyword("circle").as_slice()),
+            values("ul ul").get("list-style-type")
+        );
+        assert_eq!(
+            Some(keyword("none").as_slice()),
+            values("h1").get("display")
+        );
+    }
+}
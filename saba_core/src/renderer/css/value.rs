@@ -0,0 +1,411 @@
+use crate::error::Error;
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::layout::computed_style::Color;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::IntoIter;
+use alloc::vec::Vec;
+use core::iter::Peekable;
+
+/// https://www.w3.org/TR/css-values-3/#lengths
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LengthUnit {
+    /// https://www.w3.org/TR/css-values-3/#px
+    Px,
+    /// https://www.w3.org/TR/css-values-3/#em
+    Em,
+    /// https://www.w3.org/TR/css-values-3/#rem
+    Rem,
+    /// https://www.w3.org/TR/css-values-3/#pt
+    Pt,
+    /// https://www.w3.org/TR/css-values-3/#pc
+    Pc,
+    /// https://www.w3.org/TR/css-values-3/#in
+    In,
+    /// https://www.w3.org/TR/css-values-3/#cm
+    Cm,
+    /// https://www.w3.org/TR/css-values-3/#mm
+    Mm,
+}
+
+impl LengthUnit {
+    /// 単位は大文字と小文字を区別しない
+    pub fn from_name(unit: &str) -> Result<Self, Error> {
+        match unit.to_ascii_lowercase().as_str() {
+            "px" => Ok(Self::Px),
+            "em" => Ok(Self::Em),
+            "rem" => Ok(Self::Rem),
+            "pt" => Ok(Self::Pt),
+            "pc" => Ok(Self::Pc),
+            "in" => Ok(Self::In),
+            "cm" => Ok(Self::Cm),
+            "mm" => Ok(Self::Mm),
+            _ => Err(Error::UnexpectedInput(format!(
+                "length unit {:?} is not supported yet",
+                unit
+            ))),
+        }
+    }
+}
+
+/// https://www.w3.org/TR/css-values-3/#length-value
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Length {
+    value: f64,
+    unit: LengthUnit,
+}
+
+impl Length {
+    pub fn new(value: f64, unit: LengthUnit) -> Self {
+        Self { value, unit }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn unit(&self) -> LengthUnit {
+        self.unit
+    }
+
+    /// https://www.w3.org/TR/css-values-3/#absolute-lengths
+    /// 長さをpxに変換する。font_sizeとroot_font_sizeは、emとremの基準になるフォントサイズ(px)
+    pub fn to_px(&self, font_size: f64, root_font_size: f64) -> f64 {
+        match self.unit {
+            LengthUnit::Px => self.value,
+            LengthUnit::Em => self.value * font_size,
+            LengthUnit::Rem => self.value * root_font_size,
+            LengthUnit::Pt => self.value * 96.0 / 72.0,
+            LengthUnit::Pc => self.value * 16.0,
+            LengthUnit::In => self.value * 96.0,
+            LengthUnit::Cm => self.value * 96.0 / 2.54,
+            LengthUnit::Mm => self.value * 96.0 / 25.4,
+        }
+    }
+}
+
+/// https://www.w3.org/TR/css-values-3/#calc-syntax
+/// calc()の式の木
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcNode {
+    Number(f64),
+    Length(Length),
+    Percentage(f64),
+    Add(Box<CalcNode>, Box<CalcNode>),
+    Subtract(Box<CalcNode>, Box<CalcNode>),
+    Multiply(Box<CalcNode>, Box<CalcNode>),
+    Divide(Box<CalcNode>, Box<CalcNode>),
+}
+
+impl CalcNode {
+    /// https://www.w3.org/TR/css-values-3/#calc-computed-value
+    /// 式を計算し、pxの値にする。percentage_basisは、パーセンテージの基準になる長さ(px)。
+    /// この実装では、数値と長さの型の検査はしない
+    pub fn resolve(&self, font_size: f64, root_font_size: f64, percentage_basis: f64) -> f64 {
+        let resolve = |node: &CalcNode| node.resolve(font_size, root_font_size, percentage_basis);
+        match self {
+            CalcNode::Number(n) => *n,
+            CalcNode::Length(l) => l.to_px(font_size, root_font_size),
+            CalcNode::Percentage(p) => percentage_basis * p / 100.0,
+            CalcNode::Add(a, b) => resolve(a) + resolve(b),
+            CalcNode::Subtract(a, b) => resolve(a) - resolve(b),
+            CalcNode::Multiply(a, b) => resolve(a) * resolve(b),
+            CalcNode::Divide(a, b) => resolve(a) / resolve(b),
+        }
+    }
+}
+
+/// https://www.w3.org/TR/css-values-3/#component-types
+/// 宣言の値を、プロパティから使いやすい型に解釈したもの
+#[derive(Debug, Clone, PartialEq)]
+pub enum CssValue {
+    /// https://www.w3.org/TR/css-values-3/#keywords
+    /// キーワードは大文字と小文字を区別しないので、小文字にそろえる
+    Keyword(String),
+    /// https://www.w3.org/TR/css-values-3/#numbers
+    Number(f64),
+    Length(Length),
+    /// https://www.w3.org/TR/css-values-3/#percentages
+    Percentage(f64),
+    /// https://www.w3.org/TR/css-color-4/#color-type
+    Color(Color),
+    /// https://www.w3.org/TR/css-values-3/#strings
+    String(String),
+    /// https://www.w3.org/TR/css-values-3/#urls
+    Url(String),
+    /// https://www.w3.org/TR/css-values-3/#calc-notation
+    Calc(CalcNode),
+    /// 値を区切る,と/。例: font-family: a, b、font: 12px/1.5
+    Separator(char),
+}
+
+impl CssValue {
+    /// 1つのコンポーネント値を解釈する
+    pub fn parse(value: &ComponentValue) -> Result<Self, Error> {
+        match value {
+            ComponentValue::PreservedToken(token) => match token {
+                CssToken::Ident(name) => {
+                    let keyword = name.to_ascii_lowercase();
+                    match Color::from_name(&keyword) {
+                        Ok(color) => Ok(CssValue::Color(color)),
+                        Err(_) => Ok(CssValue::Keyword(keyword)),
+                    }
+                }
+                CssToken::HashToken(code) => Ok(CssValue::Color(Color::from_hex(code)?)),
+                CssToken::Number(n) => Ok(CssValue::Number(*n)),
+                CssToken::Dimension(n, unit) => Ok(CssValue::Length(Length::new(
+                    *n,
+                    LengthUnit::from_name(unit)?,
+                ))),
+                CssToken::Percentage(p) => Ok(CssValue::Percentage(*p)),
+                CssToken::StringToken(s) => Ok(CssValue::String(s.clone())),
+                CssToken::Url(url) => Ok(CssValue::Url(url.clone())),
+                CssToken::Comma => Ok(CssValue::Separator(',')),
+                CssToken::Delim('/') => Ok(CssValue::Separator('/')),
+                _ => Err(Error::UnexpectedInput(format!(
+                    "{:?} is not a supported value",
+                    token
+                ))),
+            },
+            ComponentValue::Function { name, values } => {
+                match name.to_ascii_lowercase().as_str() {
+                    "calc" => Ok(CssValue::Calc(parse_calc(values)?)),
+                    "rgb" | "rgba" => Ok(CssValue::Color(parse_rgb(values)?)),
+                    // url("a.png")のように引用符で囲まれたURL
+                    "url" => match parse_values(values)?.as_slice() {
+                        [CssValue::String(url)] => Ok(CssValue::Url(url.clone())),
+                        _ => Err(Error::UnexpectedInput(format!(
+                            "invalid url(): {:?}",
+                            values
+                        ))),
+                    },
+                    _ => Err(Error::UnexpectedInput(format!(
+                        "function {:?} is not supported yet",
+                        name
+                    ))),
+                }
+            }
+            ComponentValue::SimpleBlock { .. } => Err(Error::UnexpectedInput(format!(
+                "{:?} is not a supported value",
+                value
+            ))),
+        }
+    }
+}
+
+/// 宣言の値を、空白を除いた型付きの値の列にする。
+/// 解釈できない値が1つでもあれば、宣言全体が無効になる
+pub fn parse_values(values: &[ComponentValue]) -> Result<Vec<CssValue>, Error> {
+    values
+        .iter()
+        .filter(|v| !v.is_whitespace())
+        .map(CssValue::parse)
+        .collect()
+}
+
+/// https://www.w3.org/TR/css-color-4/#rgb-functions
+/// rgb(255, 0, 0)、rgb(100% 0% 0% / 50%)のような色。この実装では、アルファ値は無視する
+fn parse_rgb(values: &[ComponentValue]) -> Result<Color, Error> {
+    let mut channels = Vec::new();
+    for value in parse_values(values)? {
+        match value {
+            CssValue::Number(n) => channels.push(n),
+            CssValue::Percentage(p) => channels.push(p * 255.0 / 100.0),
+            CssValue::Separator(_) => {}
+            _ => {
+                return Err(Error::UnexpectedInput(format!(
+                    "{:?} is not a color channel",
+                    value
+                )))
+            }
+        }
+    }
+    if channels.len() < 3 {
+        return Err(Error::UnexpectedInput(format!(
+            "rgb() needs 3 channels but got {:?}",
+            channels
+        )));
+    }
+
+    // 0から255の範囲に収め、四捨五入する
+    let channel = |c: f64| (c.clamp(0.0, 255.0) + 0.5) as u8;
+    Ok(Color::from_rgb(
+        channel(channels[0]),
+        channel(channels[1]),
+        channel(channels[2]),
+    ))
+}
+
+type CalcValues<'a> = Peekable<IntoIter<&'a ComponentValue>>;
+
+/// https://www.w3.org/TR/css-values-3/#calc-syntax
+fn parse_calc(values: &[ComponentValue]) -> Result<CalcNode, Error> {
+    let values: Vec<&ComponentValue> = values.iter().filter(|v| !v.is_whitespace()).collect();
+    let mut values = values.into_iter().peekable();
+    let node = parse_calc_sum(&mut values)?;
+    match values.next() {
+        None => Ok(node),
+        Some(value) => Err(Error::UnexpectedInput(format!(
+            "unexpected {:?} in calc()",
+            value
+        ))),
+    }
+}
+
+/// <calc-sum> = <calc-product> [ [ '+' | '-' ] <calc-product> ]*
+fn parse_calc_sum(values: &mut CalcValues) -> Result<CalcNode, Error> {
+    let mut node = parse_calc_product(values)?;
+    loop {
+        match values.peek() {
+            Some(ComponentValue::PreservedToken(CssToken::Delim('+'))) => {
+                values.next();
+                node = CalcNode::Add(Box::new(node), Box::new(parse_calc_product(values)?));
+            }
+            Some(ComponentValue::PreservedToken(CssToken::Delim('-'))) => {
+                values.next();
+                node = CalcNode::Subtract(Box::new(node), Box::new(parse_calc_product(values)?));
+            }
+            _ => return Ok(node),
+        }
+    }
+}
+
+/// <calc-product> = <calc-value> [ [ '*' | '/' ] <calc-value> ]*
+fn parse_calc_product(values: &mut CalcValues) -> Result<CalcNode, Error> {
+    let mut node = parse_calc_value(values)?;
+    loop {
+        match values.peek() {
+            Some(ComponentValue::PreservedToken(CssToken::Delim('*'))) => {
+                values.next();
+                node = CalcNode::Multiply(Box::new(node), Box::new(parse_calc_value(values)?));
+            }
+            Some(ComponentValue::PreservedToken(CssToken::Delim('/'))) => {
+                values.next();
+                node = CalcNode::Divide(Box::new(node), Box::new(parse_calc_value(values)?));
+            }
+            _ => return Ok(node),
+        }
+    }
+}
+
+/// <calc-value> = <number> | <dimension> | <percentage> | ( <calc-sum> )
+fn parse_calc_value(values: &mut CalcValues) -> Result<CalcNode, Error> {
+    let value = values
+        .next()
+        .ok_or_else(|| Error::UnexpectedInput("calc() ended unexpectedly".to_string()))?;
+    match value {
+        ComponentValue::SimpleBlock {
+            associated: CssToken::OpenParenthesis,
+            values,
+        } => parse_calc(values),
+        ComponentValue::Function { name, values } if name.eq_ignore_ascii_case("calc") => {
+            parse_calc(values)
+        }
+        _ => match CssValue::parse(value)? {
+            CssValue::Number(n) => Ok(CalcNode::Number(n)),
+            CssValue::Length(l) => Ok(CalcNode::Length(l)),
+            CssValue::Percentage(p) => Ok(CalcNode::Percentage(p)),
+            value => Err(Error::UnexpectedInput(format!(
+                "{:?} can not be used in calc()",
+                value
+            ))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use alloc::vec;
+
+    /// "p { property: value }"の形のCSSから、最初の宣言の値を解釈する
+    fn values_of(value: &str) -> Result<Vec<CssValue>, Error> {
+        let css = format!("p {{ x: {} }}", value);
+        let sheet = CssParser::new(CssTokenizer::new(css)).parse_stylesheet();
+        parse_values(&sheet.rules[0].declarations[0].value)
+    }
+
+    #[test]
+    fn test_keywords_and_lengths() {
+        assert_eq!(
+            Ok(vec![
+                CssValue::Number(0.0),
+                CssValue::Keyword("auto".to_string()),
+            ]),
+            values_of("0 AUTO")
+        );
+        assert_eq!(
+            Ok(vec![
+                CssValue::Length(Length::new(12.0, LengthUnit::Px)),
+                CssValue::Separator('/'),
+                CssValue::Number(1.5),
+                CssValue::String("Noto Sans".to_string()),
+                CssValue::Separator(','),
+                CssValue::Keyword("sans-serif".to_string()),
+            ]),
+            values_of("12PX/1.5 \"Noto Sans\", sans-serif")
+        );
+        assert_eq!(
+            Ok(vec![
+                CssValue::Percentage(50.0),
+                CssValue::Url("a.png".to_string()),
+                CssValue::Url("b.png".to_string()),
+            ]),
+            values_of("50% url(a.png) url(\"b.png\")")
+        );
+        assert!(values_of("1furlong").is_err());
+        assert!(values_of("{ a }").is_err());
+
+        assert_eq!(24.0, Length::new(1.5, LengthUnit::Em).to_px(16.0, 10.0));
+        assert_eq!(15.0, Length::new(1.5, LengthUnit::Rem).to_px(16.0, 10.0));
+        assert_eq!(96.0, Length::new(1.0, LengthUnit::In).to_px(16.0, 16.0));
+    }
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(
+            Ok(vec![
+                CssValue::Color(Color::from_name("red").unwrap()),
+                CssValue::Color(Color::from_name("blue").unwrap()),
+                CssValue::Color(Color::from_rgb(0x12, 0x34, 0x56)),
+                CssValue::Color(Color::from_name("lime").unwrap()),
+                CssValue::Color(Color::from_rgb(255, 128, 0)),
+            ]),
+            values_of("Red #00f #123456 rgb(0, 255, 0) rgba(100% 50% 0 / 0.5)")
+        );
+        assert!(values_of("#12").is_err());
+        assert!(values_of("rgb(1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_calc() {
+        let values = values_of("calc(100% - 2 * (10px + 1em))").unwrap();
+        let calc = match &values[..] {
+            [CssValue::Calc(calc)] => calc,
+            _ => panic!("expected calc() but got {:?}", values),
+        };
+        assert_eq!(
+            &CalcNode::Subtract(
+                Box::new(CalcNode::Percentage(100.0)),
+                Box::new(CalcNode::Multiply(
+                    Box::new(CalcNode::Number(2.0)),
+                    Box::new(CalcNode::Add(
+                        Box::new(CalcNode::Length(Length::new(10.0, LengthUnit::Px))),
+                        Box::new(CalcNode::Length(Length::new(1.0, LengthUnit::Em))),
+                    )),
+                )),
+            ),
+            calc
+        );
+        assert_eq!(148.0, calc.resolve(16.0, 16.0, 200.0));
+
+        assert!(values_of("calc(1px +)").is_err());
+        assert!(values_of("calc(1px 2px)").is_err());
+        assert!(values_of("calc(red)").is_err());
+    }
+}
@@ -1,90 +1,438 @@
-use crate::renderer::dom::node::Element;
+use crate::error::DomException;
+use crate::error::Error;
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::selector::Selector;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::mutation::insert;
+use crate::renderer::dom::mutation::replace_all;
+use crate::renderer::dom::mutation::replace_child;
+use crate::renderer::dom::node::DocumentFragment;
+use crate::renderer::dom::node::Dom;
 use crate::renderer::dom::node::ElementKind;
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::NodeKind;
+use crate::renderer::html::parser::HtmlParser;
+use crate::renderer::html::serializer::serialize_children;
+use crate::renderer::html::serializer::serialize_node;
+use crate::url::Url;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
+/// rootとその子孫から、ツリー順で最初のelement_kindの要素を探す
 pub fn get_target_element_node(
-    node: Option<Rc<RefCell<Node>>>,
+    dom: &Dom,
+    root: NodeId,
     element_kind: ElementKind,
-) -> Option<Rc<RefCell<Node>>> {
-    match node {
-        Some(n) => {
-            if n.borrow().kind()
-                == NodeKind::Element(Element::new(&element_kind.to_string(), Vec::new()))
-            {
-                return Some(n.clone());
-            }
-            let result1 = get_target_element_node(n.borrow().first_child(), element_kind);
-            let result2 = get_target_element_node(n.borrow().next_sibling(), element_kind);
-            if result1.is_none() && result2.is_none() {
-                return None;
-            }
-            if result1.is_none() {
-                return result2;
-            }
-            result1
+) -> Option<NodeId> {
+    dom.preorder(root)
+        .find(|n| dom.node(*n).element_kind().as_ref() == Some(&element_kind))
+}
+
+/// rootとその子孫から、element_kindの要素をツリー順にすべて集める
+pub fn get_target_element_nodes(dom: &Dom, root: NodeId, element_kind: ElementKind) -> Vec<NodeId> {
+    dom.preorder(root)
+        .filter(|n| dom.node(*n).element_kind().as_ref() == Some(&element_kind))
+        .collect()
+}
+
+/// https://dom.spec.whatwg.org/#concept-child-text-content
+/// nodeの子のテキストノードの中身を、ツリー順に連結する
+pub fn child_text_content(dom: &Dom, node: NodeId) -> String {
+    let mut content = String::new();
+    for child in dom.children(node) {
+        if let NodeKind::Text(ref s) = dom.node(child).kind {
+            content.push_str(s);
         }
-        None => None,
     }
+    content
 }
 
-pub fn get_style_content(root: Rc<RefCell<Node>>) -> String {
-    let style_node = match get_target_element_node(Some(root), ElementKind::Style) {
-        Some(node) => node,
-        None => return "".to_string(),
+/// https://html.spec.whatwg.org/multipage/dom.html#document.title
+/// 文書の最初の<title>の文字列から、前後の空白を取り除き、連続する空白を一つの空白にしたもの
+pub fn document_title(dom: &Dom, document: NodeId) -> String {
+    let title = match get_target_element_node(dom, document, ElementKind::Title) {
+        Some(t) => t,
+        None => return String::new(),
     };
-    let text_node = match style_node.borrow().first_child() {
-        Some(node) => node,
-        None => return "".to_string(),
-    };
-    let content = match &text_node.borrow().kind() {
-        NodeKind::Text(ref s) => s.clone(),
-        _ => "".to_string(),
+    let text = child_text_content(dom, title);
+    let words: Vec<&str> = text.split_ascii_whitespace().collect();
+    words.join(" ")
+}
+
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#document-base-url
+/// 文書の最初の<base href>を文書のURLで解決したURL。<base href>がない場合は文書のURLになる
+pub fn document_base_url(dom: &Dom, document: NodeId) -> String {
+    let url = match dom.node(document).get_document() {
+        Some(d) => d.url(),
+        None => return String::new(),
     };
-    content
+    let href = dom
+        .descendants(document)
+        .find_map(|n| match dom.node(n).kind {
+            NodeKind::Element(ref e) if e.kind() == ElementKind::Base => e.get_attribute("href"),
+            _ => None,
+        });
+
+    match href {
+        // https://html.spec.whatwg.org/multipage/semantics.html#set-the-frozen-base-url
+        Some(href) => resolve_url_with_base(&url, &href),
+        None => url,
+    }
 }
 
-pub fn get_element_by_id(
-    node: Option<Rc<RefCell<Node>>>,
-    id_name: &String,
-) -> Option<Rc<RefCell<Node>>> {
-    match node {
-        Some(n) => {
-            if let NodeKind::Element(e) = n.borrow().kind() {
-                for attr in &e.attributes() {
-                    if attr.name() == "id" && attr.value() == *id_name {
-                        return Some(n.clone());
-                    }
-                }
-            }
-            let result1 = get_element_by_id(n.borrow().first_child(), id_name);
-            let result2 = get_element_by_id(n.borrow().next_sibling(), id_name);
-            if result1.is_none() {
-                return result2;
-            }
-            result1
-        }
-        None => None,
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#encoding-parsing-a-url
+/// 文書のベースURLを基準に、相対URLを絶対URLに変換する
+pub fn resolve_url(dom: &Dom, document: NodeId, url: &str) -> String {
+    resolve_url_with_base(&document_base_url(dom, document), url)
+}
+
+/// baseをパースできない場合は、urlをそのまま返す
+fn resolve_url_with_base(base: &str, url: &str) -> String {
+    match Url::new(base.to_string()).parse() {
+        Ok(base) => base.resolve(url),
+        Err(_) => url.trim().to_string(),
     }
 }
 
-pub fn get_js_content(root: Rc<RefCell<Node>>) -> String {
-    let js_node = match get_target_element_node(Some(root), ElementKind::Script) {
-        Some(node) => node,
-        None => return "".to_string(),
-    };
-    let text_node = match js_node.borrow().first_child() {
-        Some(node) => node,
-        None => return "".to_string(),
+/// https://dom.spec.whatwg.org/#dom-nonelementparentnode-getelementbyid
+pub fn get_element_by_id(dom: &Dom, root: NodeId, id_name: &String) -> Option<NodeId> {
+    dom.preorder(root).find(|n| match dom.node(*n).kind {
+        NodeKind::Element(ref e) => e.get_attribute("id").as_ref() == Some(id_name),
+        _ => false,
+    })
+}
+
+/// https://dom.spec.whatwg.org/#concept-getelementsbytagname
+/// rootの子孫から、タグ名がqualified_nameの要素をツリー順にすべて集める。"*"はすべての要素に一致する
+pub fn get_elements_by_tag_name(dom: &Dom, root: NodeId, qualified_name: &str) -> Vec<NodeId> {
+    let selector = if qualified_name == "*" {
+        Selector::UniversalSelector
+    } else {
+        Selector::TypeSelector(qualified_name.to_string())
     };
-    let content = match &text_node.borrow().kind() {
-        NodeKind::Text(ref s) => s.clone(),
-        _ => "".to_string(),
+    collect_elements(dom, root, |n| selector.matches(dom, n))
+}
+
+/// https://dom.spec.whatwg.org/#concept-getelementsbyclassname
+/// rootの子孫から、空白で区切られたclass_namesのクラスをすべて持つ要素をツリー順に集める
+pub fn get_elements_by_class_name(dom: &Dom, root: NodeId, class_names: &str) -> Vec<NodeId> {
+    let selectors: Vec<Selector> = class_names
+        .split_ascii_whitespace()
+        .map(|c| Selector::ClassSelector(c.to_string()))
+        .collect();
+    if selectors.is_empty() {
+        return Vec::new();
+    }
+    collect_elements(dom, root, |n| selectors.iter().all(|s| s.matches(dom, n)))
+}
+
+/// https://dom.spec.whatwg.org/#dom-parentnode-queryselector
+/// rootの子孫から、セレクタのリストのどれかに一致する最初の要素を探す
+pub fn query_selector(dom: &Dom, root: NodeId, selectors: &str) -> Result<Option<NodeId>, Error> {
+    let selectors = parse_selectors(selectors)?;
+    Ok(dom
+        .descendants(root)
+        .find(|n| selectors.iter().any(|s| s.matches(dom, *n))))
+}
+
+/// https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall
+/// rootの子孫から、セレクタのリストのどれかに一致する要素をツリー順にすべて集める
+pub fn query_selector_all(dom: &Dom, root: NodeId, selectors: &str) -> Result<Vec<NodeId>, Error> {
+    let selectors = parse_selectors(selectors)?;
+    Ok(collect_elements(dom, root, |n| {
+        selectors.iter().any(|s| s.matches(dom, n))
+    }))
+}
+
+/// https://dom.spec.whatwg.org/#scope-match-a-selectors-string
+/// 解釈できないセレクタの場合は、SyntaxErrorになる
+fn parse_selectors(selectors: &str) -> Result<Vec<Selector>, Error> {
+    CssParser::new(CssTokenizer::new(selectors.to_string()))
+        .parse_selector_list()
+        .map_err(|_| Error::Dom(DomException::Syntax))
+}
+
+/// rootの子孫のうち、predicateを満たす要素をツリー順に集める
+fn collect_elements<F: Fn(NodeId) -> bool>(dom: &Dom, root: NodeId, predicate: F) -> Vec<NodeId> {
+    dom.descendants(root)
+        .filter(|n| matches!(dom.node(*n).kind, NodeKind::Element(_)) && predicate(*n))
+        .collect()
+}
+
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+pub fn inner_html(dom: &Dom, node: NodeId) -> String {
+    serialize_children(dom, node)
+}
+
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+/// nodeの子をすべて、htmlをパースしてできたノードで置き換える
+pub fn set_inner_html(dom: &Rc<RefCell<Dom>>, node: NodeId, html: &str) {
+    let nodes = HtmlParser::parse_fragment(dom, node, html.to_string());
+
+    let mut dom = dom.borrow_mut();
+    let fragment = create_fragment(&mut dom, nodes);
+    // <template>の場合は、子ノードの代わりにその中身を置き換える
+    let node = dom.node(node).template_contents().unwrap_or(node);
+
+    replace_all(&mut dom, Some(fragment), node);
+}
+
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+pub fn outer_html(dom: &Dom, node: NodeId) -> String {
+    serialize_node(dom, node)
+}
+
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+/// node自身を、htmlをパースしてできたノードで置き換える。
+/// 親がない場合は何もしない。親が文書の場合は仕様ではNoModificationAllowedErrorになるため、何もしない
+pub fn set_outer_html(dom: &Rc<RefCell<Dom>>, node: NodeId, html: &str) {
+    let parent = match dom.borrow().node(node).parent() {
+        Some(p) => p,
+        None => return,
     };
-    content
+    if dom.borrow().node(parent).get_document().is_some() {
+        return;
+    }
+
+    let nodes = HtmlParser::parse_fragment(dom, parent, html.to_string());
+
+    let mut dom = dom.borrow_mut();
+    let fragment = create_fragment(&mut dom, nodes);
+    // 親は文書ではなく、nodeはその子なので、置き換えは失敗しない
+    let _ = replace_child(&mut dom, parent, fragment, node);
+}
+
+/// nodesを子として持つDocumentFragmentを作る
+fn create_fragment(dom: &mut Dom, nodes: Vec<NodeId>) -> NodeId {
+    let fragment = dom.create_node(NodeKind::DocumentFragment(DocumentFragment::new(None)));
+    for node in nodes {
+        insert(dom, node, fragment, None);
+    }
+    fragment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::node::Document;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::vec;
+
+    fn body_of(html: &str) -> (Rc<RefCell<Dom>>, NodeId) {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().dom();
+        let document = window.borrow().document();
+        let body = get_target_element_node(&dom.borrow(), document, ElementKind::Body)
+            .expect("failed to get body");
+        (dom, body)
+    }
+
+    #[test]
+    fn test_inner_html() {
+        let (dom, body) = body_of("<div id=\"a\"><p>old</p></div>");
+        let div = get_element_by_id(&dom.borrow(), body, &"a".to_string()).unwrap();
+        assert_eq!("<p>old</p>".to_string(), inner_html(&dom.borrow(), div));
+
+        // <p>の中で<p>を開始すると、前の<p>が閉じられる
+        set_inner_html(&dom, div, "<p>a<p>b<!--c-->");
+        assert_eq!(
+            "<p>a</p><p>b<!--c--></p>".to_string(),
+            inner_html(&dom.borrow(), div)
+        );
+        let last = dom.borrow().node(div).last_child().unwrap();
+        assert_eq!(Some(ElementKind::P), dom.borrow().node(last).element_kind());
+        assert_eq!(Some(div), dom.borrow().node(last).parent());
+
+        set_inner_html(&dom, div, "");
+        assert_eq!("".to_string(), inner_html(&dom.borrow(), div));
+        assert!(dom.borrow().node(div).first_child().is_none());
+    }
+
+    #[test]
+    fn test_inner_html_context() {
+        // <table>の中身としてパースされるので、<tr>が<tbody>に入る
+        let (dom, body) = body_of("<table id=\"t\"></table>");
+        let table = get_element_by_id(&dom.borrow(), body, &"t".to_string()).unwrap();
+        set_inner_html(&dom, table, "<tr><td>1</td></tr>");
+        assert_eq!(
+            "<tbody><tr><td>1</td></tr></tbody>".to_string(),
+            inner_html(&dom.borrow(), table)
+        );
+
+        // <textarea>の中身はテキストとして扱われる
+        let (dom, body) = body_of("<textarea id=\"t\"></textarea>");
+        let textarea = get_element_by_id(&dom.borrow(), body, &"t".to_string()).unwrap();
+        set_inner_html(&dom, textarea, "<b>bold</b>");
+        assert_eq!(
+            "&lt;b&gt;bold&lt;/b&gt;".to_string(),
+            inner_html(&dom.borrow(), textarea)
+        );
+    }
+
+    #[test]
+    fn test_outer_html() {
+        let (dom, body) = body_of("<p>a</p><div id=\"a\">b</div><p>c</p>");
+        let div = get_element_by_id(&dom.borrow(), body, &"a".to_string()).unwrap();
+        assert_eq!(
+            "<div id=\"a\">b</div>".to_string(),
+            outer_html(&dom.borrow(), div)
+        );
+
+        set_outer_html(&dom, div, "<span>x</span>y");
+        assert_eq!(
+            "<p>a</p><span>x</span>y<p>c</p>".to_string(),
+            inner_html(&dom.borrow(), body)
+        );
+        assert!(dom.borrow().node(div).parent().is_none());
+    }
+
+    #[test]
+    fn test_template_inner_html() {
+        let (dom, body) = body_of("<body><template id=\"t\"><p>a</p></template>");
+        let template = get_element_by_id(&dom.borrow(), body, &"t".to_string()).unwrap();
+        assert_eq!("<p>a</p>".to_string(), inner_html(&dom.borrow(), template));
+
+        // <template>の中身が置き換えられ、子ノードは増えない
+        set_inner_html(&dom, template, "<tr><td>1</td></tr>");
+        assert_eq!(
+            "<tr><td>1</td></tr>".to_string(),
+            inner_html(&dom.borrow(), template)
+        );
+        assert!(dom.borrow().node(template).first_child().is_none());
+        assert_eq!(
+            "<template id=\"t\"><tr><td>1</td></tr></template>".to_string(),
+            outer_html(&dom.borrow(), template)
+        );
+    }
+
+    #[test]
+    fn test_get_element_by_id() {
+        let (dom, body) = body_of("<p id=\"a\">1</p><div><p id=\"a\">2</p><p id=\"b\">3</p></div>");
+        let dom = dom.borrow();
+        // 同じIDの要素が複数ある場合は、ツリー順で最初のものを返す
+        let a = get_element_by_id(&dom, body, &"a".to_string()).unwrap();
+        assert_eq!("1".to_string(), inner_html(&dom, a));
+        let b = get_element_by_id(&dom, body, &"b".to_string()).unwrap();
+        assert_eq!("3".to_string(), inner_html(&dom, b));
+        assert!(get_element_by_id(&dom, body, &"c".to_string()).is_none());
+    }
+
+    /// nodesのそれぞれについて、id属性の値を集める
+    fn ids(dom: &Rc<RefCell<Dom>>, nodes: Vec<NodeId>) -> Vec<String> {
+        let dom = dom.borrow();
+        nodes
+            .iter()
+            .map(|n| match dom.node(*n).get_element() {
+                Some(e) => e.get_attribute("id").unwrap_or_default(),
+                None => "".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_get_elements_by_tag_name() {
+        let (dom, body) =
+            body_of("<p id=\"a\"><b id=\"b\"></b></p><div id=\"c\"><p id=\"d\"></p></div>");
+        assert_eq!(
+            vec!["a", "d"],
+            ids(&dom, get_elements_by_tag_name(&dom.borrow(), body, "p"))
+        );
+        assert_eq!(
+            vec!["a", "d"],
+            ids(&dom, get_elements_by_tag_name(&dom.borrow(), body, "P"))
+        );
+        // "*"はすべての要素に一致するが、root自身は含まない
+        assert_eq!(
+            vec!["a", "b", "c", "d"],
+            ids(&dom, get_elements_by_tag_name(&dom.borrow(), body, "*"))
+        );
+        assert!(get_elements_by_tag_name(&dom.borrow(), body, "span").is_empty());
+    }
+
+    #[test]
+    fn test_get_elements_by_class_name() {
+        let (dom, body) = body_of(
+            "<p id=\"a\" class=\"x\"></p><p id=\"b\" class=\" y  x \"></p><p id=\"c\" class=\"xy\"></p>",
+        );
+        assert_eq!(
+            vec!["a", "b"],
+            ids(&dom, get_elements_by_class_name(&dom.borrow(), body, "x"))
+        );
+        // 空白で区切られたクラスをすべて持つ要素だけに一致する
+        assert_eq!(
+            vec!["b"],
+            ids(&dom, get_elements_by_class_name(&dom.borrow(), body, "y x"))
+        );
+        assert!(get_elements_by_class_name(&dom.borrow(), body, " ").is_empty());
+    }
+
+    #[test]
+    fn test_query_selector() {
+        let (dom, body) = body_of(
+            "<p id=\"a\" class=\"x\"></p><div id=\"b\"><span id=\"c\" class=\"x\"></span></div>",
+        );
+        let first = query_selector(&dom.borrow(), body, ".x")
+            .unwrap()
+            .expect("failed to find .x");
+        assert_eq!(vec!["a"], ids(&dom, vec![first]));
+        assert_eq!(Ok(None), query_selector(&dom.borrow(), body, "#d"));
+        // セレクタのリストのどれかに一致する要素を、ツリー順で返す
+        assert_eq!(
+            vec!["b", "c"],
+            ids(
+                &dom,
+                query_selector_all(&dom.borrow(), body, "span, #b").unwrap()
+            )
+        );
+        assert_eq!(
+            Err(Error::Dom(DomException::Syntax)),
+            query_selector_all(&dom.borrow(), body, "p,")
+        );
+    }
+
+    #[test]
+    fn test_document_metadata() {
+        let mut document = Document::new();
+        document.set_url("http://example.com/dir/index.html".to_string());
+        let html = "<head><title>\n  Hello \t world </title><base target=\"_blank\"><base href=\"/base/\"><base href=\"/other/\"></head><body><title>b</title></body>";
+        let window = HtmlParser::new_with_document(HtmlTokenizer::new(html.to_string()), document)
+            .construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+
+        assert_eq!("Hello world".to_string(), document_title(&dom, document));
+        // hrefを持つ最初の<base>が使われる
+        assert_eq!(
+            "http://example.com/base/".to_string(),
+            document_base_url(&dom, document)
+        );
+        assert_eq!(
+            "http://example.com/base/a.html".to_string(),
+            resolve_url(&dom, document, "a.html")
+        );
+        assert_eq!(
+            "http://example.org/".to_string(),
+            resolve_url(&dom, document, "http://example.org/")
+        );
+    }
+
+    #[test]
+    fn test_document_metadata_without_base() {
+        let window = HtmlParser::new(HtmlTokenizer::new("<p>a</p>".to_string())).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+
+        assert_eq!(String::new(), document_title(&dom, document));
+        assert_eq!("about:blank".to_string(), document_base_url(&dom, document));
+        // 文書のURLをパースできない場合は、そのまま返す
+        assert_eq!(
+            "a.html".to_string(),
+            resolve_url(&dom, document, " a.html ")
+        );
+    }
 }
@@ -1,2 +1,5 @@
 pub mod api;
+pub mod mutation;
+pub mod mutation_observer;
 pub mod node;
+pub mod token_list;
@@ -0,0 +1,636 @@
+use crate::error::DomException;
+use crate::error::Error;
+use crate::renderer::dom::mutation_observer::queue_mutation_record;
+use crate::renderer::dom::mutation_observer::MutationRecord;
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::Element;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// https://dom.spec.whatwg.org/#dom-node-appendchild
+/// nodeをparentの最後の子として追加し、追加したノードを返す
+pub fn append_child(dom: &mut Dom, parent: NodeId, node: NodeId) -> Result<NodeId, Error> {
+    pre_insert(dom, node, parent, None)
+}
+
+/// https://dom.spec.whatwg.org/#dom-node-insertbefore
+/// nodeをchildの直前に挿入し、挿入したノードを返す。childがNoneの場合は最後の子として追加する
+pub fn insert_before(
+    dom: &mut Dom,
+    parent: NodeId,
+    node: NodeId,
+    child: Option<NodeId>,
+) -> Result<NodeId, Error> {
+    pre_insert(dom, node, parent, child)
+}
+
+/// https://dom.spec.whatwg.org/#dom-node-removechild
+/// https://dom.spec.whatwg.org/#concept-node-pre-remove
+pub fn remove_child(dom: &mut Dom, parent: NodeId, child: NodeId) -> Result<NodeId, Error> {
+    if dom.node(child).parent() != Some(parent) {
+        return Err(Error::Dom(DomException::NotFound));
+    }
+    remove(dom, child);
+    Ok(child)
+}
+
+/// https://dom.spec.whatwg.org/#dom-node-replacechild
+/// https://dom.spec.whatwg.org/#concept-node-replace
+/// parentの子であるchildをnodeで置き換え、取り除かれたchildを返す
+pub fn replace_child(
+    dom: &mut Dom,
+    parent: NodeId,
+    node: NodeId,
+    child: NodeId,
+) -> Result<NodeId, Error> {
+    ensure_validity(dom, node, parent, Some(child), true)?;
+
+    let mut reference = dom.node(child).next_sibling();
+    if reference == Some(node) {
+        reference = dom.node(node).next_sibling();
+    }
+    let mut previous = dom.node(child).previuos_sibling();
+    if previous == Some(node) {
+        previous = dom.node(node).previuos_sibling();
+    }
+
+    // 取り除く変更と挿入する変更は、まとめて一つの記録にする
+    let added_nodes = inserted_nodes(dom, node);
+    remove_node(dom, child, true);
+    insert_node(dom, node, parent, reference, true);
+    queue_mutation_record(
+        dom,
+        MutationRecord::new_child_list(parent, added_nodes, vec![child], previous, reference),
+    );
+    Ok(child)
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-replace-all
+/// parentの子をすべて取り除き、nodeがある場合はそれを追加する
+pub fn replace_all(dom: &mut Dom, node: Option<NodeId>, parent: NodeId) {
+    let removed_nodes: Vec<NodeId> = dom.children(parent).collect();
+    let added_nodes = match node {
+        Some(node) => inserted_nodes(dom, node),
+        None => Vec::new(),
+    };
+
+    for child in &removed_nodes {
+        remove_node(dom, *child, true);
+    }
+    if let Some(node) = node {
+        insert_node(dom, node, parent, None, true);
+    }
+
+    // 取り除く変更と挿入する変更は、まとめて一つの記録にする
+    if !added_nodes.is_empty() || !removed_nodes.is_empty() {
+        queue_mutation_record(
+            dom,
+            MutationRecord::new_child_list(parent, added_nodes, removed_nodes, None, None),
+        );
+    }
+}
+
+/// https://dom.spec.whatwg.org/#dom-node-clonenode
+/// https://dom.spec.whatwg.org/#concept-node-clone
+/// deepがtrueの場合は子孫もコピーする。コピーしたノードはどの親にも属していない
+pub fn clone_node(dom: &mut Dom, node: NodeId, deep: bool) -> NodeId {
+    let copy = dom.create_node(dom.node(node).kind());
+
+    // https://html.spec.whatwg.org/multipage/scripting.html#the-template-element:concept-node-clone-ext
+    // <template>は中身もコピーする
+    if let Some(contents) = dom.node(node).template_contents() {
+        let contents_copy = clone_node(dom, contents, deep);
+        dom.node_mut(copy)
+            .set_template_contents(Some(contents_copy));
+    }
+
+    if deep {
+        let children: Vec<NodeId> = dom.children(node).collect();
+        for child in children {
+            let child_copy = clone_node(dom, child, true);
+            insert(dom, child_copy, copy, None);
+        }
+    }
+
+    copy
+}
+
+/// https://dom.spec.whatwg.org/#dom-node-normalize
+/// 子孫の空のテキストノードを取り除き、隣り合うテキストノードを一つにまとめる
+pub fn normalize(dom: &mut Dom, node: NodeId) {
+    let mut child = dom.node(node).first_child();
+    while let Some(c) = child {
+        if !matches!(dom.node(c).kind, NodeKind::Text(_)) {
+            normalize(dom, c);
+            child = dom.node(c).next_sibling();
+            continue;
+        }
+
+        // 後ろに続くテキストノードの文字列を連結して、取り除く
+        let mut data = match dom.node(c).kind {
+            NodeKind::Text(ref s) => s.clone(),
+            _ => String::new(),
+        };
+        let mut merged = Vec::new();
+        let mut next = dom.node(c).next_sibling();
+        while let Some(n) = next {
+            match dom.node(n).kind {
+                NodeKind::Text(ref s) => data.push_str(s),
+                _ => break,
+            }
+            merged.push(n);
+            next = dom.node(n).next_sibling();
+        }
+
+        if data.is_empty() {
+            remove(dom, c);
+        } else if !merged.is_empty() {
+            replace_data(dom, c, data);
+        }
+        for n in merged {
+            remove(dom, n);
+        }
+        child = next;
+    }
+}
+
+/// https://dom.spec.whatwg.org/#concept-cd-replace
+/// テキストノードやコメントの文字列をdataで置き換える
+pub fn replace_data(dom: &mut Dom, node: NodeId, data: String) {
+    let old_value = match dom.node_mut(node).kind {
+        NodeKind::Text(ref mut s) | NodeKind::Comment(ref mut s) => core::mem::replace(s, data),
+        _ => return,
+    };
+    queue_mutation_record(dom, MutationRecord::new_character_data(node, old_value));
+}
+
+/// https://dom.spec.whatwg.org/#concept-element-attributes-change
+/// nodeが要素の場合に、その属性をupdateで変更し、値が変わった属性ごとに変更を記録する。
+/// Element::set_attributeなどは変更を記録しないため、ツリーの中の要素の属性はこの関数を通して変更する
+pub fn change_attributes<R>(
+    dom: &mut Dom,
+    node: NodeId,
+    update: impl FnOnce(&mut Element) -> R,
+) -> Option<R> {
+    let element = match dom.node_mut(node).kind {
+        NodeKind::Element(ref mut e) => e,
+        _ => return None,
+    };
+    let old_attributes = element.attributes();
+    let result = update(element);
+    let new_attributes = element.attributes();
+
+    let mut records = Vec::new();
+    // 値が変わった属性と、取り除かれた属性
+    for old in &old_attributes {
+        match new_attributes.iter().find(|a| a.name() == old.name()) {
+            Some(new) if new.value() == old.value() => {}
+            _ => records.push(MutationRecord::new_attributes(
+                node,
+                old.name(),
+                Some(old.value()),
+            )),
+        }
+    }
+    // 追加された属性
+    for new in &new_attributes {
+        if !old_attributes.iter().any(|a| a.name() == new.name()) {
+            records.push(MutationRecord::new_attributes(node, new.name(), None));
+        }
+    }
+    for record in records {
+        queue_mutation_record(dom, record);
+    }
+
+    Some(result)
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-pre-insert
+fn pre_insert(
+    dom: &mut Dom,
+    node: NodeId,
+    parent: NodeId,
+    child: Option<NodeId>,
+) -> Result<NodeId, Error> {
+    ensure_validity(dom, node, parent, child, false)?;
+
+    // node自身の直前に挿入する場合は、nodeの次のノードの直前に挿入する
+    let mut reference = child;
+    if reference == Some(node) {
+        reference = dom.node(node).next_sibling();
+    }
+
+    insert(dom, node, parent, reference);
+    Ok(node)
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-insert
+/// 検証をせずに、nodeをparentの子としてchildの直前に挿入する。childがNoneの場合は最後の子として追加する。
+/// nodeがDocumentFragmentの場合は、その子をすべて移動する。
+/// パーサは仕様に従ってこの関数で直接ツリーを組み立てる
+pub fn insert(dom: &mut Dom, node: NodeId, parent: NodeId, child: Option<NodeId>) {
+    insert_node(dom, node, parent, child, false);
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-remove
+/// nodeを親から取り外す。親がない場合は何もしない
+pub fn remove(dom: &mut Dom, node: NodeId) {
+    remove_node(dom, node, false);
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-insert
+/// 挿入されるノードの列。nodeがDocumentFragmentの場合はその子になる
+fn inserted_nodes(dom: &Dom, node: NodeId) -> Vec<NodeId> {
+    match dom.node(node).kind {
+        NodeKind::DocumentFragment(_) => dom.children(node).collect(),
+        _ => vec![node],
+    }
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-insert
+/// suppress_observersがtrueの場合は、parentの変更を記録しない
+fn insert_node(
+    dom: &mut Dom,
+    node: NodeId,
+    parent: NodeId,
+    child: Option<NodeId>,
+    suppress_observers: bool,
+) {
+    let nodes = inserted_nodes(dom, node);
+    if nodes.is_empty() {
+        return;
+    }
+
+    // DocumentFragmentの子は、記録せずに取り外してから挿入する
+    if matches!(dom.node(node).kind, NodeKind::DocumentFragment(_)) {
+        for n in &nodes {
+            remove_node(dom, *n, true);
+        }
+        queue_mutation_record(
+            dom,
+            MutationRecord::new_child_list(node, Vec::new(), nodes.clone(), None, None),
+        );
+    }
+
+    let previous_sibling = match child {
+        Some(c) => dom.node(c).previuos_sibling(),
+        None => dom.node(parent).last_child(),
+    };
+
+    for n in nodes.iter().copied() {
+        // すでに親がある場合は、そこから取り除いてから挿入する
+        remove(dom, n);
+
+        let previous = match child {
+            Some(c) => dom.node(c).previuos_sibling(),
+            None => dom.node(parent).last_child(),
+        };
+        match previous {
+            Some(p) => dom.node_mut(p).set_next_sibling(Some(n)),
+            None => dom.node_mut(parent).set_first_child(Some(n)),
+        }
+        match child {
+            Some(c) => dom.node_mut(c).set_previuos_sibling(Some(n)),
+            None => dom.node_mut(parent).set_last_child(Some(n)),
+        }
+
+        let n = dom.node_mut(n);
+        n.set_parent(Some(parent));
+        n.set_previuos_sibling(previous);
+        n.set_next_sibling(child);
+    }
+
+    if !suppress_observers {
+        queue_mutation_record(
+            dom,
+            MutationRecord::new_child_list(parent, nodes, Vec::new(), previous_sibling, child),
+        );
+    }
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-remove
+/// suppress_observersがtrueの場合は、親の変更を記録しない
+fn remove_node(dom: &mut Dom, node: NodeId, suppress_observers: bool) {
+    let parent = match dom.node(node).parent() {
+        Some(p) => p,
+        None => return,
+    };
+    let previous = dom.node(node).previuos_sibling();
+    let next = dom.node(node).next_sibling();
+
+    match previous {
+        Some(p) => dom.node_mut(p).set_next_sibling(next),
+        None => dom.node_mut(parent).set_first_child(next),
+    }
+    match next {
+        Some(n) => dom.node_mut(n).set_previuos_sibling(previous),
+        None => dom.node_mut(parent).set_last_child(previous),
+    }
+
+    let n = dom.node_mut(node);
+    n.set_parent(None);
+    n.set_previuos_sibling(None);
+    n.set_next_sibling(None);
+
+    if !suppress_observers {
+        queue_mutation_record(
+            dom,
+            MutationRecord::new_child_list(parent, Vec::new(), vec![node], previous, next),
+        );
+    }
+}
+
+/// https://dom.spec.whatwg.org/#concept-tree-inclusive-ancestor
+fn is_inclusive_ancestor(dom: &Dom, ancestor: NodeId, node: NodeId) -> bool {
+    ancestor == node || dom.ancestors(node).any(|a| a == ancestor)
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-ensure-pre-insertion-validity
+/// https://dom.spec.whatwg.org/#concept-node-replace
+/// nodeをparentのchildの位置に挿入できるかを検証する。
+/// replaceがtrueの場合は、childをnodeで置き換えられるかを検証する
+fn ensure_validity(
+    dom: &Dom,
+    node: NodeId,
+    parent: NodeId,
+    child: Option<NodeId>,
+    replace: bool,
+) -> Result<(), Error> {
+    let hierarchy_request_error = Err(Error::Dom(DomException::HierarchyRequest));
+
+    if !matches!(
+        dom.node(parent).kind,
+        NodeKind::Document(_) | NodeKind::DocumentFragment(_) | NodeKind::Element(_)
+    ) {
+        return hierarchy_request_error;
+    }
+    // 自分自身や祖先を子にすることはできない
+    if is_inclusive_ancestor(dom, node, parent) {
+        return hierarchy_request_error;
+    }
+    if let Some(child) = child {
+        if dom.node(child).parent() != Some(parent) {
+            return Err(Error::Dom(DomException::NotFound));
+        }
+    }
+
+    let parent_is_document = matches!(dom.node(parent).kind, NodeKind::Document(_));
+    match dom.node(node).kind {
+        NodeKind::Document(_) => return hierarchy_request_error,
+        NodeKind::Text(_) if parent_is_document => return hierarchy_request_error,
+        NodeKind::DocumentType(_) if !parent_is_document => return hierarchy_request_error,
+        _ => {}
+    }
+    if !parent_is_document {
+        return Ok(());
+    }
+
+    // 文書は要素とDOCTYPEをそれぞれ一つまでしか持てず、DOCTYPEは要素より前になければならない
+    let is_element = |n: &NodeId| matches!(dom.node(*n).kind, NodeKind::Element(_));
+    let is_doctype = |n: &NodeId| matches!(dom.node(*n).kind, NodeKind::DocumentType(_));
+    let siblings: Vec<NodeId> = dom.children(parent).collect();
+    let child_index = child.and_then(|c| siblings.iter().position(|s| *s == c));
+    // 置き換える場合は、child自身は取り除かれるので数えない
+    let is_replaced = |n: &NodeId| replace && child == Some(*n);
+    let has_element_child = siblings.iter().any(|s| is_element(s) && !is_replaced(s));
+    let has_doctype_child = siblings.iter().any(|s| is_doctype(s) && !is_replaced(s));
+    let doctype_after_child = match child_index {
+        Some(i) => {
+            let start = if replace { i + 1 } else { i };
+            siblings[start..].iter().any(is_doctype)
+        }
+        None => false,
+    };
+    let element_before_child = match child_index {
+        Some(i) => siblings[..i].iter().any(is_element),
+        None => has_element_child,
+    };
+
+    let valid = match dom.node(node).kind {
+        NodeKind::DocumentFragment(_) => {
+            let elements = dom.children(node).filter(is_element).count();
+            let has_text = dom
+                .children(node)
+                .any(|c| matches!(dom.node(c).kind, NodeKind::Text(_)));
+            !has_text
+                && (elements == 0 || (elements == 1 && !has_element_child && !doctype_after_child))
+        }
+        NodeKind::Element(_) => !has_element_child && !doctype_after_child,
+        NodeKind::DocumentType(_) => !has_doctype_child && !element_before_child,
+        _ => true,
+    };
+    if !valid {
+        return hierarchy_request_error;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use crate::renderer::dom::node::Document;
+    use crate::renderer::dom::node::DocumentFragment;
+    use crate::renderer::dom::node::DocumentType;
+    use crate::renderer::dom::node::Element;
+    use alloc::string::String;
+
+    fn element(dom: &mut Dom, name: &str) -> NodeId {
+        dom.create_node(NodeKind::Element(Element::new(name, Vec::new())))
+    }
+
+    fn text(dom: &mut Dom, s: &str) -> NodeId {
+        dom.create_node(NodeKind::Text(s.to_string()))
+    }
+
+    /// 子の並びを名前の列にする。前後どちらのリンクからたどっても同じになることを確かめる
+    fn child_names(dom: &Dom, parent: NodeId) -> Vec<String> {
+        let name = |n: NodeId| match dom.node(n).kind() {
+            NodeKind::Element(e) => e.tag_name(),
+            NodeKind::Text(s) => s,
+            _ => "".to_string(),
+        };
+        let forward: Vec<String> = dom.children(parent).map(name).collect();
+
+        let mut backward = Vec::new();
+        let mut child = dom.node(parent).last_child();
+        while let Some(c) = child {
+            assert_eq!(Some(parent), dom.node(c).parent());
+            backward.insert(0, name(c));
+            child = dom.node(c).previuos_sibling();
+        }
+        assert_eq!(forward, backward);
+        forward
+    }
+
+    #[test]
+    fn test_append_and_insert_before() {
+        let mut dom = Dom::new();
+        let div = element(&mut dom, "div");
+        let a = element(&mut dom, "a");
+        let b = element(&mut dom, "b");
+        let c = element(&mut dom, "c");
+        assert_eq!(Ok(a), append_child(&mut dom, div, a));
+        assert_eq!(Ok(c), append_child(&mut dom, div, c));
+        assert_eq!(Ok(b), insert_before(&mut dom, div, b, Some(c)));
+        assert_eq!(vec!["a", "b", "c"], child_names(&dom, div));
+
+        // すでに子であるノードを挿入すると移動する
+        insert_before(&mut dom, div, c, Some(a)).unwrap();
+        assert_eq!(vec!["c", "a", "b"], child_names(&dom, div));
+        insert_before(&mut dom, div, a, Some(a)).unwrap();
+        assert_eq!(vec!["c", "a", "b"], child_names(&dom, div));
+
+        // 別の親に移動すると、元の親から取り除かれる
+        let span = element(&mut dom, "span");
+        append_child(&mut dom, span, a).unwrap();
+        assert_eq!(vec!["c", "b"], child_names(&dom, div));
+        assert_eq!(vec!["a"], child_names(&dom, span));
+    }
+
+    #[test]
+    fn test_remove_and_replace_child() {
+        let mut dom = Dom::new();
+        let div = element(&mut dom, "div");
+        let a = element(&mut dom, "a");
+        let b = element(&mut dom, "b");
+        let c = element(&mut dom, "c");
+        for n in [a, b, c] {
+            append_child(&mut dom, div, n).unwrap();
+        }
+
+        assert_eq!(Ok(b), remove_child(&mut dom, div, b));
+        assert_eq!(vec!["a", "c"], child_names(&dom, div));
+        assert!(dom.node(b).parent().is_none());
+        assert_eq!(
+            Err(Error::Dom(DomException::NotFound)),
+            remove_child(&mut dom, div, b)
+        );
+
+        assert_eq!(Ok(a), replace_child(&mut dom, div, b, a));
+        assert_eq!(vec!["b", "c"], child_names(&dom, div));
+        // 兄弟同士で置き換える
+        replace_child(&mut dom, div, c, b).unwrap();
+        assert_eq!(vec!["c"], child_names(&dom, div));
+    }
+
+    #[test]
+    fn test_document_fragment() {
+        let mut dom = Dom::new();
+        let fragment = dom.create_node(NodeKind::DocumentFragment(DocumentFragment::new(None)));
+        let a = element(&mut dom, "a");
+        let t = text(&mut dom, "t");
+        append_child(&mut dom, fragment, a).unwrap();
+        append_child(&mut dom, fragment, t).unwrap();
+
+        let div = element(&mut dom, "div");
+        let b = element(&mut dom, "b");
+        append_child(&mut dom, div, b).unwrap();
+        insert_before(&mut dom, div, fragment, Some(b)).unwrap();
+        assert_eq!(vec!["a", "t", "b"], child_names(&dom, div));
+        assert!(dom.node(fragment).first_child().is_none());
+    }
+
+    #[test]
+    fn test_hierarchy_request_error() {
+        let hierarchy_request_error = Err(Error::Dom(DomException::HierarchyRequest));
+        let mut dom = Dom::new();
+        let div = element(&mut dom, "div");
+        let p = element(&mut dom, "p");
+        append_child(&mut dom, div, p).unwrap();
+
+        // 祖先を子孫の子にすることはできない
+        assert_eq!(hierarchy_request_error, append_child(&mut dom, p, div));
+        assert_eq!(hierarchy_request_error, append_child(&mut dom, div, div));
+        // テキストノードは子を持てない
+        let a = text(&mut dom, "a");
+        assert_eq!(hierarchy_request_error, append_child(&mut dom, a, p));
+        // 子でないノードの直前には挿入できない
+        let a = element(&mut dom, "a");
+        assert_eq!(
+            Err(Error::Dom(DomException::NotFound)),
+            insert_before(&mut dom, p, a, Some(div))
+        );
+
+        let document = dom.create_node(NodeKind::Document(Document::new()));
+        let doctype = dom.create_node(NodeKind::DocumentType(DocumentType::new(
+            "html".to_string(),
+            String::new(),
+            String::new(),
+        )));
+        let t = text(&mut dom, "a");
+        assert_eq!(hierarchy_request_error, append_child(&mut dom, document, t));
+        assert_eq!(
+            hierarchy_request_error,
+            append_child(&mut dom, div, doctype)
+        );
+        let html = element(&mut dom, "html");
+        append_child(&mut dom, document, html).unwrap();
+        // 文書は要素を一つしか持てず、DOCTYPEは要素より前になければならない
+        assert_eq!(hierarchy_request_error, append_child(&mut dom, document, a));
+        assert_eq!(
+            hierarchy_request_error,
+            append_child(&mut dom, document, doctype)
+        );
+        assert_eq!(
+            Ok(doctype),
+            insert_before(&mut dom, document, doctype, Some(html))
+        );
+        // 置き換える場合は、置き換えられる要素は数えない
+        assert!(replace_child(&mut dom, document, a, html).is_ok());
+    }
+
+    #[test]
+    fn test_clone_node() {
+        let mut dom = Dom::new();
+        let div = element(&mut dom, "div");
+        let a = text(&mut dom, "a");
+        append_child(&mut dom, div, a).unwrap();
+        let p = element(&mut dom, "p");
+        let b = text(&mut dom, "b");
+        append_child(&mut dom, p, b).unwrap();
+        append_child(&mut dom, div, p).unwrap();
+
+        let shallow = clone_node(&mut dom, div, false);
+        assert_ne!(div, shallow);
+        assert_eq!(dom.node(div).kind(), dom.node(shallow).kind());
+        assert!(dom.node(shallow).first_child().is_none());
+
+        let deep = clone_node(&mut dom, div, true);
+        assert_eq!(vec!["a", "p"], child_names(&dom, deep));
+        let p_copy = dom.node(deep).last_child().unwrap();
+        assert_ne!(p, p_copy);
+        assert_eq!(vec!["b"], child_names(&dom, p_copy));
+        // コピーは元のツリーに影響しない
+        assert_eq!(vec!["a", "p"], child_names(&dom, div));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut dom = Dom::new();
+        let div = element(&mut dom, "div");
+        let p = element(&mut dom, "p");
+        let nodes = [
+            text(&mut dom, "a"),
+            text(&mut dom, ""),
+            text(&mut dom, "b"),
+            p,
+            text(&mut dom, ""),
+        ];
+        for n in nodes {
+            append_child(&mut dom, div, n).unwrap();
+        }
+        let c = text(&mut dom, "c");
+        let d = text(&mut dom, "d");
+        append_child(&mut dom, p, c).unwrap();
+        append_child(&mut dom, p, d).unwrap();
+
+        normalize(&mut dom, div);
+        assert_eq!(vec!["ab", "p"], child_names(&dom, div));
+        assert_eq!(vec!["cd"], child_names(&dom, p));
+    }
+}
@@ -0,0 +1,419 @@
+use crate::error::Error;
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// https://dom.spec.whatwg.org/#dom-mutationrecord-type
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MutationRecordType {
+    /// 子ノードが追加または削除された
+    ChildList,
+    /// 要素の属性が変更された
+    Attributes,
+    /// テキストノードやコメントの文字列が変更された
+    CharacterData,
+}
+
+/// https://dom.spec.whatwg.org/#interface-mutationrecord
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutationRecord {
+    record_type: MutationRecordType,
+    target: NodeId,
+    added_nodes: Vec<NodeId>,
+    removed_nodes: Vec<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+    attribute_name: Option<String>,
+    old_value: Option<String>,
+}
+
+impl MutationRecord {
+    /// https://dom.spec.whatwg.org/#queue-a-tree-mutation-record
+    pub fn new_child_list(
+        target: NodeId,
+        added_nodes: Vec<NodeId>,
+        removed_nodes: Vec<NodeId>,
+        previous_sibling: Option<NodeId>,
+        next_sibling: Option<NodeId>,
+    ) -> Self {
+        Self {
+            record_type: MutationRecordType::ChildList,
+            target,
+            added_nodes,
+            removed_nodes,
+            previous_sibling,
+            next_sibling,
+            attribute_name: None,
+            old_value: None,
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-change
+    pub fn new_attributes(target: NodeId, name: String, old_value: Option<String>) -> Self {
+        Self {
+            record_type: MutationRecordType::Attributes,
+            target,
+            added_nodes: Vec::new(),
+            removed_nodes: Vec::new(),
+            previous_sibling: None,
+            next_sibling: None,
+            attribute_name: Some(name),
+            old_value,
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-cd-replace
+    pub fn new_character_data(target: NodeId, old_value: String) -> Self {
+        Self {
+            record_type: MutationRecordType::CharacterData,
+            target,
+            added_nodes: Vec::new(),
+            removed_nodes: Vec::new(),
+            previous_sibling: None,
+            next_sibling: None,
+            attribute_name: None,
+            old_value: Some(old_value),
+        }
+    }
+
+    pub fn record_type(&self) -> MutationRecordType {
+        self.record_type
+    }
+
+    pub fn target(&self) -> NodeId {
+        self.target
+    }
+
+    pub fn added_nodes(&self) -> Vec<NodeId> {
+        self.added_nodes.clone()
+    }
+
+    pub fn removed_nodes(&self) -> Vec<NodeId> {
+        self.removed_nodes.clone()
+    }
+
+    pub fn previous_sibling(&self) -> Option<NodeId> {
+        self.previous_sibling
+    }
+
+    pub fn next_sibling(&self) -> Option<NodeId> {
+        self.next_sibling
+    }
+
+    pub fn attribute_name(&self) -> Option<String> {
+        self.attribute_name.clone()
+    }
+
+    pub fn old_value(&self) -> Option<String> {
+        self.old_value.clone()
+    }
+}
+
+/// https://dom.spec.whatwg.org/#dictdef-mutationobserverinit
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MutationObserverInit {
+    pub child_list: bool,
+    pub attributes: bool,
+    pub character_data: bool,
+    pub subtree: bool,
+    pub attribute_old_value: bool,
+    pub character_data_old_value: bool,
+    /// 監視する属性名。Noneの場合はすべての属性を監視する
+    pub attribute_filter: Option<Vec<String>>,
+}
+
+/// Dom::create_observerが返す、アリーナの中のMutationObserverを指すID
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MutationObserverId(pub(crate) usize);
+
+/// https://dom.spec.whatwg.org/#interface-mutationobserver
+/// 監視しているノードと、まだ通知されていない変更の記録を持つ。
+/// 仕様ではノードが登録済みのオブザーバーのリストを持つが、ここではオブザーバー側が監視対象を持つ
+#[derive(Debug, Clone, Default)]
+pub struct MutationObserver {
+    /// https://dom.spec.whatwg.org/#registered-observer
+    registrations: Vec<(NodeId, MutationObserverInit)>,
+    /// https://dom.spec.whatwg.org/#concept-mo-queue
+    record_queue: Vec<MutationRecord>,
+}
+
+impl MutationObserver {
+    pub fn new() -> Self {
+        Self {
+            registrations: Vec::new(),
+            record_queue: Vec::new(),
+        }
+    }
+
+    /// recordの対象を監視しているかどうか。監視している場合は、古い値を記録するかどうかを返す
+    fn interest(&self, dom: &Dom, record: &MutationRecord) -> Option<bool> {
+        let mut interested = None;
+        let target = record.target();
+        let nodes = core::iter::once(target).chain(dom.ancestors(target));
+        for node in nodes {
+            for (registered, options) in &self.registrations {
+                if *registered != node || (node != target && !options.subtree) {
+                    continue;
+                }
+                let old_value = match record.record_type() {
+                    MutationRecordType::ChildList if options.child_list => false,
+                    MutationRecordType::Attributes if options.attributes => {
+                        if let (Some(filter), Some(name)) =
+                            (&options.attribute_filter, &record.attribute_name)
+                        {
+                            if !filter.contains(name) {
+                                continue;
+                            }
+                        }
+                        options.attribute_old_value
+                    }
+                    MutationRecordType::CharacterData if options.character_data => {
+                        options.character_data_old_value
+                    }
+                    _ => continue,
+                };
+                interested = Some(interested.unwrap_or(false) || old_value);
+            }
+        }
+        interested
+    }
+}
+
+/// https://dom.spec.whatwg.org/#dom-mutationobserver-observe
+/// observerにtargetの変更を監視させる。すでに監視している場合は、オプションを置き換える
+pub fn observe(
+    dom: &mut Dom,
+    observer: MutationObserverId,
+    target: NodeId,
+    options: MutationObserverInit,
+) -> Result<(), Error> {
+    let mut options = options;
+    if options.attribute_old_value || options.attribute_filter.is_some() {
+        options.attributes = true;
+    }
+    if options.character_data_old_value {
+        options.character_data = true;
+    }
+    if !options.child_list && !options.attributes && !options.character_data {
+        return Err(Error::UnexpectedInput(
+            "The options must include childList, attributes or characterData".to_string(),
+        ));
+    }
+
+    let registrations = &mut dom.observer_mut(observer).registrations;
+    match registrations.iter_mut().find(|(node, _)| *node == target) {
+        Some(registration) => registration.1 = options,
+        None => registrations.push((target, options)),
+    }
+    Ok(())
+}
+
+/// https://dom.spec.whatwg.org/#dom-mutationobserver-disconnect
+/// observerの監視をすべて止め、まだ通知されていない記録を捨てる
+pub fn disconnect(dom: &mut Dom, observer: MutationObserverId) {
+    let observer = dom.observer_mut(observer);
+    observer.registrations = Vec::new();
+    observer.record_queue = Vec::new();
+}
+
+/// https://dom.spec.whatwg.org/#dom-mutationobserver-takerecords
+/// observerにたまっている記録を取り出す
+pub fn take_records(dom: &mut Dom, observer: MutationObserverId) -> Vec<MutationRecord> {
+    core::mem::take(&mut dom.observer_mut(observer).record_queue)
+}
+
+/// https://dom.spec.whatwg.org/#queue-a-mutation-record
+/// 変更を記録し、スタイルやレイアウトを計算し直す必要があることを印す。
+/// recordの対象を監視しているオブザーバーには、記録を追加する
+pub fn queue_mutation_record(dom: &mut Dom, record: MutationRecord) {
+    // <style>の中の文字列が変わった場合や、要素が追加、削除、変更された場合は、スタイルシートや適用されるルールが変わりうる
+    let style_changed = match record.record_type() {
+        MutationRecordType::CharacterData => dom
+            .node(record.target())
+            .parent()
+            .is_some_and(|p| dom.node(p).element_kind() == Some(ElementKind::Style)),
+        _ => true,
+    };
+    if style_changed {
+        dom.set_style_dirty(true);
+    }
+    dom.set_layout_dirty(true);
+
+    for i in 0..dom.observers_len() {
+        let id = MutationObserverId(i);
+        let record = match dom.observer(id).interest(dom, &record) {
+            Some(true) => record.clone(),
+            // 古い値を求めていないオブザーバーには、古い値を渡さない
+            Some(false) => MutationRecord {
+                old_value: None,
+                ..record.clone()
+            },
+            None => continue,
+        };
+        dom.observer_mut(id).record_queue.push(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::mutation::append_child;
+    use crate::renderer::dom::mutation::change_attributes;
+    use crate::renderer::dom::mutation::remove_child;
+    use crate::renderer::dom::mutation::replace_all;
+    use crate::renderer::dom::mutation::replace_data;
+    use crate::renderer::dom::node::Element;
+    use crate::renderer::dom::node::NodeKind;
+    use alloc::vec;
+
+    fn element(dom: &mut Dom, name: &str) -> NodeId {
+        dom.create_node(NodeKind::Element(Element::new(name, Vec::new())))
+    }
+
+    #[test]
+    fn test_child_list() {
+        let mut dom = Dom::new();
+        let parent = element(&mut dom, "div");
+        let a = element(&mut dom, "a");
+        let b = element(&mut dom, "b");
+        let observer = dom.create_observer();
+        let options = MutationObserverInit {
+            child_list: true,
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), observe(&mut dom, observer, parent, options));
+
+        append_child(&mut dom, parent, a).unwrap();
+        append_child(&mut dom, parent, b).unwrap();
+        remove_child(&mut dom, parent, a).unwrap();
+        assert_eq!(
+            vec![
+                MutationRecord::new_child_list(parent, vec![a], Vec::new(), None, None),
+                MutationRecord::new_child_list(parent, vec![b], Vec::new(), Some(a), None),
+                MutationRecord::new_child_list(parent, Vec::new(), vec![a], None, Some(b)),
+            ],
+            take_records(&mut dom, observer)
+        );
+        assert!(take_records(&mut dom, observer).is_empty());
+
+        // 子をすべて置き換えると、一つの記録になる
+        replace_all(&mut dom, Some(a), parent);
+        assert_eq!(
+            vec![MutationRecord::new_child_list(
+                parent,
+                vec![a],
+                vec![b],
+                None,
+                None
+            )],
+            take_records(&mut dom, observer)
+        );
+
+        disconnect(&mut dom, observer);
+        append_child(&mut dom, parent, b).unwrap();
+        assert!(take_records(&mut dom, observer).is_empty());
+    }
+
+    #[test]
+    fn test_subtree_and_character_data() {
+        let mut dom = Dom::new();
+        let parent = element(&mut dom, "div");
+        let child = element(&mut dom, "p");
+        let text = dom.create_node(NodeKind::Text("old".to_string()));
+        append_child(&mut dom, parent, child).unwrap();
+        append_child(&mut dom, child, text).unwrap();
+
+        let observer = dom.create_observer();
+        let shallow = dom.create_observer();
+        let options = MutationObserverInit {
+            character_data_old_value: true,
+            subtree: true,
+            ..Default::default()
+        };
+        observe(&mut dom, observer, parent, options).unwrap();
+        let options = MutationObserverInit {
+            child_list: true,
+            character_data: true,
+            ..Default::default()
+        };
+        observe(&mut dom, shallow, parent, options).unwrap();
+
+        replace_data(&mut dom, text, "new".to_string());
+        assert_eq!(
+            vec![MutationRecord::new_character_data(text, "old".to_string())],
+            take_records(&mut dom, observer)
+        );
+        // subtreeを指定していないオブザーバーには、子孫の変更は通知されない
+        assert!(take_records(&mut dom, shallow).is_empty());
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut dom = Dom::new();
+        let target = element(&mut dom, "div");
+        let observer = dom.create_observer();
+        let options = MutationObserverInit {
+            attribute_old_value: true,
+            attribute_filter: Some(vec!["class".to_string(), "id".to_string()]),
+            ..Default::default()
+        };
+        observe(&mut dom, observer, target, options).unwrap();
+
+        change_attributes(&mut dom, target, |e| {
+            e.set_attribute("id", "a").unwrap();
+            e.set_attribute("title", "t").unwrap();
+        });
+        change_attributes(&mut dom, target, |e| e.class_list().add(&["x"]));
+        change_attributes(&mut dom, target, |e| e.set_attribute("id", "b"));
+        change_attributes(&mut dom, target, |e| e.remove_attribute("class"));
+        // 値が変わらない変更は記録されない
+        change_attributes(&mut dom, target, |e| e.set_attribute("id", "b"));
+        assert_eq!(
+            vec![
+                MutationRecord::new_attributes(target, "id".to_string(), None),
+                MutationRecord::new_attributes(target, "class".to_string(), None),
+                MutationRecord::new_attributes(target, "id".to_string(), Some("a".to_string())),
+                MutationRecord::new_attributes(target, "class".to_string(), Some("x".to_string())),
+            ],
+            take_records(&mut dom, observer)
+        );
+    }
+
+    #[test]
+    fn test_observe_without_type() {
+        let mut dom = Dom::new();
+        let target = element(&mut dom, "div");
+        let observer = dom.create_observer();
+        let options = MutationObserverInit {
+            subtree: true,
+            ..Default::default()
+        };
+        assert!(observe(&mut dom, observer, target, options).is_err());
+    }
+
+    #[test]
+    fn test_dirty() {
+        let mut dom = Dom::new();
+        let parent = element(&mut dom, "div");
+        let style = element(&mut dom, "style");
+        let text = dom.create_node(NodeKind::Text("p {}".to_string()));
+        append_child(&mut dom, style, text).unwrap();
+        append_child(&mut dom, parent, style).unwrap();
+        assert!(dom.style_dirty());
+        assert!(dom.layout_dirty());
+
+        dom.set_style_dirty(false);
+        dom.set_layout_dirty(false);
+        let p = element(&mut dom, "p");
+        let text = dom.create_node(NodeKind::Text("a".to_string()));
+        append_child(&mut dom, p, text).unwrap();
+        dom.set_style_dirty(false);
+        dom.set_layout_dirty(false);
+        replace_data(&mut dom, text, "b".to_string());
+        assert!(!dom.style_dirty());
+        assert!(dom.layout_dirty());
+    }
+}
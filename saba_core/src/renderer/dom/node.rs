@@ -1,73 +1,399 @@
+use crate::error::DomException;
+use crate::error::Error;
+use crate::renderer::dom::mutation_observer::MutationObserver;
+use crate::renderer::dom::mutation_observer::MutationObserverId;
+use crate::renderer::dom::token_list::DomTokenList;
 use crate::renderer::html::attribute::Attribute;
-use alloc::format;
-use alloc::rc::{Rc, Weak};
+use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::fmt::Display;
 use core::fmt::Formatter;
-use core::str::FromStr;
+use core::iter::Skip;
 
 #[derive(Debug, Clone)]
 pub enum NodeKind {
     /// https://dom.spec.whatwg.org/#interface-document
-    Document,
+    Document(Document),
+    /// https://dom.spec.whatwg.org/#interface-documenttype
+    DocumentType(DocumentType),
     /// https://dom.spec.whatwg.org/#interface-element
     Element(Element),
     /// https://dom.spec.whatwg.org/#interface-text
     Text(String),
+    /// https://dom.spec.whatwg.org/#interface-comment
+    Comment(String),
+    /// https://dom.spec.whatwg.org/#interface-documentfragment
+    DocumentFragment(DocumentFragment),
 }
 
 impl PartialEq for NodeKind {
     fn eq(&self, other: &Self) -> bool {
         match &self {
-            NodeKind::Document => matches!(other, NodeKind::Document),
+            NodeKind::Document(_) => matches!(other, NodeKind::Document(_)),
+            NodeKind::DocumentType(_) => matches!(other, NodeKind::DocumentType(_)),
             NodeKind::Element(e1) => match &other {
                 NodeKind::Element(e2) => e1.kind == e2.kind,
                 _ => false,
             },
             NodeKind::Text(_) => matches!(other, NodeKind::Text(_)),
+            NodeKind::Comment(_) => matches!(other, NodeKind::Comment(_)),
+            NodeKind::DocumentFragment(_) => matches!(other, NodeKind::DocumentFragment(_)),
         }
     }
 }
 
+/// https://dom.spec.whatwg.org/#concept-document-mode
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DocumentMode {
+    NoQuirks,
+    Quirks,
+    LimitedQuirks,
+}
+
+/// https://html.spec.whatwg.org/multipage/dom.html#current-document-readiness
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DocumentReadyState {
+    /// パースしている途中
+    Loading,
+    /// パースが終わり、deferのスクリプトやサブリソースを待っている
+    Interactive,
+    /// すべての読み込みが終わった
+    Complete,
+}
+
+impl Display for DocumentReadyState {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let s = match self {
+            DocumentReadyState::Loading => "loading",
+            DocumentReadyState::Interactive => "interactive",
+            DocumentReadyState::Complete => "complete",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// https://dom.spec.whatwg.org/#interface-document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    mode: DocumentMode,
+    /// https://dom.spec.whatwg.org/#concept-document-url
+    url: String,
+    /// https://dom.spec.whatwg.org/#concept-document-content-type
+    content_type: String,
+    ready_state: DocumentReadyState,
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self {
+            mode: DocumentMode::NoQuirks,
+            url: "about:blank".to_string(),
+            content_type: "text/html".to_string(),
+            ready_state: DocumentReadyState::Complete,
+        }
+    }
+
+    pub fn mode(&self) -> DocumentMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: DocumentMode) {
+        self.mode = mode;
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn set_url(&mut self, url: String) {
+        self.url = url;
+    }
+
+    pub fn content_type(&self) -> String {
+        self.content_type.clone()
+    }
+
+    pub fn set_content_type(&mut self, content_type: String) {
+        self.content_type = content_type;
+    }
+
+    pub fn ready_state(&self) -> DocumentReadyState {
+        self.ready_state
+    }
+
+    pub fn set_ready_state(&mut self, ready_state: DocumentReadyState) {
+        self.ready_state = ready_state;
+    }
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// https://dom.spec.whatwg.org/#interface-documentfragment
+#[derive(Debug, Clone)]
+pub struct DocumentFragment {
+    /// https://dom.spec.whatwg.org/#concept-node-document
+    /// このフラグメントが属している文書。<template>の中身の場合は、ブラウジングコンテキストを持たない文書になる
+    owner_document: Option<NodeId>,
+}
+
+impl DocumentFragment {
+    pub fn new(owner_document: Option<NodeId>) -> Self {
+        Self { owner_document }
+    }
+
+    pub fn owner_document(&self) -> Option<NodeId> {
+        self.owner_document
+    }
+}
+
+/// https://dom.spec.whatwg.org/#interface-documenttype
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentType {
+    name: String,
+    public_id: String,
+    system_id: String,
+}
+
+impl DocumentType {
+    pub fn new(name: String, public_id: String, system_id: String) -> Self {
+        Self {
+            name,
+            public_id,
+            system_id,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn public_id(&self) -> String {
+        self.public_id.clone()
+    }
+
+    pub fn system_id(&self) -> String {
+        self.system_id.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// https://dom.spec.whatwg.org/#interface-element
 pub enum ElementKind {
     /// https://dom.spec.whatwg.org/multipage/semantics.html#the-html-element
     Html,
     /// https://dom.spec.whatwg.org/multipage/semantics.html#the-head-element
     Head,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-title-element
+    Title,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-base-element
+    Base,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-link-element
+    Link,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-meta-element
+    Meta,
     /// https://dom.spec.whatwg.org/multipage/semantics.html#the-style-element
     Style,
     /// https://dom.spec.whatwg.org/multipage/semantics.html#the-script-element
     Script,
+    /// https://html.spec.whatwg.org/multipage/scripting.html#the-noscript-element
+    Noscript,
+    /// https://html.spec.whatwg.org/multipage/scripting.html#the-template-element
+    Template,
     /// https://dom.spec.whatwg.org/multipage/semantics.html#the-body-element
     Body,
-    /// https://dom.spec.whatwg.org/multipage/semantics.html#the-p-element
-    P,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-article-element
+    Article,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-section-element
+    Section,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-nav-element
+    Nav,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-aside-element
+    Aside,
     /// https://dom.spec.whatwg.org/multipage/semantics.html#the-h1-h2-h3-h4-h5-and-h6-elements
     H1,
     H2,
+    H3,
+    H4,
+    H5,
+    H6,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-header-element
+    Header,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-footer-element
+    Footer,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-address-element
+    Address,
+    /// https://dom.spec.whatwg.org/multipage/semantics.html#the-p-element
+    P,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-hr-element
+    Hr,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-pre-element
+    Pre,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-blockquote-element
+    Blockquote,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-ol-element
+    Ol,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-ul-element
+    Ul,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-li-element
+    Li,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-dl-element
+    Dl,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-dt-element
+    Dt,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-dd-element
+    Dd,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-figure-element
+    Figure,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-figcaption-element
+    Figcaption,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-main-element
+    Main,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-div-element
+    Div,
     /// https://dom.spec.whatwg.org/multipage/semantics.html#the-a-elements
     A,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-em-element
+    Em,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-strong-element
+    Strong,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-small-element
+    Small,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-s-element
+    S,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-code-element
+    Code,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-i-element
+    I,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-b-element
+    B,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-u-element
+    U,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-span-element
+    Span,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-br-element
+    Br,
+    /// https://html.spec.whatwg.org/multipage/embedded-content.html#the-img-element
+    Img,
+    /// https://html.spec.whatwg.org/multipage/iframe-embed-object.html#the-iframe-element
+    Iframe,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-table-element
+    Table,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-caption-element
+    Caption,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-colgroup-element
+    Colgroup,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-col-element
+    Col,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-tbody-element
+    Tbody,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-thead-element
+    Thead,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-tfoot-element
+    Tfoot,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-tr-element
+    Tr,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-td-element
+    Td,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-th-element
+    Th,
+    /// https://html.spec.whatwg.org/multipage/forms.html#the-form-element
+    Form,
+    /// https://html.spec.whatwg.org/multipage/forms.html#the-label-element
+    Label,
+    /// https://html.spec.whatwg.org/multipage/input.html#the-input-element
+    Input,
+    /// https://html.spec.whatwg.org/multipage/form-elements.html#the-button-element
+    Button,
+    /// https://html.spec.whatwg.org/multipage/form-elements.html#the-select-element
+    Select,
+    /// https://html.spec.whatwg.org/multipage/form-elements.html#the-option-element
+    Option,
+    /// https://html.spec.whatwg.org/multipage/form-elements.html#the-textarea-element
+    Textarea,
+    /// 上記以外の要素。タグ名をそのまま保持する。
+    /// https://html.spec.whatwg.org/multipage/dom.html#htmlunknownelement
+    Other(String),
 }
 
-impl FromStr for ElementKind {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl From<&str> for ElementKind {
+    fn from(s: &str) -> Self {
         match s {
-            "html" => Ok(ElementKind::Html),
-            "head" => Ok(ElementKind::Head),
-            "style" => Ok(ElementKind::Style),
-            "script" => Ok(ElementKind::Script),
-            "body" => Ok(ElementKind::Body),
-            "p" => Ok(ElementKind::P),
-            "h1" => Ok(ElementKind::H1),
-            "h2" => Ok(ElementKind::H2),
-            "a" => Ok(ElementKind::A),
-            _ => Err(format!("unimplemented element name {:?}", s)),
+            "html" => ElementKind::Html,
+            "head" => ElementKind::Head,
+            "title" => ElementKind::Title,
+            "base" => ElementKind::Base,
+            "link" => ElementKind::Link,
+            "meta" => ElementKind::Meta,
+            "style" => ElementKind::Style,
+            "script" => ElementKind::Script,
+            "noscript" => ElementKind::Noscript,
+            "template" => ElementKind::Template,
+            "body" => ElementKind::Body,
+            "article" => ElementKind::Article,
+            "section" => ElementKind::Section,
+            "nav" => ElementKind::Nav,
+            "aside" => ElementKind::Aside,
+            "h1" => ElementKind::H1,
+            "h2" => ElementKind::H2,
+            "h3" => ElementKind::H3,
+            "h4" => ElementKind::H4,
+            "h5" => ElementKind::H5,
+            "h6" => ElementKind::H6,
+            "header" => ElementKind::Header,
+            "footer" => ElementKind::Footer,
+            "address" => ElementKind::Address,
+            "p" => ElementKind::P,
+            "hr" => ElementKind::Hr,
+            "pre" => ElementKind::Pre,
+            "blockquote" => ElementKind::Blockquote,
+            "ol" => ElementKind::Ol,
+            "ul" => ElementKind::Ul,
+            "li" => ElementKind::Li,
+            "dl" => ElementKind::Dl,
+            "dt" => ElementKind::Dt,
+            "dd" => ElementKind::Dd,
+            "figure" => ElementKind::Figure,
+            "figcaption" => ElementKind::Figcaption,
+            "main" => ElementKind::Main,
+            "div" => ElementKind::Div,
+            "a" => ElementKind::A,
+            "em" => ElementKind::Em,
+            "strong" => ElementKind::Strong,
+            "small" => ElementKind::Small,
+            "s" => ElementKind::S,
+            "code" => ElementKind::Code,
+            "i" => ElementKind::I,
+            "b" => ElementKind::B,
+            "u" => ElementKind::U,
+            "span" => ElementKind::Span,
+            "br" => ElementKind::Br,
+            "img" => ElementKind::Img,
+            "iframe" => ElementKind::Iframe,
+            "table" => ElementKind::Table,
+            "caption" => ElementKind::Caption,
+            "colgroup" => ElementKind::Colgroup,
+            "col" => ElementKind::Col,
+            "tbody" => ElementKind::Tbody,
+            "thead" => ElementKind::Thead,
+            "tfoot" => ElementKind::Tfoot,
+            "tr" => ElementKind::Tr,
+            "td" => ElementKind::Td,
+            "th" => ElementKind::Th,
+            "form" => ElementKind::Form,
+            "label" => ElementKind::Label,
+            "input" => ElementKind::Input,
+            "button" => ElementKind::Button,
+            "select" => ElementKind::Select,
+            "option" => ElementKind::Option,
+            "textarea" => ElementKind::Textarea,
+            _ => ElementKind::Other(s.to_string()),
         }
     }
 }
@@ -77,13 +403,73 @@ impl Display for ElementKind {
         let s = match self {
             ElementKind::Html => "html",
             ElementKind::Head => "head",
+            ElementKind::Title => "title",
+            ElementKind::Base => "base",
+            ElementKind::Link => "link",
+            ElementKind::Meta => "meta",
             ElementKind::Style => "style",
             ElementKind::Script => "script",
+            ElementKind::Noscript => "noscript",
+            ElementKind::Template => "template",
             ElementKind::Body => "body",
+            ElementKind::Article => "article",
+            ElementKind::Section => "section",
+            ElementKind::Nav => "nav",
+            ElementKind::Aside => "aside",
             ElementKind::H1 => "h1",
             ElementKind::H2 => "h2",
+            ElementKind::H3 => "h3",
+            ElementKind::H4 => "h4",
+            ElementKind::H5 => "h5",
+            ElementKind::H6 => "h6",
+            ElementKind::Header => "header",
+            ElementKind::Footer => "footer",
+            ElementKind::Address => "address",
             ElementKind::P => "p",
+            ElementKind::Hr => "hr",
+            ElementKind::Pre => "pre",
+            ElementKind::Blockquote => "blockquote",
+            ElementKind::Ol => "ol",
+            ElementKind::Ul => "ul",
+            ElementKind::Li => "li",
+            ElementKind::Dl => "dl",
+            ElementKind::Dt => "dt",
+            ElementKind::Dd => "dd",
+            ElementKind::Figure => "figure",
+            ElementKind::Figcaption => "figcaption",
+            ElementKind::Main => "main",
+            ElementKind::Div => "div",
             ElementKind::A => "a",
+            ElementKind::Em => "em",
+            ElementKind::Strong => "strong",
+            ElementKind::Small => "small",
+            ElementKind::S => "s",
+            ElementKind::Code => "code",
+            ElementKind::I => "i",
+            ElementKind::B => "b",
+            ElementKind::U => "u",
+            ElementKind::Span => "span",
+            ElementKind::Br => "br",
+            ElementKind::Img => "img",
+            ElementKind::Iframe => "iframe",
+            ElementKind::Table => "table",
+            ElementKind::Caption => "caption",
+            ElementKind::Colgroup => "colgroup",
+            ElementKind::Col => "col",
+            ElementKind::Tbody => "tbody",
+            ElementKind::Thead => "thead",
+            ElementKind::Tfoot => "tfoot",
+            ElementKind::Tr => "tr",
+            ElementKind::Td => "td",
+            ElementKind::Th => "th",
+            ElementKind::Form => "form",
+            ElementKind::Label => "label",
+            ElementKind::Input => "input",
+            ElementKind::Button => "button",
+            ElementKind::Select => "select",
+            ElementKind::Option => "option",
+            ElementKind::Textarea => "textarea",
+            ElementKind::Other(name) => name,
         };
         write!(f, "{}", s)
     }
@@ -92,25 +478,64 @@ impl Display for ElementKind {
 /// https://dom.spec.whatwg.org/multipage/nav-history-apis.html#window
 #[derive(Debug, Clone)]
 pub struct Window {
-    pub document: Rc<RefCell<Node>>,
+    dom: Rc<RefCell<Dom>>,
+    document: NodeId,
 }
 
 impl Window {
     pub fn new() -> Self {
-        let window = Self {
-            document: Rc::new(RefCell::new(Node::new(NodeKind::Document))),
-        };
+        Self::new_in(Rc::new(RefCell::new(Dom::new())))
+    }
 
-        window
-            .document
+    /// 既存のアリーナの中に、新しい文書を持つWindowを作る。
+    /// フラグメントのパースのように、作ったノードを別の文書に移したい場合に使う
+    pub fn new_in(dom: Rc<RefCell<Dom>>) -> Self {
+        let document = dom
             .borrow_mut()
-            .set_window(Rc::downgrade(&Rc::new(RefCell::new(window.clone()))));
+            .create_node(NodeKind::Document(Document::new()));
+        Self { dom, document }
+    }
+
+    /// URLなどを設定済みの文書を持つWindowを作る
+    pub fn new_with_document(document: Document) -> Self {
+        let dom = Rc::new(RefCell::new(Dom::new()));
+        let document = dom.borrow_mut().create_node(NodeKind::Document(document));
+        Self { dom, document }
+    }
+
+    pub fn dom(&self) -> Rc<RefCell<Dom>> {
+        self.dom.clone()
+    }
 
-        window
+    pub fn document(&self) -> NodeId {
+        self.document
     }
+}
 
-    pub fn document(&self) -> Rc<RefCell<Node>> {
-        self.document.clone()
+impl Default for Window {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// https://infra.spec.whatwg.org/#namespaces
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Namespace {
+    /// https://infra.spec.whatwg.org/#html-namespace
+    Html,
+    /// https://infra.spec.whatwg.org/#svg-namespace
+    Svg,
+    /// https://infra.spec.whatwg.org/#mathml-namespace
+    MathMl,
+}
+
+impl Namespace {
+    pub fn url(&self) -> &'static str {
+        match self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+        }
     }
 }
 
@@ -118,34 +543,75 @@ impl Window {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Element {
     kind: ElementKind,
+    namespace: Namespace,
     attributes: Vec<Attribute>,
 }
 
 impl Element {
     pub fn new(element_name: &str, attributes: Vec<Attribute>) -> Self {
+        Self::new_with_namespace(element_name, attributes, Namespace::Html)
+    }
+
+    /// 名前空間を指定して要素を作る。
+    /// HTML以外の名前空間の要素は、大文字と小文字を区別するためにElementKind::Otherとして名前をそのまま保持する
+    pub fn new_with_namespace(
+        element_name: &str,
+        attributes: Vec<Attribute>,
+        namespace: Namespace,
+    ) -> Self {
+        let kind = match namespace {
+            Namespace::Html => ElementKind::from(element_name),
+            _ => ElementKind::Other(element_name.to_string()),
+        };
         Self {
-            kind: ElementKind::from_str(element_name)
-                .expect("failed to convert string to ElementKind"),
+            kind,
+            namespace,
             attributes,
         }
     }
 
     pub fn kind(&self) -> ElementKind {
-        self.kind
+        self.kind.clone()
     }
 
-    pub fn is_block_element(&self) -> bool {
-        match self.kind {
-            ElementKind::Body | ElementKind::H1 | ElementKind::H2 | ElementKind::P => true,
-            _ => false,
-        }
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// 要素のタグ名(ローカル名)を返す。
+    pub fn tag_name(&self) -> String {
+        self.kind.to_string()
+    }
+
+    /// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+    /// 空要素は子を持たず、終了タグも持たない
+    pub fn is_void_element(&self) -> bool {
+        self.namespace == Namespace::Html
+            && matches!(
+                self.tag_name().as_str(),
+                "area"
+                    | "base"
+                    | "br"
+                    | "col"
+                    | "embed"
+                    | "hr"
+                    | "img"
+                    | "input"
+                    | "link"
+                    | "meta"
+                    | "source"
+                    | "track"
+                    | "wbr"
+            )
     }
 
     pub fn attributes(&self) -> Vec<Attribute> {
         self.attributes.clone()
     }
 
+    /// https://dom.spec.whatwg.org/#dom-element-getattribute
     pub fn get_attribute(&self, name: &str) -> Option<String> {
+        let name = self.adjust_attribute_name(name);
         for attr in &self.attributes {
             if attr.name() == name {
                 return Some(attr.value());
@@ -153,97 +619,578 @@ impl Element {
         }
         None
     }
+
+    /// https://dom.spec.whatwg.org/#dom-element-hasattribute
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.get_attribute(name).is_some()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-setattribute
+    /// 属性の値を変更する。属性がまだない場合は、最後の属性として追加する
+    pub fn set_attribute(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        if !is_valid_attribute_name(name) {
+            return Err(Error::Dom(DomException::InvalidCharacter));
+        }
+
+        let name = self.adjust_attribute_name(name);
+        match self.attributes.iter_mut().find(|a| a.name() == name) {
+            Some(attr) => attr.set_value(value.to_string()),
+            None => {
+                let mut attr = Attribute::new();
+                attr.set_name(name);
+                attr.set_value(value.to_string());
+                self.attributes.push(attr);
+            }
+        }
+        Ok(())
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    /// パーサが2つ目の<html>や<body>の開始タグを見つけたときに、まだ持っていない属性だけを追加する
+    pub fn add_missing_attributes(&mut self, attributes: &[Attribute]) {
+        for attr in attributes {
+            if !self.attributes.iter().any(|a| a.name() == attr.name()) {
+                self.attributes.push(attr.clone());
+            }
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-removeattribute
+    /// 属性を取り除き、取り除いた属性の値を返す。属性がない場合はNoneを返す
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        let name = self.adjust_attribute_name(name);
+        let index = self.attributes.iter().position(|a| a.name() == name)?;
+        Some(self.attributes.remove(index).value())
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-toggleattribute
+    /// forceがNoneの場合は属性の有無を反転し、Someの場合はその値に合わせる。
+    /// 最終的に属性があるかどうかを返す
+    pub fn toggle_attribute(&mut self, name: &str, force: Option<bool>) -> Result<bool, Error> {
+        if !is_valid_attribute_name(name) {
+            return Err(Error::Dom(DomException::InvalidCharacter));
+        }
+
+        if self.has_attribute(name) {
+            if force == Some(true) {
+                return Ok(true);
+            }
+            self.remove_attribute(name);
+            return Ok(false);
+        }
+        if force == Some(false) {
+            return Ok(false);
+        }
+        self.set_attribute(name, "")?;
+        Ok(true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-classlist
+    pub fn class_list(&mut self) -> DomTokenList<'_> {
+        DomTokenList::new(self, "class")
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-dataset
+    /// data-*属性の名前からdata-を取り除き、キャメルケースに変換した名前と、その値の組を返す
+    pub fn dataset(&self) -> Vec<(String, String)> {
+        let mut dataset = Vec::new();
+        for attr in &self.attributes {
+            let name = attr.name();
+            let name = match name.strip_prefix("data-") {
+                Some(n) if !n.chars().any(|c| c.is_ascii_uppercase()) => n,
+                _ => continue,
+            };
+
+            // "-"の後に小文字が続く場合は、"-"を取り除いて大文字にする
+            let mut converted = String::new();
+            let mut chars = name.chars().peekable();
+            while let Some(c) = chars.next() {
+                match chars.peek() {
+                    Some(next) if c == '-' && next.is_ascii_lowercase() => {
+                        converted.push(next.to_ascii_uppercase());
+                        chars.next();
+                    }
+                    _ => converted.push(c),
+                }
+            }
+            dataset.push((converted, attr.value()));
+        }
+        dataset
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-domstringmap-setitem
+    /// キャメルケースの名前を、対応するdata-*属性の名前に変換して値を設定する
+    pub fn set_dataset(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let mut chars = name.chars().peekable();
+        let mut attribute_name = "data-".to_string();
+        while let Some(c) = chars.next() {
+            // "-"の後に小文字が続く名前は、data-*属性の名前から変換できない
+            if c == '-' && chars.peek().is_some_and(|n| n.is_ascii_lowercase()) {
+                return Err(Error::Dom(DomException::Syntax));
+            }
+            if c.is_ascii_uppercase() {
+                attribute_name.push('-');
+                attribute_name.push(c.to_ascii_lowercase());
+            } else {
+                attribute_name.push(c);
+            }
+        }
+        self.set_attribute(&attribute_name, value)
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-get-by-name
+    /// HTMLの要素の属性名は、大文字と小文字を区別しない
+    fn adjust_attribute_name(&self, name: &str) -> String {
+        match self.namespace {
+            Namespace::Html => name.to_ascii_lowercase(),
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// https://dom.spec.whatwg.org/#valid-attribute-local-name
+/// 属性名は空でなく、ASCII空白、NULL、"/"、"="、">"を含まない
+fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_ascii_whitespace() || matches!(c, '\0' | '/' | '=' | '>'))
+}
+
+/// アリーナの中のノードを指すID。
+/// 同じアリーナの中では、IDが等しいことと同じノードであることは同値になる
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// https://dom.spec.whatwg.org/#concept-node-tree
+/// すべてのノードを保持するアリーナ。ノード同士は参照ではなくNodeIdで指しあう。
+/// ツリーから取り除かれたノードも、IDが無効にならないようにアリーナには残り続ける
+#[derive(Debug, Clone)]
+pub struct Dom {
+    nodes: Vec<Node>,
+    /// https://dom.spec.whatwg.org/#mutation-observers
+    observers: Vec<MutationObserver>,
+    /// スタイルシートや要素に適用されるスタイルを計算し直す必要があるかどうか
+    style_dirty: bool,
+    /// レイアウトを計算し直す必要があるかどうか
+    layout_dirty: bool,
+    /// https://www.w3.org/TR/selectors-4/#the-hover-pseudo
+    /// ポインタが上にある要素。:hoverに一致するかどうかに使う
+    hovered: Option<NodeId>,
+}
+
+impl Dom {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            observers: Vec::new(),
+            style_dirty: false,
+            layout_dirty: false,
+            hovered: None,
+        }
+    }
+
+    /// どの親にも属さないノードをアリーナに作り、そのIDを返す
+    pub fn create_node(&mut self, kind: NodeKind) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node::new(id, kind));
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-tree-child
+    pub fn children(&self, id: NodeId) -> Children<'_> {
+        Children {
+            dom: self,
+            next: self.node(id).first_child(),
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-tree-ancestor
+    /// 親から根に向かって祖先をたどる。id自身は含まない
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors {
+            dom: self,
+            next: self.node(id).parent(),
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-tree-order
+    /// id自身とその子孫を、ツリー順(深さ優先の行きがけ順)にたどる
+    pub fn preorder(&self, id: NodeId) -> Preorder<'_> {
+        Preorder {
+            dom: self,
+            root: id,
+            next: Some(id),
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-tree-descendant
+    /// idの子孫をツリー順にたどる。id自身は含まない
+    pub fn descendants(&self, id: NodeId) -> Skip<Preorder<'_>> {
+        self.preorder(id).skip(1)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-mutationobserver-mutationobserver
+    /// まだ何も監視していないMutationObserverを作り、そのIDを返す
+    pub fn create_observer(&mut self) -> MutationObserverId {
+        self.observers.push(MutationObserver::new());
+        MutationObserverId(self.observers.len() - 1)
+    }
+
+    pub fn observer(&self, id: MutationObserverId) -> &MutationObserver {
+        &self.observers[id.0]
+    }
+
+    pub(crate) fn observer_mut(&mut self, id: MutationObserverId) -> &mut MutationObserver {
+        &mut self.observers[id.0]
+    }
+
+    pub(crate) fn observers_len(&self) -> usize {
+        self.observers.len()
+    }
+
+    pub fn style_dirty(&self) -> bool {
+        self.style_dirty
+    }
+
+    pub fn set_style_dirty(&mut self, style_dirty: bool) {
+        self.style_dirty = style_dirty;
+    }
+
+    pub fn layout_dirty(&self) -> bool {
+        self.layout_dirty
+    }
+
+    pub fn set_layout_dirty(&mut self, layout_dirty: bool) {
+        self.layout_dirty = layout_dirty;
+    }
+
+    pub fn hovered(&self) -> Option<NodeId> {
+        self.hovered
+    }
+
+    /// ホバーされている要素が変わると、:hoverに一致する要素が変わるのでスタイルを計算し直す
+    pub fn set_hovered(&mut self, hovered: Option<NodeId>) {
+        if self.hovered != hovered {
+            self.hovered = hovered;
+            self.style_dirty = true;
+        }
+    }
+}
+
+impl Default for Dom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Dom::childrenが返すイテレータ
+#[derive(Debug, Clone)]
+pub struct Children<'a> {
+    dom: &'a Dom,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Children<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.dom.node(id).next_sibling();
+        Some(id)
+    }
+}
+
+/// Dom::ancestorsが返すイテレータ
+#[derive(Debug, Clone)]
+pub struct Ancestors<'a> {
+    dom: &'a Dom,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.dom.node(id).parent();
+        Some(id)
+    }
+}
+
+/// Dom::preorderが返すイテレータ
+#[derive(Debug, Clone)]
+pub struct Preorder<'a> {
+    dom: &'a Dom,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Preorder<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+
+        // 子があれば子へ、なければ兄弟へ、それもなければ根に戻るまで親の兄弟を探す
+        let node = self.dom.node(id);
+        self.next = node.first_child();
+        let mut current = id;
+        while self.next.is_none() && current != self.root {
+            let node = self.dom.node(current);
+            self.next = node.next_sibling();
+            current = match node.parent() {
+                Some(p) => p,
+                None => break,
+            };
+        }
+
+        Some(id)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Node {
+    id: NodeId,
     pub kind: NodeKind,
-    window: Weak<RefCell<Window>>,
-    parent: Weak<RefCell<Node>>,
-    first_child: Option<Rc<RefCell<Node>>>,
-    last_child: Weak<RefCell<Node>>,
-    previuos_sibling: Weak<RefCell<Node>>,
-    next_sibling: Option<Rc<RefCell<Node>>>,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previuos_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+    /// https://html.spec.whatwg.org/multipage/scripting.html#template-contents
+    /// <template>の中身。子ノードではないため、描画やスクリプトの探索の対象にならない
+    template_contents: Option<NodeId>,
 }
 
 impl Node {
-    pub fn new(kind: NodeKind) -> Self {
+    fn new(id: NodeId, kind: NodeKind) -> Self {
         Node {
+            id,
             kind,
-            window: Weak::new(),
-            parent: Weak::new(),
+            parent: None,
             first_child: None,
-            last_child: Weak::new(),
-            previuos_sibling: Weak::new(),
+            last_child: None,
+            previuos_sibling: None,
             next_sibling: None,
+            template_contents: None,
         }
     }
 
-    pub fn set_parent(&mut self, parent: Weak<RefCell<Node>>) {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn set_parent(&mut self, parent: Option<NodeId>) {
         self.parent = parent;
     }
 
-    pub fn parent(&self) -> Weak<RefCell<Node>> {
-        self.parent.clone()
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
     }
 
-    pub fn set_first_child(&mut self, first_child: Option<Rc<RefCell<Node>>>) {
+    pub fn set_first_child(&mut self, first_child: Option<NodeId>) {
         self.first_child = first_child;
     }
 
-    pub fn first_child(&self) -> Option<Rc<RefCell<Node>>> {
-        self.first_child.as_ref().cloned()
+    pub fn first_child(&self) -> Option<NodeId> {
+        self.first_child
     }
 
-    pub fn set_last_child(&mut self, last_child: Weak<RefCell<Node>>) {
+    pub fn set_last_child(&mut self, last_child: Option<NodeId>) {
         self.last_child = last_child;
     }
 
-    pub fn last_child(&self) -> Weak<RefCell<Node>> {
-        self.last_child.clone()
+    pub fn last_child(&self) -> Option<NodeId> {
+        self.last_child
     }
 
-    pub fn set_previuos_sibling(&mut self, previuos_sibling: Weak<RefCell<Node>>) {
+    pub fn set_previuos_sibling(&mut self, previuos_sibling: Option<NodeId>) {
         self.previuos_sibling = previuos_sibling;
     }
 
-    pub fn previuos_sibling(&self) -> Weak<RefCell<Node>> {
-        self.previuos_sibling.clone()
+    pub fn previuos_sibling(&self) -> Option<NodeId> {
+        self.previuos_sibling
     }
 
-    pub fn set_next_sibling(&mut self, next_sibling: Option<Rc<RefCell<Node>>>) {
+    pub fn set_next_sibling(&mut self, next_sibling: Option<NodeId>) {
         self.next_sibling = next_sibling;
     }
 
-    pub fn next_sibling(&self) -> Option<Rc<RefCell<Node>>> {
-        self.next_sibling.as_ref().cloned()
+    pub fn next_sibling(&self) -> Option<NodeId> {
+        self.next_sibling
+    }
+
+    pub fn set_template_contents(&mut self, template_contents: Option<NodeId>) {
+        self.template_contents = template_contents;
     }
 
-    pub fn set_window(&mut self, window: Weak<RefCell<Window>>) {
-        self.window = window;
+    pub fn template_contents(&self) -> Option<NodeId> {
+        self.template_contents
     }
 
     pub fn kind(&self) -> NodeKind {
         self.kind.clone()
     }
 
+    pub fn get_document(&self) -> Option<Document> {
+        match self.kind {
+            NodeKind::Document(ref d) => Some(d.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_document_mut(&mut self) -> Option<&mut Document> {
+        match self.kind {
+            NodeKind::Document(ref mut d) => Some(d),
+            _ => None,
+        }
+    }
+
     pub fn get_element(&self) -> Option<Element> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
             NodeKind::Element(ref e) => Some(e.clone()),
+            _ => None,
         }
     }
 
     pub fn element_kind(&self) -> Option<ElementKind> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
-            NodeKind::Element(ref e) => Some(e.kind),
+            NodeKind::Element(ref e) => Some(e.kind.clone()),
+            _ => None,
         }
     }
 }
 
+/// ノードの同一性はIDで決まる。種類が同じでも別のノードであれば等しくない
 impl PartialEq for Node {
     fn eq(&self, other: &Self) -> bool {
-        self.kind == other.kind
+        self.id == other.id
+    }
+}
+
+impl Eq for Node {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use alloc::vec;
+
+    /// <div><p>a</p><p>b</p></div>に相当するツリーを、リンクを直接設定して作る
+    fn create_tree(dom: &mut Dom) -> Vec<NodeId> {
+        let div = dom.create_node(NodeKind::Element(Element::new("div", Vec::new())));
+        let p1 = dom.create_node(NodeKind::Element(Element::new("p", Vec::new())));
+        let a = dom.create_node(NodeKind::Text("a".to_string()));
+        let p2 = dom.create_node(NodeKind::Element(Element::new("p", Vec::new())));
+        let b = dom.create_node(NodeKind::Text("b".to_string()));
+        for (parent, child) in [(div, p1), (p1, a), (div, p2), (p2, b)] {
+            dom.node_mut(child).set_parent(Some(parent));
+            match dom.node(parent).last_child() {
+                Some(last) => {
+                    dom.node_mut(last).set_next_sibling(Some(child));
+                    dom.node_mut(child).set_previuos_sibling(Some(last));
+                }
+                None => dom.node_mut(parent).set_first_child(Some(child)),
+            }
+            dom.node_mut(parent).set_last_child(Some(child));
+        }
+        vec![div, p1, a, p2, b]
+    }
+
+    #[test]
+    fn test_identity() {
+        let mut dom = Dom::new();
+        let ids = create_tree(&mut dom);
+        // 種類が同じ<p>でも、別のノードであれば等しくない
+        assert_eq!(dom.node(ids[1]).kind(), dom.node(ids[3]).kind());
+        assert_ne!(dom.node(ids[1]), dom.node(ids[3]));
+        assert_eq!(dom.node(ids[1]), dom.node(ids[1]));
+    }
+
+    #[test]
+    fn test_iterators() {
+        let mut dom = Dom::new();
+        let ids = create_tree(&mut dom);
+        assert_eq!(
+            vec![ids[1], ids[3]],
+            dom.children(ids[0]).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![ids[3], ids[0]],
+            dom.ancestors(ids[4]).collect::<Vec<_>>()
+        );
+        assert_eq!(ids, dom.preorder(ids[0]).collect::<Vec<_>>());
+        assert_eq!(
+            ids[1..].to_vec(),
+            dom.descendants(ids[0]).collect::<Vec<_>>()
+        );
+        // 部分木をたどる場合は、根の兄弟には進まない
+        assert_eq!(
+            vec![ids[1], ids[2]],
+            dom.preorder(ids[1]).collect::<Vec<_>>()
+        );
+        assert_eq!(0, dom.children(ids[2]).count());
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut element = Element::new("p", Vec::new());
+        element.set_attribute("ID", "a").unwrap();
+        element.set_attribute("title", "t").unwrap();
+        element.set_attribute("id", "b").unwrap();
+        // HTMLの要素の属性名は小文字になり、同じ名前の属性は上書きされる
+        let names: Vec<String> = element.attributes().iter().map(|a| a.name()).collect();
+        assert_eq!(vec!["id".to_string(), "title".to_string()], names);
+        assert_eq!(Some("b".to_string()), element.get_attribute("Id"));
+        assert!(element.has_attribute("TITLE"));
+        assert_eq!(
+            Err(Error::Dom(DomException::InvalidCharacter)),
+            element.set_attribute("a b", "")
+        );
+
+        assert_eq!(Some("t".to_string()), element.remove_attribute("title"));
+        assert_eq!(None, element.remove_attribute("title"));
+        assert_eq!(Ok(true), element.toggle_attribute("hidden", None));
+        assert_eq!(Some("".to_string()), element.get_attribute("hidden"));
+        assert_eq!(Ok(true), element.toggle_attribute("hidden", Some(true)));
+        assert_eq!(Ok(false), element.toggle_attribute("hidden", None));
+        assert_eq!(Ok(false), element.toggle_attribute("hidden", Some(false)));
+
+        // HTML以外の名前空間では、大文字と小文字を区別する
+        let mut svg = Element::new_with_namespace("svg", Vec::new(), Namespace::Svg);
+        svg.set_attribute("viewBox", "0 0 1 1").unwrap();
+        assert!(svg.has_attribute("viewBox"));
+        assert!(!svg.has_attribute("viewbox"));
+    }
+
+    #[test]
+    fn test_dataset() {
+        let mut element = Element::new("div", Vec::new());
+        element.set_attribute("data-user-id", "1").unwrap();
+        element.set_attribute("data-x", "2").unwrap();
+        element.set_attribute("title", "3").unwrap();
+        element.set_dataset("fooBar", "4").unwrap();
+        assert_eq!(
+            vec![
+                ("userId".to_string(), "1".to_string()),
+                ("x".to_string(), "2".to_string()),
+                ("fooBar".to_string(), "4".to_string()),
+            ],
+            element.dataset()
+        );
+        assert_eq!(Some("4".to_string()), element.get_attribute("data-foo-bar"));
+        assert_eq!(
+            Err(Error::Dom(DomException::Syntax)),
+            element.set_dataset("foo-bar", "5")
+        );
     }
 }
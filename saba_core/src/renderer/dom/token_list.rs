@@ -0,0 +1,207 @@
+use crate::error::DomException;
+use crate::error::Error;
+use crate::renderer::dom::node::Element;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// https://dom.spec.whatwg.org/#interface-domtokenlist
+/// 要素の属性の値を、空白で区切られたトークンの順序付き集合として扱う。
+/// 集合を変更すると、属性の値もすぐに書き換わる
+#[derive(Debug)]
+pub struct DomTokenList<'a> {
+    element: &'a mut Element,
+    local_name: &'static str,
+}
+
+impl<'a> DomTokenList<'a> {
+    pub fn new(element: &'a mut Element, local_name: &'static str) -> Self {
+        Self {
+            element,
+            local_name,
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-dtl-tokens
+    /// https://dom.spec.whatwg.org/#concept-ordered-set-parser
+    /// 属性の値をASCII空白で区切り、重複を取り除いたトークンの列を返す
+    pub fn tokens(&self) -> Vec<String> {
+        let value = self.value();
+        let mut tokens: Vec<String> = Vec::new();
+        for token in value.split_ascii_whitespace() {
+            if !tokens.iter().any(|t| t == token) {
+                tokens.push(token.to_string());
+            }
+        }
+        tokens
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-value
+    pub fn value(&self) -> String {
+        self.element
+            .get_attribute(self.local_name)
+            .unwrap_or_default()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-length
+    pub fn length(&self) -> usize {
+        self.tokens().len()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-item
+    pub fn item(&self, index: usize) -> Option<String> {
+        self.tokens().get(index).cloned()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-contains
+    pub fn contains(&self, token: &str) -> bool {
+        self.tokens().iter().any(|t| t == token)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-add
+    pub fn add(&mut self, tokens: &[&str]) -> Result<(), Error> {
+        for token in tokens {
+            validate_token(token)?;
+        }
+        let mut set = self.tokens();
+        for token in tokens {
+            if !set.iter().any(|t| t == token) {
+                set.push(token.to_string());
+            }
+        }
+        self.update(set);
+        Ok(())
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-remove
+    pub fn remove(&mut self, tokens: &[&str]) -> Result<(), Error> {
+        for token in tokens {
+            validate_token(token)?;
+        }
+        let mut set = self.tokens();
+        set.retain(|t| !tokens.contains(&t.as_str()));
+        self.update(set);
+        Ok(())
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-toggle
+    /// forceがNoneの場合はトークンの有無を反転し、Someの場合はその値に合わせる。
+    /// 最終的にトークンが含まれているかどうかを返す
+    pub fn toggle(&mut self, token: &str, force: Option<bool>) -> Result<bool, Error> {
+        validate_token(token)?;
+        let mut set = self.tokens();
+        if set.iter().any(|t| t == token) {
+            if force == Some(true) {
+                return Ok(true);
+            }
+            set.retain(|t| t != token);
+            self.update(set);
+            return Ok(false);
+        }
+        if force == Some(false) {
+            return Ok(false);
+        }
+        set.push(token.to_string());
+        self.update(set);
+        Ok(true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-replace
+    /// tokenをnew_tokenで置き換える。tokenが含まれていなかった場合はfalseを返す
+    pub fn replace(&mut self, token: &str, new_token: &str) -> Result<bool, Error> {
+        validate_token(token)?;
+        validate_token(new_token)?;
+        let set = self.tokens();
+        if !set.iter().any(|t| t == token) {
+            return Ok(false);
+        }
+
+        // 最初に現れた方の位置に置き換え、後に現れた方は取り除く
+        let mut replaced: Vec<String> = Vec::new();
+        for t in set {
+            let t = if t == token { new_token.to_string() } else { t };
+            if !replaced.contains(&t) {
+                replaced.push(t);
+            }
+        }
+        self.update(replaced);
+        Ok(true)
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-dtl-update
+    fn update(&mut self, tokens: Vec<String>) {
+        if !self.element.has_attribute(self.local_name) && tokens.is_empty() {
+            return;
+        }
+        // 属性名は固定の有効な名前なので、失敗することはない
+        let _ = self
+            .element
+            .set_attribute(self.local_name, &tokens.join(" "));
+    }
+}
+
+/// https://dom.spec.whatwg.org/#dom-domtokenlist-add
+/// トークンは空でなく、ASCII空白を含んではならない
+fn validate_token(token: &str) -> Result<(), Error> {
+    if token.is_empty() {
+        return Err(Error::Dom(DomException::Syntax));
+    }
+    if token.chars().any(|c| c.is_ascii_whitespace()) {
+        return Err(Error::Dom(DomException::InvalidCharacter));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_add_remove_toggle() {
+        let mut element = Element::new("p", Vec::new());
+        let mut list = element.class_list();
+        assert_eq!(0, list.length());
+        // トークンがなく、属性もない場合は属性を作らない
+        list.remove(&["a"]).unwrap();
+        assert!(!list.element.has_attribute("class"));
+
+        list.add(&["a", "b", "a"]).unwrap();
+        assert_eq!(vec!["a".to_string(), "b".to_string()], list.tokens());
+        assert!(list.contains("b"));
+        assert_eq!(Some("b".to_string()), list.item(1));
+
+        assert_eq!(Ok(false), list.toggle("a", None));
+        assert_eq!(Ok(true), list.toggle("c", None));
+        assert_eq!(Ok(true), list.toggle("c", Some(true)));
+        assert_eq!(Ok(false), list.toggle("d", Some(false)));
+        assert_eq!("b c".to_string(), list.value());
+
+        list.remove(&["b", "c"]).unwrap();
+        assert_eq!(Some("".to_string()), element.get_attribute("class"));
+    }
+
+    #[test]
+    fn test_replace_and_duplicates() {
+        let mut element = Element::new("p", Vec::new());
+        element.set_attribute("class", "  a b\ta c ").unwrap();
+        let mut list = element.class_list();
+        assert_eq!(3, list.length());
+
+        assert_eq!(Ok(true), list.replace("c", "a"));
+        assert_eq!(Ok(false), list.replace("x", "y"));
+        assert_eq!("a b".to_string(), list.value());
+    }
+
+    #[test]
+    fn test_invalid_token() {
+        let mut element = Element::new("p", Vec::new());
+        let mut list = element.class_list();
+        assert_eq!(Err(Error::Dom(DomException::Syntax)), list.add(&[""]));
+        assert_eq!(
+            Err(Error::Dom(DomException::InvalidCharacter)),
+            list.toggle("a b", None)
+        );
+        assert!(!list.element.has_attribute("class"));
+    }
+}
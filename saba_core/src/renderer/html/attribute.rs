@@ -22,10 +22,18 @@ impl Attribute {
         }
     }
 
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub fn name(&self) -> String {
         self.name.clone()
     }
 
+    pub fn set_value(&mut self, value: String) {
+        self.value = value;
+    }
+
     pub fn value(&self) -> String {
         self.value.clone()
     }
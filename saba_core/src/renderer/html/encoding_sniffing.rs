@@ -0,0 +1,343 @@
+use crate::encoding::decode;
+use crate::encoding::Encoding;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// プレスキャンで調べるバイト数
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+const PRESCAN_LENGTH: usize = 1024;
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm
+/// `content_type`はHTTPレスポンスのContent-Typeヘッダの値
+pub fn sniff_encoding(bytes: &[u8], content_type: Option<&str>) -> Encoding {
+    // 1. BOMがあればそれに従う
+    if let Some((encoding, _)) = Encoding::sniff_bom(bytes) {
+        return encoding;
+    }
+
+    // 2. トランスポート層（Content-Typeのcharsetパラメータ）で指定されたエンコーディング
+    if let Some(encoding) = content_type.and_then(charset_from_content_type) {
+        return encoding;
+    }
+
+    // 3. 先頭のバイト列から<meta charset>を探す
+    let end = core::cmp::min(bytes.len(), PRESCAN_LENGTH);
+    if let Some(encoding) = prescan(&bytes[..end]) {
+        return encoding;
+    }
+
+    // 見つからなかった場合のデフォルトは実装依存。これまで通りUTF-8として扱う
+    Encoding::Utf8
+}
+
+/// エンコーディングを判定して、バイト列を文字列にデコードする
+pub fn decode_document(bytes: &[u8], content_type: Option<&str>) -> String {
+    decode(bytes, sniff_encoding(bytes, content_type))
+}
+
+/// https://mimesniff.spec.whatwg.org/#parsing-a-mime-type
+/// Content-Typeヘッダの値からcharsetパラメータを取り出す
+pub fn charset_from_content_type(content_type: &str) -> Option<Encoding> {
+    for parameter in content_type.split(';').skip(1) {
+        let (name, value) = match parameter.split_once('=') {
+            Some((n, v)) => (n.trim(), v.trim()),
+            None => continue,
+        };
+        if !name.eq_ignore_ascii_case("charset") {
+            continue;
+        }
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.split('"').next())
+            .unwrap_or(value);
+        return Encoding::for_label(value);
+    }
+    None
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, 0x09 | 0x0A | 0x0C | 0x0D | 0x20)
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+pub fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+
+        if rest.starts_with(b"<!--") {
+            // "-->"の直後まで進める。"<!-->"のように"<!--"の"--"を共有してもよい
+            match find(&bytes[pos + 2..], b"-->") {
+                Some(i) => pos += 2 + i + 3,
+                None => return None,
+            }
+            continue;
+        }
+
+        if starts_with_ignore_ascii_case(rest, b"<meta")
+            && rest.get(5).is_some_and(|b| is_whitespace(*b) || *b == b'/')
+        {
+            pos += 5;
+            if let Some(encoding) = prescan_meta(bytes, &mut pos) {
+                return Some(encoding);
+            }
+            continue;
+        }
+
+        if rest.len() >= 2
+            && rest[0] == b'<'
+            && (rest[1].is_ascii_alphabetic()
+                || (rest[1] == b'/' && rest.get(2).is_some_and(|b| b.is_ascii_alphabetic())))
+        {
+            // 任意のタグは属性を読み飛ばす
+            while pos < bytes.len() && !is_whitespace(bytes[pos]) && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos).is_some() {}
+            pos += 1;
+            continue;
+        }
+
+        if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            match rest.iter().position(|b| *b == b'>') {
+                Some(i) => pos += i + 1,
+                None => return None,
+            }
+            continue;
+        }
+
+        pos += 1;
+    }
+
+    None
+}
+
+/// <meta>タグの属性を読み、エンコーディングが指定されていればそれを返す
+fn prescan_meta(bytes: &[u8], pos: &mut usize) -> Option<Encoding> {
+    let mut attribute_names: Vec<String> = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma: Option<bool> = None;
+    let mut charset: Option<Encoding> = None;
+
+    while let Some((name, value)) = get_attribute(bytes, pos) {
+        if attribute_names.contains(&name) {
+            continue;
+        }
+        match name.as_str() {
+            "http-equiv" if value == "content-type" => got_pragma = true,
+            "content" if charset.is_none() => {
+                if let Some(encoding) = extract_encoding_from_meta(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            "charset" => {
+                charset = Encoding::for_label(&value);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        attribute_names.push(name);
+    }
+
+    match need_pragma {
+        None => return None,
+        Some(true) if !got_pragma => return None,
+        _ => {}
+    }
+
+    match charset? {
+        // バイト列を読めている時点でUTF-16ではないので、UTF-8として扱う
+        Encoding::Utf16Be | Encoding::Utf16Le => Some(Encoding::Utf8),
+        encoding => Some(encoding),
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+/// 属性の名前と値を返す。名前と値はASCIIの小文字に変換される
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(String, String)> {
+    while *pos < bytes.len() && (is_whitespace(bytes[*pos]) || bytes[*pos] == b'/') {
+        *pos += 1;
+    }
+    if *pos >= bytes.len() || bytes[*pos] == b'>' {
+        return None;
+    }
+
+    let mut name = String::new();
+    let mut value = String::new();
+
+    // 属性名
+    loop {
+        let b = *bytes.get(*pos)?;
+        if b == b'=' && !name.is_empty() {
+            *pos += 1;
+            break;
+        }
+        if is_whitespace(b) {
+            // 属性名の後の空白を読み飛ばし、"="がなければ値のない属性とする
+            while *pos < bytes.len() && is_whitespace(bytes[*pos]) {
+                *pos += 1;
+            }
+            if bytes.get(*pos) != Some(&b'=') {
+                return Some((name, value));
+            }
+            *pos += 1;
+            break;
+        }
+        if b == b'/' || b == b'>' {
+            return Some((name, value));
+        }
+        name.push(b.to_ascii_lowercase() as char);
+        *pos += 1;
+    }
+
+    // 属性値
+    while *pos < bytes.len() && is_whitespace(bytes[*pos]) {
+        *pos += 1;
+    }
+    let b = *bytes.get(*pos)?;
+    if b == b'"' || b == b'\'' {
+        *pos += 1;
+        loop {
+            let c = *bytes.get(*pos)?;
+            *pos += 1;
+            if c == b {
+                return Some((name, value));
+            }
+            value.push(c.to_ascii_lowercase() as char);
+        }
+    }
+    if b == b'>' {
+        return Some((name, value));
+    }
+    loop {
+        let c = *bytes.get(*pos)?;
+        if is_whitespace(c) || c == b'>' {
+            return Some((name, value));
+        }
+        value.push(c.to_ascii_lowercase() as char);
+        *pos += 1;
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+pub fn extract_encoding_from_meta(content: &str) -> Option<Encoding> {
+    let s = content.as_bytes();
+    let mut pos = 0;
+
+    loop {
+        let i = find_ignore_ascii_case(&s[pos..], b"charset")?;
+        pos += i + "charset".len();
+
+        while pos < s.len() && is_whitespace(s[pos]) {
+            pos += 1;
+        }
+        if s.get(pos) != Some(&b'=') {
+            // "charset"の後に"="がなければ、その次の"charset"を探す
+            continue;
+        }
+        pos += 1;
+        while pos < s.len() && is_whitespace(s[pos]) {
+            pos += 1;
+        }
+
+        return match s.get(pos) {
+            Some(q @ (b'"' | b'\'')) => {
+                let rest = &content[pos + 1..];
+                let end = rest.find(*q as char)?;
+                Encoding::for_label(&rest[..end])
+            }
+            Some(_) => {
+                let rest = &content[pos..];
+                let end = rest
+                    .find(|c: char| c == ';' || (c.is_ascii() && is_whitespace(c as u8)))
+                    .unwrap_or(rest.len());
+                Encoding::for_label(&rest[..end])
+            }
+            None => None,
+        };
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn find_ignore_ascii_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle))
+}
+
+fn starts_with_ignore_ascii_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+
+    #[test]
+    fn test_sniff_bom() {
+        let bytes = [0xFE, 0xFF, 0x00, 0x61];
+        assert_eq!(
+            Encoding::Utf16Be,
+            sniff_encoding(&bytes, Some("text/html; charset=shift_jis"))
+        );
+        assert_eq!("a".to_string(), decode_document(&bytes, None));
+    }
+
+    #[test]
+    fn test_sniff_content_type() {
+        let bytes = b"<meta charset=utf-8>";
+        assert_eq!(
+            Encoding::EucJp,
+            sniff_encoding(bytes, Some("text/html; charset=\"EUC-JP\""))
+        );
+        assert_eq!(Encoding::Utf8, sniff_encoding(bytes, Some("text/html")));
+    }
+
+    #[test]
+    fn test_prescan_meta_charset() {
+        let bytes = b"<!DOCTYPE html><!-- <meta charset=euc-jp> --><html><head title='<meta charset=utf-8>'><META CHARSET=\"Shift_JIS\">";
+        assert_eq!(Some(Encoding::ShiftJis), prescan(bytes));
+    }
+
+    #[test]
+    fn test_prescan_http_equiv() {
+        let bytes =
+            b"<meta content=\"text/html; charset=iso-2022-jp\" http-equiv=\"Content-Type\">";
+        assert_eq!(Some(Encoding::Iso2022Jp), prescan(bytes));
+        // http-equivがない場合はcontentを使わない
+        let bytes = b"<meta content=\"text/html; charset=iso-2022-jp\">";
+        assert_eq!(None, prescan(bytes));
+        // UTF-16はUTF-8として扱う
+        assert_eq!(Some(Encoding::Utf8), prescan(b"<meta charset=utf-16>"));
+    }
+
+    #[test]
+    fn test_extract_encoding_from_meta() {
+        assert_eq!(
+            Some(Encoding::ShiftJis),
+            extract_encoding_from_meta("text/html; charset = 'sjis'")
+        );
+        assert_eq!(
+            Some(Encoding::EucJp),
+            extract_encoding_from_meta("charset; charset=euc-jp;")
+        );
+        assert_eq!(None, extract_encoding_from_meta("charset=\"sjis"));
+    }
+
+    #[test]
+    fn test_decode_document() {
+        // <meta charset=shift_jis><p>日本語
+        let mut bytes = b"<meta charset=shift_jis><p>".to_vec();
+        bytes.extend([0x93, 0xFA, 0x96, 0x7B, 0x8C, 0xEA]);
+        assert_eq!(
+            "<meta charset=shift_jis><p>日本語".to_string(),
+            decode_document(&bytes, None)
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use crate::renderer::dom::node::Element;
+use crate::renderer::dom::node::Namespace;
+use crate::renderer::html::attribute::Attribute;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+/// 外部コンテンツの中に現れると、外部コンテンツを抜けてHTMLとして処理される開始タグ
+static BREAKOUT_TAGS: [&str; 44] = [
+    "b",
+    "big",
+    "blockquote",
+    "body",
+    "br",
+    "center",
+    "code",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "embed",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "listing",
+    "menu",
+    "meta",
+    "nobr",
+    "ol",
+    "p",
+    "pre",
+    "ruby",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "strike",
+    "sub",
+    "sup",
+    "table",
+    "tt",
+    "u",
+    "ul",
+    "var",
+];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+/// 小文字に変換されたSVGのタグ名と、本来のタグ名の対応表
+static SVG_TAG_NAMES: [(&str, &str); 37] = [
+    ("altglyph", "altGlyph"),
+    ("altglyphdef", "altGlyphDef"),
+    ("altglyphitem", "altGlyphItem"),
+    ("animatecolor", "animateColor"),
+    ("animatemotion", "animateMotion"),
+    ("animatetransform", "animateTransform"),
+    ("clippath", "clipPath"),
+    ("feblend", "feBlend"),
+    ("fecolormatrix", "feColorMatrix"),
+    ("fecomponenttransfer", "feComponentTransfer"),
+    ("fecomposite", "feComposite"),
+    ("feconvolvematrix", "feConvolveMatrix"),
+    ("fediffuselighting", "feDiffuseLighting"),
+    ("fedisplacementmap", "feDisplacementMap"),
+    ("fedistantlight", "feDistantLight"),
+    ("fedropshadow", "feDropShadow"),
+    ("feflood", "feFlood"),
+    ("fefunca", "feFuncA"),
+    ("fefuncb", "feFuncB"),
+    ("fefuncg", "feFuncG"),
+    ("fefuncr", "feFuncR"),
+    ("fegaussianblur", "feGaussianBlur"),
+    ("feimage", "feImage"),
+    ("femerge", "feMerge"),
+    ("femergenode", "feMergeNode"),
+    ("femorphology", "feMorphology"),
+    ("feoffset", "feOffset"),
+    ("fepointlight", "fePointLight"),
+    ("fespecularlighting", "feSpecularLighting"),
+    ("fespotlight", "feSpotLight"),
+    ("fetile", "feTile"),
+    ("feturbulence", "feTurbulence"),
+    ("foreignobject", "foreignObject"),
+    ("glyphref", "glyphRef"),
+    ("lineargradient", "linearGradient"),
+    ("radialgradient", "radialGradient"),
+    ("textpath", "textPath"),
+];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#adjust-svg-attributes
+/// 小文字に変換されたSVGの属性名と、本来の属性名の対応表
+static SVG_ATTRIBUTE_NAMES: [(&str, &str); 58] = [
+    ("attributename", "attributeName"),
+    ("attributetype", "attributeType"),
+    ("basefrequency", "baseFrequency"),
+    ("baseprofile", "baseProfile"),
+    ("calcmode", "calcMode"),
+    ("clippathunits", "clipPathUnits"),
+    ("diffuseconstant", "diffuseConstant"),
+    ("edgemode", "edgeMode"),
+    ("filterunits", "filterUnits"),
+    ("glyphref", "glyphRef"),
+    ("gradienttransform", "gradientTransform"),
+    ("gradientunits", "gradientUnits"),
+    ("kernelmatrix", "kernelMatrix"),
+    ("kernelunitlength", "kernelUnitLength"),
+    ("keypoints", "keyPoints"),
+    ("keysplines", "keySplines"),
+    ("keytimes", "keyTimes"),
+    ("lengthadjust", "lengthAdjust"),
+    ("limitingconeangle", "limitingConeAngle"),
+    ("markerheight", "markerHeight"),
+    ("markerunits", "markerUnits"),
+    ("markerwidth", "markerWidth"),
+    ("maskcontentunits", "maskContentUnits"),
+    ("maskunits", "maskUnits"),
+    ("numoctaves", "numOctaves"),
+    ("pathlength", "pathLength"),
+    ("patterncontentunits", "patternContentUnits"),
+    ("patterntransform", "patternTransform"),
+    ("patternunits", "patternUnits"),
+    ("pointsatx", "pointsAtX"),
+    ("pointsaty", "pointsAtY"),
+    ("pointsatz", "pointsAtZ"),
+    ("preservealpha", "preserveAlpha"),
+    ("preserveaspectratio", "preserveAspectRatio"),
+    ("primitiveunits", "primitiveUnits"),
+    ("refx", "refX"),
+    ("refy", "refY"),
+    ("repeatcount", "repeatCount"),
+    ("repeatdur", "repeatDur"),
+    ("requiredextensions", "requiredExtensions"),
+    ("requiredfeatures", "requiredFeatures"),
+    ("specularconstant", "specularConstant"),
+    ("specularexponent", "specularExponent"),
+    ("spreadmethod", "spreadMethod"),
+    ("startoffset", "startOffset"),
+    ("stddeviation", "stdDeviation"),
+    ("stitchtiles", "stitchTiles"),
+    ("surfacescale", "surfaceScale"),
+    ("systemlanguage", "systemLanguage"),
+    ("tablevalues", "tableValues"),
+    ("targetx", "targetX"),
+    ("targety", "targetY"),
+    ("textlength", "textLength"),
+    ("viewbox", "viewBox"),
+    ("viewtarget", "viewTarget"),
+    ("xchannelselector", "xChannelSelector"),
+    ("ychannelselector", "yChannelSelector"),
+    ("zoomandpan", "zoomAndPan"),
+];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+/// 外部コンテンツを抜けてHTMLとして処理される開始タグかどうか
+pub fn is_breakout_start_tag(tag: &str, attributes: &[Attribute]) -> bool {
+    if BREAKOUT_TAGS.contains(&tag) {
+        return true;
+    }
+    // <font>は色や大きさの属性がある場合のみHTMLとして扱う
+    tag == "font"
+        && attributes
+            .iter()
+            .any(|a| matches!(a.name().as_str(), "color" | "face" | "size"))
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+/// SVGのタグ名の大文字と小文字を本来のものに戻す
+pub fn adjust_svg_tag_name(tag: &str) -> String {
+    for (lower, adjusted) in SVG_TAG_NAMES.iter() {
+        if tag.eq_ignore_ascii_case(lower) {
+            return adjusted.to_string();
+        }
+    }
+    tag.to_string()
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#adjust-svg-attributes
+pub fn adjust_svg_attributes(attributes: &mut [Attribute]) {
+    for attr in attributes.iter_mut() {
+        let name = attr.name();
+        for (lower, adjusted) in SVG_ATTRIBUTE_NAMES.iter() {
+            if name.eq_ignore_ascii_case(lower) {
+                attr.set_name(adjusted.to_string());
+                break;
+            }
+        }
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#adjust-mathml-attributes
+pub fn adjust_mathml_attributes(attributes: &mut [Attribute]) {
+    for attr in attributes.iter_mut() {
+        if attr.name().eq_ignore_ascii_case("definitionurl") {
+            attr.set_name("definitionURL".to_string());
+        }
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#mathml-text-integration-point
+pub fn is_mathml_text_integration_point(element: &Element) -> bool {
+    element.namespace() == Namespace::MathMl
+        && matches!(
+            element.tag_name().as_str(),
+            "mi" | "mo" | "mn" | "ms" | "mtext"
+        )
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#html-integration-point
+pub fn is_html_integration_point(element: &Element) -> bool {
+    match element.namespace() {
+        Namespace::MathMl => {
+            element.tag_name() == "annotation-xml"
+                && element.get_attribute("encoding").is_some_and(|e| {
+                    e.eq_ignore_ascii_case("text/html")
+                        || e.eq_ignore_ascii_case("application/xhtml+xml")
+                })
+        }
+        Namespace::Svg => matches!(
+            element.tag_name().as_str(),
+            "foreignObject" | "desc" | "title"
+        ),
+        Namespace::Html => false,
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#special
+/// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-scope
+/// 特別な要素であり、スコープの境界にもなるSVGとMathMLの要素かどうか
+pub fn is_special_foreign_element(element: &Element) -> bool {
+    match element.namespace() {
+        Namespace::MathMl => matches!(
+            element.tag_name().as_str(),
+            "mi" | "mo" | "mn" | "ms" | "mtext" | "annotation-xml"
+        ),
+        Namespace::Svg => matches!(
+            element.tag_name().as_str(),
+            "foreignObject" | "desc" | "title"
+        ),
+        Namespace::Html => false,
+    }
+}
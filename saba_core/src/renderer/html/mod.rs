@@ -1,3 +1,8 @@
 pub mod attribute;
+pub mod encoding_sniffing;
+pub mod entities;
+pub mod foreign_content;
+pub mod parse_error;
 pub mod parser;
+pub mod serializer;
 pub mod token;
@@ -0,0 +1,151 @@
+use alloc::string::String;
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    // トークナイズの段階で発生するパースエラー。名前は仕様書のエラーコードに対応する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-abrupt-closing-of-empty-comment
+    AbruptClosingOfEmptyComment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-abrupt-doctype-public-identifier
+    AbruptDoctypePublicIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-abrupt-doctype-system-identifier
+    AbruptDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-absence-of-digits-in-numeric-character-reference
+    AbsenceOfDigitsInNumericCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-cdata-in-html-content
+    CdataInHtmlContent,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-character-reference-outside-unicode-range
+    CharacterReferenceOutsideUnicodeRange,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-control-character-reference
+    ControlCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-duplicate-attribute
+    DuplicateAttribute,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-end-tag-with-attributes
+    EndTagWithAttributes,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-end-tag-with-trailing-solidus
+    EndTagWithTrailingSolidus,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-eof-before-tag-name
+    EofBeforeTagName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-eof-in-cdata
+    EofInCdata,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-eof-in-comment
+    EofInComment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-eof-in-doctype
+    EofInDoctype,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-eof-in-tag
+    EofInTag,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-incorrectly-closed-comment
+    IncorrectlyClosedComment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-incorrectly-opened-comment
+    IncorrectlyOpenedComment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-invalid-character-sequence-after-doctype-name
+    InvalidCharacterSequenceAfterDoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-invalid-first-character-of-tag-name
+    InvalidFirstCharacterOfTagName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-attribute-value
+    MissingAttributeValue,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-doctype-name
+    MissingDoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-doctype-public-identifier
+    MissingDoctypePublicIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-doctype-system-identifier
+    MissingDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-end-tag-name
+    MissingEndTagName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-quote-before-doctype-public-identifier
+    MissingQuoteBeforeDoctypePublicIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-quote-before-doctype-system-identifier
+    MissingQuoteBeforeDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-semicolon-after-character-reference
+    MissingSemicolonAfterCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-whitespace-after-doctype-public-keyword
+    MissingWhitespaceAfterDoctypePublicKeyword,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-whitespace-after-doctype-system-keyword
+    MissingWhitespaceAfterDoctypeSystemKeyword,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-whitespace-before-doctype-name
+    MissingWhitespaceBeforeDoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-whitespace-between-attributes
+    MissingWhitespaceBetweenAttributes,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-missing-whitespace-between-doctype-public-and-system-identifiers
+    MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-nested-comment
+    NestedComment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-noncharacter-character-reference
+    NoncharacterCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-null-character-reference
+    NullCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-surrogate-character-reference
+    SurrogateCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unexpected-character-after-doctype-system-identifier
+    UnexpectedCharacterAfterDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unexpected-character-in-attribute-name
+    UnexpectedCharacterInAttributeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unexpected-character-in-unquoted-attribute-value
+    UnexpectedCharacterInUnquotedAttributeValue,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unexpected-equals-sign-before-attribute-name
+    UnexpectedEqualsSignBeforeAttributeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unexpected-null-character
+    UnexpectedNullCharacter,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unexpected-question-mark-instead-of-tag-name
+    UnexpectedQuestionMarkInsteadOfTagName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unexpected-solidus-in-tag
+    UnexpectedSolidusInTag,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-error-unknown-named-character-reference
+    UnknownNamedCharacterReference,
+
+    // ツリー構築の段階で発生するパースエラー。仕様書ではエラーコードが定められていない
+    /// DOCTYPEが文書の先頭にない
+    MissingDoctype,
+    /// 文書の先頭以外の場所にDOCTYPEが現れた
+    UnexpectedDoctype,
+    /// 現在の挿入モードで許されていない開始タグ
+    UnexpectedStartTag(String),
+    /// 現在の挿入モードで許されていない、または対応する開始タグがない終了タグ
+    UnexpectedEndTag(String),
+    /// 現在の挿入モードで許されていない文字。<table>の中の文字など
+    UnexpectedCharacter(char),
+    /// 閉じられていない要素が残ったままファイルが終了した
+    UnexpectedEof,
+}
+
+/// 入力の中の位置。行と列はどちらも1から始まる
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    position: Position,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, position: Position) -> Self {
+        Self { kind, position }
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind.clone()
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+}
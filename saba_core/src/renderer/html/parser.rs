@@ -1,18 +1,41 @@
+use crate::renderer::dom::mutation::change_attributes;
+use crate::renderer::dom::mutation::insert;
+use crate::renderer::dom::mutation::remove;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::DocumentFragment;
+use crate::renderer::dom::node::DocumentMode;
+use crate::renderer::dom::node::DocumentType;
+use crate::renderer::dom::node::Dom;
 use crate::renderer::dom::node::Element;
 use crate::renderer::dom::node::ElementKind;
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::Namespace;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::NodeKind;
 use crate::renderer::dom::node::Window;
+use crate::renderer::html::foreign_content::adjust_mathml_attributes;
+use crate::renderer::html::foreign_content::adjust_svg_attributes;
+use crate::renderer::html::foreign_content::adjust_svg_tag_name;
+use crate::renderer::html::foreign_content::is_breakout_start_tag;
+use crate::renderer::html::foreign_content::is_html_integration_point;
+use crate::renderer::html::foreign_content::is_mathml_text_integration_point;
+use crate::renderer::html::foreign_content::is_special_foreign_element;
+use crate::renderer::html::parse_error::ParseError;
+use crate::renderer::html::parse_error::ParseErrorKind;
 use crate::renderer::html::token::HtmlToken;
 use crate::renderer::html::token::HtmlTokenizer;
+use crate::renderer::html::token::State;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
-use core::str::FromStr;
 
 use super::attribute::Attribute;
 
+/// https://html.spec.whatwg.org/multipage/parsing.html#scripting-flag
+/// このブラウザはスクリプトを実行するので、スクリプトは常に有効として扱う。
+/// <noscript>の中身は、パーサでもシリアライザでもこのフラグに従って生のテキストとして扱う
+pub const SCRIPTING_ENABLED: bool = true;
+
 /// https://html.spec.whatwg.org/multipage/parsing.html#insertion-mode
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum InsertionMode {
@@ -23,46 +46,279 @@ pub enum InsertionMode {
     AfterHead,
     InBody,
     Text,
+    InTable,
+    InTableText,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InTemplate,
     AfterBody,
     AfterAfterBody,
 }
 
+/// https://html.spec.whatwg.org/multipage/parsing.html#list-of-active-formatting-elements
+#[derive(Debug, Clone)]
+enum ActiveFormattingElement {
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-parser-marker
+    Marker,
+    Element(NodeId),
+}
+
 #[derive(Debug, Clone)]
 pub struct HtmlParser {
     window: Rc<RefCell<Window>>,
+    /// windowの文書のノードを保持するアリーナ
+    dom: Rc<RefCell<Dom>>,
     mode: InsertionMode,
     /// https://html.spec.whatwg.org/multipage/parsing.html#original-insertion-mode
     original_insertion_mode: InsertionMode,
     /// https://html.spec.whatwg.org/multipage/parsing.html#the-stack-of-open-elements
-    stack_of_open_elements: Vec<Rc<RefCell<Node>>>,
+    stack_of_open_elements: Vec<NodeId>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#list-of-active-formatting-elements
+    active_formatting_elements: Vec<ActiveFormattingElement>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#stack-of-template-insertion-modes
+    stack_of_template_insertion_modes: Vec<InsertionMode>,
+    /// https://html.spec.whatwg.org/multipage/scripting.html#appropriate-template-contents-owner-document
+    /// <template>の中身が属する文書。ブラウジングコンテキストを持たないため、中身のスクリプトなどは動かない
+    template_contents_owner_document: Option<NodeId>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#form-element-pointer
+    form_element: Option<NodeId>,
+    /// <pre>や<listing>の開始タグ直後の改行を無視するためのフラグ
+    ignore_next_line_feed: bool,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#foster-parent
+    foster_parenting: bool,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-pending-table-char-tokens
+    pending_table_character_tokens: String,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    /// ツリー構築の段階で発生したパースエラー
+    errors: Vec<ParseError>,
+    /// trueの場合、<script>の終了タグでパースを一時停止し、スクリプトを実行できるようにする
+    pause_on_script: bool,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#pending-parsing-blocking-script
+    pending_script: Option<NodeId>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-frag-parse-context
+    /// フラグメントをパースしている場合のコンテキスト要素
+    context_element: Option<NodeId>,
+    /// trueの場合、次のトークンを外部コンテンツの規則ではなく、現在の挿入モードの規則で処理する
+    reprocess_in_html_content: bool,
     t: HtmlTokenizer,
 }
 
 impl HtmlParser {
     pub fn new(t: HtmlTokenizer) -> Self {
+        Self::new_with_window(t, Window::new())
+    }
+
+    /// URLなどを設定済みの文書の中にパースする
+    pub fn new_with_document(t: HtmlTokenizer, document: Document) -> Self {
+        Self::new_with_window(t, Window::new_with_document(document))
+    }
+
+    fn new_with_window(t: HtmlTokenizer, window: Window) -> Self {
         Self {
-            window: Rc::new(RefCell::new(Window::new())),
+            dom: window.dom(),
+            window: Rc::new(RefCell::new(window)),
             mode: InsertionMode::Initial,
             original_insertion_mode: InsertionMode::Initial,
             stack_of_open_elements: Vec::new(),
+            active_formatting_elements: Vec::new(),
+            stack_of_template_insertion_modes: Vec::new(),
+            template_contents_owner_document: None,
+            form_element: None,
+            ignore_next_line_feed: false,
+            foster_parenting: false,
+            pending_table_character_tokens: String::new(),
+            errors: Vec::new(),
+            pause_on_script: false,
+            pending_script: None,
+            context_element: None,
+            reprocess_in_html_content: false,
             t,
         }
     }
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+    /// contextの子としてhtmlをパースし、できたノードを返す。innerHTMLやouterHTMLの設定に使う。
+    /// 返されるノードはどの親にも属していない
+    pub fn parse_fragment(dom: &Rc<RefCell<Dom>>, context: NodeId, html: String) -> Vec<NodeId> {
+        // 作ったノードをcontextの文書に移せるように、同じアリーナの中に新しい文書を作ってパースする
+        let mut parser =
+            HtmlParser::new_with_window(HtmlTokenizer::new(html), Window::new_in(dom.clone()));
+        parser.context_element = Some(context);
+
+        // コンテキスト要素が属している文書のモードを引き継ぐ
+        let top = dom.borrow().ancestors(context).last().unwrap_or(context);
+        let document = dom.borrow().node(top).get_document();
+        if let Some(document) = document {
+            parser.set_document_mode(document.mode());
+        }
+
+        // コンテキスト要素の中身として扱われるように、トークナイザの状態を切り替える
+        match parser.tag_name_of(context).unwrap_or_default().as_str() {
+            "title" | "textarea" => parser.t.set_state(State::Rcdata),
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" => {
+                parser.t.set_state(State::Rawtext)
+            }
+            "noscript" if SCRIPTING_ENABLED => parser.t.set_state(State::Rawtext),
+            "script" => parser.t.set_state(State::ScriptData),
+            _ => {}
+        }
+
+        let root = parser.create_node(NodeKind::Element(Element::new("html", Vec::new())));
+        let document = parser.window.borrow().document();
+        insert(&mut dom.borrow_mut(), root, document, None);
+        parser.stack_of_open_elements.push(root);
+
+        if parser.tag_name_of(context).as_deref() == Some("template") {
+            parser
+                .stack_of_template_insertion_modes
+                .push(InsertionMode::InTemplate);
+        }
+
+        parser.reset_insertion_mode();
+
+        // コンテキスト要素またはその祖先の<form>をフォーム要素ポインタに設定する
+        parser.form_element = {
+            let dom = dom.borrow();
+            core::iter::once(context)
+                .chain(dom.ancestors(context))
+                .find(|n| dom.node(*n).element_kind() == Some(ElementKind::Form))
+        };
+
+        parser.construct_tree();
+
+        let mut dom = dom.borrow_mut();
+        let nodes: Vec<NodeId> = dom.children(root).collect();
+        for node in nodes.iter() {
+            remove(&mut dom, *node);
+        }
+        nodes
+    }
+
+    /// UTF-8でエンコードされた入力の続きを与え、与えられた分だけツリーを構築する。
+    /// 入力の途中でもそれまでのDOMツリーを返す。入力をどこで区切っても、最終的なツリーは変わらない。
+    /// ただし、Pageはレスポンス全体を受け取ってからパースするので、今のところページの読み込みでは使われていない
+    pub fn feed(&mut self, bytes: &[u8]) -> Rc<RefCell<Window>> {
+        self.t.feed(bytes);
+        self.construct_tree()
+    }
+
+    /// 入力がすべて与えられたことを知らせ、残りのツリーを構築する
+    pub fn finish(&mut self) -> Rc<RefCell<Window>> {
+        self.t.finish();
+        self.construct_tree()
+    }
+
+    pub fn set_pause_on_script(&mut self, pause_on_script: bool) {
+        self.pause_on_script = pause_on_script;
+    }
+
+    /// パースを止めている<script>要素を返す。
+    /// 呼び出し側はスクリプトを実行した後、resumeでパースを再開する
+    pub fn pending_script(&self) -> Option<NodeId> {
+        self.pending_script
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-document-write
+    /// スクリプトの実行中に呼ばれた場合、<script>の終了タグの直後に文字列を挿入する。
+    /// 挿入位置がない場合はfalseを返す
+    pub fn document_write(&mut self, text: &str) -> bool {
+        self.t.write(text)
+    }
+
+    /// スクリプトの実行が終わった後に、一時停止していたパースを再開する
+    pub fn resume(&mut self) -> Rc<RefCell<Window>> {
+        self.pending_script = None;
+        self.t.clear_insertion_point();
+        self.construct_tree()
+    }
+
+    /// トークナイズとツリー構築の段階で発生したパースエラーを、入力の中の位置の順に返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        let mut errors = self.t.errors();
+        errors.extend(self.errors.iter().cloned());
+        errors.sort_by_key(|e| e.position());
+        errors
+    }
+
+    /// 現在与えられている入力からツリーを構築する。
+    /// 入力が足りなくなった場合や、<script>で一時停止した場合は、その時点のツリーを返す
     pub fn construct_tree(&mut self) -> Rc<RefCell<Window>> {
-        let mut token = self.t.next();
+        if self.pending_script.is_some() {
+            return self.window.clone();
+        }
+
+        let mut token = self.next_token();
 
+        // Noneは、入力がまだ届いていないか、すでにEOFを処理し終えたことを表す。
+        // どちらの場合もここで止まり、入力の終わりはHtmlToken::Eofとして各挿入モードで処理する
         while token.is_some() {
+            if self.ignore_next_line_feed {
+                self.ignore_next_line_feed = false;
+                if let Some(HtmlToken::Char('\n')) = token {
+                    token = self.next_token();
+                    continue;
+                }
+            }
+
+            // https://html.spec.whatwg.org/multipage/parsing.html#tree-construction-dispatcher
+            if let Some(ref t) = token {
+                let in_html_content = core::mem::take(&mut self.reprocess_in_html_content);
+                if !in_html_content && self.should_process_in_foreign_content(t) {
+                    if !self.process_token_in_foreign_content(t) {
+                        token = self.next_token();
+                    }
+                    continue;
+                }
+            }
+
             match self.mode {
                 InsertionMode::Initial => {
-                    // この実装では、DOCTYPEトークンをサポートしていないため、
-                    // <!DOCTYPE html>のようなトークンは文字トークンとして表される。
-                    // 文字トークンは無視する。
-                    if let Some(HtmlToken::Char(_)) = token {
-                        token = self.t.next();
-                        continue;
-                    }
+                    match token {
+                        Some(HtmlToken::Char(c)) => {
+                            if c.is_ascii_whitespace() {
+                                token = self.next_token();
+                                continue;
+                            }
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            self.insert_comment_to(data, document);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype {
+                            ref name,
+                            ref public_identifier,
+                            ref system_identifier,
+                            force_quirks,
+                        }) => {
+                            let doctype = DocumentType::new(
+                                name.clone().unwrap_or_default(),
+                                public_identifier.clone().unwrap_or_default(),
+                                system_identifier.clone().unwrap_or_default(),
+                            );
+                            let document = self.window.borrow().document();
+                            let doctype = self.create_node(NodeKind::DocumentType(doctype));
+                            self.insert_node(doctype, document, None);
 
+                            self.set_document_mode(document_mode_from_doctype(
+                                name,
+                                public_identifier,
+                                system_identifier,
+                                force_quirks,
+                            ));
+                            self.mode = InsertionMode::BeforeHtml;
+                            token = self.next_token();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    // パースエラー。DOCTYPEがない文書は互換モード(quirks mode)として扱う
+                    self.parse_error(ParseErrorKind::MissingDoctype);
+                    self.set_document_mode(DocumentMode::Quirks);
                     self.mode = InsertionMode::BeforeHtml;
                     continue;
                 }
@@ -70,10 +326,22 @@ impl HtmlParser {
                     match token {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            self.insert_comment_to(data, document);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            // パースエラー。トークンを無視する。
+                            self.parse_error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
                         Some(HtmlToken::StartTag {
                             ref tag,
                             self_closing: _,
@@ -82,13 +350,10 @@ impl HtmlParser {
                             if tag == "html" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.mode = InsertionMode::BeforeHead;
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
-                        }
                         _ => {}
                     }
                     self.insert_element("html", Vec::new());
@@ -99,10 +364,21 @@ impl HtmlParser {
                     match token {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            // パースエラー。トークンを無視する。
+                            self.parse_error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
                         Some(HtmlToken::StartTag {
                             ref tag,
                             self_closing: _,
@@ -111,13 +387,10 @@ impl HtmlParser {
                             if tag == "head" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.mode = InsertionMode::InHead;
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
-                        }
                         _ => {}
                     }
                     self.insert_element("head", Vec::new());
@@ -129,21 +402,73 @@ impl HtmlParser {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
                                 self.insert_char(c);
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
+                            // それ以外の文字が出てきた場合、<head>が閉じられたものとして扱う
+                            self.pop_until(ElementKind::Head);
+                            self.mode = InsertionMode::AfterHead;
+                            continue;
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            // パースエラー。トークンを無視する。
+                            self.parse_error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
                         }
                         Some(HtmlToken::StartTag {
                             ref tag,
                             self_closing: _,
                             ref attributes,
                         }) => {
-                            if tag == "style" || tag == "script" {
-                                self.insert_element(tag, attributes.to_vec());
-                                self.original_insertion_mode = self.mode;
-                                self.mode = InsertionMode::Text;
-                                token = self.t.next();
-                                continue;
+                            match tag.as_str() {
+                                "title" => {
+                                    self.parse_text_element(
+                                        tag,
+                                        attributes.to_vec(),
+                                        State::Rcdata,
+                                    );
+                                    token = self.next_token();
+                                    continue;
+                                }
+                                "noscript" | "noframes" | "style"
+                                    if tag != "noscript" || SCRIPTING_ENABLED =>
+                                {
+                                    self.parse_text_element(
+                                        tag,
+                                        attributes.to_vec(),
+                                        State::Rawtext,
+                                    );
+                                    token = self.next_token();
+                                    continue;
+                                }
+                                "script" => {
+                                    self.parse_text_element(
+                                        tag,
+                                        attributes.to_vec(),
+                                        State::ScriptData,
+                                    );
+                                    token = self.next_token();
+                                    continue;
+                                }
+                                "template" => {
+                                    self.insert_template(attributes.to_vec());
+                                    token = self.next_token();
+                                    continue;
+                                }
+                                "base" | "basefont" | "bgsound" | "link" | "meta" => {
+                                    self.insert_element(tag, attributes.to_vec());
+                                    // 空要素は子を持たないため、すぐにスタックから取り除く
+                                    self.stack_of_open_elements.pop();
+                                    token = self.next_token();
+                                    continue;
+                                }
+                                _ => {}
                             }
                             // 仕様書には定められていないが、このブラウザは仕様をすべて実装しているわけではないため、
                             // <head>が省略されているHTMLをサポートするために必要。
@@ -153,36 +478,57 @@ impl HtmlParser {
                                 self.mode = InsertionMode::AfterHead;
                                 continue;
                             }
-                            if let Ok(_element_kind) = ElementKind::from_str(tag) {
-                                self.pop_until(ElementKind::Head);
-                                self.mode = InsertionMode::AfterHead;
-                                continue;
-                            }
+                            // それ以外の要素が出てきた場合、<head>が閉じられたものとして扱う
+                            self.pop_until(ElementKind::Head);
+                            self.mode = InsertionMode::AfterHead;
+                            continue;
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
                             if tag == "head" {
                                 self.mode = InsertionMode::AfterHead;
-                                token = self.t.next();
+                                token = self.next_token();
                                 self.pop_until(ElementKind::Head);
                                 continue;
                             }
+                            if tag == "template" {
+                                if let Some(ref t) = token {
+                                    self.close_template(t);
+                                }
+                                token = self.next_token();
+                                continue;
+                            }
                         }
-                        Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                        Some(HtmlToken::Eof) => {
+                            // <head>が閉じられたものとして扱い、AfterHeadモードでEOFを再処理する
+                            self.pop_until(ElementKind::Head);
+                            self.mode = InsertionMode::AfterHead;
+                            continue;
                         }
+                        _ => {}
                     }
                     // <meta>や<title>などのサポートしていない要素は無視する。
-                    token = self.t.next();
+                    token = self.next_token();
                     continue;
                 }
                 InsertionMode::AfterHead => {
                     match token {
                         Some(HtmlToken::Char(c)) => {
                             if c == ' ' || c == '\n' {
-                                token = self.t.next();
+                                token = self.next_token();
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            // パースエラー。トークンを無視する。
+                            self.parse_error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
                         Some(HtmlToken::StartTag {
                             ref tag,
                             self_closing: _,
@@ -190,125 +536,63 @@ impl HtmlParser {
                         }) => {
                             if tag == "body" {
                                 self.insert_element(tag, attributes.to_vec());
-                                token = self.t.next();
+                                token = self.next_token();
                                 self.mode = InsertionMode::InBody;
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
-                        }
                         _ => {}
                     }
                     self.insert_element("body", Vec::new());
                     self.mode = InsertionMode::InBody;
                     continue;
                 }
-                InsertionMode::InBody => {
-                    match token {
-                        Some(HtmlToken::StartTag {
-                            ref tag,
-                            self_closing: _,
-                            ref attributes,
-                        }) => match tag.as_str() {
-                            "p" => {
-                                self.insert_element(tag, attributes.to_vec());
-                                token = self.t.next();
-                                continue;
-                            }
-                            "h1" | "h2" => {
-                                self.insert_element(tag, attributes.to_vec());
-                                token = self.t.next();
-                                continue;
-                            }
-                            "a" => {
-                                self.insert_element(tag, attributes.to_vec());
-                                token = self.t.next();
-                                continue;
-                            }
-                            _ => {
-                                token = self.t.next();
-                            }
-                        },
-                        Some(HtmlToken::EndTag { ref tag }) => {
-                            match tag.as_str() {
-                                "body" => {
-                                    self.mode = InsertionMode::AfterBody;
-                                    token = self.t.next();
-                                    if !self.contain_in_stack(ElementKind::Body) {
-                                        // パースの失敗。トークンを無視する。
-                                        continue;
-                                    }
-                                    self.pop_until(ElementKind::Body);
-                                    continue;
-                                }
-                                "html" => {
-                                    if self.pop_current_node(ElementKind::Body) {
-                                        self.mode = InsertionMode::AfterBody;
-                                        assert!(self.pop_current_node(ElementKind::Html));
-                                    } else {
-                                        token = self.t.next();
-                                    }
-                                    continue;
-                                }
-                                "p" => {
-                                    let element_kind = ElementKind::from_str(tag)
-                                        .expect("failed to convert string to ElementKind");
-                                    token = self.t.next();
-                                    self.pop_until(element_kind);
-                                    continue;
-                                }
-                                "h1" | "h2" => {
-                                    let element_kind = ElementKind::from_str(tag)
-                                        .expect("failed to convert string to ElementKind");
-                                    token = self.t.next();
-                                    self.pop_until(element_kind);
-                                    continue;
-                                }
-                                "a" => {
-                                    let element_kind = ElementKind::from_str(tag)
-                                        .expect("failed to convert string to ElementKind");
-                                    token = self.t.next();
-                                    self.pop_until(element_kind);
-                                    continue;
-                                }
-                                _ => {
-                                    token = self.t.next();
-                                }
-                            }
-                        }
-                        Some(HtmlToken::Char(c)) => {
-                            self.insert_char(c);
-                            token = self.t.next();
+                InsertionMode::InBody => match token {
+                    Some(HtmlToken::Eof) => {
+                        if self.close_template_at_eof() {
+                            token = Some(HtmlToken::Eof);
                             continue;
                         }
-                        Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                        self.check_open_elements_at_eof();
+                        return self.window.clone();
+                    }
+                    _ => {
+                        if let Some(ref t) = token {
+                            if !self.process_token_in_body(t) {
+                                token = self.next_token();
+                            }
                         }
+                        continue;
                     }
-                }
+                },
                 InsertionMode::Text => {
                     match token {
-                        Some(HtmlToken::Eof) | None => {
+                        Some(HtmlToken::Eof) => {
+                            // パースエラー。<script>や<style>などが閉じられていない
+                            self.parse_error(ParseErrorKind::UnexpectedEof);
                             return self.window.clone();
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag == "style" {
-                                self.pop_until(ElementKind::Style);
-                                self.mode = self.original_insertion_mode;
-                                token = self.t.next();
-                                continue;
-                            }
-                            if tag == "script" {
-                                self.pop_until(ElementKind::Script);
-                                self.mode = self.original_insertion_mode;
-                                token = self.t.next();
-                                continue;
+                            // トークナイザは対応する終了タグのみを終了タグとして扱うため、
+                            // 現在のノードが閉じられる要素になる
+                            let node = self.stack_of_open_elements.pop();
+                            self.mode = self.original_insertion_mode;
+                            // <template>の中のスクリプトは実行されないので、止めない
+                            if tag == "script"
+                                && self.pause_on_script
+                                && self.stack_of_template_insertion_modes.is_empty()
+                            {
+                                // 次のトークンを読む前に止め、document.writeの挿入位置を終了タグの直後にする
+                                self.t.set_insertion_point();
+                                self.pending_script = node;
+                                return self.window.clone();
                             }
+                            token = self.next_token();
+                            continue;
                         }
                         Some(HtmlToken::Char(c)) => {
                             self.insert_char(c);
-                            token = self.t.next();
+                            token = self.next_token();
                             continue;
                         }
                         _ => {}
@@ -316,146 +600,1538 @@ impl HtmlParser {
 
                     self.mode = self.original_insertion_mode;
                 }
-                InsertionMode::AfterBody => {
-                    match token {
-                        Some(HtmlToken::Char(_c)) => {
-                            token = self.t.next();
+                InsertionMode::InTable => match token {
+                    Some(HtmlToken::Eof) => {
+                        if self.close_template_at_eof() {
+                            token = Some(HtmlToken::Eof);
                             continue;
                         }
-                        Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag == "html" {
-                                self.mode = InsertionMode::AfterAfterBody;
-                                token = self.t.next();
+                        self.check_open_elements_at_eof();
+                        return self.window.clone();
+                    }
+                    _ => {
+                        if let Some(ref t) = token {
+                            if !self.process_token_in_table(t) {
+                                token = self.next_token();
+                            }
+                        }
+                        continue;
+                    }
+                },
+                InsertionMode::InTableText => {
+                    if let Some(HtmlToken::Char(c)) = token {
+                        if c != '\0' {
+                            self.pending_table_character_tokens.push(c);
+                        }
+                        token = self.next_token();
+                        continue;
+                    }
+
+                    let pending = core::mem::take(&mut self.pending_table_character_tokens);
+                    if let Some(c) = pending.chars().find(|c| !c.is_ascii_whitespace()) {
+                        // パースエラー。フォスターペアレンティングを有効にして、InBodyモードの規則で処理する
+                        self.parse_error(ParseErrorKind::UnexpectedCharacter(c));
+                        self.foster_parenting = true;
+                        for c in pending.chars() {
+                            self.process_token_in_body(&HtmlToken::Char(c));
+                        }
+                        self.foster_parenting = false;
+                    } else {
+                        for c in pending.chars() {
+                            self.insert_char(c);
+                        }
+                    }
+                    self.mode = self.original_insertion_mode;
+                    continue;
+                }
+                InsertionMode::InCaption => {
+                    match token {
+                        Some(HtmlToken::StartTag { ref tag, .. }) => {
+                            // <table>の開始タグでもキャプションを閉じ、InTableモードで再処理する
+                            if matches!(
+                                tag.as_str(),
+                                "caption"
+                                    | "col"
+                                    | "colgroup"
+                                    | "table"
+                                    | "tbody"
+                                    | "td"
+                                    | "tfoot"
+                                    | "th"
+                                    | "thead"
+                                    | "tr"
+                            ) {
+                                if !self.close_caption() {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedStartTag(
+                                        tag.clone(),
+                                    ));
+                                    token = self.next_token();
+                                }
                                 continue;
                             }
                         }
-                        Some(HtmlToken::Eof) | None => {
+                        Some(HtmlToken::EndTag { ref tag }) => match tag.as_str() {
+                            "caption" => {
+                                self.close_caption();
+                                token = self.next_token();
+                                continue;
+                            }
+                            "table" => {
+                                if !self.close_caption() {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                            "body" | "col" | "colgroup" | "html" | "tbody" | "td" | "tfoot"
+                            | "th" | "thead" | "tr" => {
+                                // パースエラー。トークンを無視する。
+                                self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                token = self.next_token();
+                                continue;
+                            }
+                            _ => {}
+                        },
+                        Some(HtmlToken::Eof) => {
+                            if self.close_template_at_eof() {
+                                token = Some(HtmlToken::Eof);
+                                continue;
+                            }
+                            self.check_open_elements_at_eof();
                             return self.window.clone();
                         }
                         _ => {}
                     }
 
-                    self.mode = InsertionMode::InBody;
+                    // InBodyモードの規則で処理する
+                    if let Some(ref t) = token {
+                        if !self.process_token_in_body(t) {
+                            token = self.next_token();
+                        }
+                    }
+                    continue;
                 }
-                InsertionMode::AfterAfterBody => {
+                InsertionMode::InColumnGroup => {
                     match token {
-                        Some(HtmlToken::Char(_c)) => {
-                            token = self.t.next();
+                        Some(HtmlToken::Char(c)) if c.is_ascii_whitespace() => {
+                            self.insert_char(c);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            self.insert_comment(data);
+                            token = self.next_token();
                             continue;
                         }
-                        Some(HtmlToken::Eof) | None => {
+                        Some(HtmlToken::Doctype { .. }) => {
+                            // パースエラー。トークンを無視する。
+                            self.parse_error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::StartTag {
+                            ref tag,
+                            self_closing: _,
+                            ref attributes,
+                        }) => {
+                            if tag == "col" {
+                                self.insert_element(tag, attributes.to_vec());
+                                self.stack_of_open_elements.pop();
+                                token = self.next_token();
+                                continue;
+                            }
+                            if tag == "template" {
+                                self.insert_template(attributes.to_vec());
+                                token = self.next_token();
+                                continue;
+                            }
+                        }
+                        Some(HtmlToken::EndTag { ref tag }) => {
+                            if tag == "colgroup" {
+                                if self.pop_current_node(ElementKind::Colgroup) {
+                                    self.mode = InsertionMode::InTable;
+                                }
+                                token = self.next_token();
+                                continue;
+                            }
+                            if tag == "col" {
+                                // パースエラー。トークンを無視する。
+                                self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                token = self.next_token();
+                                continue;
+                            }
+                            if tag == "template" {
+                                if let Some(ref t) = token {
+                                    self.close_template(t);
+                                }
+                                token = self.next_token();
+                                continue;
+                            }
+                        }
+                        Some(HtmlToken::Eof) => {
+                            // InBodyモードの規則で処理する
+                            if self.close_template_at_eof() {
+                                token = Some(HtmlToken::Eof);
+                                continue;
+                            }
+                            self.check_open_elements_at_eof();
                             return self.window.clone();
                         }
                         _ => {}
                     }
 
-                    // パースの失敗
-                    self.mode = InsertionMode::InBody;
-                }
-            }
-        }
-
-        self.window.clone()
-    }
-
-    fn create_element(&self, tag: &str, attributes: Vec<Attribute>) -> Node {
-        Node::new(NodeKind::Element(Element::new(tag, attributes)))
-    }
-
-    fn insert_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
-        let window = self.window.borrow();
-        let current = match self.stack_of_open_elements.last() {
-            Some(n) => n.clone(),
-            None => window.document(),
-        };
-        let node = Rc::new(RefCell::new(self.create_element(tag, attributes)));
-
-        if current.borrow().first_child().is_some() {
-            let mut last_sibling = current.borrow().first_child();
-            loop {
-                last_sibling = match last_sibling {
-                    Some(ref node) => {
-                        if node.borrow().next_sibling().is_some() {
-                            node.borrow().next_sibling()
-                        } else {
-                            break;
+                    if !self.pop_current_node(ElementKind::Colgroup) {
+                        // パースエラー。トークンを無視する。
+                        if let Some(ref t) = token {
+                            self.unexpected_token(t);
                         }
+                        token = self.next_token();
+                        continue;
                     }
-                    None => unimplemented!("last_sibling shoud be Some"),
+                    self.mode = InsertionMode::InTable;
+                    continue;
                 }
-            }
-
-            last_sibling
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(node.clone()));
-            node.borrow_mut().set_previuos_sibling(Rc::downgrade(
-                &current
-                    .borrow()
-                    .first_child()
-                    .expect("failed to get a first child"),
-            ));
-        } else {
-            current.borrow_mut().set_first_child(Some(node.clone()));
-        }
-
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
-
-        self.stack_of_open_elements.push(node);
-    }
-
-    fn create_char(&mut self, c: char) -> Node {
-        let mut s = String::new();
-        s.push(c);
-        Node::new(NodeKind::Text(s))
-    }
-
-    fn insert_char(&mut self, c: char) {
-        let current = match self.stack_of_open_elements.last() {
-            Some(n) => n.clone(),
-            None => return,
+                InsertionMode::InTableBody => {
+                    match token {
+                        Some(HtmlToken::StartTag {
+                            ref tag,
+                            self_closing: _,
+                            ref attributes,
+                        }) => match tag.as_str() {
+                            "tr" => {
+                                self.clear_stack_back_to(&TABLE_BODY_CONTEXT);
+                                self.insert_element(tag, attributes.to_vec());
+                                self.mode = InsertionMode::InRow;
+                                token = self.next_token();
+                                continue;
+                            }
+                            "th" | "td" => {
+                                // パースエラー。<tr>が省略されたものとして扱う。
+                                self.parse_error(ParseErrorKind::UnexpectedStartTag(tag.clone()));
+                                self.clear_stack_back_to(&TABLE_BODY_CONTEXT);
+                                self.insert_element("tr", Vec::new());
+                                self.mode = InsertionMode::InRow;
+                                continue;
+                            }
+                            "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead" => {
+                                if !self.close_table_body() {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedStartTag(
+                                        tag.clone(),
+                                    ));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                            _ => {}
+                        },
+                        Some(HtmlToken::EndTag { ref tag }) => match tag.as_str() {
+                            "tbody" | "tfoot" | "thead" => {
+                                if self.has_element_in_table_scope(tag) {
+                                    self.clear_stack_back_to(&TABLE_BODY_CONTEXT);
+                                    self.stack_of_open_elements.pop();
+                                    self.mode = InsertionMode::InTable;
+                                }
+                                token = self.next_token();
+                                continue;
+                            }
+                            "table" => {
+                                if !self.close_table_body() {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                            "body" | "caption" | "col" | "colgroup" | "html" | "td" | "th"
+                            | "tr" => {
+                                // パースエラー。トークンを無視する。
+                                self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                token = self.next_token();
+                                continue;
+                            }
+                            _ => {}
+                        },
+                        Some(HtmlToken::Eof) => {
+                            if self.close_template_at_eof() {
+                                token = Some(HtmlToken::Eof);
+                                continue;
+                            }
+                            self.check_open_elements_at_eof();
+                            return self.window.clone();
+                        }
+                        _ => {}
+                    }
+
+                    // InTableモードの規則で処理する
+                    if let Some(ref t) = token {
+                        if !self.process_token_in_table(t) {
+                            token = self.next_token();
+                        }
+                    }
+                    continue;
+                }
+                InsertionMode::InRow => {
+                    match token {
+                        Some(HtmlToken::StartTag {
+                            ref tag,
+                            self_closing: _,
+                            ref attributes,
+                        }) => match tag.as_str() {
+                            "th" | "td" => {
+                                self.clear_stack_back_to(&TABLE_ROW_CONTEXT);
+                                self.insert_element(tag, attributes.to_vec());
+                                self.mode = InsertionMode::InCell;
+                                self.active_formatting_elements
+                                    .push(ActiveFormattingElement::Marker);
+                                token = self.next_token();
+                                continue;
+                            }
+                            "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead" | "tr" => {
+                                if !self.close_row() {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedStartTag(
+                                        tag.clone(),
+                                    ));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                            _ => {}
+                        },
+                        Some(HtmlToken::EndTag { ref tag }) => match tag.as_str() {
+                            "tr" => {
+                                self.close_row();
+                                token = self.next_token();
+                                continue;
+                            }
+                            "table" => {
+                                if !self.close_row() {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                            "tbody" | "tfoot" | "thead" => {
+                                if !self.has_element_in_table_scope(tag) || !self.close_row() {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                            "body" | "caption" | "col" | "colgroup" | "html" | "td" | "th" => {
+                                // パースエラー。トークンを無視する。
+                                self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                token = self.next_token();
+                                continue;
+                            }
+                            _ => {}
+                        },
+                        Some(HtmlToken::Eof) => {
+                            if self.close_template_at_eof() {
+                                token = Some(HtmlToken::Eof);
+                                continue;
+                            }
+                            self.check_open_elements_at_eof();
+                            return self.window.clone();
+                        }
+                        _ => {}
+                    }
+
+                    // InTableモードの規則で処理する
+                    if let Some(ref t) = token {
+                        if !self.process_token_in_table(t) {
+                            token = self.next_token();
+                        }
+                    }
+                    continue;
+                }
+                InsertionMode::InCell => {
+                    match token {
+                        Some(HtmlToken::StartTag { ref tag, .. }) => {
+                            if matches!(
+                                tag.as_str(),
+                                "caption"
+                                    | "col"
+                                    | "colgroup"
+                                    | "tbody"
+                                    | "td"
+                                    | "tfoot"
+                                    | "th"
+                                    | "thead"
+                                    | "tr"
+                            ) {
+                                if self.has_element_in_specific_scope(
+                                    &["td", "th"],
+                                    Self::is_table_scope,
+                                ) {
+                                    self.close_cell();
+                                } else {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedStartTag(
+                                        tag.clone(),
+                                    ));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                        }
+                        Some(HtmlToken::EndTag { ref tag }) => match tag.as_str() {
+                            "td" | "th" => {
+                                if self.has_element_in_table_scope(tag) {
+                                    self.generate_implied_end_tags(None);
+                                    self.pop_until(ElementKind::from(tag.as_str()));
+                                    self.clear_active_formatting_elements_to_last_marker();
+                                    self.mode = InsertionMode::InRow;
+                                }
+                                token = self.next_token();
+                                continue;
+                            }
+                            "body" | "caption" | "col" | "colgroup" | "html" => {
+                                // パースエラー。トークンを無視する。
+                                self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                token = self.next_token();
+                                continue;
+                            }
+                            "table" | "tbody" | "tfoot" | "thead" | "tr" => {
+                                if self.has_element_in_table_scope(tag) {
+                                    self.close_cell();
+                                } else {
+                                    // パースエラー。トークンを無視する。
+                                    self.parse_error(ParseErrorKind::UnexpectedEndTag(tag.clone()));
+                                    token = self.next_token();
+                                }
+                                continue;
+                            }
+                            _ => {}
+                        },
+                        Some(HtmlToken::Eof) => {
+                            if self.close_template_at_eof() {
+                                token = Some(HtmlToken::Eof);
+                                continue;
+                            }
+                            self.check_open_elements_at_eof();
+                            return self.window.clone();
+                        }
+                        _ => {}
+                    }
+
+                    // InBodyモードの規則で処理する
+                    if let Some(ref t) = token {
+                        if !self.process_token_in_body(t) {
+                            token = self.next_token();
+                        }
+                    }
+                    continue;
+                }
+                InsertionMode::InTemplate => match token {
+                    Some(HtmlToken::Eof) => {
+                        if self.close_template_at_eof() {
+                            token = Some(HtmlToken::Eof);
+                            continue;
+                        }
+                        return self.window.clone();
+                    }
+                    _ => {
+                        if let Some(ref t) = token {
+                            if !self.process_token_in_template(t) {
+                                token = self.next_token();
+                            }
+                        }
+                        continue;
+                    }
+                },
+                InsertionMode::AfterBody => {
+                    match token {
+                        Some(HtmlToken::Char(_c)) => {
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            // コメントは<html>要素の最後の子として追加する
+                            if let Some(html) = self.stack_of_open_elements.first().copied() {
+                                self.insert_comment_to(data, html);
+                            }
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            // パースエラー。トークンを無視する。
+                            self.parse_error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::EndTag { ref tag }) => {
+                            if tag == "html" {
+                                self.mode = InsertionMode::AfterAfterBody;
+                                token = self.next_token();
+                                continue;
+                            }
+                        }
+                        Some(HtmlToken::Eof) => {
+                            return self.window.clone();
+                        }
+                        _ => {}
+                    }
+
+                    // パースエラー。InBodyモードで再処理する
+                    if let Some(ref t) = token {
+                        self.unexpected_token(t);
+                    }
+                    self.mode = InsertionMode::InBody;
+                }
+                InsertionMode::AfterAfterBody => {
+                    match token {
+                        Some(HtmlToken::Char(_c)) => {
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Comment(ref data)) => {
+                            let document = self.window.borrow().document();
+                            self.insert_comment_to(data, document);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Doctype { .. }) => {
+                            // パースエラー。トークンを無視する。
+                            self.parse_error(ParseErrorKind::UnexpectedDoctype);
+                            token = self.next_token();
+                            continue;
+                        }
+                        Some(HtmlToken::Eof) => {
+                            return self.window.clone();
+                        }
+                        _ => {}
+                    }
+
+                    // パースの失敗
+                    if let Some(ref t) = token {
+                        self.unexpected_token(t);
+                    }
+                    self.mode = InsertionMode::InBody;
+                }
+            }
+        }
+
+        self.window.clone()
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    /// トークンを再処理する必要がある場合はtrueを返す
+    fn process_token_in_body(&mut self, token: &HtmlToken) -> bool {
+        match *token {
+            HtmlToken::StartTag {
+                ref tag,
+                self_closing,
+                ref attributes,
+            } => match tag.as_str() {
+                "html" => {
+                    // パースエラー。<template>の中でなければ、まだない属性を<html>要素に追加する
+                    self.unexpected_token(token);
+                    if !self.contain_in_stack(ElementKind::Template) {
+                        if let Some(html) = self.stack_of_open_elements.first().copied() {
+                            change_attributes(&mut self.dom.borrow_mut(), html, |e| {
+                                e.add_missing_attributes(attributes)
+                            });
+                        }
+                    }
+                }
+                "body" => {
+                    // パースエラー。スタックの2番目の要素が<body>の場合は、まだない属性を<body>要素に追加する
+                    self.unexpected_token(token);
+                    let body = self.stack_of_open_elements.get(1).copied().filter(|n| {
+                        self.dom.borrow().node(*n).element_kind() == Some(ElementKind::Body)
+                    });
+                    if let Some(body) = body {
+                        if !self.contain_in_stack(ElementKind::Template) {
+                            change_attributes(&mut self.dom.borrow_mut(), body, |e| {
+                                e.add_missing_attributes(attributes)
+                            });
+                        }
+                    }
+                }
+                "frameset" => {
+                    // パースエラー。トークンを無視する。
+                    self.unexpected_token(token);
+                }
+                "base" | "basefont" | "bgsound" | "link" | "meta" => {
+                    // InHeadモードと同様に、空要素として挿入する
+                    self.insert_element(tag, attributes.to_vec());
+                    self.stack_of_open_elements.pop();
+                }
+                "title" => {
+                    self.parse_text_element(tag, attributes.to_vec(), State::Rcdata);
+                }
+                "noframes" | "style" | "iframe" | "noembed" | "noscript"
+                    if tag != "noscript" || SCRIPTING_ENABLED =>
+                {
+                    self.parse_text_element(tag, attributes.to_vec(), State::Rawtext);
+                }
+                "script" => {
+                    self.parse_text_element(tag, attributes.to_vec(), State::ScriptData);
+                }
+                "template" => {
+                    self.insert_template(attributes.to_vec());
+                }
+                "textarea" => {
+                    self.parse_text_element(tag, attributes.to_vec(), State::Rcdata);
+                    // 開始タグ直後の改行は無視する
+                    self.ignore_next_line_feed = true;
+                }
+                "xmp" => {
+                    if self.has_element_in_button_scope("p") {
+                        self.close_p_element();
+                    }
+                    self.reconstruct_active_formatting_elements();
+                    self.parse_text_element(tag, attributes.to_vec(), State::Rawtext);
+                }
+                "address" | "article" | "aside" | "blockquote" | "center" | "details"
+                | "dialog" | "dir" | "div" | "dl" | "fieldset" | "figcaption" | "figure"
+                | "footer" | "header" | "hgroup" | "main" | "menu" | "nav" | "ol" | "p"
+                | "search" | "section" | "summary" | "ul" => {
+                    if self.has_element_in_button_scope("p") {
+                        self.close_p_element();
+                    }
+                    self.insert_element(tag, attributes.to_vec());
+                }
+                "table" => {
+                    // 互換モードでは<p>の中に<table>を入れることができる
+                    if self.document_mode() != DocumentMode::Quirks
+                        && self.has_element_in_button_scope("p")
+                    {
+                        self.close_p_element();
+                    }
+                    self.insert_element(tag, attributes.to_vec());
+                    self.mode = InsertionMode::InTable;
+                }
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    if self.has_element_in_button_scope("p") {
+                        self.close_p_element();
+                    }
+                    // 見出しの中で見出しが始まった場合、前の見出しを閉じる
+                    if self
+                        .current_tag_name()
+                        .is_some_and(|t| HEADING_ELEMENTS.contains(&t.as_str()))
+                    {
+                        // パースエラー
+                        self.unexpected_token(token);
+                        self.stack_of_open_elements.pop();
+                    }
+                    self.insert_element(tag, attributes.to_vec());
+                }
+                "pre" | "listing" => {
+                    if self.has_element_in_button_scope("p") {
+                        self.close_p_element();
+                    }
+                    self.insert_element(tag, attributes.to_vec());
+                    // 開始タグ直後の改行は無視する
+                    self.ignore_next_line_feed = true;
+                }
+                "form" => {
+                    if self.form_element.is_some() {
+                        // パースエラー。トークンを無視する。
+                        self.unexpected_token(token);
+                        return false;
+                    }
+                    if self.has_element_in_button_scope("p") {
+                        self.close_p_element();
+                    }
+                    let form = self.insert_element(tag, attributes.to_vec());
+                    self.form_element = Some(form);
+                }
+                "li" | "dd" | "dt" => {
+                    // <li>は直前の<li>を、<dd>と<dt>は直前の<dd>か<dt>を暗黙的に閉じる
+                    let closing_tags: &[&str] = if tag == "li" { &["li"] } else { &["dd", "dt"] };
+                    for i in (0..self.stack_of_open_elements.len()).rev() {
+                        let node = self.stack_of_open_elements[i];
+                        let tag_name = self.tag_name_of(node).unwrap_or_default();
+                        if closing_tags.contains(&tag_name.as_str()) {
+                            self.generate_implied_end_tags(Some(&tag_name));
+                            self.stack_of_open_elements.truncate(i);
+                            break;
+                        }
+                        if self.is_special(node)
+                            && !matches!(tag_name.as_str(), "address" | "div" | "p")
+                        {
+                            break;
+                        }
+                    }
+                    if self.has_element_in_button_scope("p") {
+                        self.close_p_element();
+                    }
+                    self.insert_element(tag, attributes.to_vec());
+                }
+                "button" => {
+                    if self.has_element_in_scope("button") {
+                        // パースエラー
+                        self.unexpected_token(token);
+                        self.generate_implied_end_tags(None);
+                        self.pop_until(ElementKind::Button);
+                    }
+                    self.reconstruct_active_formatting_elements();
+                    self.insert_element(tag, attributes.to_vec());
+                }
+                "a" => {
+                    // 閉じられていない<a>が残っている場合、先に閉じる
+                    if let Some(a) = self.find_active_formatting_element("a") {
+                        // パースエラー
+                        self.unexpected_token(token);
+                        self.run_adoption_agency_algorithm("a");
+                        self.remove_from_active_formatting_elements(a);
+                        self.remove_from_stack(a);
+                    }
+                    self.reconstruct_active_formatting_elements();
+                    let node = self.insert_element(tag, attributes.to_vec());
+                    self.push_active_formatting_element(node);
+                }
+                "b" | "big" | "code" | "em" | "font" | "i" | "s" | "small" | "strike"
+                | "strong" | "tt" | "u" => {
+                    self.reconstruct_active_formatting_elements();
+                    let node = self.insert_element(tag, attributes.to_vec());
+                    self.push_active_formatting_element(node);
+                }
+                "nobr" => {
+                    self.reconstruct_active_formatting_elements();
+                    if self.has_element_in_scope("nobr") {
+                        // パースエラー
+                        self.unexpected_token(token);
+                        self.run_adoption_agency_algorithm("nobr");
+                        self.reconstruct_active_formatting_elements();
+                    }
+                    let node = self.insert_element(tag, attributes.to_vec());
+                    self.push_active_formatting_element(node);
+                }
+                "applet" | "marquee" | "object" => {
+                    self.reconstruct_active_formatting_elements();
+                    self.insert_element(tag, attributes.to_vec());
+                    self.active_formatting_elements
+                        .push(ActiveFormattingElement::Marker);
+                }
+                "area" | "br" | "embed" | "img" | "keygen" | "wbr" | "input" => {
+                    self.reconstruct_active_formatting_elements();
+                    self.insert_element(tag, attributes.to_vec());
+                    // 空要素は子を持たないため、すぐにスタックから取り除く
+                    self.stack_of_open_elements.pop();
+                }
+                "param" | "source" | "track" => {
+                    self.insert_element(tag, attributes.to_vec());
+                    self.stack_of_open_elements.pop();
+                }
+                "hr" => {
+                    if self.has_element_in_button_scope("p") {
+                        self.close_p_element();
+                    }
+                    self.insert_element(tag, attributes.to_vec());
+                    self.stack_of_open_elements.pop();
+                }
+                "image" => {
+                    // パースエラー。<img>として再処理する。
+                    self.unexpected_token(token);
+                    return self.process_token_in_body(&HtmlToken::StartTag {
+                        tag: String::from("img"),
+                        self_closing,
+                        attributes: attributes.to_vec(),
+                    });
+                }
+                "optgroup" | "option" => {
+                    if self.current_tag_name().as_deref() == Some("option") {
+                        self.stack_of_open_elements.pop();
+                    }
+                    self.reconstruct_active_formatting_elements();
+                    self.insert_element(tag, attributes.to_vec());
+                }
+                "rb" | "rtc" | "rp" | "rt" => {
+                    if self.has_element_in_scope("ruby") {
+                        if tag == "rp" || tag == "rt" {
+                            self.generate_implied_end_tags(Some("rtc"));
+                        } else {
+                            self.generate_implied_end_tags(None);
+                        }
+                    }
+                    self.insert_element(tag, attributes.to_vec());
+                }
+                "math" | "svg" => {
+                    self.reconstruct_active_formatting_elements();
+                    let mut attributes = attributes.to_vec();
+                    let namespace = if tag == "math" {
+                        adjust_mathml_attributes(&mut attributes);
+                        Namespace::MathMl
+                    } else {
+                        adjust_svg_attributes(&mut attributes);
+                        Namespace::Svg
+                    };
+                    self.insert_foreign_element(tag, attributes, namespace);
+                    if self_closing {
+                        self.stack_of_open_elements.pop();
+                    }
+                }
+                "caption" | "col" | "colgroup" | "frame" | "head" | "tbody" | "td" | "tfoot"
+                | "th" | "thead" | "tr" => {
+                    // パースエラー。トークンを無視する。
+                    self.unexpected_token(token);
+                }
+                _ => {
+                    self.reconstruct_active_formatting_elements();
+                    self.insert_element(tag, attributes.to_vec());
+                }
+            },
+            HtmlToken::EndTag { ref tag } => match tag.as_str() {
+                "template" => {
+                    self.close_template(token);
+                }
+                "body" => {
+                    if !self.has_element_in_scope("body") {
+                        // パースの失敗。トークンを無視する。
+                        self.unexpected_token(token);
+                        return false;
+                    }
+                    self.mode = InsertionMode::AfterBody;
+                }
+                "html" => {
+                    if !self.has_element_in_scope("body") {
+                        // パースの失敗。トークンを無視する。
+                        self.unexpected_token(token);
+                        return false;
+                    }
+                    // AfterBodyモードでトークンを再処理する
+                    self.mode = InsertionMode::AfterBody;
+                    return true;
+                }
+                "address" | "article" | "aside" | "blockquote" | "button" | "center"
+                | "details" | "dialog" | "dir" | "div" | "dl" | "fieldset" | "figcaption"
+                | "figure" | "footer" | "header" | "hgroup" | "listing" | "main" | "menu"
+                | "nav" | "ol" | "pre" | "search" | "section" | "summary" | "ul" | "applet"
+                | "marquee" | "object" => {
+                    let is_marker_element = matches!(tag.as_str(), "applet" | "marquee" | "object");
+                    if !self.has_element_in_scope(tag) {
+                        // パースの失敗。トークンを無視する。
+                        self.unexpected_token(token);
+                        return false;
+                    }
+                    self.generate_implied_end_tags(None);
+                    self.check_current_node_is(tag);
+                    self.pop_until(ElementKind::from(tag.as_str()));
+                    if is_marker_element {
+                        self.clear_active_formatting_elements_to_last_marker();
+                    }
+                }
+                "form" => {
+                    let form = match self.form_element.take() {
+                        Some(form) => form,
+                        None => {
+                            // パースの失敗。トークンを無視する。
+                            self.unexpected_token(token);
+                            return false;
+                        }
+                    };
+                    if !self.has_element_in_scope("form") {
+                        // パースの失敗。トークンを無視する。
+                        self.unexpected_token(token);
+                        return false;
+                    }
+                    self.generate_implied_end_tags(None);
+                    self.remove_from_stack(form);
+                }
+                "p" => {
+                    if !self.has_element_in_button_scope("p") {
+                        // パースエラー。空の<p>が開始されたものとして扱う。
+                        self.unexpected_token(token);
+                        self.insert_element("p", Vec::new());
+                    }
+                    self.close_p_element();
+                }
+                "li" | "dd" | "dt" => {
+                    let element_kind = ElementKind::from(tag.as_str());
+                    let in_scope = if tag == "li" {
+                        self.has_element_in_list_item_scope("li")
+                    } else {
+                        self.has_element_in_scope(tag)
+                    };
+                    if !in_scope {
+                        // パースの失敗。トークンを無視する。
+                        self.unexpected_token(token);
+                        return false;
+                    }
+                    self.generate_implied_end_tags(Some(tag.as_str()));
+                    self.check_current_node_is(tag);
+                    self.pop_until(element_kind);
+                }
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    if !self
+                        .has_element_in_specific_scope(&HEADING_ELEMENTS, Self::is_default_scope)
+                    {
+                        // パースの失敗。トークンを無視する。
+                        self.unexpected_token(token);
+                        return false;
+                    }
+                    self.generate_implied_end_tags(None);
+                    self.check_current_node_is(tag);
+                    self.pop_until_one_of(&HEADING_ELEMENTS);
+                }
+                "a" | "b" | "big" | "code" | "em" | "font" | "i" | "nobr" | "s" | "small"
+                | "strike" | "strong" | "tt" | "u" => {
+                    self.run_adoption_agency_algorithm(tag);
+                }
+                "caption" | "col" | "colgroup" | "tbody" | "td" | "tfoot" | "th" | "thead"
+                | "tr" => {
+                    // パースエラー。トークンを無視する。
+                    self.unexpected_token(token);
+                }
+                "br" => {
+                    // パースエラー。<br>の開始タグとして再処理する。
+                    self.unexpected_token(token);
+                    return self.process_token_in_body(&HtmlToken::StartTag {
+                        tag: String::from("br"),
+                        self_closing: false,
+                        attributes: Vec::new(),
+                    });
+                }
+                _ => {
+                    self.any_other_end_tag(tag);
+                }
+            },
+            HtmlToken::Char(c) => {
+                self.reconstruct_active_formatting_elements();
+                self.insert_char(c);
+            }
+            HtmlToken::Comment(ref data) => {
+                self.insert_comment(data);
+            }
+            HtmlToken::Doctype { .. } => {
+                // パースエラー。トークンを無視する。
+                self.unexpected_token(token);
+            }
+            HtmlToken::Eof => {}
+        }
+
+        false
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intable
+    /// トークンを再処理する必要がある場合はtrueを返す
+    fn process_token_in_table(&mut self, token: &HtmlToken) -> bool {
+        match *token {
+            HtmlToken::Char(_) => {
+                if self.current_tag_name().is_some_and(|t| {
+                    matches!(
+                        t.as_str(),
+                        "table" | "tbody" | "template" | "tfoot" | "thead" | "tr"
+                    )
+                }) {
+                    self.pending_table_character_tokens = String::new();
+                    self.original_insertion_mode = self.mode;
+                    self.mode = InsertionMode::InTableText;
+                    return true;
+                }
+            }
+            HtmlToken::Comment(ref data) => {
+                self.insert_comment(data);
+                return false;
+            }
+            HtmlToken::Doctype { .. } => {
+                // パースエラー。トークンを無視する。
+                self.unexpected_token(token);
+                return false;
+            }
+            HtmlToken::StartTag {
+                ref tag,
+                self_closing: _,
+                ref attributes,
+            } => match tag.as_str() {
+                "caption" => {
+                    self.clear_stack_back_to(&TABLE_CONTEXT);
+                    self.active_formatting_elements
+                        .push(ActiveFormattingElement::Marker);
+                    self.insert_element(tag, attributes.to_vec());
+                    self.mode = InsertionMode::InCaption;
+                    return false;
+                }
+                "colgroup" => {
+                    self.clear_stack_back_to(&TABLE_CONTEXT);
+                    self.insert_element(tag, attributes.to_vec());
+                    self.mode = InsertionMode::InColumnGroup;
+                    return false;
+                }
+                "col" => {
+                    // <colgroup>が省略されたものとして扱う
+                    self.clear_stack_back_to(&TABLE_CONTEXT);
+                    self.insert_element("colgroup", Vec::new());
+                    self.mode = InsertionMode::InColumnGroup;
+                    return true;
+                }
+                "tbody" | "tfoot" | "thead" => {
+                    self.clear_stack_back_to(&TABLE_CONTEXT);
+                    self.insert_element(tag, attributes.to_vec());
+                    self.mode = InsertionMode::InTableBody;
+                    return false;
+                }
+                "td" | "th" | "tr" => {
+                    // <tbody>が省略されたものとして扱う
+                    self.clear_stack_back_to(&TABLE_CONTEXT);
+                    self.insert_element("tbody", Vec::new());
+                    self.mode = InsertionMode::InTableBody;
+                    return true;
+                }
+                "table" => {
+                    // パースエラー。前の<table>を閉じてから再処理する。
+                    self.unexpected_token(token);
+                    if !self.has_element_in_table_scope("table") {
+                        return false;
+                    }
+                    self.pop_until(ElementKind::Table);
+                    self.reset_insertion_mode();
+                    return true;
+                }
+                "style" => {
+                    self.parse_text_element(tag, attributes.to_vec(), State::Rawtext);
+                    return false;
+                }
+                "script" => {
+                    self.parse_text_element(tag, attributes.to_vec(), State::ScriptData);
+                    return false;
+                }
+                "template" => {
+                    self.insert_template(attributes.to_vec());
+                    return false;
+                }
+                "input" => {
+                    let is_hidden = attributes
+                        .iter()
+                        .any(|a| a.name() == "type" && a.value().eq_ignore_ascii_case("hidden"));
+                    if is_hidden {
+                        // パースエラー
+                        self.unexpected_token(token);
+                        self.insert_element(tag, attributes.to_vec());
+                        self.stack_of_open_elements.pop();
+                        return false;
+                    }
+                }
+                "form" => {
+                    // パースエラー
+                    self.unexpected_token(token);
+                    if self.form_element.is_none() {
+                        let form = self.insert_element(tag, attributes.to_vec());
+                        self.form_element = Some(form);
+                        self.stack_of_open_elements.pop();
+                    }
+                    return false;
+                }
+                _ => {}
+            },
+            HtmlToken::EndTag { ref tag } => match tag.as_str() {
+                "table" => {
+                    if self.has_element_in_table_scope("table") {
+                        self.pop_until(ElementKind::Table);
+                        self.reset_insertion_mode();
+                    }
+                    return false;
+                }
+                "body" | "caption" | "col" | "colgroup" | "html" | "tbody" | "td" | "tfoot"
+                | "th" | "thead" | "tr" => {
+                    // パースエラー。トークンを無視する。
+                    self.unexpected_token(token);
+                    return false;
+                }
+                "template" => {
+                    self.close_template(token);
+                    return false;
+                }
+                _ => {}
+            },
+            HtmlToken::Eof => return false,
+        }
+
+        // パースエラー。フォスターペアレンティングを有効にして、InBodyモードの規則で処理する
+        self.unexpected_token(token);
+        self.foster_parenting = true;
+        let reprocess = self.process_token_in_body(token);
+        self.foster_parenting = false;
+        reprocess
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intemplate
+    /// トークンを再処理する必要がある場合はtrueを返す
+    fn process_token_in_template(&mut self, token: &HtmlToken) -> bool {
+        match *token {
+            HtmlToken::StartTag { ref tag, .. } => {
+                // <template>の中身のテーブル関連の要素は、その要素を受け入れる挿入モードで処理する
+                let mode = match tag.as_str() {
+                    "caption" | "colgroup" | "tbody" | "tfoot" | "thead" => InsertionMode::InTable,
+                    "col" => InsertionMode::InColumnGroup,
+                    "tr" => InsertionMode::InTableBody,
+                    "td" | "th" => InsertionMode::InRow,
+                    _ => InsertionMode::InBody,
+                };
+                // InHeadモードの規則で処理する要素は、InBodyモードでも同じ規則で処理される
+                if mode == InsertionMode::InBody {
+                    return self.process_token_in_body(token);
+                }
+                self.stack_of_template_insertion_modes.pop();
+                self.stack_of_template_insertion_modes.push(mode);
+                self.mode = mode;
+                true
+            }
+            HtmlToken::EndTag { ref tag } => {
+                if tag == "template" {
+                    self.close_template(token);
+                } else {
+                    // パースエラー。トークンを無視する。
+                    self.unexpected_token(token);
+                }
+                false
+            }
+            _ => self.process_token_in_body(token),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
+    /// <template>の開始タグを処理する
+    fn insert_template(&mut self, attributes: Vec<Attribute>) {
+        self.insert_element("template", attributes);
+        self.active_formatting_elements
+            .push(ActiveFormattingElement::Marker);
+        self.mode = InsertionMode::InTemplate;
+        self.stack_of_template_insertion_modes
+            .push(InsertionMode::InTemplate);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
+    /// <template>の終了タグを処理する
+    fn close_template(&mut self, token: &HtmlToken) {
+        if !self.contain_in_stack(ElementKind::Template) {
+            // パースエラー。トークンを無視する。
+            self.unexpected_token(token);
+            return;
+        }
+        self.generate_all_implied_end_tags_thoroughly();
+        self.check_current_node_is("template");
+        self.pop_until(ElementKind::Template);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.stack_of_template_insertion_modes.pop();
+        self.reset_insertion_mode();
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intemplate
+    /// 入力の終わりで<template>が開いたままの場合は閉じて、EOFを再処理する必要があればtrueを返す
+    fn close_template_at_eof(&mut self) -> bool {
+        if !self.contain_in_stack(ElementKind::Template) {
+            return false;
+        }
+        // パースエラー
+        self.parse_error(ParseErrorKind::UnexpectedEof);
+        self.pop_until(ElementKind::Template);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.stack_of_template_insertion_modes.pop();
+        self.reset_insertion_mode();
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#tree-construction-dispatcher
+    /// トークンを挿入モードの規則ではなく、外部コンテンツの規則で処理するかどうか
+    fn should_process_in_foreign_content(&self, token: &HtmlToken) -> bool {
+        if self.stack_of_open_elements.is_empty() {
+            return false;
+        }
+        let element = match self.element_of(self.adjusted_current_node()) {
+            Some(e) => e,
+            None => return false,
+        };
+        if element.namespace() == Namespace::Html {
+            return false;
+        }
+
+        match *token {
+            HtmlToken::StartTag { ref tag, .. } => {
+                if is_mathml_text_integration_point(&element)
+                    && tag != "mglyph"
+                    && tag != "malignmark"
+                {
+                    return false;
+                }
+                if element.namespace() == Namespace::MathMl
+                    && element.tag_name() == "annotation-xml"
+                    && tag == "svg"
+                {
+                    return false;
+                }
+                !is_html_integration_point(&element)
+            }
+            HtmlToken::Char(_) => {
+                !is_mathml_text_integration_point(&element) && !is_html_integration_point(&element)
+            }
+            HtmlToken::Eof => false,
+            _ => true,
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+    /// トークンを再処理する必要がある場合はtrueを返す
+    fn process_token_in_foreign_content(&mut self, token: &HtmlToken) -> bool {
+        match *token {
+            HtmlToken::Char(c) => {
+                if c == '\0' {
+                    self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                    self.insert_char('\u{FFFD}');
+                    return false;
+                }
+                self.insert_char(c);
+            }
+            HtmlToken::Comment(ref data) => self.insert_comment(data),
+            HtmlToken::Doctype { .. } => self.unexpected_token(token),
+            HtmlToken::StartTag {
+                ref tag,
+                self_closing,
+                ref attributes,
+            } => {
+                if is_breakout_start_tag(tag, attributes) {
+                    // パースエラー。HTMLとして処理できる位置まで要素を取り除き、トークンを再処理する
+                    self.unexpected_token(token);
+                    while let Some(node) = self.stack_of_open_elements.last() {
+                        let breaks_out = match self.element_of(*node) {
+                            Some(e) => {
+                                e.namespace() == Namespace::Html
+                                    || is_mathml_text_integration_point(&e)
+                                    || is_html_integration_point(&e)
+                            }
+                            None => true,
+                        };
+                        if breaks_out {
+                            break;
+                        }
+                        self.stack_of_open_elements.pop();
+                    }
+                    return true;
+                }
+
+                let namespace = match self.element_of(self.adjusted_current_node()) {
+                    Some(e) => e.namespace(),
+                    None => Namespace::Html,
+                };
+                let mut attributes = attributes.to_vec();
+                let tag = match namespace {
+                    Namespace::MathMl => {
+                        adjust_mathml_attributes(&mut attributes);
+                        tag.clone()
+                    }
+                    Namespace::Svg => {
+                        adjust_svg_attributes(&mut attributes);
+                        adjust_svg_tag_name(tag)
+                    }
+                    Namespace::Html => tag.clone(),
+                };
+                self.insert_foreign_element(&tag, attributes, namespace);
+                // SVGの<script/>は終了タグと同じように扱う。スクリプトは実行しない
+                if self_closing {
+                    self.stack_of_open_elements.pop();
+                }
+            }
+            HtmlToken::EndTag { ref tag } => {
+                let mut i = self.stack_of_open_elements.len() - 1;
+                let node_tag_name = |parser: &Self, node: NodeId| {
+                    parser
+                        .element_of(node)
+                        .map(|e| e.tag_name().to_ascii_lowercase())
+                };
+                if node_tag_name(self, self.stack_of_open_elements[i]).as_deref()
+                    != Some(tag.as_str())
+                {
+                    self.unexpected_token(token);
+                }
+                loop {
+                    // 一番下のノードまで対応する要素が見つからなければ、トークンを無視する
+                    if i == 0 {
+                        return false;
+                    }
+                    let node = self.stack_of_open_elements[i];
+                    if node_tag_name(self, node).as_deref() == Some(tag.as_str()) {
+                        self.stack_of_open_elements.truncate(i);
+                        return false;
+                    }
+                    i -= 1;
+                    if self.tag_name_of(self.stack_of_open_elements[i]).is_some() {
+                        // HTML要素に到達した場合、現在の挿入モードの規則で処理する
+                        self.reprocess_in_html_content = true;
+                        return true;
+                    }
+                }
+            }
+            HtmlToken::Eof => {}
+        }
+
+        false
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-place-for-inserting-a-node
+    /// 挿入先の親ノードと、その中で直前に挿入するべきノードを返す
+    fn appropriate_place_for_inserting_node(
+        &self,
+        override_target: Option<NodeId>,
+    ) -> (NodeId, Option<NodeId>) {
+        let target = override_target.unwrap_or_else(|| self.current_node());
+
+        let is_table_element = self
+            .tag_name_of(target)
+            .is_some_and(|t| matches!(t.as_str(), "table" | "tbody" | "tfoot" | "thead" | "tr"));
+        let (parent, reference) = if !self.foster_parenting || !is_table_element {
+            (target, None)
+        } else {
+            // フォスターペアレンティング：最後の<table>の直前に挿入する
+            let last_of = |tag_name: &str| {
+                self.stack_of_open_elements
+                    .iter()
+                    .rposition(|n| self.tag_name_of(*n).as_deref() == Some(tag_name))
+            };
+            match (last_of("template"), last_of("table")) {
+                // <table>より後に開かれた<template>がある場合は、その中身に挿入する
+                (Some(template), table) if table.map_or(true, |t| template > t) => {
+                    (self.stack_of_open_elements[template], None)
+                }
+                (_, Some(i)) => {
+                    let table = self.stack_of_open_elements[i];
+                    let parent = self.dom.borrow().node(table).parent();
+                    match parent {
+                        Some(parent) => (parent, Some(table)),
+                        None => (self.stack_of_open_elements[i - 1], None),
+                    }
+                }
+                (_, None) => (self.stack_of_open_elements[0], None),
+            }
+        };
+
+        // <template>の中に挿入する場合は、子ノードではなくその中身に挿入する
+        let template_contents = self.dom.borrow().node(parent).template_contents();
+        match template_contents {
+            Some(contents) => (contents, None),
+            None => (parent, reference),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#current-node
+    fn current_node(&self) -> NodeId {
+        match self.stack_of_open_elements.last() {
+            Some(n) => *n,
+            None => self.window.borrow().document(),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adjusted-current-node
+    /// フラグメントのパースでスタックに<html>しかない場合は、コンテキスト要素を返す
+    fn adjusted_current_node(&self) -> NodeId {
+        match self.context_element {
+            Some(context) if self.stack_of_open_elements.len() == 1 => context,
+            _ => self.current_node(),
+        }
+    }
+
+    /// 次のトークンを取得する。外部コンテンツの中ではCDATAセクションを認識させる
+    fn next_token(&mut self) -> Option<HtmlToken> {
+        let in_foreign_content = !self.stack_of_open_elements.is_empty()
+            && self
+                .element_of(self.adjusted_current_node())
+                .is_some_and(|e| e.namespace() != Namespace::Html);
+        self.t.set_in_foreign_content(in_foreign_content);
+        self.t.next()
+    }
+
+    fn insert_element(&mut self, tag: &str, attributes: Vec<Attribute>) -> NodeId {
+        self.insert_element_node(Element::new(tag, attributes))
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-an-html-element
+    fn insert_element_node(&mut self, element: Element) -> NodeId {
+        let (parent, reference) = self.appropriate_place_for_inserting_node(None);
+        let is_template = element.kind() == ElementKind::Template;
+        let node = self.create_node(NodeKind::Element(element));
+        if is_template {
+            let contents = self.create_template_contents();
+            self.dom
+                .borrow_mut()
+                .node_mut(node)
+                .set_template_contents(Some(contents));
+        }
+
+        self.insert_node(node, parent, reference);
+
+        self.stack_of_open_elements.push(node);
+        node
+    }
+
+    /// https://html.spec.whatwg.org/multipage/scripting.html#template-contents
+    /// <template>の中身となる、空のDocumentFragmentを作る
+    fn create_template_contents(&mut self) -> NodeId {
+        let owner_document = match self.template_contents_owner_document {
+            Some(document) => document,
+            None => {
+                let document = self.create_node(NodeKind::Document(Document::new()));
+                self.template_contents_owner_document = Some(document);
+                document
+            }
         };
+        self.create_node(NodeKind::DocumentFragment(DocumentFragment::new(Some(
+            owner_document,
+        ))))
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-foreign-element
+    fn insert_foreign_element(
+        &mut self,
+        tag: &str,
+        attributes: Vec<Attribute>,
+        namespace: Namespace,
+    ) -> NodeId {
+        self.insert_element_node(Element::new_with_namespace(tag, attributes, namespace))
+    }
+
+    fn create_char(&mut self, c: char) -> NodeId {
+        let mut s = String::new();
+        s.push(c);
+        self.create_node(NodeKind::Text(s))
+    }
 
-        // 現在参照しているノードがテキストノードである場合、そのノードに文字を追加する
-        if let NodeKind::Text(ref mut s) = current.borrow_mut().kind {
-            s.push(c);
+    fn insert_char(&mut self, c: char) {
+        if self.stack_of_open_elements.is_empty() {
             return;
         }
+        let (parent, reference) = self.appropriate_place_for_inserting_node(None);
+
+        // 直前のノードがテキストノードである場合、そのノードに文字を追加する
+        let mut dom = self.dom.borrow_mut();
+        let previous = match reference {
+            Some(r) => dom.node(r).previuos_sibling(),
+            None => dom.node(parent).last_child(),
+        };
+        if let Some(previous) = previous {
+            if let NodeKind::Text(ref mut s) = dom.node_mut(previous).kind {
+                s.push(c);
+                return;
+            }
+        }
+        drop(dom);
 
-        // 改行文字や空白のときはテキストノードを追加しない
-        if c == ' ' || c == '\n' {
+        // 改行文字や空白のときはテキストノードを追加しない。
+        // ただし、空白も中身として扱う<pre>、<listing>、<textarea>の中では追加する
+        let preserves_whitespace = matches!(
+            self.tag_name_of(parent).as_deref(),
+            Some("pre" | "listing" | "textarea")
+        );
+        if (c == ' ' || c == '\n') && !preserves_whitespace {
             return;
         }
 
-        let node = Rc::new(RefCell::new(self.create_char(c)));
+        let node = self.create_char(c);
+        self.insert_node(node, parent, reference);
+    }
 
-        if current.borrow().first_child().is_some() {
-            current
-                .borrow()
-                .first_child()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(node.clone()));
-            node.borrow_mut().set_previuos_sibling(Rc::downgrade(
-                &current
-                    .borrow()
-                    .first_child()
-                    .expect("failed to get a first child"),
-            ));
-        } else {
-            current.borrow_mut().set_first_child(Some(node.clone()));
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generic-raw-text-element-parsing-algorithm
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generic-rcdata-element-parsing-algorithm
+    /// 要素を挿入し、その中身をテキストとして扱うようにトークナイザの状態を切り替える
+    fn parse_text_element(&mut self, tag: &str, attributes: Vec<Attribute>, state: State) {
+        self.insert_element(tag, attributes);
+        self.t.set_state(state);
+        self.original_insertion_mode = self.mode;
+        self.mode = InsertionMode::Text;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment
+    fn insert_comment(&mut self, data: &str) {
+        let (parent, reference) = self.appropriate_place_for_inserting_node(None);
+        let node = self.create_node(NodeKind::Comment(String::from(data)));
+        self.insert_node(node, parent, reference);
+    }
+
+    /// parentの最後の子としてコメントを追加する
+    fn insert_comment_to(&mut self, data: &str, parent: NodeId) {
+        let node = self.create_node(NodeKind::Comment(String::from(data)));
+        self.insert_node(node, parent, None);
+    }
+
+    /// どの親にも属さないノードをアリーナに作る
+    fn create_node(&self, kind: NodeKind) -> NodeId {
+        self.dom.borrow_mut().create_node(kind)
+    }
+
+    /// 検証をせずに、nodeをparentの子としてreferenceの直前に挿入する
+    fn insert_node(&self, node: NodeId, parent: NodeId, reference: Option<NodeId>) {
+        insert(&mut self.dom.borrow_mut(), node, parent, reference);
+    }
+
+    /// 要素のノードであれば、その要素を返す
+    fn element_of(&self, node: NodeId) -> Option<Element> {
+        self.dom.borrow().node(node).get_element()
+    }
+
+    fn document_mode(&self) -> DocumentMode {
+        let document = self.window.borrow().document();
+        let mode = match self.dom.borrow().node(document).get_document() {
+            Some(d) => d.mode(),
+            None => DocumentMode::NoQuirks,
+        };
+        mode
+    }
+
+    fn set_document_mode(&mut self, mode: DocumentMode) {
+        let document = self.window.borrow().document();
+        let mut dom = self.dom.borrow_mut();
+        if let NodeKind::Document(ref mut d) = dom.node_mut(document).kind {
+            d.set_mode(mode);
         }
+    }
 
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    fn parse_error(&mut self, kind: ParseErrorKind) {
+        let position = self.t.current_position();
+        self.errors.push(ParseError::new(kind, position));
+    }
 
-        self.stack_of_open_elements.push(node);
+    /// 現在の挿入モードで許されていないトークンをパースエラーとして記録する
+    fn unexpected_token(&mut self, token: &HtmlToken) {
+        let kind = match *token {
+            HtmlToken::StartTag { ref tag, .. } => ParseErrorKind::UnexpectedStartTag(tag.clone()),
+            HtmlToken::EndTag { ref tag } => ParseErrorKind::UnexpectedEndTag(tag.clone()),
+            HtmlToken::Char(c) => ParseErrorKind::UnexpectedCharacter(c),
+            HtmlToken::Doctype { .. } => ParseErrorKind::UnexpectedDoctype,
+            HtmlToken::Eof => ParseErrorKind::UnexpectedEof,
+            // コメントはどこに現れてもパースエラーにならない
+            HtmlToken::Comment(_) => return,
+        };
+        self.parse_error(kind);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    /// ファイルの終わりで、終了タグを省略できない要素が開いたままになっている場合はパースエラー
+    fn check_open_elements_at_eof(&mut self) {
+        let has_unclosed_element = self.stack_of_open_elements.iter().any(|n| {
+            !self
+                .tag_name_of(*n)
+                .is_some_and(|t| OPTIONAL_END_TAG_ELEMENTS.contains(&t.as_str()))
+        });
+        if has_unclosed_element {
+            self.parse_error(ParseErrorKind::UnexpectedEof);
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    /// 終了タグで閉じる要素が現在のノードでない場合はパースエラー
+    fn check_current_node_is(&mut self, tag: &str) {
+        if self.current_tag_name().as_deref() != Some(tag) {
+            self.parse_error(ParseErrorKind::UnexpectedEndTag(String::from(tag)));
+        }
     }
 
     fn contain_in_stack(&self, element_kind: ElementKind) -> bool {
         for i in 0..self.stack_of_open_elements.len() {
-            if self.stack_of_open_elements[i].borrow().element_kind() == Some(element_kind) {
+            if self
+                .dom
+                .borrow()
+                .node(self.stack_of_open_elements[i])
+                .element_kind()
+                .as_ref()
+                == Some(&element_kind)
+            {
                 return true;
             }
         }
@@ -464,11 +2140,10 @@ impl HtmlParser {
     }
 
     fn pop_until(&mut self, element_kind: ElementKind) {
-        assert!(
-            self.contain_in_stack(element_kind),
-            "stack doesn't contain the element {:?}",
-            element_kind
-        );
+        // スタックに含まれていない場合は、すべての要素を取り除いてしまわないように何もしない
+        if !self.contain_in_stack(element_kind.clone()) {
+            return;
+        }
 
         loop {
             let current = match self.stack_of_open_elements.pop() {
@@ -476,30 +2151,755 @@ impl HtmlParser {
                 None => return,
             };
 
-            if current.borrow().element_kind() == Some(element_kind) {
-                return;
-            }
-        }
+            if self.dom.borrow().node(current).element_kind().as_ref() == Some(&element_kind) {
+                return;
+            }
+        }
+    }
+
+    fn pop_current_node(&mut self, element_kind: ElementKind) -> bool {
+        let current = match self.stack_of_open_elements.last() {
+            Some(n) => *n,
+            None => return false,
+        };
+
+        if self.dom.borrow().node(current).element_kind().as_ref() == Some(&element_kind) {
+            self.stack_of_open_elements.pop();
+            return true;
+        }
+
+        false
+    }
+
+    fn pop_until_one_of(&mut self, tag_names: &[&str]) {
+        while let Some(node) = self.stack_of_open_elements.pop() {
+            if self
+                .tag_name_of(node)
+                .is_some_and(|t| tag_names.contains(&t.as_str()))
+            {
+                return;
+            }
+        }
+    }
+
+    /// HTML要素のタグ名を返す。SVGやMathMLの要素、要素でないノードの場合はNone
+    fn tag_name_of(&self, node: NodeId) -> Option<String> {
+        self.element_of(node)
+            .filter(|e| e.namespace() == Namespace::Html)
+            .map(|e| e.tag_name())
+    }
+
+    fn current_tag_name(&self) -> Option<String> {
+        self.stack_of_open_elements
+            .last()
+            .and_then(|n| self.tag_name_of(*n))
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#special
+    fn is_special(&self, node: NodeId) -> bool {
+        match self.tag_name_of(node) {
+            Some(t) => SPECIAL_ELEMENTS.contains(&t.as_str()),
+            None => self.is_special_foreign(node),
+        }
+    }
+
+    fn is_special_foreign(&self, node: NodeId) -> bool {
+        self.element_of(node)
+            .is_some_and(|e| is_special_foreign_element(&e))
+    }
+
+    fn is_default_scope(&self, node: NodeId) -> bool {
+        match self.tag_name_of(node) {
+            Some(t) => DEFAULT_SCOPE_ELEMENTS.contains(&t.as_str()),
+            None => self.is_special_foreign(node),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope
+    fn has_element_in_specific_scope<F>(&self, tag_names: &[&str], is_scope: F) -> bool
+    where
+        F: Fn(&Self, NodeId) -> bool,
+    {
+        for node in self.stack_of_open_elements.iter().rev() {
+            if self
+                .tag_name_of(*node)
+                .is_some_and(|t| tag_names.contains(&t.as_str()))
+            {
+                return true;
+            }
+            if is_scope(self, *node) {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-scope
+    fn has_element_in_scope(&self, tag_name: &str) -> bool {
+        self.has_element_in_specific_scope(&[tag_name], Self::is_default_scope)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-list-item-scope
+    fn has_element_in_list_item_scope(&self, tag_name: &str) -> bool {
+        self.has_element_in_specific_scope(&[tag_name], |p, n| {
+            p.is_default_scope(n) || p.tag_name_of(n).is_some_and(|t| t == "ol" || t == "ul")
+        })
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-button-scope
+    fn has_element_in_button_scope(&self, tag_name: &str) -> bool {
+        self.has_element_in_specific_scope(&[tag_name], |p, n| {
+            p.is_default_scope(n) || p.tag_name_of(n).is_some_and(|t| t == "button")
+        })
+    }
+
+    fn is_table_scope(&self, node: NodeId) -> bool {
+        self.tag_name_of(node)
+            .is_some_and(|t| matches!(t.as_str(), "html" | "table" | "template"))
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-table-scope
+    fn has_element_in_table_scope(&self, tag_name: &str) -> bool {
+        self.has_element_in_specific_scope(&[tag_name], Self::is_table_scope)
+    }
+
+    /// 現在のノードがcontextのいずれかになるまで、スタックから要素を取り除く
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context
+    fn clear_stack_back_to(&mut self, context: &[&str]) {
+        while let Some(node) = self.stack_of_open_elements.last() {
+            if self
+                .tag_name_of(*node)
+                .is_some_and(|t| context.contains(&t.as_str()))
+            {
+                return;
+            }
+            self.stack_of_open_elements.pop();
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+    fn reset_insertion_mode(&mut self) {
+        for i in (0..self.stack_of_open_elements.len()).rev() {
+            let last = i == 0;
+            // フラグメントのパースでは、スタックの最初のノードの代わりにコンテキスト要素を使う
+            let node = match (self.context_element, last) {
+                (Some(context), true) => context,
+                _ => self.stack_of_open_elements[i],
+            };
+            let tag_name = self.tag_name_of(node).unwrap_or_default();
+            self.mode = match tag_name.as_str() {
+                "td" | "th" if !last => InsertionMode::InCell,
+                "tr" => InsertionMode::InRow,
+                "tbody" | "thead" | "tfoot" => InsertionMode::InTableBody,
+                "caption" => InsertionMode::InCaption,
+                "colgroup" => InsertionMode::InColumnGroup,
+                "table" => InsertionMode::InTable,
+                "template" => self
+                    .stack_of_template_insertion_modes
+                    .last()
+                    .copied()
+                    .unwrap_or(InsertionMode::InTemplate),
+                "head" if !last => InsertionMode::InHead,
+                "body" => InsertionMode::InBody,
+                // フラグメントのパースでは<head>が作られていない
+                "html" if self.context_element.is_some() => InsertionMode::BeforeHead,
+                // このパーサでは<head>が必ず<body>より先に作られるため、AfterHeadモードになる
+                "html" => InsertionMode::AfterHead,
+                _ if last => InsertionMode::InBody,
+                _ => continue,
+            };
+            return;
+        }
+
+        self.mode = InsertionMode::InBody;
+    }
+
+    /// <caption>を閉じる。<caption>がテーブルスコープにない場合はfalseを返す
+    fn close_caption(&mut self) -> bool {
+        if !self.has_element_in_table_scope("caption") {
+            return false;
+        }
+        self.generate_implied_end_tags(None);
+        self.pop_until(ElementKind::Caption);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.mode = InsertionMode::InTable;
+        true
+    }
+
+    /// <tbody>、<thead>、<tfoot>を閉じる。いずれもテーブルスコープにない場合はfalseを返す
+    fn close_table_body(&mut self) -> bool {
+        if !self.has_element_in_specific_scope(&["tbody", "thead", "tfoot"], Self::is_table_scope) {
+            return false;
+        }
+        self.clear_stack_back_to(&TABLE_BODY_CONTEXT);
+        self.stack_of_open_elements.pop();
+        self.mode = InsertionMode::InTable;
+        true
+    }
+
+    /// <tr>を閉じる。<tr>がテーブルスコープにない場合はfalseを返す
+    fn close_row(&mut self) -> bool {
+        if !self.has_element_in_table_scope("tr") {
+            return false;
+        }
+        self.clear_stack_back_to(&TABLE_ROW_CONTEXT);
+        self.stack_of_open_elements.pop();
+        self.mode = InsertionMode::InTableBody;
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        self.pop_until_one_of(&["td", "th"]);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.mode = InsertionMode::InRow;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
+    fn generate_implied_end_tags(&mut self, exception: Option<&str>) {
+        while let Some(tag_name) = self.current_tag_name() {
+            if Some(tag_name.as_str()) == exception
+                || !IMPLIED_END_TAG_ELEMENTS.contains(&tag_name.as_str())
+            {
+                return;
+            }
+            self.stack_of_open_elements.pop();
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-all-implied-end-tags-thoroughly
+    fn generate_all_implied_end_tags_thoroughly(&mut self) {
+        while let Some(tag_name) = self.current_tag_name() {
+            if !IMPLIED_END_TAG_ELEMENTS.contains(&tag_name.as_str())
+                && !THOROUGHLY_IMPLIED_END_TAG_ELEMENTS.contains(&tag_name.as_str())
+            {
+                return;
+            }
+            self.stack_of_open_elements.pop();
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-a-p-element
+    fn close_p_element(&mut self) {
+        self.generate_implied_end_tags(Some("p"));
+        self.pop_until(ElementKind::P);
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    /// "any other end tag"の処理
+    fn any_other_end_tag(&mut self, tag: &str) {
+        for i in (0..self.stack_of_open_elements.len()).rev() {
+            let node = self.stack_of_open_elements[i];
+            if self.tag_name_of(node).as_deref() == Some(tag) {
+                self.generate_implied_end_tags(Some(tag));
+                self.check_current_node_is(tag);
+                self.stack_of_open_elements.truncate(i);
+                return;
+            }
+            if self.is_special(node) {
+                // パースの失敗。トークンを無視する。
+                self.parse_error(ParseErrorKind::UnexpectedEndTag(String::from(tag)));
+                return;
+            }
+        }
+    }
+
+    fn is_in_stack(&self, node: NodeId) -> bool {
+        self.stack_of_open_elements.iter().any(|n| *n == node)
+    }
+
+    fn remove_from_stack(&mut self, node: NodeId) {
+        self.stack_of_open_elements.retain(|n| *n != node);
+    }
+
+    fn position_in_active_formatting_elements(&self, node: NodeId) -> Option<usize> {
+        self.active_formatting_elements
+            .iter()
+            .position(|entry| match entry {
+                ActiveFormattingElement::Element(n) => *n == node,
+                ActiveFormattingElement::Marker => false,
+            })
+    }
+
+    fn remove_from_active_formatting_elements(&mut self, node: NodeId) {
+        if let Some(i) = self.position_in_active_formatting_elements(node) {
+            self.active_formatting_elements.remove(i);
+        }
+    }
+
+    /// 最後のマーカー以降にある、tag_nameを持つ最後の要素を返す
+    fn find_active_formatting_element(&self, tag_name: &str) -> Option<NodeId> {
+        for entry in self.active_formatting_elements.iter().rev() {
+            match entry {
+                ActiveFormattingElement::Marker => return None,
+                ActiveFormattingElement::Element(n) => {
+                    if self.tag_name_of(*n).as_deref() == Some(tag_name) {
+                        return Some(*n);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#push-onto-the-list-of-active-formatting-elements
+    fn push_active_formatting_element(&mut self, node: NodeId) {
+        // 最後のマーカー以降に同じタグ名と属性を持つ要素がすでに3つある場合、最も古いものを取り除く
+        let element = self.element_of(node);
+        let mut count = 0;
+        let mut earliest = None;
+        for (i, entry) in self.active_formatting_elements.iter().enumerate().rev() {
+            match entry {
+                ActiveFormattingElement::Marker => break,
+                ActiveFormattingElement::Element(n) => {
+                    if self.element_of(*n) == element {
+                        count += 1;
+                        earliest = Some(i);
+                    }
+                }
+            }
+        }
+        if count >= 3 {
+            if let Some(i) = earliest {
+                self.active_formatting_elements.remove(i);
+            }
+        }
+
+        self.active_formatting_elements
+            .push(ActiveFormattingElement::Element(node));
+    }
+
+    /// エントリがマーカーでなく、スタックにも含まれていない場合はtrue
+    fn needs_reconstruction(&self, i: usize) -> bool {
+        match &self.active_formatting_elements[i] {
+            ActiveFormattingElement::Marker => false,
+            ActiveFormattingElement::Element(n) => !self.is_in_stack(*n),
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements
+    fn reconstruct_active_formatting_elements(&mut self) {
+        let len = self.active_formatting_elements.len();
+        if len == 0 || !self.needs_reconstruction(len - 1) {
+            return;
+        }
+
+        // 再構築が必要な最初のエントリまで遡る
+        let mut first = len - 1;
+        while first > 0 && self.needs_reconstruction(first - 1) {
+            first -= 1;
+        }
+
+        for i in first..len {
+            let element = match &self.active_formatting_elements[i] {
+                ActiveFormattingElement::Element(n) => self.element_of(*n),
+                ActiveFormattingElement::Marker => None,
+            };
+            if let Some(element) = element {
+                let node = self.insert_element_node(element);
+                self.active_formatting_elements[i] = ActiveFormattingElement::Element(node);
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-list-of-active-formatting-elements-up-to-the-last-marker
+    fn clear_active_formatting_elements_to_last_marker(&mut self) {
+        while let Some(entry) = self.active_formatting_elements.pop() {
+            if let ActiveFormattingElement::Marker = entry {
+                return;
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    fn run_adoption_agency_algorithm(&mut self, subject: &str) {
+        // 現在のノードが対象の要素で、かつ活性整形要素リストに含まれていない場合はスタックから取り除くだけでよい
+        let current = self.current_node();
+        if self.tag_name_of(current).as_deref() == Some(subject)
+            && self
+                .position_in_active_formatting_elements(current)
+                .is_none()
+        {
+            self.stack_of_open_elements.pop();
+            return;
+        }
+
+        for _ in 0..8 {
+            let formatting_element = match self.find_active_formatting_element(subject) {
+                Some(n) => n,
+                None => {
+                    self.any_other_end_tag(subject);
+                    return;
+                }
+            };
+
+            let formatting_element_index = match self
+                .stack_of_open_elements
+                .iter()
+                .position(|n| *n == formatting_element)
+            {
+                Some(i) => i,
+                None => {
+                    // パースエラー
+                    self.parse_error(ParseErrorKind::UnexpectedEndTag(String::from(subject)));
+                    self.remove_from_active_formatting_elements(formatting_element);
+                    return;
+                }
+            };
+            if !self.has_element_in_scope(subject) {
+                // パースエラー。トークンを無視する。
+                self.parse_error(ParseErrorKind::UnexpectedEndTag(String::from(subject)));
+                return;
+            }
+
+            // 整形要素よりもスタックの上にある、最も下の特殊要素
+            let furthest_block_index = match (formatting_element_index + 1
+                ..self.stack_of_open_elements.len())
+                .find(|&i| self.is_special(self.stack_of_open_elements[i]))
+            {
+                Some(i) => i,
+                None => {
+                    self.stack_of_open_elements
+                        .truncate(formatting_element_index);
+                    self.remove_from_active_formatting_elements(formatting_element);
+                    return;
+                }
+            };
+            let furthest_block = self.stack_of_open_elements[furthest_block_index];
+            let common_ancestor = self.stack_of_open_elements[formatting_element_index - 1];
+
+            let mut bookmark = match self.position_in_active_formatting_elements(formatting_element)
+            {
+                Some(i) => i,
+                None => return,
+            };
+
+            let mut node_index = furthest_block_index;
+            let mut last_node = furthest_block;
+            let mut inner_loop_counter = 0;
+            loop {
+                inner_loop_counter += 1;
+                node_index -= 1;
+                let node = self.stack_of_open_elements[node_index];
+                if node == formatting_element {
+                    break;
+                }
+
+                let mut position = self.position_in_active_formatting_elements(node);
+                if inner_loop_counter > 3 {
+                    if let Some(i) = position {
+                        self.active_formatting_elements.remove(i);
+                        if i < bookmark {
+                            bookmark -= 1;
+                        }
+                        position = None;
+                    }
+                }
+                let position = match position {
+                    Some(i) => i,
+                    None => {
+                        self.stack_of_open_elements.remove(node_index);
+                        continue;
+                    }
+                };
+
+                // nodeの複製を作成し、リストとスタックのnodeを置き換える
+                let element = match self.element_of(node) {
+                    Some(e) => e,
+                    None => return,
+                };
+                let new_node = self.create_node(NodeKind::Element(element));
+                self.active_formatting_elements[position] =
+                    ActiveFormattingElement::Element(new_node);
+                self.stack_of_open_elements[node_index] = new_node;
+
+                if last_node == furthest_block {
+                    bookmark = position + 1;
+                }
+
+                self.insert_node(last_node, new_node, None);
+                last_node = new_node;
+            }
+
+            let (parent, reference) =
+                self.appropriate_place_for_inserting_node(Some(common_ancestor));
+            self.insert_node(last_node, parent, reference);
+
+            // 整形要素の複製を作成し、furthest blockの子をすべて移す
+            let element = match self.element_of(formatting_element) {
+                Some(e) => e,
+                None => return,
+            };
+            let new_element = self.create_node(NodeKind::Element(element));
+            let children: Vec<NodeId> = self.dom.borrow().children(furthest_block).collect();
+            for child in children {
+                self.insert_node(child, new_element, None);
+            }
+            self.insert_node(new_element, furthest_block, None);
+
+            if let Some(i) = self.position_in_active_formatting_elements(formatting_element) {
+                self.active_formatting_elements.remove(i);
+                if i < bookmark {
+                    bookmark -= 1;
+                }
+            }
+            let bookmark = bookmark.min(self.active_formatting_elements.len());
+            self.active_formatting_elements
+                .insert(bookmark, ActiveFormattingElement::Element(new_element));
+
+            self.remove_from_stack(formatting_element);
+            if let Some(i) = self
+                .stack_of_open_elements
+                .iter()
+                .position(|n| *n == furthest_block)
+            {
+                self.stack_of_open_elements.insert(i + 1, new_element);
+            }
+        }
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+/// ファイルの終わりで開いたままでもパースエラーにならない要素
+static OPTIONAL_END_TAG_ELEMENTS: [&str; 18] = [
+    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc", "tbody", "td", "tfoot",
+    "th", "thead", "tr", "body", "html",
+];
+
+static HEADING_ELEMENTS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-scope
+static DEFAULT_SCOPE_ELEMENTS: [&str; 9] = [
+    "applet", "caption", "html", "table", "td", "th", "marquee", "object", "template",
+];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context
+static TABLE_CONTEXT: [&str; 3] = ["table", "template", "html"];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-body-context
+static TABLE_BODY_CONTEXT: [&str; 5] = ["tbody", "tfoot", "thead", "template", "html"];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-row-context
+static TABLE_ROW_CONTEXT: [&str; 3] = ["tr", "template", "html"];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
+static IMPLIED_END_TAG_ELEMENTS: [&str; 10] = [
+    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#generate-all-implied-end-tags-thoroughly
+/// IMPLIED_END_TAG_ELEMENTSに加えて、すべての暗黙の終了タグを生成するときに閉じる要素
+static THOROUGHLY_IMPLIED_END_TAG_ELEMENTS: [&str; 8] = [
+    "caption", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#special
+static SPECIAL_ELEMENTS: [&str; 83] = [
+    "address",
+    "applet",
+    "area",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "bgsound",
+    "blockquote",
+    "body",
+    "br",
+    "button",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "embed",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "iframe",
+    "img",
+    "input",
+    "keygen",
+    "li",
+    "link",
+    "listing",
+    "main",
+    "marquee",
+    "menu",
+    "meta",
+    "nav",
+    "noembed",
+    "noframes",
+    "noscript",
+    "object",
+    "ol",
+    "p",
+    "param",
+    "plaintext",
+    "pre",
+    "script",
+    "search",
+    "section",
+    "select",
+    "source",
+    "style",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "template",
+    "textarea",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+    "wbr",
+    "xmp",
+];
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+/// 公開識別子がこれらの文字列で始まる場合、互換モード(quirks mode)になる
+static QUIRKS_PUBLIC_IDENTIFIER_PREFIXES: [&str; 55] = [
+    "+//Silmaril//dtd html Pro v0r11 19970101//",
+    "-//AS//DTD HTML 3.0 asWedit + extensions//",
+    "-//AdvaSoft Ltd//DTD HTML 3.0 asWedit + extensions//",
+    "-//IETF//DTD HTML 2.0 Level 1//",
+    "-//IETF//DTD HTML 2.0 Level 2//",
+    "-//IETF//DTD HTML 2.0 Strict Level 1//",
+    "-//IETF//DTD HTML 2.0 Strict Level 2//",
+    "-//IETF//DTD HTML 2.0 Strict//",
+    "-//IETF//DTD HTML 2.0//",
+    "-//IETF//DTD HTML 2.1E//",
+    "-//IETF//DTD HTML 3.0//",
+    "-//IETF//DTD HTML 3.2 Final//",
+    "-//IETF//DTD HTML 3.2//",
+    "-//IETF//DTD HTML 3//",
+    "-//IETF//DTD HTML Level 0//",
+    "-//IETF//DTD HTML Level 1//",
+    "-//IETF//DTD HTML Level 2//",
+    "-//IETF//DTD HTML Level 3//",
+    "-//IETF//DTD HTML Strict Level 0//",
+    "-//IETF//DTD HTML Strict Level 1//",
+    "-//IETF//DTD HTML Strict Level 2//",
+    "-//IETF//DTD HTML Strict Level 3//",
+    "-//IETF//DTD HTML Strict//",
+    "-//IETF//DTD HTML//",
+    "-//Metrius//DTD Metrius Presentational//",
+    "-//Microsoft//DTD Internet Explorer 2.0 HTML Strict//",
+    "-//Microsoft//DTD Internet Explorer 2.0 HTML//",
+    "-//Microsoft//DTD Internet Explorer 2.0 Tables//",
+    "-//Microsoft//DTD Internet Explorer 3.0 HTML Strict//",
+    "-//Microsoft//DTD Internet Explorer 3.0 HTML//",
+    "-//Microsoft//DTD Internet Explorer 3.0 Tables//",
+    "-//Netscape Comm. Corp.//DTD HTML//",
+    "-//Netscape Comm. Corp.//DTD Strict HTML//",
+    "-//O'Reilly and Associates//DTD HTML 2.0//",
+    "-//O'Reilly and Associates//DTD HTML Extended 1.0//",
+    "-//O'Reilly and Associates//DTD HTML Extended Relaxed 1.0//",
+    "-//SQ//DTD HTML 2.0 HoTMetaL + extensions//",
+    "-//SoftQuad Software//DTD HoTMetaL PRO 6.0::19990601::extensions to HTML 4.0//",
+    "-//SoftQuad//DTD HoTMetaL PRO 4.0::19971010::extensions to HTML 4.0//",
+    "-//Spyglass//DTD HTML 2.0 Extended//",
+    "-//Sun Microsystems Corp.//DTD HotJava HTML//",
+    "-//Sun Microsystems Corp.//DTD HotJava Strict HTML//",
+    "-//W3C//DTD HTML 3 1995-03-24//",
+    "-//W3C//DTD HTML 3.2 Draft//",
+    "-//W3C//DTD HTML 3.2 Final//",
+    "-//W3C//DTD HTML 3.2//",
+    "-//W3C//DTD HTML 3.2S Draft//",
+    "-//W3C//DTD HTML 4.0 Frameset//",
+    "-//W3C//DTD HTML 4.0 Transitional//",
+    "-//W3C//DTD HTML Experimental 19960712//",
+    "-//W3C//DTD HTML Experimental 970421//",
+    "-//W3C//DTD W3 HTML//",
+    "-//W3O//DTD W3 HTML 3.0//",
+    "-//WebTechs//DTD Mozilla HTML 2.0//",
+    "-//WebTechs//DTD Mozilla HTML//",
+];
+
+fn starts_with_ignore_ascii_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+/// DOCTYPEトークンから文書のモードを決める
+fn document_mode_from_doctype(
+    name: &Option<String>,
+    public_identifier: &Option<String>,
+    system_identifier: &Option<String>,
+    force_quirks: bool,
+) -> DocumentMode {
+    let public_id = public_identifier.clone().unwrap_or_default();
+    let system_id = system_identifier.clone().unwrap_or_default();
+
+    if force_quirks
+        || name.as_deref() != Some("html")
+        || public_id.eq_ignore_ascii_case("-//W3O//DTD W3 HTML Strict 3.0//EN//")
+        || public_id.eq_ignore_ascii_case("-/W3C/DTD HTML 4.0 Transitional/EN")
+        || public_id.eq_ignore_ascii_case("HTML")
+        || system_id
+            .eq_ignore_ascii_case("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || QUIRKS_PUBLIC_IDENTIFIER_PREFIXES
+            .iter()
+            .any(|prefix| starts_with_ignore_ascii_case(&public_id, prefix))
+    {
+        return DocumentMode::Quirks;
     }
 
-    fn pop_current_node(&mut self, element_kind: ElementKind) -> bool {
-        let current = match self.stack_of_open_elements.last() {
-            Some(n) => n,
-            None => return false,
-        };
+    let is_html401_frameset_or_transitional =
+        starts_with_ignore_ascii_case(&public_id, "-//W3C//DTD HTML 4.01 Frameset//")
+            || starts_with_ignore_ascii_case(&public_id, "-//W3C//DTD HTML 4.01 Transitional//");
 
-        if current.borrow().element_kind() == Some(element_kind) {
-            self.stack_of_open_elements.pop();
-            return true;
-        }
+    if system_identifier.is_none() && is_html401_frameset_or_transitional {
+        return DocumentMode::Quirks;
+    }
 
-        false
+    if starts_with_ignore_ascii_case(&public_id, "-//W3C//DTD XHTML 1.0 Frameset//")
+        || starts_with_ignore_ascii_case(&public_id, "-//W3C//DTD XHTML 1.0 Transitional//")
+        || (system_identifier.is_some() && is_html401_frameset_or_transitional)
+    {
+        return DocumentMode::LimitedQuirks;
     }
+
+    DocumentMode::NoQuirks
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::renderer::dom::api::child_text_content;
+    use crate::renderer::dom::api::get_target_element_node;
+    use crate::renderer::dom::node::Document;
+    use crate::renderer::html::serializer::serialize_children;
+    use alloc::format;
     use alloc::string::ToString;
     use alloc::vec;
 
@@ -508,9 +2908,19 @@ mod tests {
         let html = "".to_string();
         let t = HtmlTokenizer::new(html);
         let window = HtmlParser::new(t).construct_tree();
-        let expected = Rc::new(RefCell::new(Node::new(NodeKind::Document)));
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
 
-        assert_eq!(expected, window.borrow().document());
+        assert_eq!(
+            NodeKind::Document(Document::new()),
+            dom.node(document).kind()
+        );
+        // 空の文書でも、入力の終わりで<html>と<head>と<body>が作られる
+        assert_eq!(
+            "<html><head></head><body></body></html>",
+            serialize_children(&dom, document)
+        );
     }
 
     #[test]
@@ -518,46 +2928,39 @@ mod tests {
         let html = "<html><head></head><body></body></html>".to_string();
         let t = HtmlTokenizer::new(html);
         let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
         let document = window.borrow().document();
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Document))),
-            document
+            NodeKind::Document(Document::new()),
+            dom.node(document).kind()
         );
 
-        let html = document
-            .borrow()
+        let html = dom
+            .node(document)
             .first_child()
             .expect("failed to get a first child of document");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "html",
-                Vec::new()
-            ))))),
-            html
+            NodeKind::Element(Element::new("html", Vec::new())),
+            dom.node(html).kind()
         );
 
-        let head = html
-            .borrow()
+        let head = dom
+            .node(html)
             .first_child()
             .expect("failed to get a first child of html");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "head",
-                Vec::new()
-            ))))),
-            head
+            NodeKind::Element(Element::new("head", Vec::new())),
+            dom.node(head).kind()
         );
 
-        let body = head
-            .borrow()
+        let body = dom
+            .node(head)
             .next_sibling()
             .expect("failed to get a next sibling of head");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "body",
-                Vec::new()
-            ))))),
-            body
+            NodeKind::Element(Element::new("body", Vec::new())),
+            dom.node(body).kind()
         );
     }
 
@@ -566,47 +2969,41 @@ mod tests {
         let html = "<html><head></head><body>text</body></html>".to_string();
         let t = HtmlTokenizer::new(html);
         let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
         let document = window.borrow().document();
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Document))),
-            document
+            NodeKind::Document(Document::new()),
+            dom.node(document).kind()
         );
 
-        let html = document
-            .borrow()
+        let html = dom
+            .node(document)
             .first_child()
             .expect("failed to get a first child of document");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "html",
-                Vec::new()
-            ))))),
-            html
+            NodeKind::Element(Element::new("html", Vec::new())),
+            dom.node(html).kind()
         );
 
-        let body = html
-            .borrow()
+        let head = dom
+            .node(html)
             .first_child()
-            .expect("failed to get a first child of documeht")
-            .borrow()
+            .expect("failed to get a first child of html");
+        let body = dom
+            .node(head)
             .next_sibling()
             .expect("failed to get a next sibling of head");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "body",
-                Vec::new()
-            ))))),
-            body
+            NodeKind::Element(Element::new("body", Vec::new())),
+            dom.node(body).kind()
         );
 
-        let text = body
-            .borrow()
+        let text = dom
+            .node(body)
             .first_child()
-            .expect("failed to get a first child of documeht");
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Text("text".to_string())))),
-            text
-        );
+            .expect("failed to get a first child of body");
+        assert_eq!(NodeKind::Text("text".to_string()), dom.node(text).kind());
     }
 
     #[test]
@@ -614,36 +3011,24 @@ mod tests {
         let html = "<html><head></head><body><p><a foo=bar>text</a></p></body></html>".to_string();
         let t = HtmlTokenizer::new(html);
         let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
         let document = window.borrow().document();
 
-        let body = document
-            .borrow()
-            .first_child()
-            .expect("failed to get a first child of document")
-            .borrow()
-            .first_child()
-            .expect("failed to get a first child of document")
-            .borrow()
-            .next_sibling()
-            .expect("failed to get a next sibling of head");
+        let body =
+            get_target_element_node(&dom, document, ElementKind::Body).expect("failed to get body");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "body",
-                Vec::new()
-            ))))),
-            body
+            NodeKind::Element(Element::new("body", Vec::new())),
+            dom.node(body).kind()
         );
 
-        let p = body
-            .borrow()
+        let p = dom
+            .node(body)
             .first_child()
             .expect("failed to get a first child of body");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "p",
-                Vec::new()
-            ))))),
-            p
+            NodeKind::Element(Element::new("p", Vec::new())),
+            dom.node(p).kind()
         );
 
         let mut attr = Attribute::new();
@@ -653,25 +3038,618 @@ mod tests {
         attr.add_char('b', false);
         attr.add_char('a', false);
         attr.add_char('r', false);
-        let a = p
-            .borrow()
+        let a = dom
+            .node(p)
             .first_child()
             .expect("failed to get a first child of p");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "a",
-                vec![attr]
-            ))))),
-            a
+            NodeKind::Element(Element::new("a", vec![attr])),
+            dom.node(a).kind()
         );
 
-        let text = a
-            .borrow()
+        let text = dom
+            .node(a)
             .first_child()
             .expect("failed to get a first child of a");
+        assert_eq!(NodeKind::Text("text".to_string()), dom.node(text).kind());
+    }
+
+    #[test]
+    fn test_unknown_elements() {
+        let html = "<html><head><meta charset=utf-8><title>t</title></head><body><div><custom-tag>x<img src=a>y</custom-tag></span></div></body></html>".to_string();
+        let t = HtmlTokenizer::new(html);
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+
+        let title = get_target_element_node(&dom, document, ElementKind::Title)
+            .expect("failed to get title");
+        assert_eq!(Some(ElementKind::Title), dom.node(title).element_kind());
+
+        let div =
+            get_target_element_node(&dom, document, ElementKind::Div).expect("failed to get div");
+        let custom = dom
+            .node(div)
+            .first_child()
+            .expect("failed to get a first child of div");
+        assert_eq!(
+            Some(ElementKind::Other("custom-tag".to_string())),
+            dom.node(custom).element_kind()
+        );
+        assert!(dom.node(custom).next_sibling().is_none());
+
+        let x = dom
+            .node(custom)
+            .first_child()
+            .expect("failed to get a first child of custom-tag");
+        assert_eq!(NodeKind::Text("x".to_string()), dom.node(x).kind());
+        // <img>は空要素なので、後続のテキストは兄弟ノードになる
+        let img = dom.node(x).next_sibling().expect("failed to get img");
+        assert_eq!(Some(ElementKind::Img), dom.node(img).element_kind());
+        assert!(dom.node(img).first_child().is_none());
+        let y = dom.node(img).next_sibling().expect("failed to get a text");
+        assert_eq!(NodeKind::Text("y".to_string()), dom.node(y).kind());
+    }
+
+    /// <body>の子孫を、タグ名とテキストだけの簡易的な文字列に変換する
+    fn window_body_to_string(window: &Rc<RefCell<Window>>) -> String {
+        fn to_string(dom: &Dom, node: NodeId, out: &mut String) {
+            for child in dom.children(node) {
+                match dom.node(child).kind {
+                    NodeKind::Element(ref e) => {
+                        out.push_str(&format!("<{}>", e.tag_name()));
+                        to_string(dom, child, out);
+                        out.push_str(&format!("</{}>", e.tag_name()));
+                    }
+                    NodeKind::Text(ref t) => out.push_str(t),
+                    _ => {}
+                }
+            }
+        }
+
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+        let mut out = String::new();
+        if let Some(body) = get_target_element_node(&dom, document, ElementKind::Body) {
+            to_string(&dom, body, &mut out);
+        }
+        out
+    }
+
+    fn body_to_string(html: &str) -> String {
+        let t = HtmlTokenizer::new(html.to_string());
+        let window = HtmlParser::new(t).construct_tree();
+        window_body_to_string(&window)
+    }
+
+    #[test]
+    fn test_implied_end_tags() {
+        assert_eq!(
+            "<p>a</p><div>b</div><ul><li>1</li><li>2</li></ul><dl><dt>t</dt><dd>d</dd></dl><h1>x</h1><h2>y</h2>",
+            body_to_string("<p>a<div>b</div><ul><li>1<li>2</ul><dl><dt>t<dd>d</dl><h1>x<h2>y</h2>")
+        );
+        assert_eq!("<p>a</p><p>b</p>", body_to_string("<p>a<p>b"));
+        // 対応する開始タグのない</p>は空の<p>として扱う
+        assert_eq!("<p></p>", body_to_string("<body></p>"));
+        assert_eq!("<div>a</div>b", body_to_string("<div>a</span></div>b"));
+    }
+
+    fn document_to_string(html: &str) -> String {
+        let t = HtmlTokenizer::new(html.to_string());
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+        serialize_children(&dom, document)
+    }
+
+    #[test]
+    fn test_implied_head_and_body_at_eof() {
+        // 入力の終わりでも、省略された<head>と<body>が作られる
+        assert_eq!(
+            "<!DOCTYPE html><html><head><title>x</title></head><body></body></html>",
+            document_to_string("<!DOCTYPE html><title>x</title>")
+        );
+        assert_eq!(
+            "<html><head></head><body></body></html>",
+            document_to_string("</p>")
+        );
+        assert_eq!(
+            "<html><head></head><body></body></html>",
+            document_to_string("<html><head>")
+        );
+    }
+
+    #[test]
+    fn test_duplicate_html_and_body() {
+        // 2つ目の開始タグの属性のうち、まだない属性だけが追加される
+        assert_eq!(
+            "<html lang=\"ja\" dir=\"ltr\"><head></head><body a=\"1\" b=\"2\"></body></html>",
+            document_to_string("<html lang=ja><body a=1><body b=2 a=3><html dir=ltr lang=en>")
+        );
+        // <template>の中では無視される
+        assert_eq!(
+            "<html><head></head><body a=\"1\"><template></template></body></html>",
+            document_to_string("<body a=1><template><body b=2></template>")
+        );
+    }
+
+    #[test]
+    fn test_adoption_agency() {
+        assert_eq!(
+            "<p>1<b>2<i>3</i></b><i>4</i>5</p>",
+            body_to_string("<p>1<b>2<i>3</b>4</i>5</p>")
+        );
+        assert_eq!(
+            "<b>1</b><p><b>2</b>3</p>",
+            body_to_string("<b>1<p>2</b>3</p>")
+        );
+        assert_eq!("<a>1</a><a>2</a>", body_to_string("<a href=x>1<a href=y>2"));
+    }
+
+    #[test]
+    fn test_reconstruct_active_formatting_elements() {
+        assert_eq!(
+            "<p><b>x</b></p><p><b>y</b></p>",
+            body_to_string("<p><b>x</p><p>y")
+        );
+        assert_eq!(
+            "<em></em><p><em>a</em></p><p>b</p>",
+            body_to_string("<em><p>a</em></p><p>b")
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            "<table><tbody><tr><td>1</td><td>2</td></tr></tbody></table>",
+            body_to_string("<table><tr><td>1<td>2</table>")
+        );
+        assert_eq!(
+            "<table><caption>c</caption><colgroup><col></col></colgroup><thead><tr><th>h</th></tr></thead><tbody><tr><td>d</td></tr></tbody></table>",
+            body_to_string("<table><caption>c<col><thead><tr><th>h<tbody><tr><td>d</table>")
+        );
+        assert_eq!(
+            "<table><tbody><tr><td>1</td></tr></tbody></table>",
+            body_to_string("<table>\n  <tr>\n    <td>1</td>\n  </tr>\n</table>")
+        );
+        // キャプションの中の<table>はキャプションとテーブルを閉じ、次のテーブルになる
+        assert_eq!(
+            "<table><caption>c</caption></table><table><tbody><tr><td>d</td></tr></tbody></table>",
+            body_to_string("<table><caption>c<table><tr><td>d</table>")
+        );
+        // 整形要素はセルの外に持ち越されない
+        assert_eq!(
+            "<table><tbody><tr><td><b>x</b></td></tr></tbody></table>y",
+            body_to_string("<table><tr><td><b>x</td></tr></table>y")
+        );
+    }
+
+    #[test]
+    fn test_foster_parenting() {
+        assert_eq!(
+            "ac<table><tbody><tr><td>b</td></tr></tbody></table>",
+            body_to_string("<table>a<tr><td>b</td></tr>c</table>")
+        );
+        assert_eq!(
+            "<div>x</div><table><tbody><tr><td>y</td></tr></tbody></table>",
+            body_to_string("<table><div>x</div><tr><td>y</table>")
+        );
+    }
+
+    #[test]
+    fn test_text_elements() {
+        let html = "<html><head><style>p > a { color: red; }</style><title>a<b>&amp;</title></head><body><textarea>\n<p>x</p></textarea><xmp><b></xmp></body></html>";
+        let t = HtmlTokenizer::new(html.to_string());
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+
+        assert_eq!(
+            "p > a { color: red; }".to_string(),
+            child_text_content(
+                &dom,
+                get_target_element_node(&dom, document, ElementKind::Style)
+                    .expect("failed to get style")
+            )
+        );
+        let title = get_target_element_node(&dom, document, ElementKind::Title)
+            .expect("failed to get title");
+        let text = dom
+            .node(title)
+            .first_child()
+            .expect("failed to get a first child of title");
+        assert_eq!(NodeKind::Text("a<b>&".to_string()), dom.node(text).kind());
+
+        assert_eq!(
+            "<textarea><p>x</p></textarea><xmp><b></xmp>",
+            body_to_string(html)
+        );
+    }
+
+    fn document_mode(html: &str) -> DocumentMode {
+        let t = HtmlTokenizer::new(html.to_string());
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let document = window.borrow().document();
+        let mode = dom
+            .borrow()
+            .node(document)
+            .get_document()
+            .expect("failed to get a document")
+            .mode();
+        mode
+    }
+
+    #[test]
+    fn test_doctype_and_comments() {
+        let html = "<!DOCTYPE html><!--a--><html><body><!--b--></body></html><!--c-->".to_string();
+        let t = HtmlTokenizer::new(html);
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+
+        let doctype = dom
+            .node(document)
+            .first_child()
+            .expect("failed to get a first child of document");
+        match dom.node(doctype).kind {
+            NodeKind::DocumentType(ref d) => assert_eq!("html", d.name()),
+            ref k => panic!("expected a DocumentType node but got {:?}", k),
+        }
+
+        let comment = dom
+            .node(doctype)
+            .next_sibling()
+            .expect("failed to get a next sibling of doctype");
+        assert_eq!(NodeKind::Comment("a".to_string()), dom.node(comment).kind());
+
+        let html = dom
+            .node(comment)
+            .next_sibling()
+            .expect("failed to get a next sibling of comment");
+        assert_eq!(
+            NodeKind::Element(Element::new("html", Vec::new())),
+            dom.node(html).kind()
+        );
+
+        let last = dom
+            .node(document)
+            .last_child()
+            .expect("failed to get a last child of document");
+        assert_eq!(NodeKind::Comment("c".to_string()), dom.node(last).kind());
+
+        let body = dom
+            .node(html)
+            .last_child()
+            .expect("failed to get a last child of html");
+        let comment_in_body = dom
+            .node(body)
+            .first_child()
+            .expect("failed to get a first child of body");
+        assert_eq!(
+            NodeKind::Comment("b".to_string()),
+            dom.node(comment_in_body).kind()
+        );
+    }
+
+    #[test]
+    fn test_document_mode() {
+        assert_eq!(DocumentMode::NoQuirks, document_mode("<!DOCTYPE html><p>"));
+        assert_eq!(DocumentMode::Quirks, document_mode("<p>"));
+        assert_eq!(
+            DocumentMode::Quirks,
+            document_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\">")
+        );
+        assert_eq!(
+            DocumentMode::LimitedQuirks,
+            document_mode(
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \"http://www.w3.org/TR/html4/loose.dtd\">"
+            )
+        );
+        assert_eq!(
+            DocumentMode::NoQuirks,
+            document_mode(
+                "<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \"http://www.w3.org/TR/html4/strict.dtd\">"
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let html = "<!DOCTYPE html><body><div><span></div>\n</p class=x><table>a</table></body>";
+        let t = HtmlTokenizer::new(html.to_string());
+        let mut parser = HtmlParser::new(t);
+        parser.construct_tree();
+
+        let errors: Vec<(ParseErrorKind, usize, usize)> = parser
+            .errors()
+            .iter()
+            .map(|e| (e.kind(), e.position().line(), e.position().column()))
+            .collect();
+        assert_eq!(
+            vec![
+                (ParseErrorKind::UnexpectedEndTag("div".to_string()), 1, 38),
+                (ParseErrorKind::EndTagWithAttributes, 2, 12),
+                (ParseErrorKind::UnexpectedEndTag("p".to_string()), 2, 12),
+                (ParseErrorKind::UnexpectedCharacter('a'), 2, 28),
+            ],
+            errors
+        );
+        assert_eq!(
+            "<div><span></span></div><p></p>a<table></table>",
+            body_to_string(html)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_at_eof() {
+        let errors = |html: &str| -> Vec<(ParseErrorKind, usize, usize)> {
+            let mut parser = HtmlParser::new(HtmlTokenizer::new(html.to_string()));
+            parser.construct_tree();
+            parser
+                .errors()
+                .iter()
+                .map(|e| (e.kind(), e.position().line(), e.position().column()))
+                .collect()
+        };
+
+        // 入力の終わりで閉じられていない要素はパースエラーになる
+        assert_eq!(
+            vec![(ParseErrorKind::UnexpectedEof, 1, 21)],
+            errors("<!DOCTYPE html><div>")
+        );
+        // 終了タグを省略できる要素は、閉じられていなくてもよい
+        assert!(errors("<!DOCTYPE html><p>a<li>b").is_empty());
+    }
+
+    #[test]
+    fn test_feed_chunks() {
+        let html = "<!DOCTYPE html><html><head><title>a&amp;b</title><style>p{}</style></head><body><p>あ<b>1<i>2</b>3</i><table><tr><td>x</table><!-- c --></body></html>";
+        let expected = body_to_string(html);
+
+        for chunk_size in 1..=html.len() {
+            let mut parser = HtmlParser::new(HtmlTokenizer::new_streaming());
+            for chunk in html.as_bytes().chunks(chunk_size) {
+                parser.feed(chunk);
+            }
+            let window = parser.finish();
+            assert_eq!(
+                expected,
+                window_body_to_string(&window),
+                "chunk size: {}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn test_feed_partial_tree() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new_streaming());
+        let window = parser.feed(b"<body><p>a</p><p>b");
+        // 入力の途中でも、それまでに与えた分のツリーを参照できる
+        assert_eq!("<p>a</p><p>b</p>", window_body_to_string(&window));
+        parser.feed(b"c</p><div");
+        assert_eq!("<p>a</p><p>bc</p>", window_body_to_string(&window));
+        parser.feed(b">d");
+        let window = parser.finish();
+        assert_eq!(
+            "<p>a</p><p>bc</p><div>d</div>",
+            window_body_to_string(&window)
+        );
+    }
+
+    #[test]
+    fn test_feed_waits_for_finish() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new_streaming());
+        parser.feed(b"<!DOCTYPE html><div>a");
+        // 入力が途中で止まっても、入力の終わりとしては扱わない
+        assert!(parser.errors().is_empty());
+        parser.feed(b"b");
+        assert!(parser.errors().is_empty());
+        let window = parser.finish();
+        assert_eq!("<div>ab</div>", window_body_to_string(&window));
+        assert_eq!(
+            vec![ParseErrorKind::UnexpectedEof],
+            parser.errors().iter().map(|e| e.kind()).collect::<Vec<_>>()
+        );
+        // EOFは一度だけ処理される
+        parser.finish();
+        assert_eq!(1, parser.errors().len());
+    }
+
+    #[test]
+    fn test_document_write() {
+        let mut parser = HtmlParser::new(HtmlTokenizer::new_streaming());
+        parser.set_pause_on_script(true);
+        parser.feed(b"<body><script>document.write()</script><p>b</p>");
+        let script = parser
+            .pending_script()
+            .expect("failed to pause at the script");
+        assert_eq!(
+            Some(ElementKind::Script),
+            RefCell::borrow(&parser.dom).node(script).element_kind()
+        );
+
+        // スクリプトの実行中は、後から届いた入力も処理しない
+        parser.feed(b"<p>c</p>");
+        assert!(parser.pending_script().is_some());
+
+        assert!(parser.document_write("<p>a"));
+        assert!(parser.document_write("</p>"));
+        parser.resume();
+        // 挿入位置はスクリプトの実行後に解除される
+        assert!(!parser.document_write("<p>x</p>"));
+        let window = parser.finish();
+        assert_eq!(
+            "<script>document.write()</script><p>a</p><p>b</p><p>c</p>",
+            window_body_to_string(&window)
+        );
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let tag_names = |context: &str, html: &str| -> Vec<String> {
+            let dom = Rc::new(RefCell::new(Dom::new()));
+            let context = dom
+                .borrow_mut()
+                .create_node(NodeKind::Element(Element::new(context, Vec::new())));
+            let nodes = HtmlParser::parse_fragment(&dom, context, html.to_string());
+            let dom = dom.borrow();
+            nodes
+                .iter()
+                .map(|n| match dom.node(*n).kind() {
+                    NodeKind::Element(e) => e.tag_name(),
+                    NodeKind::Text(s) => s,
+                    _ => "".to_string(),
+                })
+                .collect()
+        };
+
+        assert_eq!(vec!["p", "p"], tag_names("div", "<p>a<p>b"));
+        // <tr>の中の<td>はInRowモードで処理される
+        assert_eq!(vec!["td", "td"], tag_names("tr", "<td>a<td>b"));
+        // <html>の中身としてパースすると、<head>と<body>が作られる
+        assert_eq!(vec!["head", "body"], tag_names("html", "<title>a</title>b"));
+        // <title>の中では終了タグもテキストになる
+        assert_eq!(vec!["<b>a</title>"], tag_names("title", "<b>a</title>"));
+    }
+
+    #[test]
+    fn test_foreign_content() {
+        // SVGのタグ名は本来の大文字と小文字に戻される
+        assert_eq!(
+            "<svg><linearGradient></linearGradient><foreignObject><p>a</p></foreignObject></svg>",
+            body_to_string("<svg><lineargradient/><foreignobject><p>a</p></foreignobject></svg>")
+        );
+        // <p>はSVGを抜けてHTMLとして処理される
+        assert_eq!(
+            "<svg><g></g></svg><p>a</p>",
+            body_to_string("<svg><g><p>a</p></g></svg>")
+        );
+        // 外部コンテンツの中ではCDATAセクションがテキストになる
+        assert_eq!(
+            "<math><mi>x</mi><mtext><b>y</b></mtext></math><svg><text><b>&</text></svg>",
+            body_to_string(
+                "<math><mi>x</mi><mtext><b>y</b></mtext></math><svg><text><![CDATA[<b>&]]></text></svg>"
+            )
+        );
+
+        let t = HtmlTokenizer::new(
+            "<svg viewbox='0 0 1 1'><rect/></svg><math definitionurl=a></math>".to_string(),
+        );
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+        let body =
+            get_target_element_node(&dom, document, ElementKind::Body).expect("failed to get body");
+        let svg = dom.node(body).first_child().expect("failed to get svg");
+        let svg = dom
+            .node(svg)
+            .get_element()
+            .expect("failed to get svg element");
+        assert_eq!(Namespace::Svg, svg.namespace());
+        assert_eq!(Some("0 0 1 1".to_string()), svg.get_attribute("viewBox"));
+        let math = dom.node(body).last_child().expect("failed to get math");
+        let math = dom
+            .node(math)
+            .get_element()
+            .expect("failed to get math element");
+        assert_eq!(Namespace::MathMl, math.namespace());
+        assert_eq!(Some("a".to_string()), math.get_attribute("definitionURL"));
+    }
+
+    #[test]
+    fn test_template() {
+        // <template>の中身は子ノードではなく、DocumentFragmentに入る
+        assert_eq!(
+            "<template></template><div>b</div>",
+            body_to_string("<body><template><p>a</p></template><div>b</div>")
+        );
+
+        let template_contents = |html: &str| -> String {
+            let t = HtmlTokenizer::new(html.to_string());
+            let window = HtmlParser::new(t).construct_tree();
+            let dom = window.borrow().dom();
+            let dom = dom.borrow();
+            let document = window.borrow().document();
+            let template = get_target_element_node(&dom, document, ElementKind::Template)
+                .expect("failed to get template");
+            let contents = dom
+                .node(template)
+                .template_contents()
+                .expect("failed to get template contents");
+            match dom.node(contents).kind {
+                NodeKind::DocumentFragment(ref f) => {
+                    let owner = f.owner_document().expect("failed to get owner document");
+                    assert_ne!(owner, document);
+                }
+                _ => panic!("template contents should be a DocumentFragment"),
+            }
+            serialize_children(&dom, template)
+        };
+
+        assert_eq!(
+            "<p>a</p>",
+            template_contents("<template><p>a</p></template>")
+        );
+        // テーブルの中でなくても、<tr>や<td>をそのまま持てる
+        assert_eq!(
+            "<tr><td>a</td></tr>",
+            template_contents("<template><tr><td>a</td></tr></template>")
+        );
+        assert_eq!(
+            "<style>p {}</style>",
+            template_contents("<head><template><style>p {}</style></template></head>")
+        );
+        // 閉じられていない<template>は入力の終わりで閉じられる
+        assert_eq!("<div>a</div>", template_contents("<template><div>a"));
+    }
+
+    #[test]
+    fn test_template_at_eof() {
+        // <head>の中で閉じられていない<template>は、入力の終わりで閉じられ、<body>が作られる
+        assert_eq!(
+            "<!DOCTYPE html><html><head><template>x</template></head><body></body></html>",
+            document_to_string("<!DOCTYPE html><template>x")
+        );
+
+        let t = HtmlTokenizer::new("<!DOCTYPE html><body><b><template><i>x".to_string());
+        let mut parser = HtmlParser::new(t);
+        let window = parser.construct_tree();
+        // <template>の中で入力が終わったことと、<b>が閉じられていないことがパースエラーになる
+        assert_eq!(
+            vec![ParseErrorKind::UnexpectedEof, ParseErrorKind::UnexpectedEof],
+            parser.errors().iter().map(|e| e.kind()).collect::<Vec<_>>()
+        );
+        // <template>までの要素をスタックから取り除き、挿入モードを戻す
+        assert_eq!(
+            vec!["html", "body", "b"],
+            parser
+                .stack_of_open_elements
+                .iter()
+                .map(|n| parser.tag_name_of(*n).unwrap_or_default())
+                .collect::<Vec<_>>()
+        );
+        assert!(parser.stack_of_template_insertion_modes.is_empty());
+        assert_eq!(InsertionMode::InBody, parser.mode);
+        // アクティブな整形要素のリストは、<template>のマーカーまで取り除かれる
+        assert_eq!(1, parser.active_formatting_elements.len());
+        assert!(matches!(
+            parser.active_formatting_elements[0],
+            ActiveFormattingElement::Element(b) if parser.tag_name_of(b).as_deref() == Some("b")
+        ));
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let body = parser.stack_of_open_elements[1];
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Text("text".to_string())))),
-            text
+            "<b><template><i>x</i></template></b>",
+            serialize_children(&dom, body)
         );
     }
 }
@@ -0,0 +1,204 @@
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::Namespace;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use crate::renderer::html::parser::SCRIPTING_ENABLED;
+use alloc::string::String;
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-serialisation-algorithm
+/// nodeの子孫をHTMLの文字列に変換する。innerHTMLの取得に使う
+pub fn serialize_children(dom: &Dom, node: NodeId) -> String {
+    // <template>は子ノードの代わりに、その中身を出力する
+    let node = dom.node(node).template_contents().unwrap_or(node);
+
+    let mut result = String::new();
+    for child in dom.children(node) {
+        serialize_node_to(dom, child, &mut result);
+    }
+    result
+}
+
+/// node自身とその子孫をHTMLの文字列に変換する。outerHTMLの取得に使う
+pub fn serialize_node(dom: &Dom, node: NodeId) -> String {
+    let mut result = String::new();
+    serialize_node_to(dom, node, &mut result);
+    result
+}
+
+fn serialize_node_to(dom: &Dom, node: NodeId, result: &mut String) {
+    match dom.node(node).kind {
+        NodeKind::Document(_) | NodeKind::DocumentFragment(_) => {
+            result.push_str(&serialize_children(dom, node))
+        }
+        NodeKind::DocumentType(ref doctype) => {
+            result.push_str("<!DOCTYPE ");
+            result.push_str(&doctype.name());
+            result.push('>');
+        }
+        NodeKind::Element(ref e) => {
+            let tag_name = e.tag_name();
+            result.push('<');
+            result.push_str(&tag_name);
+            for attr in e.attributes() {
+                result.push(' ');
+                result.push_str(&attr.name());
+                result.push_str("=\"");
+                escape_string(&attr.value(), true, result);
+                result.push('"');
+            }
+            result.push('>');
+
+            // 空要素は子を持たず、終了タグも出力しない
+            if e.is_void_element() {
+                return;
+            }
+
+            // パーサは<pre>、<textarea>、<listing>の開始タグ直後の改行を1つ無視するので、
+            // 中身が改行で始まる場合は改行を1つ補う
+            if e.namespace() == Namespace::Html
+                && matches!(tag_name.as_str(), "pre" | "textarea" | "listing")
+                && starts_with_line_feed(dom, node)
+            {
+                result.push('\n');
+            }
+
+            result.push_str(&serialize_children(dom, node));
+            result.push_str("</");
+            result.push_str(&tag_name);
+            result.push('>');
+        }
+        NodeKind::Text(ref s) => {
+            if is_raw_text_parent(dom, node) {
+                result.push_str(s);
+            } else {
+                escape_string(s, false, result);
+            }
+        }
+        NodeKind::Comment(ref s) => {
+            result.push_str("<!--");
+            result.push_str(s);
+            result.push_str("-->");
+        }
+    }
+}
+
+/// テキストノードの親が、中身をエスケープせずに出力する要素かどうか
+fn is_raw_text_parent(dom: &Dom, node: NodeId) -> bool {
+    let parent = match dom.node(node).parent() {
+        Some(p) => p,
+        None => return false,
+    };
+    let tag_name = match dom.node(parent).get_element() {
+        Some(e) if e.namespace() == Namespace::Html => e.tag_name(),
+        _ => return false,
+    };
+    match tag_name.as_str() {
+        "style" | "script" | "xmp" | "iframe" | "noembed" | "noframes" | "plaintext" => true,
+        // パーサと同じく、スクリプトが有効な場合は<noscript>の中身をエスケープしない
+        "noscript" => SCRIPTING_ENABLED,
+        _ => false,
+    }
+}
+
+/// nodeの最初の子が、改行で始まるテキストノードかどうか
+fn starts_with_line_feed(dom: &Dom, node: NodeId) -> bool {
+    let first_child = match dom.node(node).first_child() {
+        Some(c) => c,
+        None => return false,
+    };
+    match dom.node(first_child).kind {
+        NodeKind::Text(ref s) => s.starts_with('\n'),
+        _ => false,
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape_string(s: &str, attribute_mode: bool, result: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '\u{A0}' => result.push_str("&nbsp;"),
+            '"' if attribute_mode => result.push_str("&quot;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            _ => result.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    fn body_inner_html(html: &str) -> String {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().dom();
+        let dom = dom.borrow();
+        let document = window.borrow().document();
+        let html = dom.node(document).last_child().expect("failed to get html");
+        let body = dom.node(html).last_child().expect("failed to get body");
+        serialize_children(&dom, body)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let html = "<p class=\"a b\" id=\"x\">text<br><img src=\"a.png\"></p><!--comment--><div><span>a</span>b</div>";
+        assert_eq!(html.to_string(), body_inner_html(html));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            "<p title=\"&quot;a&quot; &amp; &lt;b&gt;\">&lt;a&gt; &amp;&nbsp;\"</p>".to_string(),
+            body_inner_html("<p title='\"a\" &amp; <b>'>&lt;a&gt; &amp;&nbsp;\"</p>")
+        );
+    }
+
+    #[test]
+    fn test_raw_text() {
+        assert_eq!(
+            "<p>a</p><script>if (a < b && c) {}</script><textarea>&lt;b&gt;</textarea>".to_string(),
+            body_inner_html("<p>a</p><script>if (a < b && c) {}</script><textarea><b></textarea>")
+        );
+    }
+
+    #[test]
+    fn test_noscript_round_trip() {
+        // スクリプトが有効なので、<noscript>の中身はパーサでもシリアライザでも生のテキストになる
+        let html = "<div><noscript><p>a &amp; b</p></noscript></div>";
+        assert_eq!(html.to_string(), body_inner_html(html));
+        assert_eq!(html.to_string(), body_inner_html(&body_inner_html(html)));
+    }
+
+    #[test]
+    fn test_leading_line_feed_round_trip() {
+        // 先頭の改行は1つ無視されるので、中身が改行で始まる場合は改行を補って出力する
+        let html =
+            "<pre>\n\na</pre><textarea>\n\nb</textarea><listing>\n\n</listing><pre>c\n</pre>";
+        assert_eq!(html.to_string(), body_inner_html(html));
+        assert_eq!(html.to_string(), body_inner_html(&body_inner_html(html)));
+    }
+
+    #[test]
+    fn test_serialize_node() {
+        let window = HtmlParser::new(HtmlTokenizer::new(
+            "<!doctype html><html><head></head><body><p>a</p></body></html>".to_string(),
+        ))
+        .construct_tree();
+        let dom = window.borrow().dom();
+        let document = window.borrow().document();
+        assert_eq!(
+            "<!DOCTYPE html><html><head></head><body><p>a</p></body></html>".to_string(),
+            serialize_node(&dom.borrow(), document)
+        );
+    }
+
+    #[test]
+    fn test_foreign_content() {
+        let html = "<svg viewBox=\"0 0 1 1\"><clipPath></clipPath><style>a &lt; b</style></svg>";
+        assert_eq!(html.to_string(), body_inner_html(html));
+    }
+}
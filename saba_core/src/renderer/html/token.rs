@@ -1,7 +1,17 @@
+use alloc::collections::VecDeque;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 
+use crate::encoding::Decoder;
+use crate::encoding::Encoding;
+
 use super::attribute::Attribute;
+use super::entities::MAX_NAMED_CHARACTER_REFERENCE_LENGTH;
+use super::entities::NAMED_CHARACTER_REFERENCES;
+use super::parse_error::ParseError;
+use super::parse_error::ParseErrorKind;
+use super::parse_error::Position;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HtmlToken {
@@ -17,6 +27,15 @@ pub enum HtmlToken {
     },
     // 文字列
     Char(char),
+    // コメント
+    Comment(String),
+    // DOCTYPE
+    Doctype {
+        name: Option<String>,
+        public_identifier: Option<String>,
+        system_identifier: Option<String>,
+        force_quirks: bool,
+    },
     // ファイルの終了
     Eof,
 }
@@ -49,6 +68,22 @@ pub enum State {
     AfterAttrbuteValueQuoted,
     /// https://html.spec.whatwg.org/multipage/parsing.html#self-closing-start-tag-state
     SelfClosingStartTag,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state
+    Rcdata,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-less-than-sign-state
+    RcdataLessThanSign,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-open-state
+    RcdataEndTagOpen,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-name-state
+    RcdataEndTagName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-state
+    Rawtext,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-less-than-sign-state
+    RawtextLessThanSign,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-open-state
+    RawtextEndTagOpen,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-name-state
+    RawtextEndTagName,
     /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-state
     ScriptData,
     /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-less-than-sign-state
@@ -57,8 +92,84 @@ pub enum State {
     ScriptDataEndTagOpen,
     /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-less-end-tag-name-state
     ScriptDataEndTagName,
-    /// https://html.spec.whatwg.org/multipage/parsing.html#temporary-buffer
-    TemporaryBuffer,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+    CharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+    NamedCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#ambiguous-ampersand-state
+    AmbiguousAmpersand,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-state
+    NumericCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#hexadecimal-character-reference-start-state
+    HexadecimalCharacterReferenceStart,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#decimal-character-reference-start-state
+    DecimalCharacterReferenceStart,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#hexadecimal-character-reference-state
+    HexadecimalCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#decimal-character-reference-state
+    DecimalCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#markup-declaration-open-state
+    MarkupDeclarationOpen,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#bogus-comment-state
+    BogusComment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-start-state
+    CommentStart,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-start-dash-state
+    CommentStartDash,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-state
+    Comment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-state
+    CommentLessThanSign,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-bang-state
+    CommentLessThanSignBang,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-bang-dash-state
+    CommentLessThanSignBangDash,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-bang-dash-dash-state
+    CommentLessThanSignBangDashDash,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-end-dash-state
+    CommentEndDash,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-end-state
+    CommentEnd,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-end-bang-state
+    CommentEndBang,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-state
+    Doctype,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-name-state
+    BeforeDoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-name-state
+    DoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-name-state
+    AfterDoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-keyword-state
+    AfterDoctypePublicKeyword,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-public-identifier-state
+    BeforeDoctypePublicIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-public-identifier-(double-quoted)-state
+    DoctypePublicIdentifierDoubleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-public-identifier-(single-quoted)-state
+    DoctypePublicIdentifierSingleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-identifier-state
+    AfterDoctypePublicIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#between-doctype-public-and-system-identifiers-state
+    BetweenDoctypePublicAndSystemIdentifiers,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-keyword-state
+    AfterDoctypeSystemKeyword,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-system-identifier-state
+    BeforeDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-system-identifier-(double-quoted)-state
+    DoctypeSystemIdentifierDoubleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-system-identifier-(single-quoted)-state
+    DoctypeSystemIdentifierSingleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-identifier-state
+    AfterDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#bogus-doctype-state
+    BogusDoctype,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-state
+    CdataSection,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-bracket-state
+    CdataSectionBracket,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-end-state
+    CdataSectionEnd,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -69,36 +180,290 @@ pub struct HtmlTokenizer {
     latest_token: Option<HtmlToken>,
     input: Vec<char>,
     buf: String,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#return-state
+    return_state: State,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-code
+    character_reference_code: u32,
+    /// 1回の状態遷移で複数の文字トークンが生成されたときに、まだ返していないトークン
+    pending_tokens: VecDeque<HtmlToken>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adjusted-current-node
+    /// 調整された現在のノードがHTML名前空間の要素ではないかどうか
+    in_foreign_content: bool,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    /// 最後に出力された開始タグの名前
+    last_start_tag_name: Option<String>,
+    /// 現在の終了タグに属性が書かれていたかどうか。終了タグの属性は捨てられる
+    end_tag_has_attributes: bool,
+    /// 入力がすべて与えられたかどうか。falseの場合、入力の終わりはEOFではなく、次のfeedを待つ
+    finished: bool,
+    /// feedで与えられたバイト列をデコードするデコーダ
+    decoder: Decoder,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insertion-point
+    insertion_point: Option<usize>,
+    /// 各行の先頭の文字の位置。パースエラーの行と列を求めるために使う
+    line_starts: Vec<usize>,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    errors: Vec<ParseError>,
 }
 
 impl HtmlTokenizer {
     pub fn new(html: String) -> Self {
+        let mut tokenizer = Self::new_streaming();
+        tokenizer.append_input(html.chars());
+        tokenizer.finished = true;
+        tokenizer
+    }
+
+    /// 入力を少しずつ受け取るトークナイザを作る。入力はUTF-8のバイト列としてfeedで与え、最後にfinishを呼ぶ
+    pub fn new_streaming() -> Self {
+        Self::new_streaming_with_encoding(Encoding::Utf8)
+    }
+
+    /// feedで与えるバイト列のエンコーディングを指定して、入力を少しずつ受け取るトークナイザを作る
+    pub fn new_streaming_with_encoding(encoding: Encoding) -> Self {
         Self {
             state: State::Data,
             pos: 0,
             reconsume: false,
             latest_token: None,
-            input: html.chars().collect(),
+            input: Vec::new(),
             buf: String::new(),
+            return_state: State::Data,
+            character_reference_code: 0,
+            pending_tokens: VecDeque::new(),
+            in_foreign_content: false,
+            last_start_tag_name: None,
+            end_tag_has_attributes: false,
+            finished: false,
+            decoder: Decoder::new(encoding),
+            insertion_point: None,
+            line_starts: vec![0],
+            errors: Vec::new(),
+        }
+    }
+
+    /// バイト列で与えられた入力の続きを与える。バイト列はトークナイザのエンコーディングでデコードされ、
+    /// 文字の途中で区切られたバイト列は、次のfeedまたはfinishまで保持する
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let s = self.decoder.decode(bytes, false);
+        self.append_input(s.chars());
+    }
+
+    /// 入力がすべて与えられたことを知らせる。これ以降、入力の終わりはEOFとして扱われる
+    pub fn finish(&mut self) {
+        // 文字として完成していない末尾のバイト列はU+FFFDになる
+        let s = self.decoder.decode(&[], true);
+        self.append_input(s.chars());
+        self.finished = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn append_input<I: Iterator<Item = char>>(&mut self, chars: I) {
+        for c in chars {
+            self.input.push(c);
+            if c == '\n' {
+                self.line_starts.push(self.input.len());
+            }
         }
     }
 
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insertion-point
+    /// 次に消費される文字の直前に挿入位置を設定する
+    pub fn set_insertion_point(&mut self) {
+        let next = match self.reconsume {
+            true => self.pos - 1,
+            false => self.pos,
+        };
+        self.insertion_point = Some(next);
+    }
+
+    pub fn clear_insertion_point(&mut self) {
+        self.insertion_point = None;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#document-write-steps
+    /// 挿入位置に文字列を挿入し、挿入位置を挿入した文字列の直後に進める。
+    /// 挿入位置が設定されていない場合は何もせずにfalseを返す
+    pub fn write(&mut self, s: &str) -> bool {
+        let insertion_point = match self.insertion_point {
+            Some(i) => i,
+            None => return false,
+        };
+
+        let chars: Vec<char> = s.chars().collect();
+        let len = chars.len();
+        self.input.splice(insertion_point..insertion_point, chars);
+        self.insertion_point = Some(insertion_point + len);
+
+        self.line_starts = vec![0];
+        for (i, c) in self.input.iter().enumerate() {
+            if *c == '\n' {
+                self.line_starts.push(i + 1);
+            }
+        }
+        true
+    }
+
+    /// 現在の文字からn文字分の入力がまだ届いていない場合にtrueを返す。
+    /// 先読みが必要な状態では、入力が揃うまで現在の文字を再消費する形で一時停止する
+    fn needs_more_input(&self, n: usize) -> bool {
+        !self.finished && self.pos - 1 + n > self.input.len()
+    }
+
+    /// これまでにトークナイズの段階で発生したパースエラーを返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.errors.clone()
+    }
+
+    /// 最後に消費した文字の位置を返す。入力の終わりに達している場合は、最後の文字の次の位置を返す
+    pub fn current_position(&self) -> Position {
+        let index = core::cmp::min(self.pos.saturating_sub(1), self.input.len());
+        let line = match self.line_starts.binary_search(&index) {
+            Ok(l) => l,
+            Err(l) => l - 1,
+        };
+        Position::new(line + 1, index - self.line_starts[line] + 1)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    fn parse_error(&mut self, kind: ParseErrorKind) {
+        let position = self.current_position();
+        self.errors.push(ParseError::new(kind, position));
+    }
+
     fn is_eof(&self) -> bool {
         self.pos > self.input.len()
     }
 
     fn consume_next_input(&mut self) -> char {
-        let c = self.input[self.pos];
+        // 入力の終わりに達したときもposを進め、is_eofで判定できるようにする
+        let c = self.input.get(self.pos).copied().unwrap_or('\0');
         self.pos += 1;
         c
     }
 
     fn reconsume_input(&mut self) -> char {
         self.reconsume = false;
-        self.input[self.pos - 1]
+        self.input.get(self.pos - 1).copied().unwrap_or('\0')
+    }
+
+    /// 次に消費される文字を消費せずに返す
+    fn peek_next_input(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#charref-in-attribute
+    fn is_consumed_as_part_of_attribute(&self) -> bool {
+        matches!(
+            self.return_state,
+            State::AttributeValueDoubleQuoted
+                | State::AttributeValueSingleQuoted
+                | State::AttributeValueUnquoted
+        )
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#flush-code-points-consumed-as-a-character-reference
+    fn flush_code_points_consumed_as_character_reference(&mut self) {
+        let buf = core::mem::take(&mut self.buf);
+        for c in buf.chars() {
+            if self.is_consumed_as_part_of_attribute() {
+                self.append_attribute(c, /* is_name */ false);
+            } else {
+                self.pending_tokens.push_back(HtmlToken::Char(c));
+            }
+        }
+    }
+
+    /// 現在の文字から始まり、名前付き文字参照の表に含まれる最長の名前を探す。
+    /// 見つかった場合、名前の文字数と置き換える文字列を返す。
+    fn find_named_character_reference(&self) -> Option<(usize, &'static str)> {
+        let start = self.pos - 1;
+        let end = core::cmp::min(
+            start + MAX_NAMED_CHARACTER_REFERENCE_LENGTH,
+            self.input.len(),
+        );
+        let candidate: String = self.input[start..end].iter().collect();
+
+        for len in (1..=candidate.chars().count()).rev() {
+            let name: String = candidate.chars().take(len).collect();
+            if let Ok(i) =
+                NAMED_CHARACTER_REFERENCES.binary_search_by(|(n, _)| (*n).cmp(name.as_str()))
+            {
+                return Some((len, NAMED_CHARACTER_REFERENCES[i].1));
+            }
+        }
+
+        None
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+    fn end_numeric_character_reference(&mut self) {
+        let code = self.character_reference_code;
+        match code {
+            0x00 => self.parse_error(ParseErrorKind::NullCharacterReference),
+            c if c > 0x10FFFF => {
+                self.parse_error(ParseErrorKind::CharacterReferenceOutsideUnicodeRange)
+            }
+            0xD800..=0xDFFF => self.parse_error(ParseErrorKind::SurrogateCharacterReference),
+            0xFDD0..=0xFDEF => self.parse_error(ParseErrorKind::NoncharacterCharacterReference),
+            c if c & 0xFFFE == 0xFFFE => {
+                self.parse_error(ParseErrorKind::NoncharacterCharacterReference)
+            }
+            // ASCII空白文字以外の制御文字
+            0x0D | 0x01..=0x08 | 0x0B | 0x0E..=0x1F | 0x7F..=0x9F => {
+                self.parse_error(ParseErrorKind::ControlCharacterReference)
+            }
+            _ => {}
+        }
+
+        let code = match code {
+            // ヌル文字、範囲外のコードポイント、サロゲートはパースエラー。U+FFFDに置き換える。
+            0x00 => 0xFFFD,
+            c if c > 0x10FFFF => 0xFFFD,
+            0xD800..=0xDFFF => 0xFFFD,
+            // C1制御文字のうち、windows-1252で文字が割り当てられているものは置き換える
+            0x80 => 0x20AC,
+            0x82 => 0x201A,
+            0x83 => 0x0192,
+            0x84 => 0x201E,
+            0x85 => 0x2026,
+            0x86 => 0x2020,
+            0x87 => 0x2021,
+            0x88 => 0x02C6,
+            0x89 => 0x2030,
+            0x8A => 0x0160,
+            0x8B => 0x2039,
+            0x8C => 0x0152,
+            0x8E => 0x017D,
+            0x91 => 0x2018,
+            0x92 => 0x2019,
+            0x93 => 0x201C,
+            0x94 => 0x201D,
+            0x95 => 0x2022,
+            0x96 => 0x2013,
+            0x97 => 0x2014,
+            0x98 => 0x02DC,
+            0x99 => 0x2122,
+            0x9A => 0x0161,
+            0x9B => 0x203A,
+            0x9C => 0x0153,
+            0x9E => 0x017E,
+            0x9F => 0x0178,
+            c => c,
+        };
+
+        self.buf = String::new();
+        self.buf
+            .push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+        self.flush_code_points_consumed_as_character_reference();
+        self.state = self.return_state.clone();
     }
 
     fn create_tag(&mut self, start_tag_token: bool) {
+        self.end_tag_has_attributes = false;
         if start_tag_token {
             self.latest_token = Some(HtmlToken::StartTag {
                 tag: String::new(),
@@ -111,8 +476,6 @@ impl HtmlTokenizer {
     }
 
     fn append_tag_name(&mut self, c: char) {
-        assert!(self.latest_token.is_some());
-
         if let Some(t) = self.latest_token.as_mut() {
             match t {
                 HtmlToken::StartTag {
@@ -121,83 +484,271 @@ impl HtmlTokenizer {
                     attributes: _,
                 }
                 | HtmlToken::EndTag { ref mut tag } => tag.push(c),
-                _ => panic!("`latest_token` shoud be either StartTag or EndTag"),
+                _ => {}
             }
         }
     }
 
     fn take_latest_token(&mut self) -> Option<HtmlToken> {
-        assert!(self.latest_token.is_some());
+        self.remove_duplicate_attribute();
+        let t = self.latest_token.take();
 
-        let t = self.latest_token.as_ref().cloned();
-        self.latest_token = None;
-        assert!(self.latest_token.is_none());
+        match t {
+            Some(HtmlToken::StartTag { ref tag, .. }) => {
+                self.last_start_tag_name = Some(tag.clone());
+            }
+            Some(HtmlToken::EndTag { .. }) => {
+                if self.end_tag_has_attributes {
+                    self.parse_error(ParseErrorKind::EndTagWithAttributes);
+                }
+            }
+            _ => {}
+        }
 
         t
     }
 
-    fn start_new_attribute(&mut self) {
-        assert!(self.latest_token.is_some());
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    fn is_appropriate_end_tag_token(&self) -> bool {
+        match (&self.latest_token, &self.last_start_tag_name) {
+            (Some(HtmlToken::EndTag { tag }), Some(name)) => tag == name,
+            _ => false,
+        }
+    }
 
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    tag: _,
-                    self_closing: _,
-                    ref mut attributes,
-                } => {
-                    attributes.push(Attribute::new());
-                }
-                _ => panic!("`latest_token` should be StartTag"),
-            }
+    /// 終了タグとして扱えなかった"</"と一時的なバッファの文字を、文字トークンとして出力する
+    fn emit_end_tag_open_as_chars(&mut self) {
+        self.latest_token = None;
+        self.pending_tokens.push_back(HtmlToken::Char('<'));
+        self.pending_tokens.push_back(HtmlToken::Char('/'));
+        let buf = core::mem::take(&mut self.buf);
+        for c in buf.chars() {
+            self.pending_tokens.push_back(HtmlToken::Char(c));
         }
     }
 
-    fn append_attribute(&mut self, c: char, is_name: bool) {
-        assert!(self.latest_token.is_some());
+    /// RCDATA、RAWTEXT、スクリプトデータの終了タグ名状態の共通処理。
+    /// 終了タグが完成した場合はそのトークンを返す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-name-state
+    fn consume_text_end_tag_name(&mut self, c: char, text_state: State) -> Option<HtmlToken> {
+        if self.is_appropriate_end_tag_token() {
+            if is_whitespace(c) {
+                self.state = State::BeforeAttributeName;
+                return None;
+            }
 
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    tag: _,
-                    self_closing: _,
-                    ref mut attributes,
-                } => {
-                    let len = attributes.len();
-                    assert!(len > 0);
+            if c == '/' {
+                self.state = State::SelfClosingStartTag;
+                return None;
+            }
+
+            if c == '>' {
+                self.state = State::Data;
+                return self.take_latest_token();
+            }
+        }
+
+        if c.is_ascii_alphabetic() {
+            self.append_tag_name(c.to_ascii_lowercase());
+            self.buf.push(c);
+            return None;
+        }
+
+        self.emit_end_tag_open_as_chars();
+        self.reconsume = true;
+        self.state = text_state;
+        None
+    }
+
+    fn start_new_attribute(&mut self) {
+        self.remove_duplicate_attribute();
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                tag: _,
+                self_closing: _,
+                ref mut attributes,
+            }) => {
+                attributes.push(Attribute::new());
+            }
+            // 終了タグの属性はパースエラーとして、トークンを出力するときに報告する。属性そのものは捨てる
+            Some(HtmlToken::EndTag { .. }) => self.end_tag_has_attributes = true,
+            _ => {}
+        }
+    }
 
-                    attributes[len - 1].add_char(c, is_name);
+    /// https://html.spec.whatwg.org/multipage/parsing.html#attribute-name-state
+    /// 最後の属性と同じ名前の属性がすでにある場合は、パースエラーとして最後の属性を取り除く。
+    /// 最初に現れた属性が優先される
+    fn remove_duplicate_attribute(&mut self) {
+        let duplicated = match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                tag: _,
+                self_closing: _,
+                ref mut attributes,
+            }) => match attributes.split_last() {
+                Some((last, others)) if others.iter().any(|a| a.name() == last.name()) => {
+                    attributes.pop();
+                    true
                 }
-                _ => panic!("`latest_token` should be StartTag"),
+                _ => false,
+            },
+            _ => false,
+        };
+        if duplicated {
+            self.parse_error(ParseErrorKind::DuplicateAttribute);
+        }
+    }
+
+    fn append_attribute(&mut self, c: char, is_name: bool) {
+        if let Some(HtmlToken::StartTag {
+            tag: _,
+            self_closing: _,
+            ref mut attributes,
+        }) = self.latest_token
+        {
+            if let Some(attribute) = attributes.last_mut() {
+                attribute.add_char(c, is_name);
             }
         }
     }
 
     fn set_self_closing_flag(&mut self) {
-        assert!(self.latest_token.is_some());
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                tag: _,
+                ref mut self_closing,
+                attributes: _,
+            }) => *self_closing = true,
+            Some(HtmlToken::EndTag { .. }) => {
+                self.parse_error(ParseErrorKind::EndTagWithTrailingSolidus)
+            }
+            _ => {}
+        }
+    }
 
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    tag: _,
-                    ref mut self_closing,
-                    attributes: _,
-                } => *self_closing = true,
-                _ => panic!("`latest_token` should be StartTag"),
+    /// 現在の文字から始まる入力がsと一致するかどうかを返す
+    fn next_input_starts_with(&self, s: &str, case_insensitive: bool) -> bool {
+        let mut i = self.pos - 1;
+        for expected in s.chars() {
+            match self.input.get(i) {
+                Some(c) if *c == expected => i += 1,
+                Some(c) if case_insensitive && c.eq_ignore_ascii_case(&expected) => i += 1,
+                _ => return false,
             }
         }
+        true
+    }
+
+    /// ツリー構築の段階から、トークナイザの状態を切り替える。
+    /// <title>や<style>などの要素の中身をテキストとして扱うために使う
+    pub fn set_state(&mut self, state: State) {
+        self.state = state;
+    }
+
+    /// 外部コンテンツ(SVGやMathML)の中にいるかどうかを設定する。
+    /// 外部コンテンツの中では、"<![CDATA["はCDATAセクションとして扱われる。
+    pub fn set_in_foreign_content(&mut self, in_foreign_content: bool) {
+        self.in_foreign_content = in_foreign_content;
+    }
+
+    fn create_comment(&mut self) {
+        self.latest_token = Some(HtmlToken::Comment(String::new()));
+    }
+
+    fn append_comment(&mut self, s: &str) {
+        if let Some(HtmlToken::Comment(ref mut data)) = self.latest_token {
+            data.push_str(s);
+        }
+    }
+
+    fn create_doctype(&mut self) {
+        self.latest_token = Some(HtmlToken::Doctype {
+            name: None,
+            public_identifier: None,
+            system_identifier: None,
+            force_quirks: false,
+        });
+    }
+
+    fn append_doctype_name(&mut self, c: char) {
+        if let Some(HtmlToken::Doctype { ref mut name, .. }) = self.latest_token {
+            name.get_or_insert_with(String::new).push(c);
+        }
+    }
+
+    fn start_doctype_public_identifier(&mut self) {
+        if let Some(HtmlToken::Doctype {
+            ref mut public_identifier,
+            ..
+        }) = self.latest_token
+        {
+            *public_identifier = Some(String::new());
+        }
+    }
+
+    fn append_doctype_public_identifier(&mut self, c: char) {
+        if let Some(HtmlToken::Doctype {
+            public_identifier: Some(ref mut id),
+            ..
+        }) = self.latest_token
+        {
+            id.push(c);
+        }
+    }
+
+    fn start_doctype_system_identifier(&mut self) {
+        if let Some(HtmlToken::Doctype {
+            ref mut system_identifier,
+            ..
+        }) = self.latest_token
+        {
+            *system_identifier = Some(String::new());
+        }
+    }
+
+    fn append_doctype_system_identifier(&mut self, c: char) {
+        if let Some(HtmlToken::Doctype {
+            system_identifier: Some(ref mut id),
+            ..
+        }) = self.latest_token
+        {
+            id.push(c);
+        }
+    }
+
+    fn set_force_quirks_flag(&mut self) {
+        if let Some(HtmlToken::Doctype {
+            ref mut force_quirks,
+            ..
+        }) = self.latest_token
+        {
+            *force_quirks = true;
+        }
     }
 }
 
+/// https://infra.spec.whatwg.org/#ascii-whitespace
+fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0C' | ' ')
+}
+
 impl Iterator for HtmlTokenizer {
     type Item = HtmlToken;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
-            return None;
-        }
-
         loop {
+            if let Some(t) = self.pending_tokens.pop_front() {
+                return Some(t);
+            }
+
+            if self.pos >= self.input.len() && !self.reconsume {
+                // 入力がまだ届いていない場合は、次のfeedまで一時停止する。
+                // EOFトークンは一度だけ出力し、それ以降は何も出力しない
+                if !self.finished || self.pos > self.input.len() {
+                    return None;
+                }
+            }
+
             let c = match self.reconsume {
                 true => self.reconsume_input(),
                 false => self.consume_next_input(),
@@ -205,6 +756,12 @@ impl Iterator for HtmlTokenizer {
 
             match self.state {
                 State::Data => {
+                    if c == '&' {
+                        self.return_state = State::Data;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
                     if c == '<' {
                         self.state = State::TagOpen;
                         continue;
@@ -214,9 +771,19 @@ impl Iterator for HtmlTokenizer {
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '\0' {
+                        // パースエラー。ヌル文字はそのまま出力する
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                    }
+
                     return Some(HtmlToken::Char(c));
                 }
                 State::TagOpen => {
+                    if c == '!' {
+                        self.state = State::MarkupDeclarationOpen;
+                        continue;
+                    }
+
                     if c == '/' {
                         self.state = State::EndTagOpen;
                         continue;
@@ -229,16 +796,35 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '?' {
+                        // パースエラー。"<?xml ...>"のような処理命令はコメントとして扱う
+                        self.parse_error(ParseErrorKind::UnexpectedQuestionMarkInsteadOfTagName);
+                        self.create_comment();
+                        self.reconsume = true;
+                        self.state = State::BogusComment;
+                        continue;
+                    }
+
                     if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                        // パースエラー。"<"を文字として出力する
+                        self.parse_error(ParseErrorKind::EofBeforeTagName);
+                        self.pending_tokens.push_back(HtmlToken::Eof);
+                        return Some(HtmlToken::Char('<'));
                     }
 
+                    // パースエラー。"<"を文字として出力する
+                    self.parse_error(ParseErrorKind::InvalidFirstCharacterOfTagName);
                     self.reconsume = true;
                     self.state = State::Data;
+                    return Some(HtmlToken::Char('<'));
                 }
                 State::EndTagOpen => {
                     if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                        // パースエラー。"</"を文字として出力する
+                        self.parse_error(ParseErrorKind::EofBeforeTagName);
+                        self.pending_tokens.push_back(HtmlToken::Char('/'));
+                        self.pending_tokens.push_back(HtmlToken::Eof);
+                        return Some(HtmlToken::Char('<'));
                     }
 
                     if c.is_ascii_alphabetic() {
@@ -247,9 +833,22 @@ impl Iterator for HtmlTokenizer {
                         self.create_tag(false);
                         continue;
                     }
+
+                    if c == '>' {
+                        // パースエラー。"</>"は無視する
+                        self.parse_error(ParseErrorKind::MissingEndTagName);
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    // パースエラー
+                    self.parse_error(ParseErrorKind::InvalidFirstCharacterOfTagName);
+                    self.create_comment();
+                    self.reconsume = true;
+                    self.state = State::BogusComment;
                 }
                 State::TagName => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
@@ -270,26 +869,49 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        // パースエラー。作りかけのタグは捨てる
+                        self.parse_error(ParseErrorKind::EofInTag);
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_tag_name(char::REPLACEMENT_CHARACTER);
+                        continue;
+                    }
+
                     self.append_tag_name(c);
 
                     self.buf.push(c);
                 }
                 State::BeforeAttributeName => {
+                    if is_whitespace(c) {
+                        // 空白は無視
+                        continue;
+                    }
+
                     if c == '/' || c == '>' || self.is_eof() {
                         self.reconsume = true;
                         self.state = State::AfterAttributeName;
                         continue;
                     }
 
+                    if c == '=' {
+                        // パースエラー。"="を属性名の最初の文字として扱う
+                        self.parse_error(ParseErrorKind::UnexpectedEqualsSignBeforeAttributeName);
+                        self.start_new_attribute();
+                        self.append_attribute(c, /* is_name */ true);
+                        self.state = State::AttributeName;
+                        continue;
+                    }
+
                     self.reconsume = true;
                     self.state = State::AttributeName;
                     self.start_new_attribute();
                 }
                 State::AttributeName => {
-                    if c == ' ' || c == '/' || c == '>' || self.is_eof() {
+                    if is_whitespace(c) || c == '/' || c == '>' || self.is_eof() {
                         self.reconsume = true;
                         self.state = State::AfterAttributeName;
                         continue;
@@ -305,10 +927,22 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_attribute(char::REPLACEMENT_CHARACTER, /* is_name */ true);
+                        continue;
+                    }
+
+                    if c == '"' || c == '\'' || c == '<' {
+                        // パースエラー。属性名の一部として扱う
+                        self.parse_error(ParseErrorKind::UnexpectedCharacterInAttributeName);
+                    }
+
                     self.append_attribute(c, /* is_name */ true);
                 }
                 State::AfterAttributeName => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         // 空白は無視
                         continue;
                     }
@@ -329,6 +963,8 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        // パースエラー。作りかけのタグは捨てる
+                        self.parse_error(ParseErrorKind::EofInTag);
                         return Some(HtmlToken::Eof);
                     }
 
@@ -337,7 +973,7 @@ impl Iterator for HtmlTokenizer {
                     self.start_new_attribute();
                 }
                 State::BeforeAttributeValue => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         // 空白は無視
                         continue;
                     }
@@ -352,6 +988,13 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '>' {
+                        // パースエラー。属性値を空文字列としてタグを出力する
+                        self.parse_error(ParseErrorKind::MissingAttributeValue);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
                     self.reconsume = true;
                     self.state = State::AttributeValueUnquoted;
                 }
@@ -361,10 +1004,28 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '&' {
+                        self.return_state = State::AttributeValueDoubleQuoted;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
                     if self.is_eof() {
+                        // パースエラー。作りかけのタグは捨てる
+                        self.parse_error(ParseErrorKind::EofInTag);
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_attribute(
+                            char::REPLACEMENT_CHARACTER,
+                            /* is_name */ false,
+                        );
+                        continue;
+                    }
+
                     self.append_attribute(c, /* is_name */ false);
                 }
                 State::AttributeValueSingleQuoted => {
@@ -373,31 +1034,74 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '&' {
+                        self.return_state = State::AttributeValueSingleQuoted;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
                     if self.is_eof() {
+                        // パースエラー。作りかけのタグは捨てる
+                        self.parse_error(ParseErrorKind::EofInTag);
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_attribute(
+                            char::REPLACEMENT_CHARACTER,
+                            /* is_name */ false,
+                        );
+                        continue;
+                    }
+
                     self.append_attribute(c, /* is_name */ false);
                 }
                 State::AttributeValueUnquoted => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
 
+                    if c == '&' {
+                        self.return_state = State::AttributeValueUnquoted;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
                     if c == '>' {
                         self.state = State::Data;
                         return self.take_latest_token();
                     }
 
                     if self.is_eof() {
+                        // パースエラー。作りかけのタグは捨てる
+                        self.parse_error(ParseErrorKind::EofInTag);
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_attribute(
+                            char::REPLACEMENT_CHARACTER,
+                            /* is_name */ false,
+                        );
+                        continue;
+                    }
+
+                    if matches!(c, '"' | '\'' | '<' | '=' | '`') {
+                        // パースエラー。属性値の一部として扱う
+                        self.parse_error(
+                            ParseErrorKind::UnexpectedCharacterInUnquotedAttributeValue,
+                        );
+                    }
+
                     self.append_attribute(c, /* is_name */ false);
                 }
                 State::AfterAttrbuteValueQuoted => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
@@ -413,11 +1117,15 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        // パースエラー。作りかけのタグは捨てる
+                        self.parse_error(ParseErrorKind::EofInTag);
                         return Some(HtmlToken::Eof);
                     }
 
+                    // パースエラー。属性の間に空白があるものとして扱う
+                    self.parse_error(ParseErrorKind::MissingWhitespaceBetweenAttributes);
                     self.reconsume = true;
-                    self.state = State::BeforeAttributeValue;
+                    self.state = State::BeforeAttributeName;
                 }
                 State::SelfClosingStartTag => {
                     if c == '>' {
@@ -427,12 +1135,25 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        // パースエラー。作りかけのタグは捨てる
+                        self.parse_error(ParseErrorKind::EofInTag);
                         return Some(HtmlToken::Eof);
                     }
+
+                    // パースエラー。"/"を無視して属性名の前として扱う
+                    self.parse_error(ParseErrorKind::UnexpectedSolidusInTag);
+                    self.reconsume = true;
+                    self.state = State::BeforeAttributeName;
                 }
-                State::ScriptData => {
+                State::Rcdata => {
+                    if c == '&' {
+                        self.return_state = State::Rcdata;
+                        self.state = State::CharacterReference;
+                        continue;
+                    }
+
                     if c == '<' {
-                        self.state = State::ScriptDataLessThanSign;
+                        self.state = State::RcdataLessThanSign;
                         continue;
                     }
 
@@ -440,84 +1161,944 @@ impl Iterator for HtmlTokenizer {
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        return Some(HtmlToken::Char(char::REPLACEMENT_CHARACTER));
+                    }
+
                     return Some(HtmlToken::Char(c));
                 }
-                State::ScriptDataLessThanSign => {
+                State::RcdataLessThanSign => {
                     if c == '/' {
                         // 一時的なバッファをクリア
                         self.buf = String::new();
-                        self.state = State::ScriptDataEndTagOpen;
+                        self.state = State::RcdataEndTagOpen;
                         continue;
                     }
 
                     self.reconsume = true;
-                    self.state = State::ScriptData;
+                    self.state = State::Rcdata;
                     return Some(HtmlToken::Char('<'));
                 }
-                State::ScriptDataEndTagOpen => {
+                State::RcdataEndTagOpen => {
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
-                        self.state = State::ScriptDataEndTagName;
+                        self.state = State::RcdataEndTagName;
                         self.create_tag(false);
                         continue;
                     }
 
                     self.reconsume = true;
-                    self.state = State::ScriptData;
-                    // 仕様では、"<"と"/"の2つの文字トークンを返すとなっているが、
-                    // この実装ではnextメソッドからは1つのトークンしか返せないため、
-                    // "<"のトークンのみを返す
-                    return Some(HtmlToken::Char('<'));
+                    self.state = State::Rcdata;
+                    self.emit_end_tag_open_as_chars();
+                    continue;
                 }
-                State::ScriptDataEndTagName => {
-                    if c == ' ' {
-                        self.state = State::ScriptData;
-                        return self.take_latest_token();
+                State::RcdataEndTagName => {
+                    let t = self.consume_text_end_tag_name(c, State::Rcdata);
+                    if t.is_some() {
+                        return t;
                     }
-
-                    if c.is_ascii_alphabetic() {
-                        self.buf.push(c);
-                        self.append_tag_name(c.to_ascii_lowercase());
+                    continue;
+                }
+                State::Rawtext => {
+                    if c == '<' {
+                        self.state = State::RawtextLessThanSign;
                         continue;
                     }
 
-                    self.state = State::TemporaryBuffer;
-                    self.buf = String::from("</") + &self.buf;
-                    self.buf.push(c);
-                    continue;
-                }
-                State::TemporaryBuffer => {
-                    self.reconsume = true;
+                    if self.is_eof() {
+                        return Some(HtmlToken::Eof);
+                    }
 
-                    if self.buf.chars().count() == 0 {
-                        self.state = State::ScriptData;
-                        continue;
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        return Some(HtmlToken::Char(char::REPLACEMENT_CHARACTER));
                     }
 
-                    // 最初の1文字を削除
-                    let c = self
-                        .buf
-                        .chars()
-                        .nth(0)
-                        .expect("self.buf have at least 1 char");
-                    self.buf.remove(0);
                     return Some(HtmlToken::Char(c));
                 }
-            }
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::alloc::string::ToString;
-    use crate::alloc::vec;
+                State::RawtextLessThanSign => {
+                    if c == '/' {
+                        // 一時的なバッファをクリア
+                        self.buf = String::new();
+                        self.state = State::RawtextEndTagOpen;
+                        continue;
+                    }
 
-    #[test]
-    fn test_empty() {
-        let html = "".to_string();
-        let mut tokenizer = HtmlTokenizer::new(html);
+                    self.reconsume = true;
+                    self.state = State::Rawtext;
+                    return Some(HtmlToken::Char('<'));
+                }
+                State::RawtextEndTagOpen => {
+                    if c.is_ascii_alphabetic() {
+                        self.reconsume = true;
+                        self.state = State::RawtextEndTagName;
+                        self.create_tag(false);
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Rawtext;
+                    self.emit_end_tag_open_as_chars();
+                    continue;
+                }
+                State::RawtextEndTagName => {
+                    let t = self.consume_text_end_tag_name(c, State::Rawtext);
+                    if t.is_some() {
+                        return t;
+                    }
+                    continue;
+                }
+                State::ScriptData => {
+                    if c == '<' {
+                        self.state = State::ScriptDataLessThanSign;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        return Some(HtmlToken::Eof);
+                    }
+
+                    if c == '\0' {
+                        // パースエラー。U+FFFDに置き換える
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        return Some(HtmlToken::Char(char::REPLACEMENT_CHARACTER));
+                    }
+
+                    return Some(HtmlToken::Char(c));
+                }
+                State::ScriptDataLessThanSign => {
+                    if c == '/' {
+                        // 一時的なバッファをクリア
+                        self.buf = String::new();
+                        self.state = State::ScriptDataEndTagOpen;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::ScriptData;
+                    return Some(HtmlToken::Char('<'));
+                }
+                State::ScriptDataEndTagOpen => {
+                    if c.is_ascii_alphabetic() {
+                        self.reconsume = true;
+                        self.state = State::ScriptDataEndTagName;
+                        self.create_tag(false);
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::ScriptData;
+                    self.emit_end_tag_open_as_chars();
+                    continue;
+                }
+                State::ScriptDataEndTagName => {
+                    let t = self.consume_text_end_tag_name(c, State::ScriptData);
+                    if t.is_some() {
+                        return t;
+                    }
+                    continue;
+                }
+                State::CharacterReference => {
+                    self.buf = String::from("&");
+
+                    if c.is_ascii_alphanumeric() {
+                        self.reconsume = true;
+                        self.state = State::NamedCharacterReference;
+                        continue;
+                    }
+
+                    if c == '#' {
+                        self.buf.push(c);
+                        self.state = State::NumericCharacterReference;
+                        continue;
+                    }
+
+                    self.flush_code_points_consumed_as_character_reference();
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
+                State::NamedCharacterReference => {
+                    // 最長の名前と、その直後の1文字が揃うまで待つ
+                    if self.needs_more_input(MAX_NAMED_CHARACTER_REFERENCE_LENGTH + 1) {
+                        self.reconsume = true;
+                        return None;
+                    }
+
+                    let (len, value) = match self.find_named_character_reference() {
+                        Some(r) => r,
+                        None => {
+                            // 一致する名前がない場合、"&"をそのまま出力する
+                            self.flush_code_points_consumed_as_character_reference();
+                            self.reconsume = true;
+                            self.state = State::AmbiguousAmpersand;
+                            continue;
+                        }
+                    };
+
+                    // 最初の1文字はすでに消費しているので、残りの文字を消費する
+                    self.buf.push(c);
+                    for _ in 1..len {
+                        let c = self.consume_next_input();
+                        self.buf.push(c);
+                    }
+
+                    let last_matched_is_semicolon = self.buf.ends_with(';');
+                    let next_is_equals_or_alphanumeric = match self.peek_next_input() {
+                        Some(n) => n == '=' || n.is_ascii_alphanumeric(),
+                        None => false,
+                    };
+
+                    // 歴史的な理由により、属性値の中で";"で終わらない文字参照の直後に
+                    // "="または英数字が続く場合は、文字参照として扱わない
+                    if self.is_consumed_as_part_of_attribute()
+                        && !last_matched_is_semicolon
+                        && next_is_equals_or_alphanumeric
+                    {
+                        self.flush_code_points_consumed_as_character_reference();
+                        self.state = self.return_state.clone();
+                        continue;
+                    }
+
+                    if !last_matched_is_semicolon {
+                        // パースエラー。";"がなくても文字参照として扱う
+                        self.parse_error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+                    }
+
+                    self.buf = String::from(value);
+                    self.flush_code_points_consumed_as_character_reference();
+                    self.state = self.return_state.clone();
+                }
+                State::AmbiguousAmpersand => {
+                    if c.is_ascii_alphanumeric() {
+                        if self.is_consumed_as_part_of_attribute() {
+                            self.append_attribute(c, /* is_name */ false);
+                            continue;
+                        }
+                        return Some(HtmlToken::Char(c));
+                    }
+
+                    if c == ';' {
+                        self.parse_error(ParseErrorKind::UnknownNamedCharacterReference);
+                    }
+
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
+                State::NumericCharacterReference => {
+                    self.character_reference_code = 0;
+
+                    if c == 'x' || c == 'X' {
+                        self.buf.push(c);
+                        self.state = State::HexadecimalCharacterReferenceStart;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::DecimalCharacterReferenceStart;
+                }
+                State::HexadecimalCharacterReferenceStart => {
+                    if c.is_ascii_hexdigit() && !self.is_eof() {
+                        self.reconsume = true;
+                        self.state = State::HexadecimalCharacterReference;
+                        continue;
+                    }
+
+                    // パースエラー。消費した文字をそのまま出力する
+                    self.parse_error(ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference);
+                    self.flush_code_points_consumed_as_character_reference();
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
+                State::DecimalCharacterReferenceStart => {
+                    if c.is_ascii_digit() && !self.is_eof() {
+                        self.reconsume = true;
+                        self.state = State::DecimalCharacterReference;
+                        continue;
+                    }
+
+                    // パースエラー。消費した文字をそのまま出力する
+                    self.parse_error(ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference);
+                    self.flush_code_points_consumed_as_character_reference();
+                    self.reconsume = true;
+                    self.state = self.return_state.clone();
+                }
+                State::HexadecimalCharacterReference => {
+                    if let (Some(d), false) = (c.to_digit(16), self.is_eof()) {
+                        // 大きすぎる値は範囲外として扱うので、それ以上増やさない
+                        self.character_reference_code = self
+                            .character_reference_code
+                            .saturating_mul(16)
+                            .saturating_add(d);
+                        continue;
+                    }
+
+                    if c != ';' || self.is_eof() {
+                        // パースエラー。";"がなくても文字参照として扱う
+                        self.parse_error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+                        self.reconsume = true;
+                    }
+                    self.end_numeric_character_reference();
+                }
+                State::DecimalCharacterReference => {
+                    if let (Some(d), false) = (c.to_digit(10), self.is_eof()) {
+                        self.character_reference_code = self
+                            .character_reference_code
+                            .saturating_mul(10)
+                            .saturating_add(d);
+                        continue;
+                    }
+
+                    if c != ';' || self.is_eof() {
+                        // パースエラー。";"がなくても文字参照として扱う
+                        self.parse_error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+                        self.reconsume = true;
+                    }
+                    self.end_numeric_character_reference();
+                }
+                State::MarkupDeclarationOpen => {
+                    if self.needs_more_input("[CDATA[".len()) {
+                        self.reconsume = true;
+                        return None;
+                    }
+
+                    if self.next_input_starts_with("--", false) {
+                        // 2つ目の"-"を消費する
+                        self.pos += 1;
+                        self.create_comment();
+                        self.state = State::CommentStart;
+                        continue;
+                    }
+
+                    if self.next_input_starts_with("DOCTYPE", true) {
+                        self.pos += "DOCTYPE".len() - 1;
+                        self.state = State::Doctype;
+                        continue;
+                    }
+
+                    if self.next_input_starts_with("[CDATA[", false) {
+                        self.pos += "[CDATA[".len() - 1;
+                        if self.in_foreign_content {
+                            self.state = State::CdataSection;
+                            continue;
+                        }
+
+                        // パースエラー。HTMLの中のCDATAセクションはコメントとして扱う
+                        self.parse_error(ParseErrorKind::CdataInHtmlContent);
+                        self.create_comment();
+                        self.append_comment("[CDATA[");
+                        self.state = State::BogusComment;
+                        continue;
+                    }
+
+                    // パースエラー
+                    self.parse_error(ParseErrorKind::IncorrectlyOpenedComment);
+                    self.create_comment();
+                    self.reconsume = true;
+                    self.state = State::BogusComment;
+                }
+                State::BogusComment => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.is_eof() {
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_comment("\u{FFFD}");
+                        continue;
+                    }
+
+                    self.append_comment(c.encode_utf8(&mut [0; 4]));
+                }
+                State::CommentStart => {
+                    if c == '-' {
+                        self.state = State::CommentStartDash;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        // パースエラー。空のコメントとして扱う
+                        self.parse_error(ParseErrorKind::AbruptClosingOfEmptyComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentStartDash => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInComment);
+                        return self.take_latest_token();
+                    }
+
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        // パースエラー。空のコメントとして扱う
+                        self.parse_error(ParseErrorKind::AbruptClosingOfEmptyComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("-");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::Comment => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInComment);
+                        return self.take_latest_token();
+                    }
+
+                    if c == '<' {
+                        self.append_comment("<");
+                        self.state = State::CommentLessThanSign;
+                        continue;
+                    }
+
+                    if c == '-' {
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    if c == '\0' {
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                        self.append_comment("\u{FFFD}");
+                        continue;
+                    }
+
+                    self.append_comment(c.encode_utf8(&mut [0; 4]));
+                }
+                State::CommentLessThanSign => {
+                    if c == '!' {
+                        self.append_comment("!");
+                        self.state = State::CommentLessThanSignBang;
+                        continue;
+                    }
+
+                    if c == '<' {
+                        self.append_comment("<");
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentLessThanSignBang => {
+                    if c == '-' {
+                        self.state = State::CommentLessThanSignBangDash;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentLessThanSignBangDash => {
+                    if c == '-' {
+                        self.state = State::CommentLessThanSignBangDashDash;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::CommentEndDash;
+                }
+                State::CommentLessThanSignBangDashDash => {
+                    // ">"とEOF以外はパースエラー(ネストしたコメント)だが、どちらもCommentEndで処理する
+                    if c != '>' && !self.is_eof() {
+                        self.parse_error(ParseErrorKind::NestedComment);
+                    }
+                    self.reconsume = true;
+                    self.state = State::CommentEnd;
+                }
+                State::CommentEndDash => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInComment);
+                        return self.take_latest_token();
+                    }
+
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    self.append_comment("-");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentEnd => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInComment);
+                        return self.take_latest_token();
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '!' {
+                        self.state = State::CommentEndBang;
+                        continue;
+                    }
+
+                    if c == '-' {
+                        self.append_comment("-");
+                        continue;
+                    }
+
+                    self.append_comment("--");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentEndBang => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInComment);
+                        return self.take_latest_token();
+                    }
+
+                    if c == '-' {
+                        self.append_comment("--!");
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        // パースエラー。"--!>"でコメントを閉じる
+                        self.parse_error(ParseErrorKind::IncorrectlyClosedComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("--!");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::Doctype => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.create_doctype();
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        self.state = State::BeforeDoctypeName;
+                        continue;
+                    }
+
+                    // ">"の場合、それ以外の場合はパースエラー。どちらもBeforeDoctypeNameで処理する
+                    if c != '>' {
+                        self.parse_error(ParseErrorKind::MissingWhitespaceBeforeDoctypeName);
+                    }
+                    self.reconsume = true;
+                    self.state = State::BeforeDoctypeName;
+                }
+                State::BeforeDoctypeName => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.create_doctype();
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        // 空白は無視
+                        continue;
+                    }
+
+                    if c == '>' {
+                        // パースエラー
+                        self.parse_error(ParseErrorKind::MissingDoctypeName);
+                        self.create_doctype();
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                    }
+                    self.create_doctype();
+                    self.append_doctype_name(match c {
+                        '\0' => '\u{FFFD}',
+                        _ => c.to_ascii_lowercase(),
+                    });
+                    self.state = State::DoctypeName;
+                }
+                State::DoctypeName => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        self.state = State::AfterDoctypeName;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                    }
+                    self.append_doctype_name(match c {
+                        '\0' => '\u{FFFD}',
+                        _ => c.to_ascii_lowercase(),
+                    });
+                }
+                State::AfterDoctypeName => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        // 空白は無視
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if self.needs_more_input("PUBLIC".len()) {
+                        self.reconsume = true;
+                        return None;
+                    }
+
+                    if self.next_input_starts_with("PUBLIC", true) {
+                        self.pos += "PUBLIC".len() - 1;
+                        self.state = State::AfterDoctypePublicKeyword;
+                        continue;
+                    }
+
+                    if self.next_input_starts_with("SYSTEM", true) {
+                        self.pos += "SYSTEM".len() - 1;
+                        self.state = State::AfterDoctypeSystemKeyword;
+                        continue;
+                    }
+
+                    // パースエラー
+                    self.parse_error(ParseErrorKind::InvalidCharacterSequenceAfterDoctypeName);
+                    self.set_force_quirks_flag();
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::AfterDoctypePublicKeyword | State::BeforeDoctypePublicIdentifier => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        // PUBLICキーワードの直後の空白で次の状態に移る。それ以降の空白は無視
+                        self.state = State::BeforeDoctypePublicIdentifier;
+                        continue;
+                    }
+
+                    if c == '"' {
+                        if self.state == State::AfterDoctypePublicKeyword {
+                            self.parse_error(
+                                ParseErrorKind::MissingWhitespaceAfterDoctypePublicKeyword,
+                            );
+                        }
+                        self.start_doctype_public_identifier();
+                        self.state = State::DoctypePublicIdentifierDoubleQuoted;
+                        continue;
+                    }
+
+                    if c == '\'' {
+                        if self.state == State::AfterDoctypePublicKeyword {
+                            self.parse_error(
+                                ParseErrorKind::MissingWhitespaceAfterDoctypePublicKeyword,
+                            );
+                        }
+                        self.start_doctype_public_identifier();
+                        self.state = State::DoctypePublicIdentifierSingleQuoted;
+                        continue;
+                    }
+
+                    // パースエラー
+                    self.set_force_quirks_flag();
+                    if c == '>' {
+                        self.parse_error(ParseErrorKind::MissingDoctypePublicIdentifier);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.parse_error(ParseErrorKind::MissingQuoteBeforeDoctypePublicIdentifier);
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::DoctypePublicIdentifierDoubleQuoted
+                | State::DoctypePublicIdentifierSingleQuoted => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if (c == '"' && self.state == State::DoctypePublicIdentifierDoubleQuoted)
+                        || (c == '\'' && self.state == State::DoctypePublicIdentifierSingleQuoted)
+                    {
+                        self.state = State::AfterDoctypePublicIdentifier;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        // パースエラー
+                        self.parse_error(ParseErrorKind::AbruptDoctypePublicIdentifier);
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                    }
+                    self.append_doctype_public_identifier(match c {
+                        '\0' => '\u{FFFD}',
+                        _ => c,
+                    });
+                }
+                State::AfterDoctypePublicIdentifier
+                | State::BetweenDoctypePublicAndSystemIdentifiers => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        self.state = State::BetweenDoctypePublicAndSystemIdentifiers;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '"' {
+                        if self.state == State::AfterDoctypePublicIdentifier {
+                            self.parse_error(
+                                ParseErrorKind::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+                            );
+                        }
+                        self.start_doctype_system_identifier();
+                        self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                        continue;
+                    }
+
+                    if c == '\'' {
+                        if self.state == State::AfterDoctypePublicIdentifier {
+                            self.parse_error(
+                                ParseErrorKind::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+                            );
+                        }
+                        self.start_doctype_system_identifier();
+                        self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                        continue;
+                    }
+
+                    // パースエラー
+                    self.parse_error(ParseErrorKind::MissingQuoteBeforeDoctypeSystemIdentifier);
+                    self.set_force_quirks_flag();
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::AfterDoctypeSystemKeyword | State::BeforeDoctypeSystemIdentifier => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        // SYSTEMキーワードの直後の空白で次の状態に移る。それ以降の空白は無視
+                        self.state = State::BeforeDoctypeSystemIdentifier;
+                        continue;
+                    }
+
+                    if c == '"' {
+                        if self.state == State::AfterDoctypeSystemKeyword {
+                            self.parse_error(
+                                ParseErrorKind::MissingWhitespaceAfterDoctypeSystemKeyword,
+                            );
+                        }
+                        self.start_doctype_system_identifier();
+                        self.state = State::DoctypeSystemIdentifierDoubleQuoted;
+                        continue;
+                    }
+
+                    if c == '\'' {
+                        if self.state == State::AfterDoctypeSystemKeyword {
+                            self.parse_error(
+                                ParseErrorKind::MissingWhitespaceAfterDoctypeSystemKeyword,
+                            );
+                        }
+                        self.start_doctype_system_identifier();
+                        self.state = State::DoctypeSystemIdentifierSingleQuoted;
+                        continue;
+                    }
+
+                    // パースエラー
+                    self.set_force_quirks_flag();
+                    if c == '>' {
+                        self.parse_error(ParseErrorKind::MissingDoctypeSystemIdentifier);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.parse_error(ParseErrorKind::MissingQuoteBeforeDoctypeSystemIdentifier);
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::DoctypeSystemIdentifierDoubleQuoted
+                | State::DoctypeSystemIdentifierSingleQuoted => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if (c == '"' && self.state == State::DoctypeSystemIdentifierDoubleQuoted)
+                        || (c == '\'' && self.state == State::DoctypeSystemIdentifierSingleQuoted)
+                    {
+                        self.state = State::AfterDoctypeSystemIdentifier;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        // パースエラー
+                        self.parse_error(ParseErrorKind::AbruptDoctypeSystemIdentifier);
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                    }
+                    self.append_doctype_system_identifier(match c {
+                        '\0' => '\u{FFFD}',
+                        _ => c,
+                    });
+                }
+                State::AfterDoctypeSystemIdentifier => {
+                    if self.is_eof() {
+                        self.parse_error(ParseErrorKind::EofInDoctype);
+                        self.set_force_quirks_flag();
+                        return self.take_latest_token();
+                    }
+
+                    if is_whitespace(c) {
+                        // 空白は無視
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    // パースエラー。ただし、force-quirksフラグは設定しない
+                    self.parse_error(
+                        ParseErrorKind::UnexpectedCharacterAfterDoctypeSystemIdentifier,
+                    );
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::BogusDoctype => {
+                    if self.is_eof() {
+                        return self.take_latest_token();
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '\0' {
+                        self.parse_error(ParseErrorKind::UnexpectedNullCharacter);
+                    }
+
+                    // それ以外の文字は無視
+                }
+                State::CdataSection => {
+                    if self.is_eof() {
+                        // パースエラー
+                        self.parse_error(ParseErrorKind::EofInCdata);
+                        return Some(HtmlToken::Eof);
+                    }
+
+                    if c == ']' {
+                        self.state = State::CdataSectionBracket;
+                        continue;
+                    }
+
+                    return Some(HtmlToken::Char(c));
+                }
+                State::CdataSectionBracket => {
+                    if c == ']' {
+                        self.state = State::CdataSectionEnd;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::CdataSection;
+                    return Some(HtmlToken::Char(']'));
+                }
+                State::CdataSectionEnd => {
+                    if c == ']' {
+                        return Some(HtmlToken::Char(']'));
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::CdataSection;
+                    self.pending_tokens.push_back(HtmlToken::Char(']'));
+                    return Some(HtmlToken::Char(']'));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use crate::alloc::vec;
+
+    #[test]
+    fn test_empty() {
+        let html = "".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        // EOFトークンは一度だけ出力される
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
         assert!(tokenizer.next().is_none());
     }
 
@@ -621,4 +2202,429 @@ mod tests {
             assert_eq!(Some(e), tokenizer.next());
         }
     }
+
+    #[test]
+    fn test_named_character_reference() {
+        let html = "&lt;&amp;&gt".to_string();
+        let tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::Char('<'),
+            HtmlToken::Char('&'),
+            HtmlToken::Char('>'),
+            HtmlToken::Eof,
+        ];
+        assert_eq!(expected.to_vec(), tokenizer.collect::<Vec<HtmlToken>>());
+    }
+
+    #[test]
+    fn test_numeric_character_reference() {
+        let html = "&#169;&#x1F600;&#X41&#128;&#0;".to_string();
+        let tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::Char('\u{a9}'),
+            HtmlToken::Char('\u{1f600}'),
+            HtmlToken::Char('A'),
+            HtmlToken::Char('\u{20ac}'),
+            HtmlToken::Char('\u{fffd}'),
+            HtmlToken::Eof,
+        ];
+        assert_eq!(expected.to_vec(), tokenizer.collect::<Vec<HtmlToken>>());
+    }
+
+    #[test]
+    fn test_ambiguous_ampersand() {
+        let html = "a&b &#x;".to_string();
+        let tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::Char('a'),
+            HtmlToken::Char('&'),
+            HtmlToken::Char('b'),
+            HtmlToken::Char(' '),
+            HtmlToken::Char('&'),
+            HtmlToken::Char('#'),
+            HtmlToken::Char('x'),
+            HtmlToken::Char(';'),
+            HtmlToken::Eof,
+        ];
+        assert_eq!(expected.to_vec(), tokenizer.collect::<Vec<HtmlToken>>());
+    }
+
+    #[test]
+    fn test_character_reference_in_attribute() {
+        let html = "<a href=\"?a=1&amp;b=2&copy=3\" title='&quot;x&quot;' alt=&lt;>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+
+        let mut attr1 = Attribute::new();
+        for c in "href".chars() {
+            attr1.add_char(c, true);
+        }
+        // 属性値の中で";"で終わらず、"="が続く文字参照は置き換えない
+        for c in "?a=1&b=2&copy=3".chars() {
+            attr1.add_char(c, false);
+        }
+
+        let mut attr2 = Attribute::new();
+        for c in "title".chars() {
+            attr2.add_char(c, true);
+        }
+        for c in "\"x\"".chars() {
+            attr2.add_char(c, false);
+        }
+
+        let mut attr3 = Attribute::new();
+        for c in "alt".chars() {
+            attr3.add_char(c, true);
+        }
+        attr3.add_char('<', false);
+
+        let expected = HtmlToken::StartTag {
+            tag: "a".to_string(),
+            self_closing: false,
+            attributes: vec![attr1, attr2, attr3],
+        };
+        assert_eq!(Some(expected), tokenizer.next());
+    }
+
+    #[test]
+    fn test_comment() {
+        let html = "<!-- a -- b --><!---->".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::Comment(" a -- b ".to_string()),
+            HtmlToken::Comment("".to_string()),
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_bogus_comment() {
+        let html = "<?xml version=\"1.0\"?></ x><![CDATA[foo]]>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::Comment("?xml version=\"1.0\"?".to_string()),
+            HtmlToken::Comment(" x".to_string()),
+            HtmlToken::Comment("[CDATA[foo]]".to_string()),
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_cdata_in_foreign_content() {
+        let html = "<![CDATA[a<b]]]>c".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        tokenizer.set_in_foreign_content(true);
+        let expected = [
+            HtmlToken::Char('a'),
+            HtmlToken::Char('<'),
+            HtmlToken::Char('b'),
+            HtmlToken::Char(']'),
+            HtmlToken::Char('c'),
+            HtmlToken::Eof,
+        ];
+        assert_eq!(expected.to_vec(), tokenizer.collect::<Vec<HtmlToken>>());
+    }
+
+    #[test]
+    fn test_doctype() {
+        let html = "<!DOCTYPE html><!doctype HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\" 'http://www.w3.org/TR/html4/strict.dtd'><!DOCTYPE>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::Doctype {
+                name: Some("html".to_string()),
+                public_identifier: None,
+                system_identifier: None,
+                force_quirks: false,
+            },
+            HtmlToken::Doctype {
+                name: Some("html".to_string()),
+                public_identifier: Some("-//W3C//DTD HTML 4.01//EN".to_string()),
+                system_identifier: Some("http://www.w3.org/TR/html4/strict.dtd".to_string()),
+                force_quirks: false,
+            },
+            HtmlToken::Doctype {
+                name: None,
+                public_identifier: None,
+                system_identifier: None,
+                force_quirks: true,
+            },
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+    }
+
+    /// 開始タグを読んだ後にトークナイザの状態を切り替え、残りのトークンを集める
+    fn tokenize_text_element(html: &str, state: State) -> Vec<HtmlToken> {
+        let mut tokenizer = HtmlTokenizer::new(html.to_string());
+        let mut tokens = vec![tokenizer.next().expect("failed to get a start tag")];
+        tokenizer.set_state(state);
+        for t in tokenizer {
+            tokens.push(t);
+        }
+        tokens
+    }
+
+    fn chars(s: &str) -> Vec<HtmlToken> {
+        s.chars().map(HtmlToken::Char).collect()
+    }
+
+    #[test]
+    fn test_rcdata() {
+        let tokens = tokenize_text_element("<title>a<b>&amp;</x></title>", State::Rcdata);
+        let mut expected = vec![HtmlToken::StartTag {
+            tag: "title".to_string(),
+            self_closing: false,
+            attributes: Vec::new(),
+        }];
+        expected.extend(chars("a<b>&</x>"));
+        expected.push(HtmlToken::EndTag {
+            tag: "title".to_string(),
+        });
+        expected.push(HtmlToken::Eof);
+        assert_eq!(expected, tokens);
+    }
+
+    #[test]
+    fn test_rawtext() {
+        let tokens = tokenize_text_element("<style>a > b &amp; </styl</style>", State::Rawtext);
+        let mut expected = vec![HtmlToken::StartTag {
+            tag: "style".to_string(),
+            self_closing: false,
+            attributes: Vec::new(),
+        }];
+        expected.extend(chars("a > b &amp; </styl"));
+        expected.push(HtmlToken::EndTag {
+            tag: "style".to_string(),
+        });
+        expected.push(HtmlToken::Eof);
+        assert_eq!(expected, tokens);
+    }
+
+    #[test]
+    fn test_script_data() {
+        let tokens = tokenize_text_element("<script>if (a<b) {}</script>", State::ScriptData);
+        let mut expected = vec![HtmlToken::StartTag {
+            tag: "script".to_string(),
+            self_closing: false,
+            attributes: Vec::new(),
+        }];
+        expected.extend(chars("if (a<b) {}"));
+        expected.push(HtmlToken::EndTag {
+            tag: "script".to_string(),
+        });
+        expected.push(HtmlToken::Eof);
+        assert_eq!(expected, tokens);
+    }
+
+    fn error_kinds(tokenizer: &HtmlTokenizer) -> Vec<ParseErrorKind> {
+        tokenizer.errors().iter().map(|e| e.kind()).collect()
+    }
+
+    #[test]
+    fn test_end_tag_with_attributes() {
+        let mut tokenizer = HtmlTokenizer::new("</p class=\"x\"></a/>".to_string());
+        let expected = [
+            HtmlToken::EndTag {
+                tag: "p".to_string(),
+            },
+            HtmlToken::EndTag {
+                tag: "a".to_string(),
+            },
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+        assert_eq!(
+            vec![
+                ParseErrorKind::EndTagWithAttributes,
+                ParseErrorKind::EndTagWithTrailingSolidus
+            ],
+            error_kinds(&tokenizer)
+        );
+    }
+
+    #[test]
+    fn test_attribute_recovery() {
+        let mut tokenizer = HtmlTokenizer::new("<a href=\"x\"title='y' / >".to_string());
+        let mut attr1 = Attribute::new();
+        for c in "href".chars() {
+            attr1.add_char(c, true);
+        }
+        attr1.add_char('x', false);
+        let mut attr2 = Attribute::new();
+        for c in "title".chars() {
+            attr2.add_char(c, true);
+        }
+        attr2.add_char('y', false);
+        let expected = HtmlToken::StartTag {
+            tag: "a".to_string(),
+            self_closing: false,
+            attributes: vec![attr1, attr2],
+        };
+        assert_eq!(Some(expected), tokenizer.next());
+        assert_eq!(
+            vec![
+                ParseErrorKind::MissingWhitespaceBetweenAttributes,
+                ParseErrorKind::UnexpectedSolidusInTag
+            ],
+            error_kinds(&tokenizer)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_attribute() {
+        let mut tokenizer = HtmlTokenizer::new("<p id=a ID=b class=c id=d CLASS=e>".to_string());
+        let mut id = Attribute::new();
+        id.set_name("id".to_string());
+        id.set_value("a".to_string());
+        let mut class = Attribute::new();
+        class.set_name("class".to_string());
+        class.set_value("c".to_string());
+        let expected = HtmlToken::StartTag {
+            tag: "p".to_string(),
+            self_closing: false,
+            attributes: vec![id, class],
+        };
+        assert_eq!(Some(expected), tokenizer.next());
+        assert_eq!(
+            vec![
+                ParseErrorKind::DuplicateAttribute,
+                ParseErrorKind::DuplicateAttribute,
+                ParseErrorKind::DuplicateAttribute
+            ],
+            error_kinds(&tokenizer)
+        );
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let mut tokenizer = HtmlTokenizer::new("a < b\n  <?x>\n</>".to_string());
+        let mut tokens = Vec::new();
+        for t in tokenizer.by_ref() {
+            tokens.push(t);
+        }
+        let mut expected = chars("a < b\n  ");
+        expected.push(HtmlToken::Comment("?x".to_string()));
+        expected.extend(chars("\n"));
+        expected.push(HtmlToken::Eof);
+        assert_eq!(expected, tokens);
+
+        let errors = tokenizer.errors();
+        assert_eq!(
+            vec![
+                ParseErrorKind::InvalidFirstCharacterOfTagName,
+                ParseErrorKind::UnexpectedQuestionMarkInsteadOfTagName,
+                ParseErrorKind::MissingEndTagName
+            ],
+            error_kinds(&tokenizer)
+        );
+        assert_eq!(Position::new(1, 4), errors[0].position());
+        assert_eq!(Position::new(2, 4), errors[1].position());
+        assert_eq!(Position::new(3, 3), errors[2].position());
+    }
+
+    /// 入力をchunk_sizeバイトごとに区切って与え、得られたトークンを集める
+    fn tokenize_in_chunks(html: &str, chunk_size: usize) -> (Vec<HtmlToken>, Vec<ParseError>) {
+        let mut tokenizer = HtmlTokenizer::new_streaming();
+        let mut tokens = Vec::new();
+        for chunk in html.as_bytes().chunks(chunk_size) {
+            tokenizer.feed(chunk);
+            for t in tokenizer.by_ref() {
+                tokens.push(t);
+            }
+        }
+        tokenizer.finish();
+        for t in tokenizer.by_ref() {
+            tokens.push(t);
+        }
+        (tokens, tokenizer.errors())
+    }
+
+    #[test]
+    fn test_feed_chunks() {
+        let html = "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\">\n<p class=\"あ\">&notin; &not &#x41;<!-- c --></p foo>い<!--";
+        let mut tokenizer = HtmlTokenizer::new(html.to_string());
+        let mut expected = Vec::new();
+        for t in tokenizer.by_ref() {
+            expected.push(t);
+        }
+
+        for chunk_size in 1..=html.len() {
+            assert_eq!(
+                (expected.clone(), tokenizer.errors()),
+                tokenize_in_chunks(html, chunk_size),
+                "chunk size: {}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn test_feed_invalid_utf8() {
+        let mut tokenizer = HtmlTokenizer::new_streaming();
+        // "あ"(E3 81 82)の途中で区切る
+        tokenizer.feed(&[b'a', 0xE3, 0x81]);
+        assert_eq!(Some(HtmlToken::Char('a')), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+        tokenizer.feed(&[0x82, 0xFF, 0xE3]);
+        assert_eq!(Some(HtmlToken::Char('あ')), tokenizer.next());
+        assert_eq!(Some(HtmlToken::Char('\u{FFFD}')), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+        // 完成しないまま入力が終わったバイト列もU+FFFDになる
+        tokenizer.finish();
+        assert_eq!(Some(HtmlToken::Char('\u{FFFD}')), tokenizer.next());
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_feed_shift_jis() {
+        let mut tokenizer = HtmlTokenizer::new_streaming_with_encoding(Encoding::ShiftJis);
+        // "<p>日本"の"日"(93 FA)の途中で区切る
+        tokenizer.feed(&[b'<', b'p', b'>', 0x93]);
+        tokenizer.feed(&[0xFA, 0x96, 0x7B]);
+        tokenizer.finish();
+        let tokens: Vec<HtmlToken> = tokenizer.collect();
+        let expected = [
+            HtmlToken::StartTag {
+                tag: "p".to_string(),
+                self_closing: false,
+                attributes: Vec::new(),
+            },
+            HtmlToken::Char('日'),
+            HtmlToken::Char('本'),
+            HtmlToken::Eof,
+        ];
+        assert_eq!(expected.to_vec(), tokens);
+    }
+
+    #[test]
+    fn test_write() {
+        let mut tokenizer = HtmlTokenizer::new("<a>c".to_string());
+        assert!(!tokenizer.write("x"));
+        assert!(tokenizer.next().is_some());
+        tokenizer.set_insertion_point();
+        assert!(tokenizer.write("a"));
+        assert!(tokenizer.write("b"));
+        tokenizer.clear_insertion_point();
+        let mut tokens = Vec::new();
+        for t in tokenizer.by_ref() {
+            tokens.push(t);
+        }
+        let mut expected = chars("abc");
+        expected.push(HtmlToken::Eof);
+        assert_eq!(expected, tokens);
+    }
 }
@@ -47,6 +47,21 @@ pub enum Node {
         callee: Option<Rc<Node>>,
         arguments: Vec<Option<Rc<Node>>>,
     },
+    /// https://262.ecma-international.org/#sec-new-operator
+    NewExpression {
+        callee: Option<Rc<Node>>,
+        arguments: Vec<Option<Rc<Node>>>,
+    },
+    /// https://262.ecma-international.org/#sec-object-initializer
+    ObjectExpression {
+        properties: Vec<Option<Rc<Node>>>,
+    },
+    Property {
+        key: Option<Rc<Node>>,
+        value: Option<Rc<Node>>,
+    },
+    /// https://262.ecma-international.org/#sec-boolean-literals
+    BooleanLiteral(bool),
 }
 
 impl Node {
@@ -130,6 +145,25 @@ impl Node {
     ) -> Option<Rc<Self>> {
         Some(Rc::new(Node::CallExpression { callee, arguments }))
     }
+
+    pub fn new_new_expression(
+        callee: Option<Rc<Self>>,
+        arguments: Vec<Option<Rc<Self>>>,
+    ) -> Option<Rc<Self>> {
+        Some(Rc::new(Node::NewExpression { callee, arguments }))
+    }
+
+    pub fn new_object_expression(properties: Vec<Option<Rc<Self>>>) -> Option<Rc<Self>> {
+        Some(Rc::new(Node::ObjectExpression { properties }))
+    }
+
+    pub fn new_property(key: Option<Rc<Self>>, value: Option<Rc<Self>>) -> Option<Rc<Self>> {
+        Some(Rc::new(Node::Property { key, value }))
+    }
+
+    pub fn new_boolean_literal(value: bool) -> Option<Rc<Self>> {
+        Some(Rc::new(Node::BooleanLiteral(value)))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -296,24 +330,42 @@ impl JsParser {
     }
 
     fn member_expression(&mut self) -> Option<Rc<Node>> {
-        let expr = self.primary_expression();
+        if let Some(Token::Keyword(keyword)) = self.t.peek() {
+            if keyword == "new" {
+                // "new"の予約語を消費する
+                assert!(self.t.next().is_some());
+                return self.new_expression();
+            }
+        }
 
-        let t = match self.t.peek() {
-            Some(token) => token,
-            None => return expr,
-        };
+        let mut expr = self.primary_expression();
 
-        match t {
-            Token::Punctuator(c) => {
-                if c == &'.' {
+        // a.b.cやrecords[0].typeのように、プロパティへのアクセスが続く限り読み進める
+        loop {
+            match self.t.peek() {
+                Some(Token::Punctuator('.')) => {
                     // '.'を消費する
                     assert!(self.t.next().is_some());
-                    return Node::new_member_expression(expr, self.identifier());
+                    // '.'の後のプロパティ名は変数として評価しないように、文字列として扱う
+                    let property = match self.t.next() {
+                        Some(Token::Identifier(name)) => Node::new_string_literal(name),
+                        _ => None,
+                    };
+                    expr = Node::new_member_expression(expr, property);
                 }
-
-                expr
+                Some(Token::Punctuator('[')) => {
+                    // '['を消費する
+                    assert!(self.t.next().is_some());
+                    let property = self.assignment_expression();
+                    // ']'を消費する。もし次のトークンが']'でない場合は、解釈できないのでNoneを返す
+                    match self.t.next() {
+                        Some(Token::Punctuator(']')) => {}
+                        _ => return None,
+                    }
+                    expr = Node::new_member_expression(expr, property);
+                }
+                _ => return expr,
             }
-            _ => expr,
         }
     }
 
@@ -327,10 +379,75 @@ impl JsParser {
             Token::Identifier(value) => Node::new_identifer(value),
             Token::StringLiteral(value) => Node::new_string_literal(value),
             Token::Number(value) => Node::new_numeric_literal(value),
+            Token::Keyword(keyword) => match keyword.as_str() {
+                "true" => Node::new_boolean_literal(true),
+                "false" => Node::new_boolean_literal(false),
+                _ => None,
+            },
+            // '{'から始まる場合は、オブジェクトリテラルとして解釈する
+            Token::Punctuator('{') => self.object_literal(),
             _ => None,
         }
     }
 
+    /// https://262.ecma-international.org/#sec-new-operator
+    /// new MutationObserver(callback)のように、"new"の後に続くコンストラクタの呼び出しを解釈する
+    fn new_expression(&mut self) -> Option<Rc<Node>> {
+        let callee = self.member_expression();
+
+        // 引数がない場合は、括弧を省略できる
+        if let Some(Token::Punctuator('(')) = self.t.peek() {
+            // '('を消費する
+            assert!(self.t.next().is_some());
+            return Node::new_new_expression(callee, self.arguments());
+        }
+
+        Node::new_new_expression(callee, Vec::new())
+    }
+
+    /// https://262.ecma-international.org/#sec-object-initializer
+    /// '{'の後に続く、`key: value`の組をカンマで区切った並びを解釈する
+    fn object_literal(&mut self) -> Option<Rc<Node>> {
+        let mut properties = Vec::new();
+
+        loop {
+            // '}'に到達するまで、プロパティを追加する
+            match self.t.peek() {
+                Some(t) => match t {
+                    Token::Punctuator('}') => {
+                        // '}'を消費する
+                        assert!(self.t.next().is_some());
+                        return Node::new_object_expression(properties);
+                    }
+                    Token::Punctuator(',') => {
+                        // ','を消費する
+                        assert!(self.t.next().is_some());
+                    }
+                    _ => {
+                        let key = self.identifier();
+                        match self.t.peek() {
+                            Some(Token::Punctuator(':')) => {
+                                // ':'を消費する
+                                assert!(self.t.next().is_some());
+                                properties
+                                    .push(Node::new_property(key, self.assignment_expression()));
+                            }
+                            // {childList}のような省略記法では、同じ名前の変数の値をプロパティの値にする
+                            Some(Token::Punctuator(',')) | Some(Token::Punctuator('}'))
+                                if key.is_some() =>
+                            {
+                                properties.push(Node::new_property(key.clone(), key));
+                            }
+                            // サポートしていない構文の場合は、オブジェクトを作らない
+                            _ => return None,
+                        }
+                    }
+                },
+                None => return Node::new_object_expression(properties),
+            }
+        }
+    }
+
     fn variable_declaration(&mut self) -> Option<Rc<Node>> {
         let ident = self.identifier();
 
@@ -458,7 +575,10 @@ impl JsParser {
                         if c == &',' {
                             // ','を消費する
                             assert!(self.t.next().is_some());
+                            continue;
                         }
+                        // '{'から始まるオブジェクトリテラルのように、記号から始まる引数
+                        arguments.push(self.assignment_expression());
                     }
                     _ => arguments.push(self.assignment_expression()),
                 },
@@ -645,4 +765,85 @@ mod tests {
         expected.set_body(body);
         assert_eq!(expected, parser.parse_ast());
     }
+
+    #[test]
+    fn test_new_expression() {
+        let input = "var o = new Foo(f, { a: true, b: false });".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let mut expected = Program::new();
+        let property = |key: &str, value: bool| {
+            Some(Rc::new(Node::Property {
+                key: Some(Rc::new(Node::Identifier(key.to_string()))),
+                value: Some(Rc::new(Node::BooleanLiteral(value))),
+            }))
+        };
+        let declaration = Rc::new(Node::VariableDeclaration {
+            declarations: [Some(Rc::new(Node::VariableDeclarator {
+                id: Some(Rc::new(Node::Identifier("o".to_string()))),
+                init: Some(Rc::new(Node::NewExpression {
+                    callee: Some(Rc::new(Node::Identifier("Foo".to_string()))),
+                    arguments: [
+                        Some(Rc::new(Node::Identifier("f".to_string()))),
+                        Some(Rc::new(Node::ObjectExpression {
+                            properties: [property("a", true), property("b", false)].to_vec(),
+                        })),
+                    ]
+                    .to_vec(),
+                })),
+            }))]
+            .to_vec(),
+        });
+        expected.set_body([declaration].to_vec());
+        assert_eq!(expected, parser.parse_ast());
+    }
+
+    #[test]
+    fn test_shorthand_property() {
+        let input = "var o = { childList };".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let mut expected = Program::new();
+        let child_list = Some(Rc::new(Node::Identifier("childList".to_string())));
+        let declaration = Rc::new(Node::VariableDeclaration {
+            declarations: [Some(Rc::new(Node::VariableDeclarator {
+                id: Some(Rc::new(Node::Identifier("o".to_string()))),
+                init: Some(Rc::new(Node::ObjectExpression {
+                    properties: [Some(Rc::new(Node::Property {
+                        key: child_list.clone(),
+                        value: child_list,
+                    }))]
+                    .to_vec(),
+                })),
+            }))]
+            .to_vec(),
+        });
+        expected.set_body([declaration].to_vec());
+        assert_eq!(expected, parser.parse_ast());
+
+        // サポートしていない構文でもパニックしない
+        let input = "var o = { a b };".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        parser.parse_ast();
+    }
+
+    #[test]
+    fn test_member_expression() {
+        let input = "records[0].type;".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let mut expected = Program::new();
+        let expression = Rc::new(Node::ExpressionStatement(Some(Rc::new(
+            Node::MemberExpression {
+                object: Some(Rc::new(Node::MemberExpression {
+                    object: Some(Rc::new(Node::Identifier("records".to_string()))),
+                    property: Some(Rc::new(Node::NumericLiteral(0))),
+                })),
+                property: Some(Rc::new(Node::StringLiteral("type".to_string()))),
+            },
+        ))));
+        expected.set_body([expression].to_vec());
+        assert_eq!(expected, parser.parse_ast());
+    }
 }
@@ -1,5 +1,24 @@
 use crate::renderer::dom::api::get_element_by_id;
-use crate::renderer::dom::node::Node as DomNode;
+use crate::renderer::dom::api::set_inner_html;
+use crate::renderer::dom::api::set_outer_html;
+use crate::renderer::dom::mutation::append_child;
+use crate::renderer::dom::mutation::change_attributes;
+use crate::renderer::dom::mutation::insert_before;
+use crate::renderer::dom::mutation::normalize;
+use crate::renderer::dom::mutation::remove_child;
+use crate::renderer::dom::mutation::replace_all;
+use crate::renderer::dom::mutation::replace_child;
+use crate::renderer::dom::mutation::replace_data;
+use crate::renderer::dom::mutation_observer::disconnect;
+use crate::renderer::dom::mutation_observer::observe;
+use crate::renderer::dom::mutation_observer::take_records;
+use crate::renderer::dom::mutation_observer::MutationObserverId;
+use crate::renderer::dom::mutation_observer::MutationObserverInit;
+use crate::renderer::dom::mutation_observer::MutationRecord;
+use crate::renderer::dom::mutation_observer::MutationRecordType;
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::Element as DomElement;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::NodeKind as DomNodeKind;
 use crate::renderer::js::ast::Node;
 use crate::renderer::js::ast::Program;
@@ -21,7 +40,17 @@ pub enum RuntimeValue {
     Number(u64),
     StringLiteral(String),
     HtmlElement {
-        object: Rc<RefCell<DomNode>>,
+        object: NodeId,
+        property: Option<String>,
+    },
+    /// https://262.ecma-international.org/#sec-ecmascript-language-types-boolean-type
+    Boolean(bool),
+    /// https://262.ecma-international.org/#sec-object-type
+    /// プロパティの名前と値の組を、定義された順に持つ
+    Object(Vec<(String, RuntimeValue)>),
+    /// https://dom.spec.whatwg.org/#interface-mutationobserver
+    MutationObserver {
+        object: MutationObserverId,
         property: Option<String>,
     },
 }
@@ -35,7 +64,15 @@ impl Display for RuntimeValue {
                 object,
                 property: _,
             } => {
-                format!("HtmlElement {:#?}", object)
+                format!("HtmlElement {:?}", object)
+            }
+            RuntimeValue::Boolean(value) => format!("{}", value),
+            RuntimeValue::Object(_) => "[object Object]".to_string(),
+            RuntimeValue::MutationObserver {
+                object,
+                property: _,
+            } => {
+                format!("MutationObserver {:?}", object)
             }
         };
         write!(f, "{}", s)
@@ -66,6 +103,51 @@ impl Sub<RuntimeValue> for RuntimeValue {
     }
 }
 
+/// https://dom.spec.whatwg.org/#interface-mutationrecord
+/// MutationObserverのコールバックに渡す記録の列を作る。
+/// 配列をサポートしていないため、記録の列は"0"、"1"などの添字と"length"をキーに持つオブジェクトで表す。
+/// それぞれの記録は、type、target、attributeName、oldValueのプロパティを持つ
+fn mutation_records_value(records: &[MutationRecord]) -> RuntimeValue {
+    let mut list = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let record_type = match record.record_type() {
+            MutationRecordType::ChildList => "childList",
+            MutationRecordType::Attributes => "attributes",
+            MutationRecordType::CharacterData => "characterData",
+        };
+        let mut properties = Vec::new();
+        properties.push((
+            "type".to_string(),
+            RuntimeValue::StringLiteral(record_type.to_string()),
+        ));
+        properties.push((
+            "target".to_string(),
+            RuntimeValue::HtmlElement {
+                object: record.target(),
+                property: None,
+            },
+        ));
+        if let Some(name) = record.attribute_name() {
+            properties.push((
+                "attributeName".to_string(),
+                RuntimeValue::StringLiteral(name),
+            ));
+        }
+        if let Some(old_value) = record.old_value() {
+            properties.push((
+                "oldValue".to_string(),
+                RuntimeValue::StringLiteral(old_value),
+            ));
+        }
+        list.push((i.to_string(), RuntimeValue::Object(properties)));
+    }
+    list.push((
+        "length".to_string(),
+        RuntimeValue::Number(records.len() as u64),
+    ));
+    RuntimeValue::Object(list)
+}
+
 type VariableMap = Vec<(String, Option<RuntimeValue>)>;
 
 /// https:://262.ecma-international.org/#sec-environment-records
@@ -97,6 +179,11 @@ impl Environment {
     }
 
     fn add_variable(&mut self, name: String, value: Option<RuntimeValue>) {
+        // 同じスコープで再び宣言された場合は、既存の変数の値を置き換える
+        if let Some(variable) = self.variables.iter_mut().find(|v| v.0 == name) {
+            variable.1 = value;
+            return;
+        }
         self.variables.push((name, value));
     }
 
@@ -127,17 +214,23 @@ impl Function {
 
 #[derive(Debug, Clone)]
 pub struct JsRuntime {
-    dom_root: Rc<RefCell<DomNode>>,
+    dom: Rc<RefCell<Dom>>,
+    /// スクリプトから`document`として参照される文書
+    document: NodeId,
     env: Rc<RefCell<Environment>>,
     functions: Vec<Function>,
+    /// スクリプトが作ったMutationObserverと、そのコールバックの関数名
+    observers: Vec<(MutationObserverId, String)>,
 }
 
 impl JsRuntime {
-    pub fn new(dom_root: Rc<RefCell<DomNode>>) -> Self {
+    pub fn new(dom: Rc<RefCell<Dom>>, document: NodeId) -> Self {
         Self {
-            dom_root,
+            dom,
+            document,
             functions: Vec::new(),
             env: Rc::new(RefCell::new(Environment::new(None))),
+            observers: Vec::new(),
         }
     }
 
@@ -145,6 +238,56 @@ impl JsRuntime {
         for node in program.body() {
             self.eval(&Some(node.clone()), self.env.clone());
         }
+
+        // https://html.spec.whatwg.org/multipage/webappapis.html#clean-up-after-running-script
+        // スクリプトの実行が終わった後のマイクロタスクとして、変更をMutationObserverに通知する
+        self.notify_mutation_observers();
+    }
+
+    /// https://dom.spec.whatwg.org/#notify-mutation-observers
+    /// 記録がたまっているMutationObserverのコールバックを、記録の列とオブザーバー自身を引数にして呼ぶ。
+    /// コールバックがさらにDOMツリーを変更した場合は、記録がなくなるまで繰り返す
+    pub fn notify_mutation_observers(&mut self) {
+        loop {
+            let mut notified = false;
+
+            for (observer, callback) in self.observers.clone() {
+                let records = take_records(&mut self.dom.borrow_mut(), observer);
+                if records.is_empty() {
+                    continue;
+                }
+                notified = true;
+
+                let function = match self.functions.iter().rev().find(|f| f.id == callback) {
+                    Some(f) => f.clone(),
+                    None => continue,
+                };
+                let arguments = [
+                    mutation_records_value(&records),
+                    RuntimeValue::MutationObserver {
+                        object: observer,
+                        property: None,
+                    },
+                ];
+
+                // コールバックをグローバル環境の下の新しいスコープで呼ぶ
+                let new_env = Rc::new(RefCell::new(Environment::new(Some(self.env.clone()))));
+                for (param, argument) in function.params.iter().zip(arguments) {
+                    if let Some(node) = param {
+                        if let Node::Identifier(name) = node.borrow() {
+                            new_env
+                                .borrow_mut()
+                                .add_variable(name.to_string(), Some(argument));
+                        }
+                    }
+                }
+                self.eval(&function.body, new_env);
+            }
+
+            if !notified {
+                return;
+            }
+        }
     }
 
     fn eval(
@@ -210,11 +353,28 @@ impl JsRuntime {
                     if let Some(p) = property {
                         // target.textContent = "foobar";のようにノードのテキストを変更する
                         if p == "textContent" {
-                            object
-                                .borrow_mut()
-                                .set_first_child(Some(Rc::new(RefCell::new(DomNode::new(
-                                    DomNodeKind::Text(right_value.to_string()),
-                                )))));
+                            let text = right_value.to_string();
+                            let mut dom = self.dom.borrow_mut();
+                            // テキストノードとコメントは、子を持つ代わりに自身の文字列を置き換える
+                            if matches!(
+                                dom.node(object).kind,
+                                DomNodeKind::Text(_) | DomNodeKind::Comment(_)
+                            ) {
+                                replace_data(&mut dom, object, text);
+                                return None;
+                            }
+                            let node = match text.is_empty() {
+                                true => None,
+                                false => Some(dom.create_node(DomNodeKind::Text(text))),
+                            };
+                            replace_all(&mut dom, node, object);
+                        }
+                        // target.innerHTML = "<b>foo</b>";のようにノードの子をHTMLで置き換える
+                        if p == "innerHTML" {
+                            set_inner_html(&self.dom, object, &right_value.to_string());
+                        }
+                        if p == "outerHTML" {
+                            set_outer_html(&self.dom, object, &right_value.to_string());
                         }
                     }
                 }
@@ -230,9 +390,31 @@ impl JsRuntime {
                     // プロパティが存在しないため、object_valueをここで返す
                     None => return Some(object_value),
                 };
+                // オブジェクトの場合は、プロパティの値を返す
+                if let RuntimeValue::Object(properties) = &object_value {
+                    let name = property_value.to_string();
+                    return properties
+                        .iter()
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| value.clone());
+                }
+                // observer.observeのように、MutationObserverのpropertyにメソッド名をセットする
+                if let RuntimeValue::MutationObserver { object, property } = object_value {
+                    // メソッドのプロパティにはアクセスできない
+                    if property.is_some() {
+                        return None;
+                    }
+                    return Some(RuntimeValue::MutationObserver {
+                        object,
+                        property: Some(property_value.to_string()),
+                    });
+                }
                 // もしオブジェクトがDOMノードの場合、HtmlElementのpropertyを更新する
                 if let RuntimeValue::HtmlElement { object, property } = object_value {
-                    assert!(property.is_none());
+                    // element.style.colorのような、プロパティのプロパティはサポートしていない
+                    if property.is_some() {
+                        return None;
+                    }
                     // HtmlElementのpropertyにproperty_valueの文字列をセットする
                     return Some(RuntimeValue::HtmlElement {
                         object,
@@ -240,6 +422,17 @@ impl JsRuntime {
                     });
                 }
 
+                // https://html.spec.whatwg.org/multipage/dom.html#dom-document-readystate
+                if object_value == RuntimeValue::StringLiteral("document".to_string())
+                    && property_value == RuntimeValue::StringLiteral("readyState".to_string())
+                {
+                    let dom = RefCell::borrow(&self.dom);
+                    return dom
+                        .node(self.document)
+                        .get_document()
+                        .map(|d| RuntimeValue::StringLiteral(d.ready_state().to_string()));
+                }
+
                 // document.getElementByIdは、"document.getElementById"という一つの文字列として扱う
                 // このメソッドへの呼び出しは、"document.getElementById"という名前の関数への呼び出しになる
                 return Some(
@@ -337,6 +530,53 @@ impl JsRuntime {
                 };
                 None
             }
+            Node::NewExpression { callee, arguments } => {
+                let callee_value = match self.eval(callee, env.clone()) {
+                    Some(value) => value,
+                    None => return None,
+                };
+
+                // https://dom.spec.whatwg.org/#dom-mutationobserver-mutationobserver
+                // コールバックは関数名として受け取る
+                if callee_value == RuntimeValue::StringLiteral("MutationObserver".to_string()) {
+                    let callback = match arguments.first() {
+                        Some(argument) => match self.eval(argument, env.clone()) {
+                            Some(value) => value.to_string(),
+                            None => return None,
+                        },
+                        None => return None,
+                    };
+                    let observer = self.dom.borrow_mut().create_observer();
+                    self.observers.push((observer, callback));
+                    return Some(RuntimeValue::MutationObserver {
+                        object: observer,
+                        property: None,
+                    });
+                }
+
+                None
+            }
+            Node::ObjectExpression { properties } => {
+                let mut object = Vec::new();
+                for property in properties.iter().flatten() {
+                    if let Node::Property {
+                        key: Some(key),
+                        value,
+                    } = property.borrow()
+                    {
+                        // キーは変数として評価せず、識別子の名前をそのまま使う
+                        if let Node::Identifier(name) = key.borrow() {
+                            if let Some(value) = self.eval(value, env.clone()) {
+                                object.push((name.to_string(), value));
+                            }
+                        }
+                    }
+                }
+                Some(RuntimeValue::Object(object))
+            }
+            // プロパティはObjectExpressionの中で評価する
+            Node::Property { key: _, value: _ } => None,
+            Node::BooleanLiteral(value) => Some(RuntimeValue::Boolean(*value)),
         }
     }
 
@@ -354,7 +594,11 @@ impl JsRuntime {
                 Some(a) => a,
                 None => return (true, None),
             };
-            let target = match get_element_by_id(Some(self.dom_root.clone()), &arg.to_string()) {
+            let target = match get_element_by_id(
+                &RefCell::borrow(&self.dom),
+                self.document,
+                &arg.to_string(),
+            ) {
                 Some(t) => t,
                 None => return (true, None),
             };
@@ -367,6 +611,187 @@ impl JsRuntime {
             );
         }
 
+        // document.createElement("p")やdocument.createTextNode("text")で、どの親にも属さないノードを作る
+        if func == &RuntimeValue::StringLiteral("document.createElement".to_string())
+            || func == &RuntimeValue::StringLiteral("document.createTextNode".to_string())
+        {
+            let arg = match self.eval(&arguments[0], env.clone()) {
+                Some(a) => a.to_string(),
+                None => return (true, None),
+            };
+            let kind = match func.to_string().as_str() {
+                "document.createElement" => {
+                    DomNodeKind::Element(DomElement::new(&arg.to_ascii_lowercase(), Vec::new()))
+                }
+                _ => DomNodeKind::Text(arg),
+            };
+            return (
+                true,
+                Some(RuntimeValue::HtmlElement {
+                    object: self.dom.borrow_mut().create_node(kind),
+                    property: None,
+                }),
+            );
+        }
+
+        // observer.observe(target, { childList: true })のように、MutationObserverで変更を監視する
+        if let RuntimeValue::MutationObserver {
+            object,
+            property: Some(method),
+        } = func
+        {
+            match method.as_str() {
+                "observe" => {
+                    let target = match arguments.first().and_then(|a| self.eval(a, env.clone())) {
+                        Some(RuntimeValue::HtmlElement {
+                            object,
+                            property: None,
+                        }) => object,
+                        _ => return (true, None),
+                    };
+                    let properties = match arguments.get(1).and_then(|a| self.eval(a, env.clone()))
+                    {
+                        Some(RuntimeValue::Object(properties)) => properties,
+                        _ => Vec::new(),
+                    };
+                    // 指定されていないオプションはNoneになる
+                    let given = |name: &str| {
+                        properties
+                            .iter()
+                            .find(|(key, _)| key == name)
+                            .map(|(_, value)| *value == RuntimeValue::Boolean(true))
+                    };
+                    let option = |name: &str| given(name).unwrap_or(false);
+                    let attribute_old_value = option("attributeOldValue");
+                    let character_data_old_value = option("characterDataOldValue");
+                    // https://dom.spec.whatwg.org/#dom-mutationobserver-observe
+                    // 手順1と2: attributesとcharacterDataが指定されていない場合は、古い値を記録するかどうかから決める
+                    let attributes = given("attributes").unwrap_or(attribute_old_value);
+                    let character_data = given("characterData").unwrap_or(character_data_old_value);
+                    // 手順3と4: falseを指定したのに古い値を記録しようとした場合はTypeErrorになるが、
+                    // 例外はサポートしていないため、監視せずに何もしない
+                    if (attribute_old_value && !attributes)
+                        || (character_data_old_value && !character_data)
+                    {
+                        return (true, None);
+                    }
+                    let options = MutationObserverInit {
+                        child_list: option("childList"),
+                        attributes,
+                        character_data,
+                        subtree: option("subtree"),
+                        attribute_old_value,
+                        character_data_old_value,
+                        attribute_filter: None,
+                    };
+                    // 例外はサポートしていないため、監視できなかった場合は何もしない
+                    let _ = observe(&mut self.dom.borrow_mut(), *object, target, options);
+                }
+                "disconnect" => disconnect(&mut self.dom.borrow_mut(), *object),
+                _ => return (false, None),
+            }
+            return (true, None);
+        }
+
+        // target.setAttribute("class", "a")のように、要素の属性を読み書きする
+        if let RuntimeValue::HtmlElement {
+            object,
+            property: Some(method),
+        } = func
+        {
+            if matches!(
+                method.as_str(),
+                "getAttribute"
+                    | "hasAttribute"
+                    | "setAttribute"
+                    | "removeAttribute"
+                    | "toggleAttribute"
+            ) {
+                let mut values = Vec::new();
+                for argument in arguments {
+                    values.push(self.eval(argument, env.clone()));
+                }
+                let name = match values.first() {
+                    Some(Some(value)) => value.to_string(),
+                    _ => return (true, None),
+                };
+                let value = values.get(1).cloned().flatten();
+
+                let mut dom = self.dom.borrow_mut();
+                let element = match dom.node(*object).get_element() {
+                    Some(e) => e,
+                    None => return (true, None),
+                };
+                let result = match method.as_str() {
+                    "getAttribute" => element
+                        .get_attribute(&name)
+                        .map(RuntimeValue::StringLiteral),
+                    "hasAttribute" => Some(RuntimeValue::Boolean(element.has_attribute(&name))),
+                    "setAttribute" => {
+                        let value = value.map(|v| v.to_string()).unwrap_or_default();
+                        change_attributes(&mut dom, *object, |e| e.set_attribute(&name, &value));
+                        None
+                    }
+                    "removeAttribute" => {
+                        change_attributes(&mut dom, *object, |e| e.remove_attribute(&name));
+                        None
+                    }
+                    _ => {
+                        let force = match value {
+                            Some(RuntimeValue::Boolean(force)) => Some(force),
+                            _ => None,
+                        };
+                        // 例外はサポートしていないため、変更できなかった場合は何も返さない
+                        change_attributes(&mut dom, *object, |e| e.toggle_attribute(&name, force))
+                            .and_then(|r| r.ok())
+                            .map(RuntimeValue::Boolean)
+                    }
+                };
+                return (true, result);
+            }
+        }
+
+        // target.appendChild(node)のように、DOMノードのメソッドでツリーを変更する
+        if let RuntimeValue::HtmlElement {
+            object,
+            property: Some(method),
+        } = func
+        {
+            let mut nodes = Vec::new();
+            for argument in arguments {
+                match self.eval(argument, env.clone()) {
+                    Some(RuntimeValue::HtmlElement {
+                        object,
+                        property: None,
+                    }) => nodes.push(Some(object)),
+                    _ => nodes.push(None),
+                }
+            }
+            let node = |i: usize| nodes.get(i).copied().flatten();
+
+            let object = *object;
+            let mut dom = self.dom.borrow_mut();
+            let result = match (method.as_str(), node(0), node(1)) {
+                ("appendChild", Some(n), _) => append_child(&mut dom, object, n),
+                ("insertBefore", Some(n), child) => insert_before(&mut dom, object, n, child),
+                ("removeChild", Some(child), _) => remove_child(&mut dom, object, child),
+                ("replaceChild", Some(n), Some(child)) => replace_child(&mut dom, object, n, child),
+                ("normalize", _, _) => {
+                    normalize(&mut dom, object);
+                    return (true, None);
+                }
+                _ => return (false, None),
+            };
+            // 例外はサポートしていないため、ツリーを変更できなかった場合は何も返さない
+            return (
+                true,
+                result.ok().map(|n| RuntimeValue::HtmlElement {
+                    object: n,
+                    property: None,
+                }),
+            );
+        }
+
         (false, None)
     }
 }
@@ -374,6 +799,10 @@ impl JsRuntime {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::renderer::dom::api::inner_html;
+    use crate::renderer::dom::node::Window;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
     use crate::renderer::js::ast::JsParser;
     use crate::renderer::js::token::JsLexer;
 
@@ -383,8 +812,8 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
-        let mut runtime = JsRuntime::new(dom);
+        let window = Window::new();
+        let mut runtime = JsRuntime::new(window.dom(), window.document());
         let expected = [Some(RuntimeValue::Number(42))];
         let mut i = 0;
 
@@ -401,8 +830,8 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
-        let mut runtime = JsRuntime::new(dom);
+        let window = Window::new();
+        let mut runtime = JsRuntime::new(window.dom(), window.document());
         let expected = [Some(RuntimeValue::Number(3))];
         let mut i = 0;
 
@@ -419,8 +848,8 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
-        let mut runtime = JsRuntime::new(dom);
+        let window = Window::new();
+        let mut runtime = JsRuntime::new(window.dom(), window.document());
         let expected = [Some(RuntimeValue::Number(1))];
         let mut i = 0;
 
@@ -437,8 +866,8 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
-        let mut runtime = JsRuntime::new(dom);
+        let window = Window::new();
+        let mut runtime = JsRuntime::new(window.dom(), window.document());
         let expected = [None];
         let mut i = 0;
 
@@ -455,8 +884,8 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
-        let mut runtime = JsRuntime::new(dom);
+        let window = Window::new();
+        let mut runtime = JsRuntime::new(window.dom(), window.document());
         let expected = [None, Some(RuntimeValue::Number(43))];
         let mut i = 0;
 
@@ -473,8 +902,8 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
-        let mut runtime = JsRuntime::new(dom);
+        let window = Window::new();
+        let mut runtime = JsRuntime::new(window.dom(), window.document());
         let expected = [None, None, Some(RuntimeValue::Number(1))];
         let mut i = 0;
 
@@ -484,4 +913,212 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_redeclare_variable() {
+        let input = "var foo=42; var foo=1; foo".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let window = Window::new();
+        let mut runtime = JsRuntime::new(window.dom(), window.document());
+        let expected = [None, None, Some(RuntimeValue::Number(1))];
+
+        for (i, node) in ast.body().iter().enumerate() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+        }
+    }
+
+    #[test]
+    fn test_set_inner_html() {
+        let html = "<div id=\"target\">text</div>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).dom();
+        let document = RefCell::borrow(&window).document();
+        let input =
+            "var target = document.getElementById(\"target\"); target.innerHTML = \"<b>bold</b>\";"
+                .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        runtime.execute(&ast);
+
+        let dom = RefCell::borrow(&dom);
+        let target = get_element_by_id(&dom, document, &"target".to_string()).unwrap();
+        assert_eq!("<b>bold</b>".to_string(), inner_html(&dom, target));
+    }
+
+    #[test]
+    fn test_text_content() {
+        let html = "<div id=\"target\">a<b>b</b></div>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).dom();
+        let document = RefCell::borrow(&window).document();
+        let input =
+            "var target = document.getElementById(\"target\"); target.textContent = \"text\";"
+                .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        runtime.execute(&ast);
+
+        // 子はすべて一つのテキストノードに置き換えられる
+        let dom = RefCell::borrow(&dom);
+        let target = get_element_by_id(&dom, document, &"target".to_string()).unwrap();
+        assert_eq!("text".to_string(), inner_html(&dom, target));
+        assert_eq!(
+            dom.node(target).first_child(),
+            dom.node(target).last_child()
+        );
+    }
+
+    #[test]
+    fn test_dom_mutation() {
+        let html = "<div id=\"target\"><p id=\"old\">old</p></div>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).dom();
+        let document = RefCell::borrow(&window).document();
+        let input = r#"var target = document.getElementById("target");
+var old = document.getElementById("old");
+var a = document.createElement("span");
+var b = document.createTextNode("b");
+target.appendChild(a);
+target.insertBefore(b, a);
+target.removeChild(old);
+a.appendChild(document.createTextNode("a"));
+target.appendChild(target);"#
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        runtime.execute(&ast);
+
+        let dom = RefCell::borrow(&dom);
+        let target = get_element_by_id(&dom, document, &"target".to_string()).unwrap();
+        assert_eq!("b<span>a</span>".to_string(), inner_html(&dom, target));
+    }
+
+    #[test]
+    fn test_mutation_observer() {
+        let html = "<div id=\"target\"></div><p id=\"log\"></p>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).dom();
+        let document = RefCell::borrow(&window).document();
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        let scripts = [
+            r#"function callback(records, observer) { var log = document.getElementById("log"); log.textContent = records.length; }
+var target = document.getElementById("target");
+var observer = new MutationObserver(callback);
+observer.observe(target, { childList: true, subtree: true });
+target.appendChild(document.createElement("p"));
+target.appendChild(document.createTextNode("a"));"#,
+            // 監視をやめた後の変更は通知されない
+            r#"observer.disconnect();
+target.appendChild(document.createElement("p"));"#,
+        ];
+        for script in scripts {
+            let lexer = JsLexer::new(script.to_string());
+            let mut parser = JsParser::new(lexer);
+            let ast = parser.parse_ast();
+            runtime.execute(&ast);
+        }
+
+        // スクリプトの実行が終わった後に、2つの変更の記録がまとめてコールバックに渡される
+        let dom = RefCell::borrow(&dom);
+        let log = get_element_by_id(&dom, document, &"log".to_string()).unwrap();
+        assert_eq!("2".to_string(), inner_html(&dom, log));
+    }
+
+    #[test]
+    fn test_attributes() {
+        let html = "<p id=\"target\" title=\"t\"></p><p id=\"log\"></p>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).dom();
+        let document = RefCell::borrow(&window).document();
+        let input = r#"var target = document.getElementById("target");
+var log = document.getElementById("log");
+target.setAttribute("CLASS", "a b");
+target.removeAttribute("title");
+target.toggleAttribute("hidden");
+log.textContent = target.getAttribute("class");"#
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        runtime.execute(&ast);
+
+        let dom = RefCell::borrow(&dom);
+        let target = get_element_by_id(&dom, document, &"target".to_string()).unwrap();
+        assert_eq!(
+            "<p id=\"target\" class=\"a b\" hidden=\"\"></p><p id=\"log\">a b</p>".to_string(),
+            inner_html(&dom, dom.node(target).parent().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_observe_old_value_options() {
+        let html = "<p id=\"target\"></p><p id=\"log\"></p><p id=\"rejected\"></p>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).dom();
+        let document = RefCell::borrow(&window).document();
+        // attributeOldValueだけを指定した場合も属性の変更を監視し、
+        // attributesにfalseを指定した場合は監視しない
+        let input = r#"function callback(records, observer) { var log = document.getElementById("log"); log.textContent = records.length; }
+function reject(records, observer) { var rejected = document.getElementById("rejected"); rejected.textContent = records.length; }
+var target = document.getElementById("target");
+var observer = new MutationObserver(callback);
+observer.observe(target, { attributeOldValue: true });
+var rejecting = new MutationObserver(reject);
+rejecting.observe(target, { attributes: false, attributeOldValue: true });
+target.setAttribute("class", "a");
+target.setAttribute("title", "t");"#
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        runtime.execute(&ast);
+
+        let dom = RefCell::borrow(&dom);
+        let log = get_element_by_id(&dom, document, &"log".to_string()).unwrap();
+        assert_eq!("2".to_string(), inner_html(&dom, log));
+        let rejected = get_element_by_id(&dom, document, &"rejected".to_string()).unwrap();
+        assert_eq!("".to_string(), inner_html(&dom, rejected));
+    }
+
+    #[test]
+    fn test_mutation_records() {
+        let html =
+            "<p id=\"target\" class=\"x\"></p><p id=\"log\"></p><p id=\"old\"></p>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).dom();
+        let document = RefCell::borrow(&window).document();
+        // コールバックには記録の列が渡され、それぞれの記録の種類や対象のノードを読める。
+        // オプションは省略記法でも書ける
+        let input = r#"function callback(records, observer) { var log = document.getElementById("log"); log.textContent = records[0].type + " " + records[0].attributeName + " " + records.length; var old = records[1].target; old.textContent = records[1].oldValue; }
+var target = document.getElementById("target");
+var observer = new MutationObserver(callback);
+var attributeOldValue = true;
+observer.observe(target, { attributeOldValue });
+target.setAttribute("class", "a");
+target.setAttribute("class", "b");"#
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        runtime.execute(&ast);
+
+        let dom = RefCell::borrow(&dom);
+        let log = get_element_by_id(&dom, document, &"log".to_string()).unwrap();
+        assert_eq!("attributes class 2".to_string(), inner_html(&dom, log));
+        // 2つ目の記録の対象は#targetで、変更前の値を持つ
+        let target = get_element_by_id(&dom, document, &"target".to_string()).unwrap();
+        assert_eq!("a".to_string(), inner_html(&dom, target));
+    }
 }
@@ -2,7 +2,7 @@ use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 
-static RESERVED_WORDS: [&str; 3] = ["var", "function", "return"];
+static RESERVED_WORDS: [&str; 6] = ["var", "function", "return", "new", "true", "false"];
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
@@ -55,6 +55,9 @@ impl JsLexer {
 
     fn contains(&self, keyword: &str) -> bool {
         for i in 0..keyword.len() {
+            if self.pos + i >= self.input.len() {
+                return false;
+            }
             if keyword
                 .chars()
                 .nth(i)
@@ -69,7 +72,12 @@ impl JsLexer {
 
     fn check_reserved_word(&self) -> Option<String> {
         for word in RESERVED_WORDS {
-            if self.contains(word) {
+            // newNodeのように、予約語で始まる識別子は予約語として扱わない
+            let is_part_of_identifier = self
+                .input
+                .get(self.pos + word.len())
+                .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '$');
+            if self.contains(word) && !is_part_of_identifier {
                 return Some(word.to_string());
             }
         }
@@ -140,7 +148,7 @@ impl Iterator for JsLexer {
         let c = self.input[self.pos];
 
         let token = match c {
-            '+' | '-' | '=' | ';' | '(' | ')' | '{' | '}' | ',' | '.' => {
+            '+' | '-' | '=' | ';' | '(' | ')' | '{' | '}' | '[' | ']' | ',' | '.' | ':' => {
                 let t = Token::Punctuator(c);
                 self.pos += 1;
                 t
@@ -276,4 +284,31 @@ mod tests {
         }
         assert!(lexer.peek().is_none());
     }
+
+    #[test]
+    fn test_keywords_and_identifiers() {
+        let input = "var newNode = new Foo({ a: true, b: false });".to_string();
+        let tokens: Vec<Token> = JsLexer::new(input).collect();
+        let expected = [
+            Token::Keyword("var".to_string()),
+            Token::Identifier("newNode".to_string()),
+            Token::Punctuator('='),
+            Token::Keyword("new".to_string()),
+            Token::Identifier("Foo".to_string()),
+            Token::Punctuator('('),
+            Token::Punctuator('{'),
+            Token::Identifier("a".to_string()),
+            Token::Punctuator(':'),
+            Token::Keyword("true".to_string()),
+            Token::Punctuator(','),
+            Token::Identifier("b".to_string()),
+            Token::Punctuator(':'),
+            Token::Keyword("false".to_string()),
+            Token::Punctuator('}'),
+            Token::Punctuator(')'),
+            Token::Punctuator(';'),
+        ]
+        .to_vec();
+        assert_eq!(expected, tokens);
+    }
 }
@@ -1,11 +1,10 @@
 use crate::error::Error;
-use crate::renderer::dom::node::ElementKind;
+use crate::renderer::css::cascade::DeclaredValues;
+use crate::renderer::css::value::CssValue;
 use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
 use alloc::format;
-use alloc::rc::Rc;
 use alloc::string::{String, ToString};
-use core::cell::RefCell;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Color {
@@ -89,6 +88,46 @@ impl Color {
         })
     }
 
+    /// 赤、緑、青の値から色を作る。名前のある色の場合は、名前も設定する
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        let code = format!("#{:02x}{:02x}{:02x}", r, g, b);
+        let name = Self::from_code(&code).ok().and_then(|c| c.name);
+        Self { name, code }
+    }
+
+    /// https://www.w3.org/TR/css-color-4/#hex-notation
+    /// #rgbと#rrggbbの形式の色。from_codeと違い、名前のない色も作れる
+    pub fn from_hex(code: &str) -> Result<Self, Error> {
+        let digits = match code.strip_prefix('#') {
+            Some(d) if d.chars().all(|c| c.is_ascii_hexdigit()) => d,
+            _ => {
+                return Err(Error::UnexpectedInput(format!(
+                    "invalid color code {}",
+                    code
+                )))
+            }
+        };
+        let digits = match digits.len() {
+            // #rgbは、それぞれの桁を2回繰り返した#rrggbbと同じ
+            3 => digits.chars().flat_map(|c| [c, c]).collect::<String>(),
+            6 => digits.to_string(),
+            _ => {
+                return Err(Error::UnexpectedInput(format!(
+                    "invalid color code {}",
+                    code
+                )))
+            }
+        };
+
+        let value = u32::from_str_radix(&digits, 16)
+            .map_err(|_| Error::UnexpectedInput(format!("invalid color code {}", code)))?;
+        Ok(Self::from_rgb(
+            (value >> 16) as u8,
+            (value >> 8) as u8,
+            value as u8,
+        ))
+    }
+
     pub fn white() -> Self {
         Self {
             name: Some("white".to_string()),
@@ -116,18 +155,52 @@ pub enum FontSize {
 }
 
 impl FontSize {
-    pub fn default(node: &Rc<RefCell<Node>>) -> Self {
-        match &node.borrow().kind() {
-            NodeKind::Element(element) => match element.kind() {
-                ElementKind::H1 => FontSize::XXLarge,
-                ElementKind::H2 => FontSize::XLarge,
-                _ => FontSize::Medium,
-            },
-            _ => FontSize::Medium,
+    /// https://www.w3.org/TR/css-fonts-4/#absolute-size-mapping
+    pub fn px(&self) -> f64 {
+        match self {
+            FontSize::Medium => 16.0,
+            FontSize::XLarge => 24.0,
+            FontSize::XXLarge => 32.0,
+        }
+    }
+
+    /// 描画できる大きさのうち、pxを超えない最大のもの。mediumより小さい場合はmediumになる
+    pub fn from_px(px: f64) -> Self {
+        if px >= FontSize::XXLarge.px() {
+            FontSize::XXLarge
+        } else if px >= FontSize::XLarge.px() {
+            FontSize::XLarge
+        } else {
+            FontSize::Medium
         }
     }
 }
 
+/// https://www.w3.org/TR/css-fonts-4/#font-size-prop
+/// font-sizeの値をピクセルにする。emと%、larger、smallerは親の大きさを基準にする
+fn font_size_px(value: &CssValue, parent_px: f64) -> Option<f64> {
+    // https://www.w3.org/TR/css-fonts-4/#absolute-size-mapping
+    let px = match value {
+        CssValue::Keyword(keyword) => match keyword.as_str() {
+            "xx-small" => 9.0,
+            "x-small" => 10.0,
+            "small" => 13.0,
+            "medium" => 16.0,
+            "large" => 18.0,
+            "x-large" => 24.0,
+            "xx-large" => 32.0,
+            "xxx-large" => 48.0,
+            "larger" => parent_px * 1.2,
+            "smaller" => parent_px / 1.2,
+            _ => return None,
+        },
+        CssValue::Length(length) => length.to_px(parent_px, FontSize::Medium.px()),
+        CssValue::Percentage(p) => parent_px * p / 100.0,
+        _ => return None,
+    };
+    Some(px)
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DisplayType {
     /// https://www.w3.org/TR/css-display-3/#valdef-display-block
@@ -139,17 +212,15 @@ pub enum DisplayType {
 }
 
 impl DisplayType {
-    fn default(node: &Rc<RefCell<Node>>) -> Self {
-        match &node.borrow().kind() {
-            NodeKind::Document => DisplayType::Block,
-            NodeKind::Element(e) => {
-                if e.is_block_element() {
-                    DisplayType::Block
-                } else {
-                    DisplayType::Inline
-                }
-            }
-            NodeKind::Text(_) => DisplayType::Inline,
+    /// 要素の既定の表示方法は、ユーザーエージェントのスタイルシートで決める。
+    /// ここでは、どの宣言もない場合の初期値を返す
+    fn default(node: &Node) -> Self {
+        match &node.kind {
+            NodeKind::Document(_) | NodeKind::DocumentFragment(_) => DisplayType::Block,
+            // DOCTYPEやコメントは描画しない
+            NodeKind::DocumentType(_) | NodeKind::Comment(_) => DisplayType::DisplayNone,
+            // https://www.w3.org/TR/css-display-3/#the-display-properties
+            NodeKind::Element(_) | NodeKind::Text(_) => DisplayType::Inline,
         }
     }
 
@@ -172,18 +243,6 @@ pub enum TextDecoration {
     Underline,
 }
 
-impl TextDecoration {
-    fn default(node: &Rc<RefCell<Node>>) -> Self {
-        match &node.borrow().kind() {
-            NodeKind::Element(element) => match element.kind() {
-                ElementKind::A => TextDecoration::Underline,
-                _ => TextDecoration::None,
-            },
-            _ => TextDecoration::None,
-        }
-    }
-}
-
 #[derive(Debug, Clone, PartialEq)]
 pub struct ComputedStyle {
     background_color: Option<Color>,
@@ -271,7 +330,44 @@ impl ComputedStyle {
         self.width.expect("failed to access CSS property: width")
     }
 
-    pub fn defaulting(&mut self, node: &Rc<RefCell<Node>>, parent_style: Option<ComputedStyle>) {
+    /// https://www.w3.org/TR/css-cascade-4/#cascaded
+    /// カスケードで決まった宣言値のうち、サポートしているプロパティの値を設定する。
+    /// 親のスタイルは、emなどの相対的な値を解決するのに使う
+    pub fn cascading(
+        &mut self,
+        declared_values: &DeclaredValues,
+        parent_style: Option<&ComputedStyle>,
+    ) {
+        if let Some([CssValue::Color(color)]) = declared_values.get("background-color") {
+            self.background_color = Some(color.clone());
+        }
+        if let Some([CssValue::Color(color)]) = declared_values.get("color") {
+            self.color = Some(color.clone());
+        }
+        if let Some([CssValue::Keyword(keyword)]) = declared_values.get("display") {
+            let display_type = match DisplayType::from_str(keyword) {
+                Ok(display_type) => display_type,
+                Err(_) => DisplayType::DisplayNone,
+            };
+            self.display = Some(display_type);
+        }
+        if let Some([value]) = declared_values.get("font-size") {
+            let parent_px = parent_style.map_or(FontSize::Medium.px(), |p| p.font_size().px());
+            if let Some(px) = font_size_px(value, parent_px) {
+                self.font_size = Some(FontSize::from_px(px));
+            }
+        }
+        // line-throughなど、サポートしていない値は無視する
+        if let Some([CssValue::Keyword(keyword)]) = declared_values.get("text-decoration") {
+            match keyword.as_str() {
+                "none" => self.text_decoration = Some(TextDecoration::None),
+                "underline" => self.text_decoration = Some(TextDecoration::Underline),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn defaulting(&mut self, node: &Node, parent_style: Option<ComputedStyle>) {
         // もし親ノードが存在し、親のCSSの値が初期値とは異なる場合、値を継承する。
         if let Some(parent_style) = parent_style {
             if self.background_color.is_none() && parent_style.background_color() != Color::white()
@@ -302,10 +398,10 @@ impl ComputedStyle {
             self.display = Some(DisplayType::default(node));
         }
         if self.font_size.is_none() {
-            self.font_size = Some(FontSize::default(node));
+            self.font_size = Some(FontSize::Medium);
         }
         if self.text_decoration.is_none() {
-            self.text_decoration = Some(TextDecoration::default(node));
+            self.text_decoration = Some(TextDecoration::None);
         }
         if self.height.is_none() {
             self.height = Some(0.0);
@@ -5,13 +5,13 @@ use crate::constants::CONTENT_AREA_WIDTH;
 use crate::constants::WINDOW_PADDING;
 use crate::constants::WINDOW_WIDTH;
 use crate::display_item::DisplayItem;
-use crate::renderer::css::cssom::ComponentValue;
-use crate::renderer::css::cssom::Declaration;
-use crate::renderer::css::cssom::Selector;
+use crate::renderer::css::cascade::cascade;
+use crate::renderer::css::cascade::DeclaredValues;
 use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::dom::node::Dom;
 use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::NodeKind;
-use crate::renderer::layout::computed_style::Color;
 use crate::renderer::layout::computed_style::ComputedStyle;
 use crate::renderer::layout::computed_style::DisplayType;
 use crate::renderer::layout::computed_style::FontSize;
@@ -88,7 +88,9 @@ impl LayoutSize {
 #[derive(Debug, Clone)]
 pub struct LayoutObject {
     kind: LayoutObjectKind,
-    node: Rc<RefCell<Node>>,
+    /// このオブジェクトに対応するDOMノードと、そのノードを保持するアリーナ
+    dom: Rc<RefCell<Dom>>,
+    node: NodeId,
     first_child: Option<Rc<RefCell<LayoutObject>>>,
     next_sibling: Option<Rc<RefCell<LayoutObject>>>,
     parent: Weak<RefCell<LayoutObject>>,
@@ -104,7 +106,11 @@ impl PartialEq for LayoutObject {
 }
 
 impl LayoutObject {
-    pub fn new(node: Rc<RefCell<Node>>, parent_obj: &Option<Rc<RefCell<LayoutObject>>>) -> Self {
+    pub fn new(
+        dom: Rc<RefCell<Dom>>,
+        node: NodeId,
+        parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
+    ) -> Self {
         let parent = match parent_obj {
             Some(p) => Rc::downgrade(p),
             None => Weak::new(),
@@ -112,7 +118,8 @@ impl LayoutObject {
 
         Self {
             kind: LayoutObjectKind::Block,
-            node: node.clone(),
+            dom,
+            node,
             first_child: None,
             next_sibling: None,
             parent,
@@ -126,8 +133,12 @@ impl LayoutObject {
         self.kind
     }
 
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
     pub fn node_kind(&self) -> NodeKind {
-        self.node.borrow().kind().clone()
+        self.dom.borrow().node(self.node).kind()
     }
 
     pub fn set_first_child(&mut self, first_child: Option<Rc<RefCell<LayoutObject>>>) {
@@ -162,104 +173,26 @@ impl LayoutObject {
         self.size
     }
 
-    pub fn is_node_selected(&self, selector: &Selector) -> bool {
-        match &self.node_kind() {
-            NodeKind::Element(e) => match selector {
-                Selector::TypeSelector(type_name) => {
-                    if e.kind().to_string() == *type_name {
-                        return true;
-                    }
-                    false
-                }
-                Selector::ClassSelector(class_name) => {
-                    for attr in &e.attributes() {
-                        if attr.name() == "class" && attr.value() == *class_name {
-                            return true;
-                        }
-                    }
-                    false
-                }
-                Selector::IdSelector(id_name) => {
-                    for attr in &e.attributes() {
-                        if attr.name() == "id" && attr.value() == *id_name {
-                            return true;
-                        }
-                    }
-                    false
-                }
-                Selector::UnknownSelector => false,
-            },
-            _ => false,
-        }
-    }
-
-    pub fn cascading_style(&mut self, declarations: Vec<Declaration>) {
-        for declaration in declarations {
-            match declaration.property.as_str() {
-                "background-color" => {
-                    if let ComponentValue::Ident(value) = &declaration.value {
-                        let color = match Color::from_name(&value) {
-                            Ok(color) => color,
-                            Err(_) => Color::white(),
-                        };
-                        self.style.set_background_color(color);
-                        continue;
-                    }
-
-                    if let ComponentValue::HashToken(color_code) = &declaration.value {
-                        let color = match Color::from_code(&color_code) {
-                            Ok(color) => color,
-                            Err(_) => Color::white(),
-                        };
-                        self.style.set_background_color(color);
-                        continue;
-                    }
-                }
-                "color" => {
-                    if let ComponentValue::Ident(value) = &declaration.value {
-                        let color = match Color::from_name(&value) {
-                            Ok(color) => color,
-                            Err(_) => Color::black(),
-                        };
-                        self.style.set_color(color);
-                        continue;
-                    }
-
-                    if let ComponentValue::HashToken(color_code) = &declaration.value {
-                        let color = match Color::from_code(&color_code) {
-                            Ok(color) => color,
-                            Err(_) => Color::black(),
-                        };
-                        self.style.set_color(color);
-                        continue;
-                    }
-                }
-                "display" => {
-                    if let ComponentValue::Ident(value) = &declaration.value {
-                        let display_type = match DisplayType::from_str(&value) {
-                            Ok(display_type) => display_type,
-                            Err(_) => DisplayType::DisplayNone,
-                        };
-                        self.style.set_display(display_type);
-                        continue;
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
-
-    pub fn defaulting_style(
+    pub fn cascading_style(
         &mut self,
-        node: &Rc<RefCell<Node>>,
-        parent_style: Option<ComputedStyle>,
+        declared_values: &DeclaredValues,
+        parent_style: Option<&ComputedStyle>,
     ) {
+        self.style.cascading(declared_values, parent_style);
+    }
+
+    pub fn defaulting_style(&mut self, node: &Node, parent_style: Option<ComputedStyle>) {
         self.style.defaulting(node, parent_style);
     }
 
     pub fn update_kind(&mut self) {
         match self.node_kind() {
-            NodeKind::Document => panic!("should not create a layout object for a Document node"),
+            NodeKind::Document(_) | NodeKind::DocumentFragment(_) => {
+                panic!("should not create a layout object for a Document or DocumentFragment node")
+            }
+            NodeKind::DocumentType(_) | NodeKind::Comment(_) => {
+                panic!("should not create a layout object for a DocumentType or Comment node")
+            }
             NodeKind::Element(_) => {
                 let display = self.style.display();
                 match display {
@@ -448,30 +381,31 @@ impl LayoutObject {
 }
 
 pub fn create_layout_object(
-    node: &Option<Rc<RefCell<Node>>>,
+    dom: &Rc<RefCell<Dom>>,
+    node: Option<NodeId>,
     parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
-    cssom: &StyleSheet,
+    stylesheets: &[StyleSheet],
 ) -> Option<Rc<RefCell<LayoutObject>>> {
     if let Some(n) = node {
         // LayoutObjectを作成する
-        let layout_object = Rc::new(RefCell::new(LayoutObject::new(n.clone(), parent_obj)));
-
-        // CSSのルールをセレクタで選択されたノードに適用する
-        for rule in &cssom.rules {
-            if layout_object.borrow().is_node_selected(&rule.selector) {
-                layout_object
-                    .borrow_mut()
-                    .cascading_style(rule.declarations.clone());
-            }
-        }
+        let layout_object = Rc::new(RefCell::new(LayoutObject::new(dom.clone(), n, parent_obj)));
 
-        // CSSでスタイルが指定されていない場合、デフォルトの値または親のノードから継承した値を使用する
         let parent_style = if let Some(parent) = parent_obj {
             Some(parent.borrow().style())
         } else {
             None
         };
-        layout_object.borrow_mut().defaulting_style(n, parent_style);
+
+        // カスケードでプロパティごとに勝った宣言の値を、ノードのスタイルに適用する
+        let declared_values = cascade(&dom.borrow(), n, stylesheets);
+        layout_object
+            .borrow_mut()
+            .cascading_style(&declared_values, parent_style.as_ref());
+
+        // CSSでスタイルが指定されていない場合、初期値または親のノードから継承した値を使用する
+        layout_object
+            .borrow_mut()
+            .defaulting_style(dom.borrow().node(n), parent_style);
 
         // displayプロパティが"none"の場合、ノードを作成しない
         if layout_object.borrow().style().display() == DisplayType::DisplayNone {
@@ -2,8 +2,9 @@ use crate::constants::CONTENT_AREA_WIDTH;
 use crate::display_item::DisplayItem;
 use crate::renderer::css::cssom::StyleSheet;
 use crate::renderer::dom::api::get_target_element_node;
+use crate::renderer::dom::node::Dom;
 use crate::renderer::dom::node::ElementKind;
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::layout::layout_object::create_layout_object;
 use crate::renderer::layout::layout_object::LayoutObject;
 use crate::renderer::layout::layout_object::LayoutObjectKind;
@@ -19,13 +20,13 @@ pub struct LayoutView {
 }
 
 impl LayoutView {
-    pub fn new(root: Rc<RefCell<Node>>, cssom: &StyleSheet) -> Self {
+    pub fn new(dom: Rc<RefCell<Dom>>, root: NodeId, stylesheets: &[StyleSheet]) -> Self {
         // レイアウトツリーは描画される要素だけを持つツリーなので、<body>タグを取得し、
         // その子要素以下をレイアウトツリーのノードに変換する。
-        let body_root = get_target_element_node(Some(root), ElementKind::Body);
+        let body_root = get_target_element_node(&dom.borrow(), root, ElementKind::Body);
 
         let mut tree = Self {
-            root: build_layout_tree(&body_root, &None, cssom),
+            root: build_layout_tree(&dom, body_root, &None, stylesheets),
         };
 
         tree.update_layout();
@@ -166,20 +167,21 @@ impl LayoutView {
 }
 
 fn build_layout_tree(
-    node: &Option<Rc<RefCell<Node>>>,
+    dom: &Rc<RefCell<Dom>>,
+    node: Option<NodeId>,
     parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
-    cssom: &StyleSheet,
+    stylesheets: &[StyleSheet],
 ) -> Option<Rc<RefCell<LayoutObject>>> {
     // create_layout_object関数で、ノードとなるLayoutObjectの作成を試みる。
     // CSSによって"display:none"が指定されていた場合、ノードは作成されない。
-    let mut target_node = node.clone();
-    let mut layout_object = create_layout_object(node, parent_obj, cssom);
+    let mut target_node = node;
+    let mut layout_object = create_layout_object(dom, node, parent_obj, stylesheets);
     // もしノードが作成されなかった場合、DOMノードの兄弟ノードを使用してLayoutObjectの作成を試みる。
     // LayoutObjectが作成されるまで、兄弟ノードをたどり続ける。
     while layout_object.is_none() {
         if let Some(n) = target_node {
-            target_node = n.borrow().next_sibling().clone();
-            layout_object = create_layout_object(&target_node, parent_obj, cssom);
+            target_node = dom.borrow().node(n).next_sibling();
+            layout_object = create_layout_object(dom, target_node, parent_obj, stylesheets);
         } else {
             // もし兄弟ノードがない場合、処理するべきDOMツリーは終了したので、
             // 今まで作成したレイアウトツリーを返す。
@@ -188,27 +190,30 @@ fn build_layout_tree(
     }
 
     if let Some(n) = target_node {
-        let original_first_child = n.borrow().first_child();
-        let original_next_sibling = n.borrow().next_sibling();
-        let mut first_child = build_layout_tree(&original_first_child, &layout_object, cssom);
-        let mut next_sibling = build_layout_tree(&original_next_sibling, parent_obj, cssom);
+        let original_first_child = dom.borrow().node(n).first_child();
+        let original_next_sibling = dom.borrow().node(n).next_sibling();
+        let mut first_child =
+            build_layout_tree(dom, original_first_child, &layout_object, stylesheets);
+        let mut next_sibling =
+            build_layout_tree(dom, original_next_sibling, parent_obj, stylesheets);
 
         // もし子ノードに"display:none"が指定されていた場合、LayoutObjectは作成されないため、
         // 子ノードの兄弟ノードを使用してLayoutObjectの作成を試みる。
         // LayoutObjectが作成されるか、たどるべき兄弟ノードがなくなるまで処理を繰り返す。
         if first_child.is_none() && original_first_child.is_some() {
-            let mut original_dom_node = original_first_child
-                .expect("first child should exist")
+            let mut original_dom_node = dom
                 .borrow()
+                .node(original_first_child.expect("first child should exist"))
                 .next_sibling();
 
             loop {
-                first_child = build_layout_tree(&original_dom_node, &layout_object, cssom);
+                first_child =
+                    build_layout_tree(dom, original_dom_node, &layout_object, stylesheets);
 
                 if first_child.is_none() && original_dom_node.is_some() {
-                    original_dom_node = original_dom_node
-                        .expect("next sibling should exist")
+                    original_dom_node = dom
                         .borrow()
+                        .node(original_dom_node.expect("next sibling should exist"))
                         .next_sibling();
                     continue;
                 }
@@ -220,19 +225,19 @@ fn build_layout_tree(
         // もし兄弟ノードに"display:none"が指定されていた場合、LayoutObjectは作成されないため、
         // 兄弟ノードの兄弟ノードを使用してLayoutObjectの作成を試みる。
         // LayoutObjectが作成されるか、たどるべき兄弟ノードがなくなるまで処理を繰り返す。
-        if next_sibling.is_none() && n.borrow().next_sibling().is_some() {
-            let mut original_dom_node = original_next_sibling
-                .expect("first child should exist")
+        if next_sibling.is_none() && original_next_sibling.is_some() {
+            let mut original_dom_node = dom
                 .borrow()
+                .node(original_next_sibling.expect("first child should exist"))
                 .next_sibling();
 
             loop {
-                next_sibling = build_layout_tree(&original_dom_node, &None, cssom);
+                next_sibling = build_layout_tree(dom, original_dom_node, &None, stylesheets);
 
                 if next_sibling.is_none() && original_dom_node.is_some() {
-                    original_dom_node = original_dom_node
-                        .expect("next sibling should exist")
+                    original_dom_node = dom
                         .borrow()
+                        .node(original_dom_node.expect("next sibling should exist"))
                         .next_sibling();
                     continue;
                 }
@@ -256,30 +261,40 @@ fn build_layout_tree(
 mod tests {
     use super::*;
     use crate::alloc::string::ToString;
-    use crate::renderer::css::cssom::CssParser;
-    use crate::renderer::css::token::CssTokenizer;
-    use crate::renderer::dom::api::get_style_content;
+    use crate::renderer::css::user_agent::user_agent_stylesheet;
     use crate::renderer::dom::node::Element;
     use crate::renderer::dom::node::NodeKind;
     use crate::renderer::html::parser::HtmlParser;
     use crate::renderer::html::token::HtmlTokenizer;
+    use crate::renderer::layout::computed_style::Color;
+    use crate::renderer::layout::computed_style::FontSize;
+    use crate::renderer::layout::computed_style::TextDecoration;
+    use crate::renderer::resource_loader::ResourceLoader;
     use alloc::string::String;
+    use alloc::vec;
     use alloc::vec::Vec;
 
     fn create_layout_view(html: String) -> LayoutView {
         let t = HtmlTokenizer::new(html);
         let window = HtmlParser::new(t).construct_tree();
-        let dom = window.borrow().document().clone();
-        let style = get_style_content(dom.clone());
-        let css_tokenizer = CssTokenizer::new(style);
-        let cssom = CssParser::new(css_tokenizer).parse_stylesheet();
-        LayoutView::new(dom, &cssom)
+        let dom = window.borrow().dom();
+        let document = window.borrow().document();
+        let mut stylesheets = vec![user_agent_stylesheet()];
+        stylesheets.extend(ResourceLoader::new().stylesheets(&dom.borrow(), document));
+        LayoutView::new(dom, document, &stylesheets)
     }
 
     #[test]
     fn test_empty() {
         let layout_view = create_layout_view("".to_string());
-        assert_eq!(None, layout_view.root());
+
+        // 空の文書でも、入力の終わりで<body>が作られる
+        let root = layout_view.root().expect("root should exist");
+        assert_eq!(
+            NodeKind::Element(Element::new("body", Vec::new())),
+            root.borrow().node_kind()
+        );
+        assert!(root.borrow().first_child().is_none());
     }
 
     #[test]
@@ -409,4 +424,90 @@ mod tests {
             .next_sibling()
             .is_none());
     }
+
+    #[test]
+    fn test_template() {
+        let html = "<html><head><template><style>p { display: none; }</style></template></head><body><template><p>a</p></template><p>b</p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        // <template>とその中身はレイアウトツリーに含まれず、中身の<style>も適用されない
+        let root = layout_view.root().expect("root should exist");
+        let p = root
+            .borrow()
+            .first_child()
+            .expect("first child should exist");
+        assert_eq!(
+            NodeKind::Element(Element::new("p", Vec::new())),
+            p.borrow().node_kind()
+        );
+        assert!(p.borrow().next_sibling().is_none());
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let html = "<html><head><style>p { color: #00F; background-color: rgb(255, 0, 0) !important; display: BLOCK } p { color: #12345; background-color: red blue }</style></head><body><p>a</p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        // 値を解釈できない宣言は無視される
+        let root = layout_view.root().expect("root should exist");
+        let p = root
+            .borrow()
+            .first_child()
+            .expect("first child should exist");
+        let style = p.borrow().style();
+        assert_eq!(Color::from_name("blue").unwrap(), style.color());
+        assert_eq!(Color::from_name("red").unwrap(), style.background_color());
+        assert_eq!(LayoutObjectKind::Block, p.borrow().kind());
+    }
+
+    #[test]
+    fn test_cascade_order() {
+        let html = "<html><head><style>#a { color: red } p { color: blue; display: none } .b { display: block }</style></head><body><p id=\"a\" class=\"b\" style=\"background-color: gray\">a</p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        // 詳細度の高いルールとstyle属性の宣言が、出現順に関わらず適用される
+        let root = layout_view.root().expect("root should exist");
+        let p = root
+            .borrow()
+            .first_child()
+            .expect("first child should exist");
+        let style = p.borrow().style();
+        assert_eq!(Color::from_name("red").unwrap(), style.color());
+        assert_eq!(Color::from_name("gray").unwrap(), style.background_color());
+        assert_eq!(LayoutObjectKind::Block, p.borrow().kind());
+    }
+
+    #[test]
+    fn test_user_agent_styles() {
+        let html = "<html><head></head><body><h1>a</h1><h2><small>b</small></h2><p><a href=\"/\">c</a><a>d</a></p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        let root = layout_view.root().expect("root should exist");
+        let h1 = root.borrow().first_child().expect("h1 should exist");
+        assert_eq!(LayoutObjectKind::Block, h1.borrow().kind());
+        assert_eq!(FontSize::XXLarge, h1.borrow().style().font_size());
+        // smallerは親の大きさを基準にする
+        let h2 = h1.borrow().next_sibling().expect("h2 should exist");
+        let small = h2.borrow().first_child().expect("small should exist");
+        assert_eq!(FontSize::XLarge, h2.borrow().style().font_size());
+        assert_eq!(FontSize::Medium, small.borrow().style().font_size());
+
+        // href属性のある<a>要素だけがリンクになる
+        let p = h2.borrow().next_sibling().expect("p should exist");
+        let link = p.borrow().first_child().expect("link should exist");
+        let anchor = link.borrow().next_sibling().expect("anchor should exist");
+        assert_eq!(LayoutObjectKind::Inline, link.borrow().kind());
+        assert_eq!(
+            TextDecoration::Underline,
+            link.borrow().style().text_decoration()
+        );
+        assert_eq!(
+            Color::from_hex("#0000ee").unwrap(),
+            link.borrow().style().color()
+        );
+        assert_eq!(
+            TextDecoration::None,
+            anchor.borrow().style().text_decoration()
+        );
+    }
 }
@@ -4,3 +4,4 @@ pub mod html;
 pub mod js;
 pub mod layout;
 pub mod page;
+pub mod resource_loader;
@@ -1,24 +1,32 @@
 use crate::browser::Browser;
 use crate::display_item::DisplayItem;
 use crate::http::HttpResponse;
-use crate::renderer::css::cssom::CssParser;
 use crate::renderer::css::cssom::StyleSheet;
-use crate::renderer::css::token::CssTokenizer;
-use crate::renderer::dom::api::get_js_content;
-use crate::renderer::dom::api::get_style_content;
+use crate::renderer::css::user_agent::user_agent_stylesheet;
+use crate::renderer::dom::api::document_title;
+use crate::renderer::dom::api::resolve_url;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::DocumentReadyState;
+use crate::renderer::dom::node::Dom;
 use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::NodeKind;
 use crate::renderer::dom::node::Window;
+use crate::renderer::html::encoding_sniffing::decode_document;
 use crate::renderer::html::parser::HtmlParser;
 use crate::renderer::html::token::HtmlTokenizer;
 use crate::renderer::js::ast::JsParser;
 use crate::renderer::js::runtime::JsRuntime;
 use crate::renderer::js::token::JsLexer;
 use crate::renderer::layout::layout_view::LayoutView;
+use crate::renderer::resource_loader::Fetcher;
+use crate::renderer::resource_loader::ResourceLoader;
 
 use alloc::rc::Rc;
 use alloc::rc::Weak;
 use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
@@ -26,7 +34,15 @@ use core::cell::RefCell;
 pub struct Page {
     browser: Weak<RefCell<Browser>>,
     frame: Option<Rc<RefCell<Window>>>,
-    style: Option<StyleSheet>,
+    loader: ResourceLoader,
+    /// ユーザーエージェントのスタイルシート。文書のスタイルシートより前にカスケードに渡す
+    user_agent_stylesheet: StyleSheet,
+    /// 文書の中の<style>要素と<link>要素から作ったスタイルシート。ツリー順に並ぶ
+    stylesheets: Vec<StyleSheet>,
+    /// 実行した<script>要素。実行した順に並ぶ
+    scripts: Vec<NodeId>,
+    /// 文書のすべてのスクリプトが共有するJavaScriptの実行環境
+    runtime: Option<JsRuntime>,
     layout_view: Option<LayoutView>,
     display_items: Vec<DisplayItem>,
 }
@@ -36,7 +52,11 @@ impl Page {
         Page {
             browser: Weak::new(),
             frame: None,
-            style: None,
+            loader: ResourceLoader::new(),
+            user_agent_stylesheet: user_agent_stylesheet(),
+            stylesheets: Vec::new(),
+            scripts: Vec::new(),
+            runtime: None,
             layout_view: None,
             display_items: Vec::new(),
         }
@@ -46,41 +66,157 @@ impl Page {
         self.browser = browser;
     }
 
-    pub fn receive_response(&mut self, response: HttpResponse) {
-        self.create_frame(response.body());
+    /// <link>や<script src>のリソースを取得する関数を設定する
+    pub fn set_fetcher(&mut self, fetcher: Fetcher) {
+        self.loader.set_fetcher(fetcher);
+    }
 
-        self.execute_js();
+    /// urlから取得したレスポンスを受け取り、ページを作る。相対URLは文書のベースURLを基準に解決する
+    pub fn receive_response(&mut self, url: String, response: HttpResponse) {
+        let content_type = response.header_value("Content-Type").ok();
+        let html = decode_document(&response.body(), content_type.as_deref());
 
-        self.set_layout_view();
+        // https://html.spec.whatwg.org/multipage/document-lifecycle.html#initialise-the-document-object
+        let mut document = Document::new();
+        document.set_url(url);
+        if let Some(essence) = content_type.as_deref().and_then(mime_type_essence) {
+            document.set_content_type(essence);
+        }
+        document.set_ready_state(DocumentReadyState::Loading);
+        self.create_frame_with_document(html, document);
+
+        self.update_rendering();
+    }
+
+    /// https://html.spec.whatwg.org/multipage/webappapis.html#update-the-rendering
+    /// 前回描画してからDOMツリーが変更されていれば、スタイルとレイアウトを計算し直して描画し直す。
+    /// 描画し直した場合はtrueを返す
+    pub fn update_rendering(&mut self) -> bool {
+        let (dom, document) = match &self.frame {
+            Some(frame) => (frame.borrow().dom(), frame.borrow().document()),
+            None => return false,
+        };
+
+        // 変更の記録がたまっていれば、先にMutationObserverに通知する
+        if let Some(runtime) = &mut self.runtime {
+            runtime.notify_mutation_observers();
+        }
+
+        let (style_dirty, layout_dirty) = {
+            let dom = dom.borrow();
+            (dom.style_dirty(), dom.layout_dirty())
+        };
+        if !style_dirty && !layout_dirty {
+            return false;
+        }
+
+        if style_dirty {
+            self.loader.load_stylesheets(&dom.borrow(), document);
+            self.stylesheets = self.loader.stylesheets(&dom.borrow(), document);
+        }
+        {
+            let mut dom = dom.borrow_mut();
+            dom.set_style_dirty(false);
+            dom.set_layout_dirty(false);
+        }
 
+        self.set_layout_view();
         self.paint_tree();
+        true
     }
 
+    /// HTMLをパースしてDOMツリーを作る。
+    /// パーサをブロックする<script>はその終了タグまでパースした時点で実行し、
+    /// asyncとdeferの<script>はパースが終わった後に実行する
     pub fn create_frame(&mut self, html: String) {
+        let mut document = Document::new();
+        document.set_ready_state(DocumentReadyState::Loading);
+        self.create_frame_with_document(html, document);
+    }
+
+    fn create_frame_with_document(&mut self, html: String, document: Document) {
         let html_tokenizer = HtmlTokenizer::new(html);
-        let frame = HtmlParser::new(html_tokenizer).construct_tree();
-        let dom = frame.borrow().document();
+        let mut parser = HtmlParser::new_with_document(html_tokenizer, document);
+        parser.set_pause_on_script(true);
+        let mut frame = parser.construct_tree();
+        let dom = frame.borrow().dom();
+        let document = frame.borrow().document();
 
-        let style = get_style_content(dom);
-        let css_tokenizer = CssTokenizer::new(style);
-        let cssom = CssParser::new(css_tokenizer).parse_stylesheet();
+        // すべてのスクリプトは同じグローバル環境で実行される
+        let mut runtime = JsRuntime::new(dom.clone(), document);
+        self.scripts = Vec::new();
+        self.loader.reset();
+        while let Some(script) = parser.pending_script() {
+            let js = self.loader.prepare_script(&dom.borrow(), script);
+            if let Some(js) = js {
+                // スクリプトは、それより前にある<link>のスタイルシートが読み込まれてから実行する
+                self.loader.load_stylesheets(&dom.borrow(), document);
+                self.execute_script(&mut runtime, script, js);
+            }
+            frame = parser.resume();
+        }
+
+        // https://html.spec.whatwg.org/multipage/parsing.html#the-end
+        // self.frameはまだ前のページを指しているので、新しい文書の状態を直接更新する
+        set_ready_state(&dom, document, DocumentReadyState::Interactive);
+        // asyncのスクリプトは取得がすでに終わっているので、deferのスクリプトより先に実行する
+        for (script, js) in self.loader.take_async_scripts() {
+            self.execute_script(&mut runtime, script, js);
+        }
+        for (script, js) in self.loader.take_defer_scripts() {
+            self.execute_script(&mut runtime, script, js);
+        }
 
+        // <link>のスタイルシートはレンダリングをブロックするので、レイアウトの前にすべて読み込む。
+        // スクリプトによって追加された要素も含めるため、スクリプトの実行が終わった後に集める
+        self.loader.load_stylesheets(&dom.borrow(), document);
+        self.stylesheets = self.loader.stylesheets(&dom.borrow(), document);
+        // レイアウトはupdate_renderingで計算する
+        dom.borrow_mut().set_style_dirty(false);
+        self.runtime = Some(runtime);
+        set_ready_state(&dom, document, DocumentReadyState::Complete);
         self.frame = Some(frame);
-        self.style = Some(cssom);
     }
 
-    fn set_layout_view(&mut self) {
-        let dom = match &self.frame {
-            Some(frame) => frame.borrow().document(),
-            None => return,
+    /// ページの文書。まだ読み込んでいない場合はNoneを返す
+    pub fn document(&self) -> Option<Document> {
+        let frame = self.frame.as_ref()?;
+        let dom = frame.borrow().dom();
+        let document = frame.borrow().document();
+        let document = dom.borrow().node(document).get_document();
+        document
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#document.title
+    pub fn title(&self) -> String {
+        let frame = match &self.frame {
+            Some(frame) => frame,
+            None => return String::new(),
         };
+        let dom = frame.borrow().dom();
+        let document = frame.borrow().document();
+        let title = document_title(&dom.borrow(), document);
+        title
+    }
+
+    fn execute_script(&mut self, runtime: &mut JsRuntime, script: NodeId, js: String) {
+        let lexer = JsLexer::new(js);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        runtime.execute(&ast);
 
-        let style = match self.style.clone() {
-            Some(style) => style,
+        self.scripts.push(script);
+    }
+
+    fn set_layout_view(&mut self) {
+        let (dom, document) = match &self.frame {
+            Some(frame) => (frame.borrow().dom(), frame.borrow().document()),
             None => return,
         };
 
-        let layout_view = LayoutView::new(dom, &style);
+        let mut stylesheets = vec![self.user_agent_stylesheet.clone()];
+        stylesheets.extend(self.stylesheets.iter().cloned());
+        let layout_view = LayoutView::new(dom, document, &stylesheets);
 
         self.layout_view = Some(layout_view);
     }
@@ -91,6 +227,14 @@ impl Page {
         }
     }
 
+    pub fn stylesheets(&self) -> Vec<StyleSheet> {
+        self.stylesheets.clone()
+    }
+
+    pub fn scripts(&self) -> Vec<NodeId> {
+        self.scripts.clone()
+    }
+
     pub fn display_items(&self) -> Vec<DisplayItem> {
         self.display_items.clone()
     }
@@ -99,6 +243,7 @@ impl Page {
         self.display_items = Vec::new();
     }
 
+    /// positionにあるリンクのhrefを、文書のベースURLを基準に解決したURLを返す
     pub fn clicked(&self, position: (i64, i64)) -> Option<String> {
         let view = match &self.layout_view {
             Some(v) => v,
@@ -109,7 +254,8 @@ impl Page {
             if let Some(parent) = n.borrow().parent().upgrade() {
                 if let NodeKind::Element(e) = parent.borrow().node_kind() {
                     if e.kind() == ElementKind::A {
-                        return e.get_attribute("href");
+                        let href = e.get_attribute("href")?;
+                        return Some(self.resolve_url(&href));
                     }
                 }
             }
@@ -118,19 +264,286 @@ impl Page {
         None
     }
 
-    fn execute_js(&mut self) {
-        let dom = match &self.frame {
-            Some(frame) => frame.borrow().document(),
-            None => return,
+    /// urlを文書のベースURLを基準に解決する。文書がない場合はそのまま返す
+    fn resolve_url(&self, url: &str) -> String {
+        let frame = match &self.frame {
+            Some(frame) => frame,
+            None => return url.to_string(),
         };
+        let dom = frame.borrow().dom();
+        let document = frame.borrow().document();
+        let url = resolve_url(&dom.borrow(), document, url);
+        url
+    }
+}
 
-        let js = get_js_content(dom.clone());
-        let lexer = JsLexer::new(js);
+/// https://mimesniff.spec.whatwg.org/#mime-type-essence
+/// Content-Typeの値から、パラメータを除いた「type/subtype」を小文字で返す
+fn mime_type_essence(content_type: &str) -> Option<String> {
+    let essence = content_type.split(';').next()?.trim();
+    if !essence.contains('/') {
+        return None;
+    }
+    Some(essence.to_ascii_lowercase())
+}
+
+/// https://html.spec.whatwg.org/multipage/dom.html#update-the-current-document-readiness
+fn set_ready_state(dom: &Rc<RefCell<Dom>>, document: NodeId, ready_state: DocumentReadyState) {
+    let mut dom = dom.borrow_mut();
+    if let Some(d) = dom.node_mut(document).get_document_mut() {
+        d.set_ready_state(ready_state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+    use crate::error::Error;
+    use crate::renderer::css::selector::Selector;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::dom::api::get_elements_by_tag_name;
+    use crate::renderer::dom::api::get_target_element_node;
+    use crate::renderer::dom::api::inner_html;
+    use crate::renderer::dom::api::outer_html;
+    use alloc::format;
+
+    fn body_inner_html(page: &Page) -> String {
+        let frame = page.frame.clone().expect("failed to get a frame");
+        let dom = frame.borrow().dom();
+        let dom = dom.borrow();
+        let document = frame.borrow().document();
+        let body =
+            get_target_element_node(&dom, document, ElementKind::Body).expect("failed to get body");
+        inner_html(&dom, body)
+    }
+
+    #[test]
+    fn test_stylesheets() {
+        let mut page = Page::new();
+        page.create_frame(
+            "<html><head><style>p { color: red; }</style></head><body><style>h1 { color: blue; }</style></body></html>"
+                .to_string(),
+        );
+
+        let stylesheets = page.stylesheets();
+        assert_eq!(2, stylesheets.len());
+        assert_eq!(
+            Selector::TypeSelector("p".to_string()),
+            stylesheets[0].rules[0].selector
+        );
+        assert_eq!(
+            Selector::TypeSelector("h1".to_string()),
+            stylesheets[1].rules[0].selector
+        );
+
+        // それぞれのスタイルシートは、元になった<style>要素を覚えている
+        let frame = page.frame.clone().expect("failed to get a frame");
+        let dom = frame.borrow().dom();
+        let dom = dom.borrow();
+        let owner = stylesheets[1]
+            .owner_node
+            .expect("failed to get an owner node");
+        assert_eq!(Some(ElementKind::Style), dom.node(owner).element_kind());
+        assert_eq!(
+            Some(ElementKind::Body),
+            dom.node(dom.node(owner).parent().unwrap()).element_kind()
+        );
+    }
+
+    #[test]
+    fn test_scripts_in_document_order() {
+        let mut page = Page::new();
+        page.create_frame(
+            "<body><p id=\"a\">a</p><script>var a = document.getElementById(\"a\"); a.textContent = \"x\"; var b = document.getElementById(\"b\"); b.textContent = \"y\";</script><p id=\"b\">b</p><script>var b = document.getElementById(\"b\"); b.textContent = \"z\";</script></body>"
+                .to_string(),
+        );
+
+        // 1つ目のスクリプトの実行時には、まだ#bはパースされていない
+        assert_eq!(2, page.scripts().len());
+        let html = body_inner_html(&page);
+        assert!(html.starts_with("<p id=\"a\">x</p>"));
+        assert!(html.contains("<p id=\"b\">z</p>"));
+    }
+
+    #[test]
+    fn test_non_javascript_scripts() {
+        let mut page = Page::new();
+        page.create_frame(
+            "<head><script type=\"application/ld+json\">{\"a\": [1]}</script></head><body><p id=\"a\">a</p><script type=\"text/template\"><p>{{ a }}</p></script><script>var a = document.getElementById(\"a\"); a.textContent = \"x\";</script></body>"
+                .to_string(),
+        );
 
+        // JavaScriptではないスクリプトは実行されない
+        assert_eq!(1, page.scripts().len());
+        let html = body_inner_html(&page);
+        assert!(html.starts_with("<p id=\"a\">x</p>"));
+    }
+
+    #[test]
+    fn test_scripts_share_globals() {
+        let mut page = Page::new();
+        page.create_frame(
+            "<body><p id=\"a\"></p><script>var s = \"hello\";</script><script>var a = document.getElementById(\"a\"); a.textContent = s;</script><template><script>var a = document.getElementById(\"a\"); a.textContent = \"no\";</script></template></body>"
+                .to_string(),
+        );
+
+        // <template>の中のスクリプトは実行されない
+        assert_eq!(2, page.scripts().len());
+        let frame = page.frame.clone().expect("failed to get a frame");
+        let dom = frame.borrow().dom();
+        let dom = dom.borrow();
+        let document = frame.borrow().document();
+        let a = get_element_by_id(&dom, document, &"a".to_string()).unwrap();
+        assert_eq!("hello".to_string(), inner_html(&dom, a));
+    }
+
+    fn fake_fetcher(url: String) -> Result<HttpResponse, Error> {
+        let body = match url.as_str() {
+            "http://example.com/js/first.js" => {
+                "var a = document.getElementById(\"a\"); a.textContent = \"blocking\"; var late = document.getElementById(\"late\"); late.textContent = \"too early\";"
+            }
+            "http://example.com/js/defer.js" => {
+                "var late = document.getElementById(\"late\"); late.textContent = \"defer\";"
+            }
+            "http://example.com/async.js" => {
+                "var late = document.getElementById(\"late\"); late.textContent = \"async\";"
+            }
+            "http://example.com/style.css" => "p { color: red; }",
+            _ => return HttpResponse::new(b"HTTP/1.1 404 Not Found\n\n".to_vec()),
+        };
+        HttpResponse::new(format!("HTTP/1.1 200 OK\n\n{}", body).into_bytes())
+    }
+
+    #[test]
+    fn test_external_resources() {
+        let mut page = Page::new();
+        page.set_fetcher(fake_fetcher);
+        let html = "<html><head><link rel=\"stylesheet\" href=\"/style.css\"><script defer src=\"js/defer.js\"></script><script async src=\"async.js\"></script></head><body><p id=\"a\"></p><script src=\"js/first.js\"></script><p id=\"late\"></p></body></html>";
+        let response = HttpResponse::new(
+            format!("HTTP/1.1 200 OK\nContent-Type: text/html\n\n{}", html).into_bytes(),
+        )
+        .expect("failed to create a response");
+        page.receive_response("http://example.com/index.html".to_string(), response);
+
+        assert_eq!(1, page.stylesheets().len());
+        assert_eq!(
+            Selector::TypeSelector("p".to_string()),
+            page.stylesheets()[0].rules[0].selector
+        );
+
+        // パーサをブロックするスクリプトが先に実行され、asyncとdeferのスクリプトはパースが終わった後に実行される
+        let frame = page.frame.clone().expect("failed to get a frame");
+        let dom = frame.borrow().dom();
+        let dom = dom.borrow();
+        let document = frame.borrow().document();
+        let scripts = get_elements_by_tag_name(&dom, document, "script");
+        assert_eq!(vec![scripts[2], scripts[1], scripts[0]], page.scripts());
+        assert_eq!(
+            "<p id=\"a\">blocking</p>".to_string(),
+            outer_html(
+                &dom,
+                get_element_by_id(&dom, document, &"a".to_string()).unwrap()
+            )
+        );
+        assert_eq!(
+            "<p id=\"late\">defer</p>".to_string(),
+            outer_html(
+                &dom,
+                get_element_by_id(&dom, document, &"late".to_string()).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_update_rendering() {
+        let mut page = Page::new();
+        page.create_frame(
+            "<html><head><style>p { color: red; }</style></head><body><p id=\"a\">a</p></body></html>"
+                .to_string(),
+        );
+        assert!(page.update_rendering());
+        let display_items = page.display_items();
+        // 変更がなければ、描画し直さない
+        assert!(!page.update_rendering());
+
+        // ページを読み込み直さずに、スクリプトによる変更が描画に反映される
+        let runtime = page.runtime.as_mut().expect("failed to get a runtime");
+        let lexer = JsLexer::new(
+            "var a = document.getElementById(\"a\"); a.textContent = \"changed\";".to_string(),
+        );
         let mut parser = JsParser::new(lexer);
-        let ast = parser.parse_ast();
+        runtime.execute(&parser.parse_ast());
+        assert!(page.update_rendering());
+        assert_ne!(display_items, page.display_items());
+        assert!(format!("{:?}", page.display_items()).contains("changed"));
+        assert!(!page.update_rendering());
+    }
 
-        let mut runtime = JsRuntime::new(dom);
-        runtime.execute(&ast);
+    /// URLのファイル名をidに持つ要素に、実行したときのdocument.readyStateを書き込むスクリプトを返す
+    fn ready_state_fetcher(url: String) -> Result<HttpResponse, Error> {
+        let id = url
+            .trim_start_matches("http://example.com/docs/")
+            .trim_end_matches(".js");
+        let body = format!(
+            "var e = document.getElementById(\"{}\"); e.textContent = document.readyState;",
+            id
+        );
+        HttpResponse::new(format!("HTTP/1.1 200 OK\n\n{}", body).into_bytes())
+    }
+
+    #[test]
+    fn test_document_metadata() {
+        let mut page = Page::new();
+        page.set_fetcher(ready_state_fetcher);
+        assert_eq!(None, page.document());
+
+        let html = "<html><head><title> Saba  page </title><base href=\"/docs/\"><script defer src=\"deferred.js\"></script><script async src=\"async.js\"></script></head><body><a href=\"next.html\">next</a><p id=\"blocking\"></p><script src=\"blocking.js\"></script><p id=\"deferred\"></p><p id=\"async\"></p></body></html>";
+        let response = HttpResponse::new(
+            format!(
+                "HTTP/1.1 200 OK\nContent-Type: Text/HTML; charset=utf-8\n\n{}",
+                html
+            )
+            .into_bytes(),
+        )
+        .expect("failed to create a response");
+        page.receive_response("http://example.com/index.html".to_string(), response);
+
+        let document = page.document().expect("failed to get a document");
+        assert_eq!("http://example.com/index.html".to_string(), document.url());
+        assert_eq!("text/html".to_string(), document.content_type());
+        assert_eq!(DocumentReadyState::Complete, document.ready_state());
+        assert_eq!("Saba page".to_string(), page.title());
+
+        // パース中のスクリプトはloading、asyncとdeferのスクリプトはinteractiveの状態で実行される
+        let frame = page.frame.clone().expect("failed to get a frame");
+        let dom = frame.borrow().dom();
+        let document = frame.borrow().document();
+        let state = |id: &str| {
+            let dom = dom.borrow();
+            inner_html(
+                &dom,
+                get_element_by_id(&dom, document, &id.to_string()).unwrap(),
+            )
+        };
+        assert_eq!("loading".to_string(), state("blocking"));
+        assert_eq!("interactive".to_string(), state("async"));
+        assert_eq!("interactive".to_string(), state("deferred"));
+
+        // リンクのhrefは<base href>を基準に解決される
+        let position = page
+            .display_items()
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::Text { layout_point, .. } => {
+                    Some((layout_point.x(), layout_point.y()))
+                }
+                _ => None,
+            })
+            .expect("failed to find a link");
+        assert_eq!(
+            Some("http://example.com/docs/next.html".to_string()),
+            page.clicked(position)
+        );
     }
 }
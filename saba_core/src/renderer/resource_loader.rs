@@ -0,0 +1,419 @@
+use crate::encoding::decode;
+use crate::encoding::Encoding;
+use crate::error::Error;
+use crate::http::HttpResponse;
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::api::child_text_content;
+use crate::renderer::dom::api::resolve_url;
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::html::encoding_sniffing::charset_from_content_type;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// 外部のリソースを取得する関数。src/main.rsのhandle_urlと同じ形をしている
+pub type Fetcher = fn(String) -> Result<HttpResponse, Error>;
+
+/// https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element
+/// <script>要素をいつ実行するか
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScriptTiming {
+    /// パースを止めて、すぐに実行する
+    ParserBlocking,
+    /// パースを止めずに取得し、取得できしだい実行する
+    Async,
+    /// パースを止めずに取得し、パースが終わった後に文書の順に実行する
+    Defer,
+}
+
+/// <link rel="stylesheet">と<script src>のリソースを、文書のベースURLを基準に取得する
+#[derive(Debug, Clone)]
+pub struct ResourceLoader {
+    fetcher: Option<Fetcher>,
+    /// 取得を試みた<link>要素と、取得できた場合はそのスタイルシート
+    linked_stylesheets: Vec<(NodeId, Option<StyleSheet>)>,
+    /// 取得が終わり、実行を待っているasyncのスクリプト
+    async_scripts: Vec<(NodeId, String)>,
+    /// https://html.spec.whatwg.org/multipage/scripting.html#list-of-scripts-that-will-execute-when-the-document-has-finished-parsing
+    defer_scripts: Vec<(NodeId, String)>,
+}
+
+impl ResourceLoader {
+    pub fn new() -> Self {
+        Self {
+            fetcher: None,
+            linked_stylesheets: Vec::new(),
+            async_scripts: Vec::new(),
+            defer_scripts: Vec::new(),
+        }
+    }
+
+    pub fn set_fetcher(&mut self, fetcher: Fetcher) {
+        self.fetcher = Some(fetcher);
+    }
+
+    /// 新しい文書を読み込む前に、前の文書のリソースを捨てる
+    pub fn reset(&mut self) {
+        self.linked_stylesheets = Vec::new();
+        self.async_scripts = Vec::new();
+        self.defer_scripts = Vec::new();
+    }
+
+    /// nodeの属性に書かれたurlのリソースを取得し、文字列にデコードする。
+    /// urlはnodeが属する文書のベースURLを基準に解決する。
+    /// 取得に失敗した場合や、成功を表すステータスコードでない場合はNoneを返す
+    fn fetch(&self, dom: &Dom, node: NodeId, url: &str) -> Option<String> {
+        let fetcher = self.fetcher?;
+        let document = dom.ancestors(node).last().unwrap_or(node);
+        let response = fetcher(resolve_url(dom, document, url)).ok()?;
+        if !(200..300).contains(&response.status_code()) {
+            return None;
+        }
+
+        // https://www.w3.org/TR/css-syntax-3/#input-byte-stream
+        // CSSとJavaScriptは、BOMかContent-Typeのcharsetがなければ、UTF-8としてデコードする
+        let encoding = response
+            .header_value("Content-Type")
+            .ok()
+            .and_then(|c| charset_from_content_type(&c))
+            .unwrap_or(Encoding::Utf8);
+        Some(decode(&response.body(), encoding))
+    }
+
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-stylesheet
+    /// rootの子孫の<link rel="stylesheet">のうち、まだ取得していないものを取得する。
+    /// スクリプトはそれより前のスタイルシートが読み込まれてから実行されるので、スクリプトの実行前にも呼ばれる
+    pub fn load_stylesheets(&mut self, dom: &Dom, root: NodeId) {
+        for link in dom.preorder(root) {
+            let href = match stylesheet_href(dom, link) {
+                Some(href) => href,
+                None => continue,
+            };
+            if self.linked_stylesheets.iter().any(|(n, _)| *n == link) {
+                continue;
+            }
+
+            let stylesheet = self.fetch(dom, link, &href).map(|css| {
+                let mut sheet = CssParser::new(CssTokenizer::new(css)).parse_stylesheet();
+                sheet.set_owner_node(Some(link));
+                sheet
+            });
+            self.linked_stylesheets.push((link, stylesheet));
+        }
+    }
+
+    /// https://drafts.csswg.org/cssom/#dom-documentorshadowroot-stylesheets
+    /// <style>要素と、読み込んだ<link>要素のスタイルシートを、ツリー順に並べる
+    pub fn stylesheets(&self, dom: &Dom, root: NodeId) -> Vec<StyleSheet> {
+        let mut stylesheets = Vec::new();
+        for node in dom.preorder(root) {
+            if dom.node(node).element_kind() == Some(ElementKind::Style) {
+                let css_tokenizer = CssTokenizer::new(child_text_content(dom, node));
+                let mut sheet = CssParser::new(css_tokenizer).parse_stylesheet();
+                sheet.set_owner_node(Some(node));
+                stylesheets.push(sheet);
+                continue;
+            }
+
+            let linked = self
+                .linked_stylesheets
+                .iter()
+                .find(|(n, _)| *n == node)
+                .and_then(|(_, sheet)| sheet.clone());
+            if let Some(sheet) = linked {
+                stylesheets.push(sheet);
+            }
+        }
+        stylesheets
+    }
+
+    /// https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element
+    /// パーサが<script>の終了タグに到達したときに呼ばれる。
+    /// すぐに実行するべきスクリプトの場合はその中身を返し、asyncとdeferのスクリプトは後で実行するために取っておく
+    pub fn prepare_script(&mut self, dom: &Dom, script: NodeId) -> Option<String> {
+        let element = dom.node(script).get_element()?;
+        // JavaScript以外のスクリプト(JSON-LDやテンプレートなど)は実行しない。
+        // モジュールのスクリプトとインポートマップはサポートしていない
+        let type_string = script_type_string(
+            element.get_attribute("type"),
+            element.get_attribute("language"),
+        );
+        if !is_javascript_mime_type(&type_string) {
+            return None;
+        }
+
+        let src = match element.get_attribute("src") {
+            Some(src) => src,
+            // インラインのスクリプトでは、asyncとdeferは無視される
+            None => return Some(child_text_content(dom, script)),
+        };
+
+        let timing = if element.get_attribute("async").is_some() {
+            ScriptTiming::Async
+        } else if element.get_attribute("defer").is_some() {
+            ScriptTiming::Defer
+        } else {
+            ScriptTiming::ParserBlocking
+        };
+
+        // 取得に失敗したスクリプトは実行されない
+        let js = self.fetch(dom, script, &src)?;
+        match timing {
+            ScriptTiming::ParserBlocking => return Some(js),
+            ScriptTiming::Async => self.async_scripts.push((script, js)),
+            ScriptTiming::Defer => self.defer_scripts.push((script, js)),
+        }
+        None
+    }
+
+    /// 取得が終わったasyncのスクリプトを、取得できた順に取り出す
+    pub fn take_async_scripts(&mut self) -> Vec<(NodeId, String)> {
+        core::mem::take(&mut self.async_scripts)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-end
+    /// パースが終わった後に実行するdeferのスクリプトを、文書の順に取り出す
+    pub fn take_defer_scripts(&mut self) -> Vec<(NodeId, String)> {
+        core::mem::take(&mut self.defer_scripts)
+    }
+}
+
+impl Default for ResourceLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// https://mimesniff.spec.whatwg.org/#javascript-mime-type
+const JAVASCRIPT_MIME_TYPES: [&str; 16] = [
+    "application/ecmascript",
+    "application/javascript",
+    "application/x-ecmascript",
+    "application/x-javascript",
+    "text/ecmascript",
+    "text/javascript",
+    "text/javascript1.0",
+    "text/javascript1.1",
+    "text/javascript1.2",
+    "text/javascript1.3",
+    "text/javascript1.4",
+    "text/javascript1.5",
+    "text/jscript",
+    "text/livescript",
+    "text/x-ecmascript",
+    "text/x-javascript",
+];
+
+/// https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element
+/// 手順8: type属性とlanguage属性から、スクリプトブロックの種類を表す文字列を決める
+fn script_type_string(type_attribute: Option<String>, language: Option<String>) -> String {
+    match (type_attribute, language) {
+        (Some(t), _) if !t.is_empty() => t
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .to_string(),
+        (None, Some(l)) if !l.is_empty() => format!("text/{}", l),
+        _ => "text/javascript".to_string(),
+    }
+}
+
+/// https://mimesniff.spec.whatwg.org/#javascript-mime-type-essence-match
+/// パラメータを含まず、大文字と小文字を区別せずにJavaScriptのMIMEタイプと一致するか
+fn is_javascript_mime_type(type_string: &str) -> bool {
+    JAVASCRIPT_MIME_TYPES
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case(type_string))
+}
+
+/// nodeが<link rel="stylesheet" href>の場合、そのhref属性の値を返す
+fn stylesheet_href(dom: &Dom, node: NodeId) -> Option<String> {
+    if dom.node(node).element_kind() != Some(ElementKind::Link) {
+        return None;
+    }
+    let element = dom.node(node).get_element()?;
+    // rel属性は、空白で区切られたリンクの種類のリストで、大文字と小文字を区別しない
+    let is_stylesheet = element.get_attribute("rel").is_some_and(|rel| {
+        rel.split_ascii_whitespace()
+            .any(|r| r.eq_ignore_ascii_case("stylesheet"))
+    });
+    if !is_stylesheet {
+        return None;
+    }
+    element
+        .get_attribute("href")
+        .filter(|href| !href.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::selector::Selector;
+    use crate::renderer::dom::api::get_elements_by_tag_name;
+    use crate::renderer::dom::node::Document;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use core::cell::RefCell;
+
+    fn fake_fetcher(url: String) -> Result<HttpResponse, Error> {
+        let (content_type, body) = match url.as_str() {
+            "http://example.com/dir/css/a.css" => ("text/css", "p { color: red; }"),
+            "http://example.com/b.css" => ("text/css", "h1 { color: blue; }"),
+            "http://example.com/dir/app.js" => ("text/javascript", "var a = 1;"),
+            _ => return HttpResponse::new(b"HTTP/1.1 404 Not Found\n\n".to_vec()),
+        };
+        HttpResponse::new(
+            format!(
+                "HTTP/1.1 200 OK\nContent-Type: {}\n\n{}",
+                content_type, body
+            )
+            .into_bytes(),
+        )
+    }
+
+    fn create_loader() -> ResourceLoader {
+        let mut loader = ResourceLoader::new();
+        loader.set_fetcher(fake_fetcher);
+        loader
+    }
+
+    fn parse(html: &str) -> (Rc<RefCell<Dom>>, NodeId) {
+        let mut document = Document::new();
+        document.set_url("http://example.com/dir/index.html".to_string());
+        let window = HtmlParser::new_with_document(HtmlTokenizer::new(html.to_string()), document)
+            .construct_tree();
+        let dom = window.borrow().dom();
+        let document = window.borrow().document();
+        (dom, document)
+    }
+
+    fn selectors(stylesheets: &[StyleSheet]) -> Vec<Selector> {
+        stylesheets
+            .iter()
+            .map(|s| s.rules[0].selector.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_stylesheets_in_tree_order() {
+        let (dom, document) = parse(
+            "<head><link rel=\"stylesheet\" href=\"css/a.css\"><style>div {}</style><link rel=\"icon\" href=\"/b.css\"><link rel=\"Alternate StyleSheet\" href=\"/b.css\"><link rel=\"stylesheet\" href=\"missing.css\"></head>",
+        );
+        let dom = dom.borrow();
+        let mut loader = create_loader();
+
+        // 読み込む前は<style>要素のスタイルシートだけになる
+        assert_eq!(
+            vec![Selector::TypeSelector("div".to_string())],
+            selectors(&loader.stylesheets(&dom, document))
+        );
+
+        loader.load_stylesheets(&dom, document);
+        let stylesheets = loader.stylesheets(&dom, document);
+        assert_eq!(
+            vec![
+                Selector::TypeSelector("p".to_string()),
+                Selector::TypeSelector("div".to_string()),
+                Selector::TypeSelector("h1".to_string()),
+            ],
+            selectors(&stylesheets)
+        );
+        let links = get_elements_by_tag_name(&dom, document, "link");
+        assert_eq!(Some(links[0]), stylesheets[0].owner_node);
+        assert_eq!(Some(links[2]), stylesheets[2].owner_node);
+    }
+
+    #[test]
+    fn test_prepare_script() {
+        let (dom, document) = parse(
+            "<script>var x = 1;</script><script src=\"app.js\"></script><script async src=\"app.js\"></script><script defer src=\"app.js\"></script><script defer>var y = 2;</script><script src=\"missing.js\"></script>",
+        );
+        let dom = dom.borrow();
+        let scripts = get_elements_by_tag_name(&dom, document, "script");
+        let mut loader = create_loader();
+
+        let prepared: Vec<Option<String>> = scripts
+            .iter()
+            .map(|s| loader.prepare_script(&dom, *s))
+            .collect();
+        assert_eq!(
+            vec![
+                Some("var x = 1;".to_string()),
+                Some("var a = 1;".to_string()),
+                None,
+                None,
+                // インラインのスクリプトではdeferは無視される
+                Some("var y = 2;".to_string()),
+                // 取得に失敗したスクリプトは実行しない
+                None,
+            ],
+            prepared
+        );
+        assert_eq!(
+            vec![(scripts[2], "var a = 1;".to_string())],
+            loader.take_async_scripts()
+        );
+        assert_eq!(
+            vec![(scripts[3], "var a = 1;".to_string())],
+            loader.take_defer_scripts()
+        );
+        assert!(loader.take_defer_scripts().is_empty());
+    }
+
+    #[test]
+    fn test_script_type() {
+        let (dom, document) = parse(
+            "<script type=\"application/ld+json\">{\"a\": [1]}</script><script type=\"text/template\"><p></p></script><script type=\" Text/JavaScript \">var a = 1;</script><script type=\"\">var b = 2;</script><script language=\"JavaScript1.5\">var c = 3;</script><script language=\"vbscript\">d = 4</script><script type=\"text/javascript; charset=utf-8\">var e = 5;</script><script type=\"module\" src=\"app.js\"></script>",
+        );
+        let dom = dom.borrow();
+        let scripts = get_elements_by_tag_name(&dom, document, "script");
+        let mut loader = create_loader();
+
+        let prepared: Vec<Option<String>> = scripts
+            .iter()
+            .map(|s| loader.prepare_script(&dom, *s))
+            .collect();
+        assert_eq!(
+            vec![
+                None,
+                None,
+                // 前後の空白は取り除かれ、大文字と小文字は区別しない
+                Some("var a = 1;".to_string()),
+                Some("var b = 2;".to_string()),
+                Some("var c = 3;".to_string()),
+                None,
+                // パラメータがついたMIMEタイプは一致しない
+                None,
+                // モジュールのスクリプトはサポートしていない
+                None,
+            ],
+            prepared
+        );
+    }
+
+    #[test]
+    fn test_base_url() {
+        let (dom, document) = parse(
+            "<head><base href=\"/dir/css/\"><link rel=\"stylesheet\" href=\"a.css\"></head><body><script src=\"../app.js\"></script></body>",
+        );
+        let dom = dom.borrow();
+        let mut loader = create_loader();
+
+        // 相対URLは、文書のURLではなく<base href>を基準に解決する
+        loader.load_stylesheets(&dom, document);
+        assert_eq!(
+            vec![Selector::TypeSelector("p".to_string())],
+            selectors(&loader.stylesheets(&dom, document))
+        );
+        let scripts = get_elements_by_tag_name(&dom, document, "script");
+        assert_eq!(
+            Some("var a = 1;".to_string()),
+            loader.prepare_script(&dom, scripts[0])
+        );
+    }
+}
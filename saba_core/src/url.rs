@@ -50,6 +50,71 @@ impl Url {
         Ok(self.clone())
     }
 
+    /// https://url.spec.whatwg.org/#concept-basic-url-parser
+    /// パース済みのこのURLを基準として、inputの相対URLを絶対URLの文字列に変換する
+    pub fn resolve(&self, input: &str) -> String {
+        let input = input.trim();
+
+        // スキームを持つ場合は、すでに絶対URLになっている
+        if has_scheme(input) {
+            return input.to_string();
+        }
+        if input.starts_with("//") {
+            return "http:".to_string() + input;
+        }
+
+        let mut origin = "http://".to_string() + &self.host;
+        if self.port != "80" {
+            origin.push(':');
+            origin.push_str(&self.port);
+        }
+        let query = if self.searchpart.is_empty() {
+            "".to_string()
+        } else {
+            "?".to_string() + &self.searchpart
+        };
+
+        if input.is_empty() || input.starts_with('#') {
+            return origin + "/" + &self.path + &query + input;
+        }
+        if input.starts_with('?') {
+            return origin + "/" + &self.path + input;
+        }
+
+        // パスの後ろのクエリとフラグメントはそのまま残す
+        let (path, rest) = match input.find(['?', '#']) {
+            Some(i) => input.split_at(i),
+            None => (input, ""),
+        };
+
+        // 相対パスは、基準のURLのパスの最後のセグメントを除いたものに連結する
+        let mut segments: Vec<&str> = Vec::new();
+        if !path.starts_with('/') {
+            segments = self.path.split('/').collect();
+            segments.pop();
+        }
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        for (i, part) in parts.iter().enumerate() {
+            let is_last = i == parts.len() - 1;
+            match *part {
+                "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                _ => {
+                    segments.push(part);
+                    continue;
+                }
+            }
+            // "."や".."で終わる場合は、ディレクトリを指すように末尾にスラッシュを残す
+            if is_last {
+                segments.push("");
+            }
+        }
+
+        origin + "/" + &segments.join("/") + rest
+    }
+
     fn is_http(&self) -> bool {
         if self.url.contains("http://") {
             return true;
@@ -125,6 +190,20 @@ impl Url {
     }
 }
 
+/// https://url.spec.whatwg.org/#scheme-state
+/// 先頭が"http:"のようなスキームで始まっているかどうか
+fn has_scheme(input: &str) -> bool {
+    let colon = match input.find(':') {
+        Some(i) => i,
+        None => return false,
+    };
+    let scheme = &input[..colon];
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,4 +286,40 @@ mod tests {
         let expected = Err("Only HTTP scheme is supported.".to_string());
         assert_eq!(expected, Url::new(url).parse());
     }
+
+    #[test]
+    fn test_resolve() {
+        let base = Url::new("http://example.com:8888/a/b/index.html?q=1".to_string())
+            .parse()
+            .expect("failed to parse a base url");
+        assert_eq!(
+            "http://example.com:8888/a/b/style.css",
+            base.resolve("style.css")
+        );
+        assert_eq!(
+            "http://example.com:8888/a/c/app.js",
+            base.resolve("../c/app.js")
+        );
+        assert_eq!("http://example.com:8888/a/", base.resolve(".."));
+        assert_eq!("http://example.com:8888/x?y=2", base.resolve("/x?y=2"));
+        assert_eq!(
+            "http://example.com:8888/a/b/index.html?z",
+            base.resolve("?z")
+        );
+        assert_eq!(
+            "http://example.com:8888/a/b/index.html?q=1#top",
+            base.resolve("#top")
+        );
+        assert_eq!("http://cdn.test/lib.js", base.resolve("//cdn.test/lib.js"));
+        assert_eq!(
+            "http://other.test/x.css",
+            base.resolve("http://other.test/x.css")
+        );
+
+        // ポートが80の場合は省略する
+        let base = Url::new("http://example.com".to_string())
+            .parse()
+            .expect("failed to parse a base url");
+        assert_eq!("http://example.com/style.css", base.resolve("./style.css"));
+    }
 }
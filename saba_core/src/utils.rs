@@ -1,28 +1,19 @@
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::Dom;
+use crate::renderer::dom::node::NodeId;
 use alloc::format;
-use alloc::rc::Rc;
 use alloc::string::String;
-use core::cell::RefCell;
 
-pub fn convert_dom_to_string(root: &Option<Rc<RefCell<Node>>>) -> String {
+pub fn convert_dom_to_string(dom: &Dom, root: NodeId) -> String {
     let mut result = String::from("\n");
-    convert_dom_to_string_internal(root, 0, &mut result);
+    convert_dom_to_string_internal(dom, root, 0, &mut result);
     result
 }
 
-fn convert_dom_to_string_internal(
-    node: &Option<Rc<RefCell<Node>>>,
-    depth: usize,
-    result: &mut String,
-) {
-    match node {
-        Some(n) => {
-            result.push_str(&"  ".repeat(depth));
-            result.push_str(&format!("{:?}", n.borrow().kind()));
-            result.push_str("\n");
-            convert_dom_to_string_internal(&n.borrow().first_child(), depth + 1, result);
-            convert_dom_to_string_internal(&n.borrow().next_sibling(), depth, result);
-        }
-        None => (),
+fn convert_dom_to_string_internal(dom: &Dom, node: NodeId, depth: usize, result: &mut String) {
+    result.push_str(&"  ".repeat(depth));
+    result.push_str(&format!("{:?}", dom.node(node).kind()));
+    result.push_str("\n");
+    for child in dom.children(node) {
+        convert_dom_to_string_internal(dom, child, depth + 1, result);
     }
 }
@@ -73,6 +73,14 @@ impl WasabiUI {
         loop {
             self.handle_mouse_input(handle_url)?;
             self.handle_key_input(handle_url)?;
+
+            // スクリプトなどによってDOMツリーが変更されていれば、ページを読み込み直さずに描画し直す
+            let page = self.browser.borrow().current_page();
+            let updated = page.borrow_mut().update_rendering();
+            if updated {
+                self.clear_content_area()?;
+                self.update_ui()?;
+            }
         }
         Ok(())
     }
@@ -293,10 +301,11 @@ impl WasabiUI {
     ) -> Result<(), Error> {
         self.clear_content_area()?;
 
-        match handle_url(destination) {
+        match handle_url(destination.clone()) {
             Ok(response) => {
                 let page = self.browser.borrow().current_page();
-                page.borrow_mut().receive_response(response);
+                page.borrow_mut().set_fetcher(handle_url);
+                page.borrow_mut().receive_response(destination, response);
             }
             Err(e) => {
                 return Err(e);